@@ -7,7 +7,7 @@ fn main() {
     env_logger::init();
 
     let mut users: Vec<User> = unsafe { all_users() }.collect();
-    users.sort_by(|a, b| a.uid().cmp(&b.uid()));
+    users.sort_by_key(|u| u.uid());
 
     for user in users {
         println!(
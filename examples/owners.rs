@@ -0,0 +1,61 @@
+//! This example counts the files in a directory tree by owner, resolving the
+//! owners on several threads at once.
+//!
+//! A `UsersCache` cannot be shared between threads, so instead of putting one
+//! behind a lock, as in the `threading` example, it lets
+//! `resolve_owners_parallel` create one cache for each of its threads.
+//!
+//! Run it with the directory to scan, which defaults to the current one:
+//!
+//!     cargo run --example owners -- /home
+
+extern crate uzers;
+use uzers::bulk::resolve_owners_parallel;
+use uzers::{uid_t, UsersCache};
+
+extern crate env_logger;
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+const THREADS: usize = 4;
+
+fn scan(path: &Path, counts: &mut BTreeMap<uid_t, usize>) {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        if let Ok(metadata) = entry.metadata() {
+            *counts.entry(metadata.uid()).or_default() += 1;
+            if metadata.is_dir() {
+                scan(&entry.path(), counts);
+            }
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let root = env::args_os()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let mut counts = BTreeMap::new();
+    scan(&root, &mut counts);
+
+    let owners = resolve_owners_parallel(counts.keys().copied(), UsersCache::new, THREADS);
+
+    for (uid, count) in counts {
+        match &owners[&uid] {
+            Some(user) => println!("{} files owned by {}", count, user.name().to_string_lossy()),
+            None => println!("{} files owned by unknown user #{}", count, uid),
+        }
+    }
+}
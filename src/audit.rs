@@ -0,0 +1,779 @@
+//! Auditing of the users and groups databases.
+//!
+//! The functions in this module combine the information from the other
+//! modules of this crate to answer the questions that security audits ask
+//! about a system’s accounts.
+//!
+//!
+//! ## Stale accounts
+//!
+//! Accounts that nobody uses anymore, or that have expired but are still
+//! present, are a common finding in audits. With the `utmp` feature on
+//! Linux, [`stale_accounts`](fn.stale_accounts.html) combines the login
+//! history, the shadow password aging information and the lock status of
+//! every account to find them:
+//!
+//! ```no_run
+//! # #[cfg(feature = "utmp")]
+//! # fn main() {
+//! use uzers::UsersSnapshot;
+//! use uzers::audit::{stale_accounts, StalePolicy};
+//!
+//! let snapshot = unsafe { UsersSnapshot::new() };
+//! let report = stale_accounts(&snapshot, &StalePolicy::default()).unwrap();
+//! for account in report {
+//!     println!("{:?}: {:?}", account.user().name(), account.reasons());
+//! }
+//! # }
+//! # #[cfg(not(feature = "utmp"))]
+//! # fn main() {}
+//! ```
+//!
+//!
+//! ## Changing an account
+//!
+//! Changing the name or ID of a user affects more than the user’s own
+//! entry: groups list their members by name, and files are owned by ID.
+//! [`impact_of_change`] previews those consequences before the change is
+//! written:
+//!
+//! ```no_run
+//! use uzers::{get_user_by_name, User, UsersSnapshot};
+//! use uzers::audit::impact_of_change;
+//!
+//! let snapshot = unsafe { UsersSnapshot::new() };
+//! let old = get_user_by_name("fred").unwrap();
+//! let new = User::new(old.uid(), "frederick", old.primary_group_id());
+//! for impact in impact_of_change(&old, &new, &snapshot) {
+//!     println!("{:?}", impact);
+//! }
+//! ```
+//!
+//!
+//! ## File ownership
+//!
+//! Deployments and restored backups are expected to leave their files owned
+//! by a certain user and group. [`not_owned_by`] walks a directory tree and
+//! returns the entries that are owned by anyone else, and
+//! [`unexpected_owners`] does the same for any rule about who should own
+//! what:
+//!
+//! ```no_run
+//! use uzers::{get_user_by_name, UsersCache};
+//! use uzers::audit::not_owned_by;
+//!
+//! let cache = UsersCache::new();
+//! let www = get_user_by_name("www-data").unwrap();
+//! for entry in not_owned_by("/srv/www", &cache, &www, None) {
+//!     let entry = entry.unwrap();
+//!     println!("{}: {:?}", entry.path().display(), entry.owner().map(|u| u.name()));
+//! }
+//! ```
+
+pub use self::impact::*;
+pub use self::ownership::*;
+
+#[cfg(all(feature = "utmp", target_os = "linux"))]
+pub use self::stale::*;
+
+mod impact {
+    use libc::{gid_t, uid_t};
+
+    use base::os::unix::GroupExt;
+    use base::{Group, User};
+    use traits::{AllGroups, AllUsers};
+
+    /// A consequence of changing a user, found by [`impact_of_change`].
+    #[derive(Clone, Debug)]
+    pub enum ChangeImpact {
+        /// Another user already has the new user ID.
+        UidTaken(User),
+
+        /// Another user already has the new name.
+        NameTaken(User),
+
+        /// Files owned by the old user ID will no longer belong to the user,
+        /// and have to be handed over to the new one.
+        OwnershipMoves {
+            /// The user ID the files are owned by now.
+            from: uid_t,
+
+            /// The user ID the files should be owned by.
+            to: uid_t,
+        },
+
+        /// This group lists the user as a member under the old name, so its
+        /// member list has to be updated.
+        MemberListRenamed(Group),
+
+        /// No group has the new primary group ID.
+        PrimaryGroupMissing(gid_t),
+
+        /// The old primary group will have no users left in it: no other
+        /// user has it as their primary group, and it lists no members.
+        PrimaryGroupOrphaned(Group),
+    }
+
+    /// Returns the consequences of changing the user `old` into `new` in the
+    /// database of the given provider, so they can be checked before the
+    /// change is made.
+    ///
+    /// The provider should hold the database as it is before the change.
+    /// The impacts are returned in the order of the variants of
+    /// [`ChangeImpact`]; an empty list means the change only affects the
+    /// user’s own entry.
+    pub fn impact_of_change<P>(old: &User, new: &User, provider: &P) -> Vec<ChangeImpact>
+    where
+        P: AllUsers + AllGroups,
+    {
+        let mut impacts = Vec::new();
+        let uid_changed = old.uid() != new.uid();
+        let name_changed = old.name() != new.name();
+        let gid_changed = old.primary_group_id() != new.primary_group_id();
+
+        let others = || {
+            provider
+                .get_all_users()
+                .filter(|u| u.uid() != old.uid() || u.name() != old.name())
+        };
+
+        if uid_changed {
+            if let Some(user) = others().find(|u| u.uid() == new.uid()) {
+                impacts.push(ChangeImpact::UidTaken(user.clone()));
+            }
+        }
+
+        if name_changed {
+            if let Some(user) = others().find(|u| u.name() == new.name()) {
+                impacts.push(ChangeImpact::NameTaken(user.clone()));
+            }
+        }
+
+        if uid_changed {
+            impacts.push(ChangeImpact::OwnershipMoves {
+                from: old.uid(),
+                to: new.uid(),
+            });
+        }
+
+        if name_changed {
+            impacts.extend(
+                provider
+                    .get_all_groups()
+                    .filter(|g| g.members().iter().any(|m| m == old.name()))
+                    .map(|g| ChangeImpact::MemberListRenamed(g.clone())),
+            );
+        }
+
+        if gid_changed {
+            let new_gid = new.primary_group_id();
+            if !provider.get_all_groups().any(|g| g.gid() == new_gid) {
+                impacts.push(ChangeImpact::PrimaryGroupMissing(new_gid));
+            }
+
+            let old_gid = old.primary_group_id();
+            let still_used = others().any(|u| u.primary_group_id() == old_gid);
+            let old_group = provider.get_all_groups().find(|g| g.gid() == old_gid);
+            if let Some(group) = old_group {
+                if !still_used && group.members().is_empty() {
+                    impacts.push(ChangeImpact::PrimaryGroupOrphaned(group.clone()));
+                }
+            }
+        }
+
+        impacts
+    }
+
+    #[cfg(all(test, feature = "mock"))]
+    mod test {
+        use super::*;
+
+        use mock::MockUsers;
+
+        fn users() -> MockUsers {
+            let mut users = MockUsers::with_current_uid(1000);
+            users.add_user(User::new(1000, "fred", 100));
+            users.add_user(User::new(1001, "bob", 100));
+            users.add_user(User::new(1002, "lonely", 102));
+            users.add_group(Group::new(100, "staff"));
+            users.add_group(Group::new(101, "wheel").add_member("fred"));
+            users.add_group(Group::new(102, "lonely"));
+            users
+        }
+
+        #[test]
+        fn unchanged() {
+            let fred = User::new(1000, "fred", 100);
+            assert!(impact_of_change(&fred, &fred, &users()).is_empty());
+        }
+
+        #[test]
+        fn rename() {
+            let fred = User::new(1000, "fred", 100);
+            let impacts = impact_of_change(&fred, &User::new(1000, "bob", 100), &users());
+
+            assert_eq!(impacts.len(), 2);
+            assert!(matches!(&impacts[0], ChangeImpact::NameTaken(u) if u.uid() == 1001));
+            assert!(matches!(&impacts[1], ChangeImpact::MemberListRenamed(g) if g.gid() == 101));
+        }
+
+        #[test]
+        fn new_uid() {
+            let fred = User::new(1000, "fred", 100);
+            let impacts = impact_of_change(&fred, &User::new(1002, "fred", 100), &users());
+
+            assert_eq!(impacts.len(), 2);
+            assert!(matches!(&impacts[0], ChangeImpact::UidTaken(u) if u.name() == "lonely"));
+            assert!(matches!(
+                impacts[1],
+                ChangeImpact::OwnershipMoves {
+                    from: 1000,
+                    to: 1002
+                }
+            ));
+        }
+
+        #[test]
+        fn primary_group() {
+            let lonely = User::new(1002, "lonely", 102);
+            let impacts = impact_of_change(&lonely, &User::new(1002, "lonely", 999), &users());
+
+            assert_eq!(impacts.len(), 2);
+            assert!(matches!(impacts[0], ChangeImpact::PrimaryGroupMissing(999)));
+            assert!(matches!(&impacts[1], ChangeImpact::PrimaryGroupOrphaned(g) if g.gid() == 102));
+
+            let fred = User::new(1000, "fred", 100);
+            let impacts = impact_of_change(&fred, &User::new(1000, "fred", 101), &users());
+            assert!(impacts.is_empty());
+        }
+    }
+}
+
+mod ownership {
+    use std::fs::{self, Metadata};
+    use std::io;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use libc::{gid_t, uid_t};
+
+    use base::{Group, User};
+    use traits::{Groups, Users};
+
+    /// A file or directory found by [`unexpected_owners`], along with the
+    /// user and group that own it.
+    #[derive(Clone, Debug)]
+    pub struct OwnedEntry {
+        path: PathBuf,
+        metadata: Metadata,
+        owner: Option<Arc<User>>,
+        group: Option<Arc<Group>>,
+    }
+
+    impl OwnedEntry {
+        /// Returns the path of the entry.
+        pub fn path(&self) -> &Path {
+            &self.path
+        }
+
+        /// Returns the metadata of the entry. Symbolic links are not
+        /// followed, so this is the metadata of the link itself.
+        pub fn metadata(&self) -> &Metadata {
+            &self.metadata
+        }
+
+        /// Returns the ID of the user that owns the entry.
+        pub fn uid(&self) -> uid_t {
+            self.metadata.uid()
+        }
+
+        /// Returns the ID of the group that owns the entry.
+        pub fn gid(&self) -> gid_t {
+            self.metadata.gid()
+        }
+
+        /// Returns the user that owns the entry, or `None` if there is no
+        /// user with its ID.
+        pub fn owner(&self) -> Option<&Arc<User>> {
+            self.owner.as_ref()
+        }
+
+        /// Returns the group that owns the entry, or `None` if there is no
+        /// group with its ID.
+        pub fn group(&self) -> Option<&Arc<Group>> {
+            self.group.as_ref()
+        }
+    }
+
+    /// An iterator over the entries of a directory tree whose ownership is
+    /// not as expected, returned by [`unexpected_owners`] and
+    /// [`not_owned_by`].
+    pub struct OwnershipWalk<'a, P: ?Sized, F> {
+        provider: &'a P,
+        expected: F,
+        pending: Vec<PathBuf>,
+        errors: Vec<io::Error>,
+    }
+
+    impl<P, F> Iterator for OwnershipWalk<'_, P, F>
+    where
+        P: Users + Groups + ?Sized,
+        F: FnMut(&OwnedEntry) -> bool,
+    {
+        type Item = io::Result<OwnedEntry>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            loop {
+                if let Some(error) = self.errors.pop() {
+                    return Some(Err(error));
+                }
+
+                let path = self.pending.pop()?;
+                let metadata = match fs::symlink_metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(error) => return Some(Err(error)),
+                };
+
+                if metadata.is_dir() {
+                    match fs::read_dir(&path) {
+                        Ok(entries) => {
+                            for entry in entries {
+                                match entry {
+                                    Ok(entry) => self.pending.push(entry.path()),
+                                    Err(error) => self.errors.push(error),
+                                }
+                            }
+                        }
+                        Err(error) => self.errors.push(error),
+                    }
+                }
+
+                let entry = OwnedEntry {
+                    owner: self.provider.get_user_by_uid(metadata.uid()),
+                    group: self.provider.get_group_by_gid(metadata.gid()),
+                    path,
+                    metadata,
+                };
+                if !(self.expected)(&entry) {
+                    return Some(Ok(entry));
+                }
+            }
+        }
+    }
+
+    /// Walks the directory tree at the given path, returning every entry for
+    /// which `expected` returns `false`.
+    ///
+    /// The owners of every entry are looked up in the given provider, which
+    /// should be one of the [caches](../cache/index.html), as most entries of
+    /// a tree tend to share a few owners. The root is checked too. Symbolic
+    /// links are not followed. The order of the entries is unspecified.
+    ///
+    /// # Errors
+    ///
+    /// The iterator returns `Err` for every entry or directory that cannot
+    /// be read, and carries on with the rest of the tree.
+    pub fn unexpected_owners<R, P, F>(root: R, provider: &P, expected: F) -> OwnershipWalk<'_, P, F>
+    where
+        R: AsRef<Path>,
+        P: Users + Groups + ?Sized,
+        F: FnMut(&OwnedEntry) -> bool,
+    {
+        OwnershipWalk {
+            provider,
+            expected,
+            pending: vec![root.as_ref().to_path_buf()],
+            errors: Vec::new(),
+        }
+    }
+
+    /// Walks the directory tree at the given path, returning every entry
+    /// that is not owned by the given user, or by the given group if there
+    /// is one.
+    ///
+    /// See [`unexpected_owners`].
+    pub fn not_owned_by<'a, R, P>(
+        root: R,
+        provider: &'a P,
+        user: &User,
+        group: Option<&Group>,
+    ) -> OwnershipWalk<'a, P, impl FnMut(&OwnedEntry) -> bool>
+    where
+        R: AsRef<Path>,
+        P: Users + Groups + ?Sized,
+    {
+        let uid = user.uid();
+        let gid = group.map(Group::gid);
+        unexpected_owners(root, provider, move |entry| {
+            entry.uid() == uid && gid.is_none_or(|gid| entry.gid() == gid)
+        })
+    }
+
+    #[cfg(all(test, feature = "mock"))]
+    mod test {
+        use super::*;
+
+        use mock::MockUsers;
+
+        #[test]
+        fn walk() {
+            let root = std::env::temp_dir().join(format!("uzers-ownership-{}", std::process::id()));
+            fs::create_dir_all(root.join("inner")).unwrap();
+            fs::write(root.join("inner").join("file"), "").unwrap();
+            let metadata = fs::metadata(&root).unwrap();
+
+            let mut users = MockUsers::with_current_uid(0);
+            users.add_user(User::new(metadata.uid(), "owner", metadata.gid()));
+            let owner = User::new(metadata.uid(), "owner", metadata.gid());
+            let others = Group::new(metadata.gid().wrapping_add(1), "others");
+
+            assert_eq!(not_owned_by(&root, &users, &owner, None).count(), 0);
+            let entries = not_owned_by(&root, &users, &owner, Some(&others))
+                .collect::<io::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(entries.len(), 3);
+            assert!(entries.iter().all(|e| e.owner().unwrap().name() == "owner"));
+            assert!(entries.iter().all(|e| e.group().is_none()));
+
+            let files = unexpected_owners(&root, &users, |e| e.metadata().is_dir());
+            assert_eq!(files.count(), 1);
+
+            fs::remove_dir_all(&root).unwrap();
+            assert!(unexpected_owners(&root, &users, |_| true)
+                .next()
+                .unwrap()
+                .is_err());
+        }
+    }
+}
+
+#[cfg(all(feature = "utmp", target_os = "linux"))]
+mod stale {
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::time::{Duration, SystemTime};
+
+    use libc::uid_t;
+
+    use base::User;
+    use sessions::LastLogins;
+    use shadow::{get_shadow_by_name, Shadow};
+    use time::{Clock, SystemClock};
+    use traits::AllUsers;
+
+    /// The thresholds that decide when an account counts as stale.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub struct StalePolicy {
+        /// Accounts that have not logged in for longer than this are stale.
+        pub max_inactivity: Duration,
+
+        /// Whether accounts that have never logged in are stale.
+        pub never_logged_in: bool,
+
+        /// Whether accounts whose account or password has expired are stale.
+        pub expired: bool,
+
+        /// Whether accounts that have been locked are stale.
+        pub locked: bool,
+
+        /// Accounts with a user ID below this are system accounts, and are
+        /// never reported.
+        pub min_uid: uid_t,
+    }
+
+    impl Default for StalePolicy {
+        /// Returns a policy that reports human accounts (user IDs of 1000 and
+        /// above) that have been inactive for 90 days, have never logged in,
+        /// or have expired.
+        fn default() -> Self {
+            Self {
+                max_inactivity: Duration::from_secs(90 * 24 * 60 * 60),
+                never_logged_in: true,
+                expired: true,
+                locked: false,
+                min_uid: 1000,
+            }
+        }
+    }
+
+    /// Why an account was reported as stale.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum StaleReason {
+        /// The account has never logged in.
+        NeverLoggedIn,
+
+        /// The account has not logged in for this long.
+        Inactive(Duration),
+
+        /// The account has expired.
+        AccountExpired,
+
+        /// The account’s password has expired.
+        PasswordExpired,
+
+        /// The account has been locked.
+        Locked,
+    }
+
+    /// An account that was reported as stale.
+    #[derive(Clone, Debug)]
+    pub struct StaleAccount {
+        user: User,
+        last_login: Option<SystemTime>,
+        reasons: Vec<StaleReason>,
+    }
+
+    impl StaleAccount {
+        /// Returns the stale account’s user.
+        pub fn user(&self) -> &User {
+            &self.user
+        }
+
+        /// Returns when the account last logged in, if it ever has.
+        pub fn last_login(&self) -> Option<SystemTime> {
+            self.last_login
+        }
+
+        /// Returns every reason the account was reported for.
+        pub fn reasons(&self) -> &[StaleReason] {
+            &self.reasons
+        }
+    }
+
+    /// Returns every account of the given provider that is stale according
+    /// to the policy.
+    ///
+    /// The last login of every account is read from `lastlog`, falling back
+    /// to `wtmp`, which is read only once for the whole report; see
+    /// [`last_login_of`](../sessions/fn.last_login_of.html).
+    /// Expiry and lock status are read from the shadow database, which
+    /// usually requires root privileges: without them, only inactivity is
+    /// reported.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the login databases exist but
+    /// cannot be read.
+    pub fn stale_accounts<P>(provider: &P, policy: &StalePolicy) -> io::Result<Vec<StaleAccount>>
+    where
+        P: AllUsers,
+    {
+        stale_accounts_with_clock(provider, policy, &SystemClock)
+    }
+
+    /// Returns every account of the given provider that is stale according
+    /// to the policy, at the time given by the clock.
+    ///
+    /// See [`stale_accounts`] for how accounts are checked.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the login databases exist but
+    /// cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::{Duration, SystemTime};
+    /// use uzers::UsersSnapshot;
+    /// use uzers::audit::{stale_accounts_with_clock, StalePolicy};
+    /// use uzers::time::MockClock;
+    ///
+    /// // Which accounts will be stale in thirty days?
+    /// let clock = MockClock::new(SystemTime::now() + Duration::from_secs(30 * 24 * 60 * 60));
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// let report = stale_accounts_with_clock(&snapshot, &StalePolicy::default(), &clock).unwrap();
+    /// ```
+    pub fn stale_accounts_with_clock<P, C>(
+        provider: &P,
+        policy: &StalePolicy,
+        clock: &C,
+    ) -> io::Result<Vec<StaleAccount>>
+    where
+        P: AllUsers,
+        C: Clock + ?Sized,
+    {
+        let mut logins = LastLogins::default();
+        stale_accounts_with(
+            provider,
+            policy,
+            clock,
+            |user| Ok(logins.of(user)?.map(|login| login.time())),
+            |user| get_shadow_by_name(user.name()),
+        )
+    }
+
+    /// Returns every stale account, getting the login and shadow information
+    /// of each user from the given functions.
+    pub(crate) fn stale_accounts_with<P, C, L, S>(
+        provider: &P,
+        policy: &StalePolicy,
+        clock: &C,
+        mut last_login: L,
+        mut shadow: S,
+    ) -> io::Result<Vec<StaleAccount>>
+    where
+        P: AllUsers,
+        C: Clock + ?Sized,
+        L: FnMut(&User) -> io::Result<Option<SystemTime>>,
+        S: FnMut(&User) -> Option<Shadow>,
+    {
+        let now = clock.now();
+        let mut accounts = Vec::new();
+
+        for user in provider.get_all_users() {
+            if user.uid() < policy.min_uid {
+                continue;
+            }
+
+            let mut reasons = Vec::new();
+            let login = last_login(user)?;
+
+            match login {
+                None if policy.never_logged_in => reasons.push(StaleReason::NeverLoggedIn),
+                None => {}
+                Some(time) => {
+                    let inactive = now.duration_since(time).unwrap_or_default();
+                    if inactive > policy.max_inactivity {
+                        reasons.push(StaleReason::Inactive(inactive));
+                    }
+                }
+            }
+
+            if let Some(entry) = shadow(user) {
+                if policy.expired && entry.is_account_expired_at(now) {
+                    reasons.push(StaleReason::AccountExpired);
+                }
+                if policy.expired && entry.is_password_expired_at(now) {
+                    reasons.push(StaleReason::PasswordExpired);
+                }
+                if policy.locked && entry.password().as_bytes().starts_with(b"!") {
+                    reasons.push(StaleReason::Locked);
+                }
+            }
+
+            if !reasons.is_empty() {
+                accounts.push(StaleAccount {
+                    user: user.clone(),
+                    last_login: login,
+                    reasons,
+                });
+            }
+        }
+
+        accounts.sort_by_key(|a| a.user.uid());
+        Ok(accounts)
+    }
+}
+
+#[cfg(all(test, feature = "mock", feature = "utmp", target_os = "linux"))]
+mod test {
+    use super::stale::stale_accounts_with;
+    use super::*;
+
+    use std::io;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use base::User;
+    use mock::MockUsers;
+    use time::MockClock;
+
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn day(n: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(n * DAY)
+    }
+
+    fn clock() -> MockClock {
+        MockClock::new(day(1000))
+    }
+
+    fn users() -> MockUsers {
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(0, "root", 0));
+        users.add_user(User::new(1000, "active", 100));
+        users.add_user(User::new(1001, "idle", 100));
+        users.add_user(User::new(1002, "ghost", 100));
+        users
+    }
+
+    fn last_login(user: &User) -> io::Result<Option<SystemTime>> {
+        Ok(match user.uid() {
+            1000 => Some(day(995)),
+            1001 => Some(day(500)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn inactive_and_never_logged_in() {
+        let report = stale_accounts_with(
+            &users(),
+            &StalePolicy::default(),
+            &clock(),
+            last_login,
+            |_| None,
+        )
+        .unwrap();
+
+        let found = report
+            .iter()
+            .map(|a| (a.user().uid(), a.reasons().to_vec()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    1001,
+                    vec![StaleReason::Inactive(Duration::from_secs(500 * DAY))]
+                ),
+                (1002, vec![StaleReason::NeverLoggedIn]),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_logged_in_disabled() {
+        let policy = StalePolicy {
+            never_logged_in: false,
+            ..StalePolicy::default()
+        };
+        let report =
+            stale_accounts_with(&users(), &policy, &clock(), last_login, |_| None).unwrap();
+
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].user().uid(), 1001);
+        assert_eq!(report[0].last_login(), Some(day(500)));
+    }
+
+    #[test]
+    fn time_passes() {
+        let clock = MockClock::new(day(995));
+        let policy = StalePolicy {
+            never_logged_in: false,
+            max_inactivity: Duration::from_secs(100 * DAY),
+            ..StalePolicy::default()
+        };
+
+        let report = || stale_accounts_with(&users(), &policy, &clock, last_login, |_| None);
+        assert_eq!(report().unwrap().len(), 1);
+
+        clock.advance(Duration::from_secs(101 * DAY));
+        assert_eq!(report().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn login_errors() {
+        let result = stale_accounts_with(
+            &users(),
+            &StalePolicy::default(),
+            &clock(),
+            |_| Err(io::Error::from(io::ErrorKind::PermissionDenied)),
+            |_| None,
+        );
+
+        assert!(result.is_err());
+    }
+}
@@ -29,11 +29,17 @@
 //! best bet is to check for them yourself before passing strings into any
 //! functions.
 
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::ffi::{CStr, CString, OsStr, OsString};
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::io;
 use std::mem;
+use std::ops::ControlFlow;
 use std::os::unix::ffi::OsStrExt;
+#[cfg(feature = "zeroize")]
+use std::os::unix::ffi::OsStringExt;
 use std::ptr;
 use std::sync::Arc;
 
@@ -42,6 +48,19 @@ extern crate log;
 #[cfg(feature = "logging")]
 use self::log::trace;
 
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+#[cfg(feature = "zeroize")]
+use self::zeroize::{Zeroize, ZeroizeOnDrop};
+
+#[cfg(feature = "cancellation")]
+use cancel::CancellationToken;
+#[cfg(feature = "test-override")]
+use files::{overridden_groups, overridden_users};
+use ids::{NO_GID, NO_UID};
+use progress::{Progress, WithProgress};
+use source::global_source;
+
 use libc::group as c_group;
 use libc::passwd as c_passwd;
 use libc::{c_char, c_int, gid_t, uid_t};
@@ -49,6 +68,24 @@ use libc::{c_char, c_int, gid_t, uid_t};
 /// Information about a particular user.
 ///
 /// For more information, see the [module documentation](index.html).
+///
+/// ## Equality
+///
+/// A `User` is compared, ordered and hashed as an identity: by its ID, and
+/// then by its name. Two users with the same ID and name are equal even if
+/// their passwords, home directories, shells or other fields differ, so
+/// `==` cannot tell whether a user’s entry in the database has changed.
+/// Compare the fields themselves for that:
+///
+/// ```
+/// use uzers::User;
+/// use uzers::os::unix::UserExt;
+///
+/// let before = User::new(1000, "fred", 100).with_shell("/bin/sh");
+/// let after = User::new(1000, "fred", 100).with_shell("/bin/zsh");
+/// assert_eq!(before, after);
+/// assert_ne!(before.shell(), after.shell());
+/// ```
 #[derive(Clone)]
 pub struct User {
     uid: uid_t,
@@ -112,6 +149,31 @@ impl User {
         &self.name_arc
     }
 
+    /// Returns this user’s name as a `str`, or `None` if it is not valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::User;
+    ///
+    /// let user = User::new(501, "stevedore", 100);
+    /// assert_eq!(user.name_str(), Some("stevedore"));
+    /// ```
+    pub fn name_str(&self) -> Option<&str> {
+        self.name_arc.to_str()
+    }
+
+    /// Returns this user’s name as a string, replacing any bytes that are not
+    /// valid UTF-8 with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        self.name_arc.to_string_lossy()
+    }
+
+    /// Returns the bytes of this user’s name.
+    pub fn name_bytes(&self) -> &[u8] {
+        self.name_arc.as_bytes()
+    }
+
     /// Returns the ID of this user’s primary group.
     ///
     /// # Examples
@@ -146,6 +208,14 @@ impl User {
     pub fn groups(&self) -> Option<Vec<Group>> {
         get_user_groups(self.name(), self.primary_group_id())
     }
+
+    /// Returns a builder for a `User` that sets every field, and checks
+    /// them when the user is built.
+    ///
+    /// See [`UserBuilder`].
+    pub fn builder() -> UserBuilder {
+        UserBuilder::default()
+    }
 }
 
 impl fmt::Debug for User {
@@ -163,9 +233,80 @@ impl fmt::Debug for User {
     }
 }
 
+/// Displays a user as its name followed by its ID, such as `fred (1000)`.
+///
+/// Names that aren’t valid UTF-8 are displayed lossily.
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name().to_string_lossy(), self.uid)
+    }
+}
+
+/// Users are compared by their ID and name only, so two users with the same
+/// ID and name are equal even if their other fields differ.
+impl PartialEq for User {
+    fn eq(&self, other: &Self) -> bool {
+        self.uid == other.uid && self.name_arc == other.name_arc
+    }
+}
+
+impl Eq for User {}
+
+/// Users are ordered by their ID, and then by their name.
+impl Ord for User {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.uid
+            .cmp(&other.uid)
+            .then_with(|| self.name_arc.cmp(&other.name_arc))
+    }
+}
+
+impl PartialOrd for User {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for User {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uid.hash(state);
+        self.name_arc.hash(state);
+    }
+}
+
+/// Zeroizing a user overwrites its encrypted password, which is the only
+/// one of its fields that is secret. The password is also overwritten when
+/// the user is dropped.
+#[cfg(feature = "zeroize")]
+impl Zeroize for User {
+    fn zeroize(&mut self) {
+        self.extras.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for User {}
+
 /// Information about a particular group.
 ///
 /// For more information, see the [module documentation](index.html).
+///
+/// ## Equality
+///
+/// A `Group` is compared, ordered and hashed as an identity: by its ID, and
+/// then by its name. Two groups with the same ID and name are equal even if
+/// their members differ, so `==` cannot tell whether a group’s entry in the
+/// database has changed. Compare the fields themselves for that:
+///
+/// ```
+/// use uzers::Group;
+/// use uzers::os::unix::GroupExt;
+///
+/// let before = Group::new(100, "staff");
+/// let after = Group::new(100, "staff").add_member("fred");
+/// assert_eq!(before, after);
+/// assert_ne!(before.members(), after.members());
+/// ```
 #[derive(Clone)]
 pub struct Group {
     gid: gid_t,
@@ -226,6 +367,39 @@ impl Group {
     pub fn name(&self) -> &OsStr {
         &self.name_arc
     }
+
+    /// Returns this group’s name as a `str`, or `None` if it is not valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::Group;
+    ///
+    /// let group = Group::new(102, "database");
+    /// assert_eq!(group.name_str(), Some("database"));
+    /// ```
+    pub fn name_str(&self) -> Option<&str> {
+        self.name_arc.to_str()
+    }
+
+    /// Returns this group’s name as a string, replacing any bytes that are not
+    /// valid UTF-8 with `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        self.name_arc.to_string_lossy()
+    }
+
+    /// Returns the bytes of this group’s name.
+    pub fn name_bytes(&self) -> &[u8] {
+        self.name_arc.as_bytes()
+    }
+
+    /// Returns a builder for a `Group` that sets every field, and checks
+    /// them when the group is built.
+    ///
+    /// See [`GroupBuilder`].
+    pub fn builder() -> GroupBuilder {
+        GroupBuilder::default()
+    }
 }
 
 impl fmt::Debug for Group {
@@ -247,6 +421,363 @@ impl fmt::Debug for Group {
     }
 }
 
+/// Displays a group as its name followed by its ID and then its members,
+/// such as `staff (100) [fred, wilma]`. The member list is left off for
+/// groups without any members.
+///
+/// Names that aren’t valid UTF-8 are displayed lossily.
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name().to_string_lossy(), self.gid)?;
+
+        if let Some((first, rest)) = self.extras.members.split_first() {
+            write!(f, " [{}", first.to_string_lossy())?;
+            for member in rest {
+                write!(f, ", {}", member.to_string_lossy())?;
+            }
+            write!(f, "]")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Groups are compared by their ID and name only, so two groups with the
+/// same ID and name are equal even if their members differ.
+impl PartialEq for Group {
+    fn eq(&self, other: &Self) -> bool {
+        self.gid == other.gid && self.name_arc == other.name_arc
+    }
+}
+
+impl Eq for Group {}
+
+/// Groups are ordered by their ID, and then by their name.
+impl Ord for Group {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.gid
+            .cmp(&other.gid)
+            .then_with(|| self.name_arc.cmp(&other.name_arc))
+    }
+}
+
+impl PartialOrd for Group {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Group {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.gid.hash(state);
+        self.name_arc.hash(state);
+    }
+}
+
+/// Returns an `InvalidInput` error for a field that cannot be built.
+fn invalid_field(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/// Checks that a field holds none of the given bytes, so that it can be
+/// written to a `passwd` or `group` file and read back the same.
+fn check_field(field: &str, value: &OsStr, forbidden: &[u8]) -> io::Result<()> {
+    match value.as_bytes().iter().find(|b| forbidden.contains(b)) {
+        Some(byte) => Err(invalid_field(format!(
+            "{} {:?} holds the byte {:?}",
+            field, value, *byte as char
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A builder of [`User`]s, returned by [`User::builder`].
+///
+/// Unlike [`User::new`] followed by the `with_` methods of
+/// [`UserExt`](os/unix/trait.UserExt.html), a builder checks the user it
+/// builds. The ID, name and primary group have to be given, the IDs cannot
+/// be the sentinel `(uid_t) -1`, the name cannot be empty, and no field can
+/// hold a colon, newline or NUL byte, so that the user could be written to
+/// a `passwd` file. The fields that are not given have the same dummy values
+/// as with `User::new`.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::User;
+/// use uzers::os::unix::UserExt;
+///
+/// let user = User::builder()
+///     .uid(1000)
+///     .name("fred")
+///     .primary_group(100)
+///     .home_dir("/home/fred")
+///     .shell("/bin/sh")
+///     .gecos("Fred Santa")
+///     .build()
+///     .unwrap();
+/// assert_eq!(user.gecos(), "Fred Santa");
+///
+/// assert!(User::builder().uid(1000).name("fred:x").primary_group(100).build().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct UserBuilder {
+    uid: Option<uid_t>,
+    name: Option<OsString>,
+    primary_group: Option<gid_t>,
+    home_dir: Option<OsString>,
+    shell: Option<OsString>,
+    password: Option<OsString>,
+    gecos: Option<OsString>,
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    times: (libc::time_t, libc::time_t),
+}
+
+impl UserBuilder {
+    /// Sets the user’s ID.
+    pub fn uid(mut self, uid: uid_t) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Sets the user’s name.
+    pub fn name<S: AsRef<OsStr> + ?Sized>(mut self, name: &S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the ID of the user’s primary group.
+    pub fn primary_group(mut self, gid: gid_t) -> Self {
+        self.primary_group = Some(gid);
+        self
+    }
+
+    /// Sets the user’s home directory.
+    pub fn home_dir<S: AsRef<OsStr> + ?Sized>(mut self, home_dir: &S) -> Self {
+        self.home_dir = Some(home_dir.into());
+        self
+    }
+
+    /// Sets the user’s shell.
+    pub fn shell<S: AsRef<OsStr> + ?Sized>(mut self, shell: &S) -> Self {
+        self.shell = Some(shell.into());
+        self
+    }
+
+    /// Sets the user’s encrypted password.
+    pub fn password<S: AsRef<OsStr> + ?Sized>(mut self, password: &S) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Sets the user’s GECOS comment.
+    pub fn gecos<S: AsRef<OsStr> + ?Sized>(mut self, gecos: &S) -> Self {
+        self.gecos = Some(gecos.into());
+        self
+    }
+
+    /// Sets the time the user’s password was last changed.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    pub fn password_change_time(mut self, time: libc::time_t) -> Self {
+        self.times.0 = time;
+        self
+    }
+
+    /// Sets the time the user’s password expires.
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    pub fn password_expire_time(mut self, time: libc::time_t) -> Self {
+        self.times.1 = time;
+        self
+    }
+
+    /// Checks the fields, and builds the user.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an `InvalidInput` error if a field that
+    /// has to be given is missing, or if a field is not valid.
+    pub fn build(self) -> io::Result<User> {
+        self.build_with(b":\n\0")
+    }
+
+    /// Builds the user, checking only what every entry of the system’s
+    /// database satisfies, so that a user read from it can be built again
+    /// even if a field holds a colon or newline.
+    #[cfg(all(feature = "serde", feature = "cache"))]
+    pub(crate) fn build_unchecked(self) -> io::Result<User> {
+        self.build_with(b"\0")
+    }
+
+    fn build_with(self, forbidden: &[u8]) -> io::Result<User> {
+        use self::os::unix::UserExt;
+
+        let uid = self
+            .uid
+            .filter(|&uid| uid != NO_UID)
+            .ok_or_else(|| invalid_field("user has no valid ID".into()))?;
+        let primary_group = self
+            .primary_group
+            .filter(|&gid| gid != NO_GID)
+            .ok_or_else(|| invalid_field("user has no valid primary group".into()))?;
+        let name = self
+            .name
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| invalid_field("user has no name".into()))?;
+
+        check_field("name", &name, forbidden)?;
+        let mut user = User::new(uid, &name, primary_group);
+
+        if let Some(home_dir) = self.home_dir {
+            check_field("home directory", &home_dir, forbidden)?;
+            user = user.with_home_dir(&home_dir);
+        }
+        if let Some(shell) = self.shell {
+            check_field("shell", &shell, forbidden)?;
+            user = user.with_shell(&shell);
+        }
+        if let Some(password) = self.password {
+            check_field("password", &password, forbidden)?;
+            user = user.with_password(&password);
+        }
+        if let Some(gecos) = self.gecos {
+            check_field("GECOS comment", &gecos, forbidden)?;
+            user = user.with_gecos(&gecos);
+        }
+
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        {
+            user.extras.change = self.times.0;
+            user.extras.expire = self.times.1;
+        }
+
+        Ok(user)
+    }
+}
+
+/// A builder of [`Group`]s, returned by [`Group::builder`].
+///
+/// As with a [`UserBuilder`], the group is checked when it’s built: the ID
+/// and name have to be given, the ID cannot be the sentinel `(gid_t) -1`,
+/// the name cannot be empty, and neither the name nor the members can hold
+/// a colon, newline or NUL byte. Members cannot hold commas either.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::Group;
+/// use uzers::os::unix::GroupExt;
+///
+/// let group = Group::builder()
+///     .gid(100)
+///     .name("staff")
+///     .members(["fred", "wilma"])
+///     .member("barney")
+///     .build()
+///     .unwrap();
+/// assert_eq!(group.members().len(), 3);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct GroupBuilder {
+    gid: Option<gid_t>,
+    name: Option<OsString>,
+    members: Vec<OsString>,
+}
+
+impl GroupBuilder {
+    /// Sets the group’s ID.
+    pub fn gid(mut self, gid: gid_t) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Sets the group’s name.
+    pub fn name<S: AsRef<OsStr> + ?Sized>(mut self, name: &S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Adds a member to the group.
+    pub fn member<S: AsRef<OsStr> + ?Sized>(mut self, member: &S) -> Self {
+        self.members.push(member.into());
+        self
+    }
+
+    /// Adds every given member to the group.
+    pub fn members<I>(mut self, members: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<OsStr>,
+    {
+        self.members
+            .extend(members.into_iter().map(|m| m.as_ref().to_owned()));
+        self
+    }
+
+    /// Checks the fields, and builds the group.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an `InvalidInput` error if a field that
+    /// has to be given is missing, or if a field is not valid.
+    pub fn build(self) -> io::Result<Group> {
+        self.build_with(b":\n\0", b",:\n\0")
+    }
+
+    /// Builds the group, checking only what every entry of the system’s
+    /// database satisfies, as with [`UserBuilder::build_unchecked`].
+    #[cfg(all(feature = "serde", feature = "cache"))]
+    pub(crate) fn build_unchecked(self) -> io::Result<Group> {
+        self.build_with(b"\0", b"\0")
+    }
+
+    fn build_with(self, forbidden: &[u8], forbidden_in_members: &[u8]) -> io::Result<Group> {
+        use self::os::unix::GroupExt;
+
+        let gid = self
+            .gid
+            .filter(|&gid| gid != NO_GID)
+            .ok_or_else(|| invalid_field("group has no valid ID".into()))?;
+        let name = self
+            .name
+            .filter(|name| !name.is_empty())
+            .ok_or_else(|| invalid_field("group has no name".into()))?;
+
+        check_field("name", &name, forbidden)?;
+        let mut group = Group::new(gid, &name);
+
+        for member in self.members {
+            check_field("member", &member, forbidden_in_members)?;
+            group = group.add_member(&member);
+        }
+
+        Ok(group)
+    }
+}
+
 /// Reads data from a `*char` field in `c_passwd` or `g_group`. The return
 /// type will be an `Arc<OsStr>` if the text is meant to be shared in a cache,
 /// or a plain `OsString` if it’s not.
@@ -260,6 +791,13 @@ where
     T::from(OsStr::from_bytes(CStr::from_ptr(p).to_bytes()))
 }
 
+/// Overwrites the bytes of the string with zeroes, along with any spare
+/// capacity it has, and leaves it empty.
+#[cfg(feature = "zeroize")]
+pub(crate) fn zeroize_os_string(string: &mut OsString) {
+    mem::take(string).into_vec().zeroize();
+}
+
 /// Reads data from the `c_passwd` and returns it as a `User`.
 unsafe fn passwd_to_user(passwd: c_passwd) -> User {
     #[cfg(feature = "logging")]
@@ -312,9 +850,98 @@ unsafe fn members(groups: *mut *mut c_char) -> Vec<OsString> {
     members
 }
 
+/// The buffer that the C library’s reentrant lookup functions copy the
+/// strings of an entry into.
+///
+/// It may hold a password hash, so with the `zeroize` feature, it’s
+/// overwritten before it’s grown and when it’s dropped, however the lookup
+/// ends.
+pub(crate) struct EntryBuffer(Vec<c_char>);
+
+impl EntryBuffer {
+    /// Creates a buffer of the size that fits most entries.
+    pub(crate) fn new() -> Self {
+        Self(vec![0; 2048])
+    }
+
+    /// Returns the pointer to pass to the lookup function.
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut c_char {
+        self.0.as_mut_ptr()
+    }
+
+    /// Returns the size to pass to the lookup function.
+    pub(crate) fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Replaces the buffer with one twice as large, for a lookup that
+    /// returned `ERANGE`, or returns `None` if it cannot grow any more.
+    pub(crate) fn grow(&mut self) -> Option<()> {
+        let size = self.0.len().checked_mul(2)?;
+        #[cfg(feature = "zeroize")]
+        self.0.zeroize();
+        self.0 = vec![0; size];
+        Some(())
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for EntryBuffer {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Runs one of the C library’s reentrant lookup functions, such as
+/// `getpwuid_r`, growing the buffer it is given until the entry fits, and
+/// converts the entry it fills in while the buffer is still alive.
+///
+/// Returns `Ok(None)` if there is no such entry, and the error the function
+/// returned if the lookup failed.
+fn lookup_entry<T, R>(
+    mut lookup: impl FnMut(*mut T, *mut c_char, usize, *mut *mut T) -> c_int,
+    convert: unsafe fn(T) -> R,
+) -> io::Result<Option<R>> {
+    let mut entry = unsafe { mem::zeroed::<T>() };
+    let mut buf = EntryBuffer::new();
+    let mut result = ptr::null_mut::<T>();
+
+    loop {
+        match lookup(&mut entry, buf.as_mut_ptr(), buf.size(), &mut result) {
+            0 => break,
+            libc::ERANGE => {
+                buf.grow()
+                    .ok_or_else(|| io::Error::from_raw_os_error(libc::ERANGE))?;
+            }
+            // getpwnam_r(3) lists these as ways of saying that the entry
+            // was not found.
+            libc::ENOENT | libc::ESRCH | libc::EBADF | libc::EPERM => return Ok(None),
+            error => return Err(io::Error::from_raw_os_error(error)),
+        }
+    }
+
+    if result.is_null() {
+        // There is no such entry.
+        return Ok(None);
+    }
+
+    if !ptr::eq(result, &entry) {
+        // The result should be the input struct.
+        return Ok(None);
+    }
+
+    Ok(Some(unsafe { convert(result.read()) }))
+}
+
 /// Searches for a `User` with the given ID in the system’s user database.
 /// Returns it if one is found, otherwise returns `None`.
 ///
+/// The sentinel [`NO_UID`](ids/constant.NO_UID.html), `(uid_t) -1`, never
+/// names a user, so this returns `None` for it without asking the system.
+///
+/// Use [`try_get_user_by_uid`] to tell a user that does not exist from a
+/// lookup that failed.
+///
 /// # libc functions used
 ///
 /// - [`getpwuid_r`](https://docs.rs/libc/*/libc/fn.getpwuid_r.html)
@@ -330,43 +957,63 @@ unsafe fn members(groups: *mut *mut c_char) -> Vec<OsString> {
 /// }
 /// ```
 pub fn get_user_by_uid(uid: uid_t) -> Option<User> {
-    let mut passwd = unsafe { mem::zeroed::<c_passwd>() };
-    let mut buf = vec![0; 2048];
-    let mut result = ptr::null_mut::<c_passwd>();
-
-    #[cfg(feature = "logging")]
-    trace!("Running getpwuid_r for user #{}", uid);
-
-    loop {
-        let r =
-            unsafe { libc::getpwuid_r(uid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
-
-        if r != libc::ERANGE {
-            break;
-        }
+    try_get_user_by_uid(uid).ok().flatten()
+}
 
-        let newsize = buf.len().checked_mul(2)?;
-        buf.resize(newsize, 0);
+/// Searches for a `User` with the given ID in the system’s user database.
+/// Returns it if one is found, `Ok(None)` if there is no such user, or the
+/// error the C library reported if the lookup failed, such as when a
+/// directory service could not be reached.
+///
+/// # Errors
+///
+/// This function will return `Err` if the user could not be looked up.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::try_get_user_by_uid;
+///
+/// match try_get_user_by_uid(501) {
+///     Ok(Some(user)) => println!("Found user {:?}", user.name()),
+///     Ok(None)       => println!("User not found"),
+///     Err(e)         => println!("Lookup failed: {}", e),
+/// }
+/// ```
+pub fn try_get_user_by_uid(uid: uid_t) -> io::Result<Option<User>> {
+    if uid == NO_UID {
+        return Ok(None);
     }
 
-    if result.is_null() {
-        // There is no such user, or an error has occurred.
-        // errno gets set if there’s an error.
-        return None;
+    #[cfg(feature = "test-override")]
+    if let Some(users) = overridden_users() {
+        return Ok(users.into_iter().find(|u| u.uid() == uid));
     }
 
-    if result != &mut passwd {
-        // The result of getpwuid_r should be its input passwd.
-        return None;
+    if let Some(source) = global_source() {
+        return Ok(source.user_by_uid(uid));
     }
 
-    let user = unsafe { passwd_to_user(result.read()) };
-    Some(user)
+    sys_user_by_uid(uid)
+}
+
+/// Searches for a `User` with the given ID in the C library’s user database.
+pub(crate) fn sys_user_by_uid(uid: uid_t) -> io::Result<Option<User>> {
+    #[cfg(feature = "logging")]
+    trace!("Running getpwuid_r for user #{}", uid);
+
+    lookup_entry(
+        |passwd, buf, len, result| unsafe { libc::getpwuid_r(uid, passwd, buf, len, result) },
+        passwd_to_user,
+    )
 }
 
 /// Searches for a `User` with the given username in the system’s user database.
 /// Returns it if one is found, otherwise returns `None`.
 ///
+/// Use [`try_get_user_by_name`] to tell a user that does not exist from a
+/// lookup that failed.
+///
 /// # libc functions used
 ///
 /// - [`getpwnam_r`](https://docs.rs/libc/*/libc/fn.getpwnam_r.html)
@@ -382,59 +1029,61 @@ pub fn get_user_by_uid(uid: uid_t) -> Option<User> {
 /// }
 /// ```
 pub fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(username: &S) -> Option<User> {
+    try_get_user_by_name(username).ok().flatten()
+}
+
+/// Searches for a `User` with the given username in the system’s user
+/// database. Returns it if one is found, `Ok(None)` if there is no such
+/// user, or the error the C library reported if the lookup failed.
+///
+/// # Errors
+///
+/// This function will return `Err` if the user could not be looked up.
+pub fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(username: &S) -> io::Result<Option<User>> {
+    #[cfg(feature = "test-override")]
+    if let Some(users) = overridden_users() {
+        return Ok(users.into_iter().find(|u| u.name() == username.as_ref()));
+    }
+
+    if let Some(source) = global_source() {
+        return Ok(source.user_by_name(username.as_ref()));
+    }
+
+    sys_user_by_name(username)
+}
+
+/// Searches for a `User` with the given username in the C library’s user
+/// database.
+pub(crate) fn sys_user_by_name<S: AsRef<OsStr> + ?Sized>(username: &S) -> io::Result<Option<User>> {
     let username = match CString::new(username.as_ref().as_bytes()) {
         Ok(u) => u,
         Err(_) => {
             // The username that was passed in contained a null character,
             // which will match no usernames.
-            return None;
+            return Ok(None);
         }
     };
 
-    let mut passwd = unsafe { mem::zeroed::<c_passwd>() };
-    let mut buf = vec![0; 2048];
-    let mut result = ptr::null_mut::<c_passwd>();
-
     #[cfg(feature = "logging")]
     trace!("Running getpwnam_r for user {:?}", username.as_ref());
 
-    loop {
-        let r = unsafe {
-            libc::getpwnam_r(
-                username.as_ptr(),
-                &mut passwd,
-                buf.as_mut_ptr(),
-                buf.len(),
-                &mut result,
-            )
-        };
-
-        if r != libc::ERANGE {
-            break;
-        }
-
-        let newsize = buf.len().checked_mul(2)?;
-        buf.resize(newsize, 0);
-    }
-
-    if result.is_null() {
-        // There is no such user, or an error has occurred.
-        // errno gets set if there’s an error.
-        return None;
-    }
-
-    if result != &mut passwd {
-        // The result of getpwnam_r should be its input passwd.
-        return None;
-    }
-
-    let user = unsafe { passwd_to_user(result.read()) };
-    Some(user)
+    lookup_entry(
+        |passwd, buf, len, result| unsafe {
+            libc::getpwnam_r(username.as_ptr(), passwd, buf, len, result)
+        },
+        passwd_to_user,
+    )
 }
 
 /// Searches for a `Group` with the given ID in the system’s group database.
 /// Returns it if one is found, otherwise returns `None`.
 ///
+/// The sentinel [`NO_GID`](ids/constant.NO_GID.html), `(gid_t) -1`, never
+/// names a group, so this returns `None` for it without asking the system.
+///
+/// Use [`try_get_group_by_gid`] to tell a group that does not exist from a
+/// lookup that failed.
+///
 /// # libc functions used
 ///
 /// - [`getgrgid_r`](https://docs.rs/libc/*/libc/fn.getgrgid_r.html)
@@ -450,43 +1099,50 @@ pub fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(username: &S) -> Option<User>
 /// }
 /// ```
 pub fn get_group_by_gid(gid: gid_t) -> Option<Group> {
-    let mut passwd = unsafe { mem::zeroed::<c_group>() };
-    let mut buf = vec![0; 2048];
-    let mut result = ptr::null_mut::<c_group>();
-
-    #[cfg(feature = "logging")]
-    trace!("Running getgruid_r for group #{}", gid);
-
-    loop {
-        let r =
-            unsafe { libc::getgrgid_r(gid, &mut passwd, buf.as_mut_ptr(), buf.len(), &mut result) };
-
-        if r != libc::ERANGE {
-            break;
-        }
+    try_get_group_by_gid(gid).ok().flatten()
+}
 
-        let newsize = buf.len().checked_mul(2)?;
-        buf.resize(newsize, 0);
+/// Searches for a `Group` with the given ID in the system’s group database.
+/// Returns it if one is found, `Ok(None)` if there is no such group, or the
+/// error the C library reported if the lookup failed.
+///
+/// # Errors
+///
+/// This function will return `Err` if the group could not be looked up.
+pub fn try_get_group_by_gid(gid: gid_t) -> io::Result<Option<Group>> {
+    if gid == NO_GID {
+        return Ok(None);
     }
 
-    if result.is_null() {
-        // There is no such group, or an error has occurred.
-        // errno gets set if there’s an error.
-        return None;
+    #[cfg(feature = "test-override")]
+    if let Some(groups) = overridden_groups() {
+        return Ok(groups.into_iter().find(|g| g.gid() == gid));
     }
 
-    if result != &mut passwd {
-        // The result of getgrgid_r should be its input struct.
-        return None;
+    if let Some(source) = global_source() {
+        return Ok(source.group_by_gid(gid));
     }
 
-    let group = unsafe { struct_to_group(result.read()) };
-    Some(group)
+    sys_group_by_gid(gid)
+}
+
+/// Searches for a `Group` with the given ID in the C library’s group database.
+pub(crate) fn sys_group_by_gid(gid: gid_t) -> io::Result<Option<Group>> {
+    #[cfg(feature = "logging")]
+    trace!("Running getgruid_r for group #{}", gid);
+
+    lookup_entry(
+        |group, buf, len, result| unsafe { libc::getgrgid_r(gid, group, buf, len, result) },
+        struct_to_group,
+    )
 }
 
 /// Searches for a `Group` with the given group name in the system’s group database.
 /// Returns it if one is found, otherwise returns `None`.
 ///
+/// Use [`try_get_group_by_name`] to tell a group that does not exist from a
+/// lookup that failed.
+///
 /// # libc functions used
 ///
 /// - [`getgrnam_r`](https://docs.rs/libc/*/libc/fn.getgrnam_r.html)
@@ -502,54 +1158,52 @@ pub fn get_group_by_gid(gid: gid_t) -> Option<Group> {
 /// }
 /// ```
 pub fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(groupname: &S) -> Option<Group> {
+    try_get_group_by_name(groupname).ok().flatten()
+}
+
+/// Searches for a `Group` with the given group name in the system’s group
+/// database. Returns it if one is found, `Ok(None)` if there is no such
+/// group, or the error the C library reported if the lookup failed.
+///
+/// # Errors
+///
+/// This function will return `Err` if the group could not be looked up.
+pub fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(groupname: &S) -> io::Result<Option<Group>> {
+    #[cfg(feature = "test-override")]
+    if let Some(groups) = overridden_groups() {
+        return Ok(groups.into_iter().find(|g| g.name() == groupname.as_ref()));
+    }
+
+    if let Some(source) = global_source() {
+        return Ok(source.group_by_name(groupname.as_ref()));
+    }
+
+    sys_group_by_name(groupname)
+}
+
+/// Searches for a `Group` with the given group name in the C library’s group
+/// database.
+pub(crate) fn sys_group_by_name<S: AsRef<OsStr> + ?Sized>(
+    groupname: &S,
+) -> io::Result<Option<Group>> {
     let groupname = match CString::new(groupname.as_ref().as_bytes()) {
         Ok(u) => u,
         Err(_) => {
             // The groupname that was passed in contained a null character,
             // which will match no usernames.
-            return None;
+            return Ok(None);
         }
     };
 
-    let mut group = unsafe { mem::zeroed::<c_group>() };
-    let mut buf = vec![0; 2048];
-    let mut result = ptr::null_mut::<c_group>();
-
     #[cfg(feature = "logging")]
     trace!("Running getgrnam_r for group {:?}", groupname.as_ref());
 
-    loop {
-        let r = unsafe {
-            libc::getgrnam_r(
-                groupname.as_ptr(),
-                &mut group,
-                buf.as_mut_ptr(),
-                buf.len(),
-                &mut result,
-            )
-        };
-
-        if r != libc::ERANGE {
-            break;
-        }
-
-        let newsize = buf.len().checked_mul(2)?;
-        buf.resize(newsize, 0);
-    }
-
-    if result.is_null() {
-        // There is no such group, or an error has occurred.
-        // errno gets set if there’s an error.
-        return None;
-    }
-
-    if result != &mut group {
-        // The result of getgrnam_r should be its input struct.
-        return None;
-    }
-
-    let group = unsafe { struct_to_group(result.read()) };
-    Some(group)
+    lookup_entry(
+        |group, buf, len, result| unsafe {
+            libc::getgrnam_r(groupname.as_ptr(), group, buf, len, result)
+        },
+        struct_to_group,
+    )
 }
 
 /// Returns the user ID for the user running the process.
@@ -789,6 +1443,19 @@ pub fn group_access_list() -> io::Result<Vec<Group>> {
 /// }
 /// ```
 pub fn get_user_groups<S: AsRef<OsStr> + ?Sized>(username: &S, gid: gid_t) -> Option<Vec<Group>> {
+    if let Some(source) = global_source() {
+        return source.user_groups(username.as_ref(), gid);
+    }
+
+    sys_user_groups(username, gid)
+}
+
+/// Returns groups for a provided user name and primary group id from the C
+/// library’s group database.
+pub(crate) fn sys_user_groups<S: AsRef<OsStr> + ?Sized>(
+    username: &S,
+    gid: gid_t,
+) -> Option<Vec<Group>> {
     // MacOS uses i32 instead of gid_t in getgrouplist for unknown reasons
     #[cfg(all(unix, target_os = "macos"))]
     let mut buff: Vec<i32> = vec![0; 1024];
@@ -822,14 +1489,72 @@ pub fn get_user_groups<S: AsRef<OsStr> + ?Sized>(username: &S, gid: gid_t) -> Op
         // allow trivial cast: on macos i is i32, on linux it's already gid_t
         #[allow(trivial_numeric_casts)]
         buff.into_iter()
-            .filter_map(|i| get_group_by_gid(i as gid_t))
+            .filter_map(|i| sys_group_by_gid(i as gid_t).ok().flatten())
             .collect::<Vec<_>>()
             .into()
     }
 }
 
+/// Sets the calling thread’s `errno` to zero, so that a function that only
+/// sets it on failure can be told apart from one that has succeeded.
+#[cfg(not(target_os = "android"))]
+fn clear_errno() {
+    #[cfg(any(target_os = "linux", target_os = "redox", target_os = "dragonfly"))]
+    unsafe {
+        *libc::__errno_location() = 0
+    };
+
+    #[cfg(any(target_os = "macos", target_os = "freebsd"))]
+    unsafe {
+        *libc::__error() = 0
+    };
+
+    #[cfg(any(target_os = "netbsd", target_os = "openbsd"))]
+    unsafe {
+        *libc::__errno() = 0
+    };
+
+    #[cfg(any(target_os = "solaris", target_os = "illumos"))]
+    unsafe {
+        *libc::___errno() = 0
+    };
+
+    #[cfg(target_os = "haiku")]
+    unsafe {
+        *libc::_errnop() = 0
+    };
+}
+
+/// Returns the error that made `getpwent` or `getgrent` return a null
+/// pointer, or `None` if it did so because the end of the database was
+/// reached.
+#[cfg(not(target_os = "android"))]
+fn enumeration_error() -> Option<io::Error> {
+    let error = io::Error::last_os_error();
+    match error.raw_os_error() {
+        None | Some(0) | Some(libc::ENOENT) => None,
+        Some(_) => Some(error),
+    }
+}
+
 /// An iterator over every user present on the system.
-struct AllUsers;
+pub(crate) struct AllUsers {
+    /// The users listed by an overriding file or the global source, which
+    /// are returned instead of the system’s.
+    listed: Option<std::vec::IntoIter<User>>,
+
+    /// The error that ended the iteration early, if any.
+    error: Option<io::Error>,
+}
+
+#[cfg_attr(not(feature = "cache"), allow(dead_code))]
+impl AllUsers {
+    /// Returns the error that ended the iteration early, rather than the
+    /// end of the database being reached.
+    pub(crate) fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
 
 /// Creates a new iterator over every user present on the system.
 ///
@@ -869,12 +1594,41 @@ struct AllUsers;
 /// }
 /// ```
 pub unsafe fn all_users() -> impl Iterator<Item = User> {
+    enumerate_users()
+}
+
+/// Creates a new iterator over every user present on the system, which can
+/// report the error that ended it early.
+pub(crate) unsafe fn enumerate_users() -> AllUsers {
+    #[cfg(feature = "test-override")]
+    if let Some(users) = overridden_users() {
+        return AllUsers {
+            listed: Some(users.into_iter()),
+            error: None,
+        };
+    }
+
+    if let Some(source) = global_source() {
+        return AllUsers {
+            listed: Some(source.all_users().into_iter()),
+            error: None,
+        };
+    }
+
+    sys_all_users()
+}
+
+/// Creates a new iterator over every user in the C library’s database.
+pub(crate) unsafe fn sys_all_users() -> AllUsers {
     #[cfg(feature = "logging")]
     trace!("Running setpwent");
 
     #[cfg(not(target_os = "android"))]
     libc::setpwent();
-    AllUsers
+    AllUsers {
+        listed: None,
+        error: None,
+    }
 }
 
 impl Drop for AllUsers {
@@ -885,6 +1639,10 @@ impl Drop for AllUsers {
 
     #[cfg(not(target_os = "android"))]
     fn drop(&mut self) {
+        if self.listed.is_some() {
+            return;
+        }
+
         #[cfg(feature = "logging")]
         trace!("Running endpwent");
 
@@ -897,17 +1655,27 @@ impl Iterator for AllUsers {
 
     #[cfg(target_os = "android")]
     fn next(&mut self) -> Option<User> {
+        if let Some(users) = &mut self.listed {
+            return users.next();
+        }
+
         None
     }
 
     #[cfg(not(target_os = "android"))]
     fn next(&mut self) -> Option<User> {
+        if let Some(users) = &mut self.listed {
+            return users.next();
+        }
+
         #[cfg(feature = "logging")]
         trace!("Running getpwent");
 
+        clear_errno();
         let result = unsafe { libc::getpwent() };
 
         if result.is_null() {
+            self.error = enumeration_error();
             None
         } else {
             let user = unsafe { passwd_to_user(result.read()) };
@@ -917,7 +1685,23 @@ impl Iterator for AllUsers {
 }
 
 /// An iterator over every group present on the system.
-struct AllGroups;
+pub(crate) struct AllGroups {
+    /// The groups listed by an overriding file or the global source, which
+    /// are returned instead of the system’s.
+    listed: Option<std::vec::IntoIter<Group>>,
+
+    /// The error that ended the iteration early, if any.
+    error: Option<io::Error>,
+}
+
+#[cfg_attr(not(feature = "cache"), allow(dead_code))]
+impl AllGroups {
+    /// Returns the error that ended the iteration early, rather than the
+    /// end of the database being reached.
+    pub(crate) fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+}
 
 /// Creates a new iterator over every group present on the system.
 ///
@@ -957,12 +1741,41 @@ struct AllGroups;
 /// }
 /// ```
 pub unsafe fn all_groups() -> impl Iterator<Item = Group> {
+    enumerate_groups()
+}
+
+/// Creates a new iterator over every group present on the system, which can
+/// report the error that ended it early.
+pub(crate) unsafe fn enumerate_groups() -> AllGroups {
+    #[cfg(feature = "test-override")]
+    if let Some(groups) = overridden_groups() {
+        return AllGroups {
+            listed: Some(groups.into_iter()),
+            error: None,
+        };
+    }
+
+    if let Some(source) = global_source() {
+        return AllGroups {
+            listed: Some(source.all_groups().into_iter()),
+            error: None,
+        };
+    }
+
+    sys_all_groups()
+}
+
+/// Creates a new iterator over every group in the C library’s database.
+pub(crate) unsafe fn sys_all_groups() -> AllGroups {
     #[cfg(feature = "logging")]
     trace!("Running setgrent");
 
     #[cfg(not(target_os = "android"))]
     libc::setgrent();
-    AllGroups
+    AllGroups {
+        listed: None,
+        error: None,
+    }
 }
 
 impl Drop for AllGroups {
@@ -973,6 +1786,10 @@ impl Drop for AllGroups {
 
     #[cfg(not(target_os = "android"))]
     fn drop(&mut self) {
+        if self.listed.is_some() {
+            return;
+        }
+
         #[cfg(feature = "logging")]
         trace!("Running endgrent");
 
@@ -985,17 +1802,27 @@ impl Iterator for AllGroups {
 
     #[cfg(target_os = "android")]
     fn next(&mut self) -> Option<Group> {
+        if let Some(groups) = &mut self.listed {
+            return groups.next();
+        }
+
         None
     }
 
     #[cfg(not(target_os = "android"))]
     fn next(&mut self) -> Option<Group> {
+        if let Some(groups) = &mut self.listed {
+            return groups.next();
+        }
+
         #[cfg(feature = "logging")]
         trace!("Running getgrent");
 
+        clear_errno();
         let result = unsafe { libc::getgrent() };
 
         if result.is_null() {
+            self.error = enumeration_error();
             None
         } else {
             let group = unsafe { struct_to_group(result.read()) };
@@ -1004,6 +1831,142 @@ impl Iterator for AllGroups {
     }
 }
 
+/// Creates a new iterator over every user present on the system, reporting
+/// its progress to the given callback.
+///
+/// The callback is called before every user is read, with the number of
+/// users read so far and the time elapsed since the iteration started, and
+/// once more before the read that finds there are no users left. If it
+/// returns [`ControlFlow::Break`], the iteration ends early, without reading
+/// another user.
+///
+/// # Safety
+///
+/// This function is `unsafe` for the same reasons as
+/// [`all_users`](fn.all_users.html).
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use uzers::all_users_with_progress;
+///
+/// let iter = unsafe {
+///     all_users_with_progress(|progress| {
+///         eprint!("\rRead {} users", progress.count());
+///         ControlFlow::Continue(())
+///     })
+/// };
+/// let users = iter.collect::<Vec<_>>();
+/// ```
+pub unsafe fn all_users_with_progress<F>(progress: F) -> impl Iterator<Item = User>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    WithProgress::new(all_users(), progress)
+}
+
+/// Creates a new iterator over every group present on the system, reporting
+/// its progress to the given callback.
+///
+/// The callback is called before every group is read, with the number of
+/// groups read so far and the time elapsed since the iteration started, and
+/// once more before the read that finds there are no groups left. If it
+/// returns [`ControlFlow::Break`], the iteration ends early, without reading
+/// another group.
+///
+/// # Safety
+///
+/// This function is `unsafe` for the same reasons as
+/// [`all_groups`](fn.all_groups.html).
+///
+/// # Examples
+///
+/// ```
+/// use std::ops::ControlFlow;
+/// use std::time::Duration;
+/// use uzers::all_groups_with_progress;
+///
+/// // Give up after a second
+/// let iter = unsafe {
+///     all_groups_with_progress(|progress| {
+///         if progress.elapsed() > Duration::from_secs(1) {
+///             ControlFlow::Break(())
+///         } else {
+///             ControlFlow::Continue(())
+///         }
+///     })
+/// };
+/// let groups = iter.collect::<Vec<_>>();
+/// ```
+pub unsafe fn all_groups_with_progress<F>(progress: F) -> impl Iterator<Item = Group>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    WithProgress::new(all_groups(), progress)
+}
+
+/// Creates a new iterator over every user present on the system, which ends
+/// early once the given token is cancelled.
+///
+/// # Safety
+///
+/// This function is `unsafe` for the same reasons as
+/// [`all_users`](fn.all_users.html).
+///
+/// # Examples
+///
+/// ```
+/// use uzers::all_users_cancellable;
+/// use uzers::cancel::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// for user in unsafe { all_users_cancellable(&token) } {
+///     println!("User #{} ({:?})", user.uid(), user.name());
+/// }
+/// ```
+#[cfg(feature = "cancellation")]
+pub unsafe fn all_users_cancellable(token: &CancellationToken) -> impl Iterator<Item = User> {
+    let token = token.clone();
+    all_users_with_progress(move |_| {
+        if token.is_cancelled() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
+}
+
+/// Creates a new iterator over every group present on the system, which
+/// ends early once the given token is cancelled.
+///
+/// # Safety
+///
+/// This function is `unsafe` for the same reasons as
+/// [`all_groups`](fn.all_groups.html).
+///
+/// # Examples
+///
+/// ```
+/// use uzers::all_groups_cancellable;
+/// use uzers::cancel::CancellationToken;
+///
+/// let token = CancellationToken::new();
+/// token.cancel();
+/// assert_eq!(unsafe { all_groups_cancellable(&token) }.count(), 0);
+/// ```
+#[cfg(feature = "cancellation")]
+pub unsafe fn all_groups_cancellable(token: &CancellationToken) -> impl Iterator<Item = Group> {
+    let token = token.clone();
+    all_groups_with_progress(move |_| {
+        if token.is_cancelled() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    })
+}
+
 /// OS-specific extensions to users and groups.
 ///
 /// Every OS has a different idea of what data a user or a group comes with.
@@ -1044,6 +2007,8 @@ pub mod os {
         use std::path::{Path, PathBuf};
 
         use super::super::{c_group, c_passwd, from_raw_buf, members, Group};
+        #[cfg(feature = "zeroize")]
+        use super::super::{zeroize_os_string, Zeroize, ZeroizeOnDrop};
 
         /// Unix-specific extensions for `User`s.
         pub trait UserExt {
@@ -1142,6 +2107,23 @@ pub mod os {
             }
         }
 
+        #[cfg(feature = "zeroize")]
+        impl Zeroize for UserExtras {
+            fn zeroize(&mut self) {
+                zeroize_os_string(&mut self.password);
+            }
+        }
+
+        #[cfg(feature = "zeroize")]
+        impl ZeroizeOnDrop for UserExtras {}
+
+        #[cfg(feature = "zeroize")]
+        impl Drop for UserExtras {
+            fn drop(&mut self) {
+                self.zeroize();
+            }
+        }
+
         #[cfg(any(
             target_os = "linux",
             target_os = "android",
@@ -1184,6 +2166,8 @@ pub mod os {
             }
 
             fn with_password<S: AsRef<OsStr> + ?Sized>(mut self, password: &S) -> Self {
+                #[cfg(feature = "zeroize")]
+                zeroize_os_string(&mut self.extras.password);
                 self.extras.password = password.into();
                 self
             }
@@ -1225,6 +2209,32 @@ pub mod os {
                 self
             }
         }
+
+        impl Group {
+            /// Replaces the members of this group with the given names.
+            /// Can be used to construct test groups, which by default come
+            /// with no members.
+            ///
+            /// # Examples
+            ///
+            /// ```
+            /// use uzers::Group;
+            /// use uzers::os::unix::GroupExt;
+            ///
+            /// let group = Group::new(100, "staff")
+            ///     .with_members(["fred", "wilma"])
+            ///     .add_member("barney");
+            /// assert_eq!(group.members(), ["fred", "wilma", "barney"]);
+            /// ```
+            pub fn with_members<I>(mut self, members: I) -> Self
+            where
+                I: IntoIterator,
+                I::Item: AsRef<OsStr>,
+            {
+                self.extras.members = members.into_iter().map(|m| m.as_ref().to_owned()).collect();
+                self
+            }
+        }
     }
 
     /// Extensions to users and groups for BSD platforms.
@@ -1240,6 +2250,8 @@ pub mod os {
     ))]
     pub mod bsd {
         use super::super::{c_passwd, User};
+        #[cfg(feature = "zeroize")]
+        use super::super::{Zeroize, ZeroizeOnDrop};
         use libc::time_t;
         use std::ffi::OsStr;
         use std::path::Path;
@@ -1270,6 +2282,17 @@ pub mod os {
             }
         }
 
+        #[cfg(feature = "zeroize")]
+        impl Zeroize for UserExtras {
+            fn zeroize(&mut self) {
+                self.extras.zeroize();
+            }
+        }
+
+        // The password is kept in the Unix fields, which zeroize themselves.
+        #[cfg(feature = "zeroize")]
+        impl ZeroizeOnDrop for UserExtras {}
+
         impl super::unix::UserExt for User {
             fn home_dir(&self) -> &Path {
                 Path::new(&self.extras.extras.home_dir)
@@ -1294,6 +2317,8 @@ pub mod os {
             }
 
             fn with_password<S: AsRef<OsStr> + ?Sized>(mut self, password: &S) -> Self {
+                #[cfg(feature = "zeroize")]
+                super::super::zeroize_os_string(&mut self.extras.extras.password);
                 self.extras.extras.password = password.into();
                 self
             }
@@ -1390,7 +2415,7 @@ mod test {
         let uid = get_current_uid();
         assert_eq!(
             &*get_current_username().unwrap(),
-            &*get_user_by_uid(uid).unwrap().name()
+            get_user_by_uid(uid).unwrap().name()
         );
     }
 
@@ -1446,7 +2471,7 @@ mod test {
         let user = get_user_by_uid(uid).unwrap();
         let groups = user.groups().unwrap();
         println!("Groups: {:?}", groups);
-        assert!(groups.len() > 0);
+        assert!(!groups.is_empty());
     }
 
     #[test]
@@ -1465,6 +2490,135 @@ mod test {
         let group = get_group_by_name("users\0");
         assert!(group.is_none());
     }
+
+    #[test]
+    fn user_builder() {
+        use base::os::unix::UserExt;
+
+        let user = User::builder()
+            .uid(1000)
+            .name("fred")
+            .primary_group(100)
+            .shell("/bin/sh")
+            .build()
+            .unwrap();
+        assert_eq!(user.uid(), 1000);
+        assert_eq!(user.shell(), std::path::Path::new("/bin/sh"));
+        assert_eq!(user.home_dir(), std::path::Path::new("/var/empty"));
+
+        let invalid = |builder: UserBuilder| {
+            builder
+                .build()
+                .is_err_and(|e| e.kind() == io::ErrorKind::InvalidInput)
+        };
+        let fred = User::builder().uid(1000).name("fred").primary_group(100);
+        assert!(invalid(User::builder().name("fred").primary_group(100)));
+        assert!(invalid(fred.clone().uid(NO_UID)));
+        assert!(invalid(fred.clone().name("")));
+        assert!(invalid(fred.clone().gecos("Fred\nSanta")));
+        assert!(invalid(fred.home_dir("/home/fred:x")));
+    }
+
+    #[test]
+    fn group_members() {
+        use base::os::unix::GroupExt;
+
+        let group = Group::new(100, "staff").add_member("fred");
+        assert_eq!(group.members(), ["fred"]);
+
+        let group = group.with_members(vec![OsString::from("wilma")]);
+        assert_eq!(group.members(), ["wilma"]);
+        assert!(group.with_members(Vec::<&str>::new()).members().is_empty());
+    }
+
+    #[test]
+    fn display() {
+        use base::os::unix::GroupExt;
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(User::new(1000, "fred", 100).to_string(), "fred (1000)");
+        assert_eq!(Group::new(100, "staff").to_string(), "staff (100)");
+
+        let group = Group::new(100, "staff")
+            .add_member("fred")
+            .add_member(OsStr::from_bytes(b"wil\xffma"));
+        assert_eq!(group.to_string(), "staff (100) [fred, wil\u{fffd}ma]");
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        use base::os::unix::UserExt;
+        use base::zeroize::Zeroize;
+
+        let mut user = User::new(1000, "fred", 100).with_password("$6$salt$hash");
+        user.zeroize();
+        assert!(user.password().is_empty());
+        assert_eq!(user.name(), "fred");
+    }
+
+    #[test]
+    fn name_accessors() {
+        let user = User::new(1000, OsStr::from_bytes(b"fr\xffed"), 100);
+        assert_eq!(user.name_str(), None);
+        assert_eq!(user.name_lossy(), "fr\u{fffd}ed");
+        assert_eq!(user.name_bytes(), b"fr\xffed");
+
+        let group = Group::new(100, "staff");
+        assert_eq!(group.name_str(), Some("staff"));
+        assert_eq!(group.name_lossy(), "staff");
+        assert_eq!(group.name_bytes(), b"staff");
+    }
+
+    #[test]
+    fn ordering_and_hashing() {
+        use base::os::unix::GroupExt;
+        use std::collections::{BTreeSet, HashSet};
+
+        let mut users = vec![
+            User::new(1001, "wilma", 100),
+            User::new(1000, "fred", 100),
+            User::new(1000, "barney", 100),
+        ];
+        users.sort();
+        let names: Vec<_> = users.iter().map(|u| u.name().to_owned()).collect();
+        assert_eq!(names, ["barney", "fred", "wilma"]);
+
+        // Only the ID and name take part in comparisons.
+        assert_eq!(User::new(1000, "fred", 100), User::new(1000, "fred", 200));
+        assert_ne!(User::new(1000, "fred", 100), User::new(1001, "fred", 100));
+
+        let set: HashSet<_> = users.iter().cloned().chain(users.clone()).collect();
+        assert_eq!(set.len(), 3);
+
+        let groups: BTreeSet<_> = vec![
+            Group::new(100, "staff"),
+            Group::new(10, "wheel"),
+            Group::new(100, "staff").add_member("fred"),
+        ]
+        .into_iter()
+        .collect();
+        let gids: Vec<_> = groups.iter().map(Group::gid).collect();
+        assert_eq!(gids, [10, 100]);
+    }
+
+    #[test]
+    fn group_builder() {
+        use base::os::unix::GroupExt;
+
+        let group = Group::builder()
+            .gid(100)
+            .name("staff")
+            .members(vec!["fred", "wilma"])
+            .build()
+            .unwrap();
+        assert_eq!(group.members(), ["fred", "wilma"]);
+
+        assert!(Group::builder().name("staff").build().is_err());
+        assert!(Group::builder().gid(100).build().is_err());
+        let staff = Group::builder().gid(100).name("staff");
+        assert!(staff.member("fred,wilma").build().is_err());
+    }
 }
 
 #[cfg(all(test, feature = "test-integration"))]
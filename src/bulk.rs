@@ -0,0 +1,137 @@
+//! Resolving many users at once.
+//!
+//! Programs that scan file systems end up with a great many owner IDs to
+//! turn into users. Resolving them on several threads helps when the users
+//! come from a slow network service, but the caches that make repeated
+//! lookups cheap, such as [`UsersCache`](../cache/struct.UsersCache.html),
+//! cannot be shared between threads.
+//!
+//! [`resolve_owners_parallel`] takes care of this: it gives every thread its
+//! own provider, made by the function it’s given, splits the IDs between the
+//! threads, and merges their answers once they have all finished.
+//!
+//! ```no_run
+//! use uzers::UsersCache;
+//! use uzers::bulk::resolve_owners_parallel;
+//!
+//! let owners = resolve_owners_parallel(vec![0, 1000, 1000, 1001], UsersCache::new, 4);
+//! for (uid, user) in &owners {
+//!     match user {
+//!         Some(user) => println!("{} is {:?}", uid, user.name()),
+//!         None => println!("{} is nobody", uid),
+//!     }
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+use libc::uid_t;
+
+use base::User;
+use traits::Users;
+
+/// Looks up the users with the given IDs on up to `threads` threads, and
+/// returns the user for every distinct ID, or `None` for IDs that have no
+/// user.
+///
+/// Each thread calls `new_provider` once, and uses the provider it returns
+/// for every one of its lookups, so the provider can be a type that cannot
+/// be shared, such as a `UsersCache`. Every distinct ID is looked up only
+/// once, so duplicates cost nothing.
+///
+/// The threads are scoped: they have all finished by the time this function
+/// returns, and a panic in any of them is passed on to the caller.
+///
+/// # Panics
+///
+/// This function panics if a thread cannot be spawned, or if a lookup
+/// panics.
+pub fn resolve_owners_parallel<I, F, P>(
+    uids: I,
+    new_provider: F,
+    threads: usize,
+) -> HashMap<uid_t, Option<Arc<User>>>
+where
+    I: IntoIterator<Item = uid_t>,
+    F: Fn() -> P + Sync,
+    P: Users,
+{
+    let mut uids = uids.into_iter().collect::<Vec<_>>();
+    uids.sort_unstable();
+    uids.dedup();
+
+    if uids.is_empty() {
+        return HashMap::new();
+    }
+
+    let shard_size = uids.len().div_ceil(threads.max(1));
+    let new_provider = &new_provider;
+
+    thread::scope(|scope| {
+        let workers = uids
+            .chunks(shard_size)
+            .map(|shard| {
+                scope.spawn(move || {
+                    let provider = new_provider();
+                    shard
+                        .iter()
+                        .map(|&uid| (uid, provider.get_user_by_uid(uid)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    })
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use mock::MockUsers;
+
+    fn provider() -> MockUsers {
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(1000, "fred", 100));
+        users.add_user(User::new(1001, "bob", 100));
+        users
+    }
+
+    #[test]
+    fn resolves_every_distinct_uid() {
+        let owners = resolve_owners_parallel(vec![1001, 1000, 1000, 4242], provider, 2);
+
+        assert_eq!(owners.len(), 3);
+        assert_eq!(owners[&1000].as_ref().unwrap().name(), "fred");
+        assert_eq!(owners[&1001].as_ref().unwrap().name(), "bob");
+        assert!(owners[&4242].is_none());
+    }
+
+    #[test]
+    fn one_provider_per_thread() {
+        let made = AtomicUsize::new(0);
+        let new_provider = || {
+            made.fetch_add(1, Ordering::SeqCst);
+            provider()
+        };
+
+        let owners = resolve_owners_parallel(0..100, new_provider, 4);
+        assert_eq!(owners.len(), 100);
+        assert_eq!(made.load(Ordering::SeqCst), 4);
+
+        assert!(resolve_owners_parallel(Vec::new(), new_provider, 0).is_empty());
+        assert_eq!(made.load(Ordering::SeqCst), 4);
+    }
+}
@@ -75,25 +75,112 @@
 //! the values themselves don’t count as being stored *in* the cache anymore. So
 //! it can be queried multiple times or go out of scope and the values it
 //! produces are not affected.
+//!
+//! The `RefCell`s do mean that a `UsersCache` cannot be shared between
+//! threads. Use a [`SyncUsersCache`](cache/struct.SyncUsersCache.html), which
+//! keeps its maps behind `RwLock`s instead, to share one cache behind an
+//! `Arc`.
+//!
+//! With the `concurrent-cache` feature, a
+//! [`ConcurrentUsersCache`](cache/struct.ConcurrentUsersCache.html) splits its
+//! maps into shards instead, for programs with many threads looking up
+//! entries at the same time.
+//!
+//! All of them keep every result they have looked up. A program that looks up a
+//! great many distinct users can use a
+//! [`BoundedUsersCache`](cache/struct.BoundedUsersCache.html) instead, which
+//! keeps only the most recently used entries.
 
 use libc::{gid_t, uid_t};
 use std::cell::{Cell, RefCell};
-use std::collections::hash_map::Entry::{Occupied, Vacant};
+use std::collections::hash_map::RandomState;
 use std::collections::{HashMap, HashSet};
-use std::ffi::OsStr;
-use std::hash::Hash;
-use std::ops::Deref;
-use std::sync::Arc;
-
-use base::{all_groups, all_users, Group, User};
-use traits::{AllGroups, AllUsers, Groups, Users};
+use std::error;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::io;
+use std::iter;
+use std::mem;
+use std::ops::{ControlFlow, Deref};
+use std::sync::{Arc, OnceLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::vec;
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{all_groups, all_users, enumerate_groups, enumerate_users, Group, User};
+#[cfg(feature = "cancellation")]
+use cancel::CancellationToken;
+use ids::Uid;
+use progress::{Progress, Tracker};
+use source::UserDbSource;
+use time::{Clock, SystemClock};
+use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, SupplementaryGroups, TryGroups,
+    TryUsers, Users,
+};
+
+mod bounded;
+pub use self::bounded::BoundedUsersCache;
+
+mod builder;
+pub use self::builder::SnapshotBuilder;
+
+mod cached;
+pub use self::cached::Cached;
+
+#[cfg(feature = "concurrent-cache")]
+mod concurrent;
+#[cfg(feature = "concurrent-cache")]
+pub use self::concurrent::ConcurrentUsersCache;
+
+mod dump;
+pub use self::dump::{CacheDump, DumpedId, DumpedName};
+
+mod observer;
+pub use self::observer::{CacheObserver, EntryKind};
+
+mod persist;
+
+mod policy;
+pub use self::policy::CachePolicy;
+
+mod refresh;
+pub use self::refresh::Refresher;
+
+#[cfg(feature = "serde")]
+mod serialized;
+
+mod stats;
+pub use self::stats::CacheStats;
+use self::stats::Counters;
+
+mod sync;
+#[cfg(feature = "global-cache")]
+pub use self::sync::global_cache;
+pub use self::sync::SyncUsersCache;
+
+mod validate;
+pub use self::validate::{ValidationIssue, ValidationReport};
+
+mod view;
+pub use self::view::SnapshotView;
+
+#[cfg(all(feature = "watch", target_os = "linux"))]
+mod watch;
+#[cfg(all(feature = "watch", target_os = "linux"))]
+use self::watch::Watch;
 
 /// A producer of user and group instances that caches every result.
 ///
-/// This cache is **only additive**: it’s not possible to drop it, or erase
-/// selected entries, as when the database may have been modified, it’s best to
-/// start entirely afresh. So to accomplish this, just start using a new
-/// `UsersCache`.
+/// Entries stay cached until they are dropped with
+/// [`invalidate_user`](UsersCache::invalidate_user),
+/// [`invalidate_group`](UsersCache::invalidate_group) or
+/// [`clear`](UsersCache::clear), so a long-running program can pick up
+/// changes to the database without having to replace the cache everywhere
+/// it’s used. A cache created with [`with_policy`](UsersCache::with_policy)
+/// also asks the system again once an entry is older than its time-to-live.
 ///
 /// ## Example
 ///
@@ -117,10 +204,20 @@ use traits::{AllGroups, AllUsers, Groups, Users};
 ///
 /// For thread safety considerations, see the
 /// [`users::cache` module documentation](index.html#caching-multiple-threads-and-mutability).
-#[derive(Default)]
-pub struct UsersCache {
-    users: RefCell<IdNameMap<uid_t, Arc<OsStr>, Arc<User>>>,
-    groups: RefCell<IdNameMap<gid_t, Arc<OsStr>, Arc<Group>>>,
+pub struct UsersCache<H = RandomState> {
+    users: RefCell<IdNameMap<uid_t, Arc<OsStr>, Arc<User>, H>>,
+    groups: RefCell<IdNameMap<gid_t, Arc<OsStr>, Arc<Group>, H>>,
+
+    users_stored: RefCell<StoredAt<uid_t, Arc<OsStr>, H>>,
+    groups_stored: RefCell<StoredAt<gid_t, Arc<OsStr>, H>>,
+    user_groups: RefCell<HashMap<uid_t, (Instant, GroupList), H>>,
+    policy: CachePolicy,
+    refresh_before: Cell<Option<Instant>>,
+    clock: Box<dyn Clock + Send>,
+    source: Option<Arc<dyn UserDbSource>>,
+    stats: Counters,
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    watch: Option<Watch>,
 
     uid: Cell<Option<uid_t>>,
     gid: Cell<Option<gid_t>>,
@@ -135,42 +232,203 @@ pub struct UsersCache {
 /// only want to search based on usernames and group names. There wouldn’t be
 /// much point offering a “User to uid” map, as the uid is present in the
 /// `User` struct!
-struct IdNameMap<I, N, V>
+struct IdNameMap<I, N, V, H = RandomState>
 where
     I: Eq + Hash + Copy,
     N: Eq + Hash,
 {
-    forward: HashMap<I, Option<V>>,
-    backward: HashMap<N, Option<I>>,
+    forward: HashMap<I, Option<V>, H>,
+    backward: HashMap<N, Option<I>, H>,
 }
 
-impl<I, N, V> IdNameMap<I, N, V>
+impl<I, N, V, H> IdNameMap<I, N, V, H>
 where
     I: Eq + Hash + Copy,
     N: Eq + Hash,
+    H: BuildHasher,
 {
+    /// Creates an empty map that hashes its keys with the given hasher.
+    fn with_hasher(hasher: H) -> Self
+    where
+        H: Clone,
+    {
+        Self {
+            forward: HashMap::with_hasher(hasher.clone()),
+            backward: HashMap::with_hasher(hasher),
+        }
+    }
+
     /// Creates a new entry.
     fn insert(&mut self, id: I, name: N, value: V) {
         self.forward.insert(id, Some(value));
         self.backward.insert(name, Some(id));
     }
+
+    /// Removes the entry for an ID, along with every name that leads to it.
+    fn remove(&mut self, id: I) {
+        self.forward.remove(&id);
+        self.backward.retain(|_, entry| *entry != Some(id));
+    }
+
+    /// Removes every entry.
+    fn clear(&mut self) {
+        self.forward.clear();
+        self.backward.clear();
+    }
+
+    /// Keeps only the entries whose values pass the filter, along with the
+    /// lookups that found nothing.
+    fn retain<F: FnMut(&V) -> bool>(&mut self, mut keep: F) {
+        self.forward
+            .retain(|_, value| value.as_ref().is_none_or(&mut keep));
+        let forward = &self.forward;
+        self.backward
+            .retain(|_, id| id.is_none_or(|id| forward.contains_key(&id)));
+    }
+
+    /// Returns roughly how many bytes the maps themselves take up, not
+    /// counting what their keys and values point to.
+    fn table_size(&self) -> usize {
+        let bucket = |key: usize, value: usize| key + value + 1;
+        self.forward.capacity() * bucket(mem::size_of::<I>(), mem::size_of::<Option<V>>())
+            + self.backward.capacity() * bucket(mem::size_of::<N>(), mem::size_of::<Option<I>>())
+    }
+
+    /// Frees the room in the maps that their entries do not use.
+    fn shrink_to_fit(&mut self) {
+        self.forward.shrink_to_fit();
+        self.backward.shrink_to_fit();
+    }
+
+    /// Makes room for at least `additional` more entries, each with one
+    /// name, without growing the maps again.
+    fn reserve(&mut self, additional: usize) {
+        self.forward.reserve(additional);
+        self.backward.reserve(additional);
+    }
+}
+
+impl<I, N, V, H> IdNameMap<I, N, V, H>
+where
+    I: Eq + Hash + Copy,
+    N: Eq + Hash + Clone,
+    V: Clone,
+    H: BuildHasher + Clone,
+{
+    /// Returns a map holding the entries of both maps, with the entries of
+    /// `overlay` replacing those of this one that have the same ID or name.
+    ///
+    /// An entry of this map is dropped when `overlay` has an entry with its
+    /// ID or with one of its names, so no two entries share a name.
+    fn overlaid(&self, overlay: &Self) -> Self {
+        let mut merged = Self::with_hasher(self.forward.hasher().clone());
+
+        let renamed = self
+            .backward
+            .iter()
+            .filter(|(name, _)| overlay.backward.contains_key(*name))
+            .filter_map(|(_, id)| *id)
+            .collect::<HashSet<_>>();
+
+        for (id, value) in &self.forward {
+            if !overlay.forward.contains_key(id) && !renamed.contains(id) {
+                merged.forward.insert(*id, value.clone());
+            }
+        }
+        for (name, id) in &self.backward {
+            let clashes = id.is_some_and(|id| !merged.forward.contains_key(&id));
+            if !clashes && !overlay.backward.contains_key(name) {
+                merged.backward.insert(name.clone(), *id);
+            }
+        }
+
+        for (id, value) in &overlay.forward {
+            merged.forward.insert(*id, value.clone());
+        }
+        for (name, id) in &overlay.backward {
+            merged.backward.insert(name.clone(), *id);
+        }
+
+        merged
+    }
+}
+
+/// The groups a user is a member of, or `None` if the user does not exist.
+type GroupList = Option<Vec<Arc<Group>>>;
+
+/// The times at which the entries of an `IdNameMap` were stored.
+struct StoredAt<I, N, H = RandomState> {
+    ids: HashMap<I, Instant, H>,
+    names: HashMap<N, Instant, H>,
+}
+
+impl<I, N, H: Default> Default for StoredAt<I, N, H> {
+    fn default() -> Self {
+        Self {
+            ids: HashMap::default(),
+            names: HashMap::default(),
+        }
+    }
+}
+
+impl<I, N, H> StoredAt<I, N, H>
+where
+    I: Eq + Hash + Copy,
+    N: Eq + Hash,
+    H: BuildHasher,
+{
+    /// Creates an empty record that hashes its keys with the given hasher.
+    fn with_hasher(hasher: H) -> Self
+    where
+        H: Clone,
+    {
+        Self {
+            ids: HashMap::with_hasher(hasher.clone()),
+            names: HashMap::with_hasher(hasher),
+        }
+    }
+
+    /// Forgets the times of entries that are no longer in the map.
+    fn retain<V>(&mut self, map: &IdNameMap<I, N, V, H>) {
+        self.ids.retain(|id, _| map.forward.contains_key(id));
+        self.names.retain(|name, _| map.backward.contains_key(name));
+    }
+
+    /// Forgets every time.
+    fn clear(&mut self) {
+        self.ids.clear();
+        self.names.clear();
+    }
+
+    /// Makes room for the times of at least `additional` more entries.
+    fn reserve(&mut self, additional: usize) {
+        self.ids.reserve(additional);
+        self.names.reserve(additional);
+    }
 }
 
 // Cannot use `#[derive(Default)]` for `IdNameMap` because [`HashMap`] requires
 // some of its types to implement [`Default`].
-impl<I, N, V> Default for IdNameMap<I, N, V>
+impl<I, N, V, H> Default for IdNameMap<I, N, V, H>
 where
     I: Eq + Hash + Copy,
     N: Eq + Hash,
+    H: Default,
 {
     fn default() -> Self {
         Self {
-            forward: HashMap::new(),
-            backward: HashMap::new(),
+            forward: HashMap::default(),
+            backward: HashMap::default(),
         }
     }
 }
 
+impl Default for UsersCache {
+    fn default() -> Self {
+        Self::with_policy(CachePolicy::default())
+    }
+}
+
 impl UsersCache {
     /// Creates a new empty cache.
     ///
@@ -185,6 +443,106 @@ impl UsersCache {
         Self::default()
     }
 
+    /// Creates a new empty cache with room for at least the given numbers of
+    /// users and groups, so that looking up that many does not have to grow
+    /// its maps along the way.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersCache;
+    ///
+    /// let cache = UsersCache::with_capacity(10_000, 500);
+    /// ```
+    pub fn with_capacity(users: usize, groups: usize) -> Self {
+        let cache = Self::new();
+        cache.users.borrow_mut().reserve(users);
+        cache.users_stored.borrow_mut().reserve(users);
+        cache.groups.borrow_mut().reserve(groups);
+        cache.groups_stored.borrow_mut().reserve(groups);
+        cache
+    }
+
+    /// Creates a new empty cache that keeps entries only as long as the
+    /// given policy allows.
+    ///
+    /// Entries that have expired are looked up again when they are next
+    /// asked for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use uzers::cache::{CachePolicy, UsersCache};
+    ///
+    /// let cache = UsersCache::with_policy(CachePolicy {
+    ///     positive_ttl: Some(Duration::from_secs(300)),
+    ///     ..CachePolicy::default()
+    /// });
+    /// ```
+    pub fn with_policy(policy: CachePolicy) -> Self {
+        Self::with_policy_and_clock(policy, SystemClock)
+    }
+
+    /// Creates a new empty cache that keeps entries only as long as the
+    /// given policy allows, measuring their age with the given clock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{Duration, SystemTime};
+    /// use uzers::cache::{CachePolicy, UsersCache};
+    /// use uzers::time::MockClock;
+    ///
+    /// let clock = MockClock::new(SystemTime::now());
+    /// let policy = CachePolicy {
+    ///     positive_ttl: Some(Duration::from_secs(300)),
+    ///     ..CachePolicy::default()
+    /// };
+    /// let cache = UsersCache::with_policy_and_clock(policy, clock.clone());
+    ///
+    /// // Every entry has now expired.
+    /// clock.advance(Duration::from_secs(301));
+    /// ```
+    pub fn with_policy_and_clock<C>(policy: CachePolicy, clock: C) -> Self
+    where
+        C: Clock + Send + 'static,
+    {
+        Self::from_parts(policy, Box::new(clock), RandomState::new())
+    }
+
+    /// Creates a new empty cache that is cleared whenever `/etc/passwd`,
+    /// `/etc/group` or `/etc/nsswitch.conf` changes, so that a long-running
+    /// program picks up new and changed users without being restarted.
+    ///
+    /// The changes are noticed with inotify, which is checked, without
+    /// blocking, at the start of every lookup. Users and groups that come
+    /// from a directory service rather than these files are not watched; use
+    /// a [policy](UsersCache::with_policy) with a time-to-live for those.
+    ///
+    /// This is only available on Linux, with the `watch` feature.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an error if the inotify watch cannot be set up,
+    /// for example because the per-user limit of watches has been reached.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::cache::UsersCache;
+    ///
+    /// let cache = UsersCache::with_watch().expect("Cannot watch /etc");
+    /// ```
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    pub fn with_watch() -> std::io::Result<Self> {
+        let watch = Watch::new(std::path::Path::new(watch::ETC), watch::FILES)?;
+        Ok(Self {
+            watch: Some(watch),
+            ..Self::new()
+        })
+    }
+
     /// Creates a new cache preloaded with all users present on the system.
     ///
     /// This is a legacy method for code where `UsersCache` is required.
@@ -218,6 +576,7 @@ impl UsersCache {
     pub unsafe fn with_all_users() -> Self {
         let cache = Self::new();
 
+        let now = cache.clock.instant();
         for user in all_users() {
             let uid = user.uid();
             let user_arc = Arc::new(user);
@@ -226,151 +585,617 @@ impl UsersCache {
                 Arc::clone(&user_arc.name_arc),
                 Arc::clone(&user_arc),
             );
+
+            let mut stored = cache.users_stored.borrow_mut();
+            stored.ids.insert(uid, now);
+            stored.names.insert(Arc::clone(&user_arc.name_arc), now);
         }
 
         cache
     }
 }
 
-// TODO: stop using ‘Arc::from’ with entry API
-// The ‘get_*_by_name’ functions below create a new Arc before even testing if
-// the user exists in the cache, essentially creating an unnecessary Arc.
-// https://internals.rust-lang.org/t/pre-rfc-abandonning-morals-in-the-name-of-performance-the-raw-entry-api/7043/51
-// https://github.com/rust-lang/rfcs/pull/1769
+impl<H: BuildHasher + Clone> UsersCache<H> {
+    /// Creates a new empty cache whose maps hash their keys with the given
+    /// hasher instead of the default SipHash.
+    ///
+    /// A faster hasher, such as FxHash or aHash, pays off when a program
+    /// resolves tens of thousands of users. The IDs and names being hashed
+    /// come from the users database, so there is little to gain from
+    /// SipHash’s resistance to collisions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    /// use uzers::cache::UsersCache;
+    ///
+    /// let cache = UsersCache::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+    /// ```
+    pub fn with_hasher(hasher: H) -> Self {
+        Self::with_policy_and_hasher(CachePolicy::default(), hasher)
+    }
 
-impl Users for UsersCache {
-    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
-        let mut users = self.users.borrow_mut();
+    /// Creates a new empty cache that keeps entries only as long as the
+    /// given policy allows, and whose maps hash their keys with the given
+    /// hasher.
+    pub fn with_policy_and_hasher(policy: CachePolicy, hasher: H) -> Self {
+        Self::from_parts(policy, Box::new(SystemClock), hasher)
+    }
 
-        let entry = match users.forward.entry(uid) {
-            Vacant(e) => e,
-            Occupied(e) => return e.get().clone(),
-        };
+    /// Creates a new cache that holds every user and group in the given
+    /// snapshot, and asks the system only for the ones it’s missing.
+    ///
+    /// This combines the consistency of a snapshot for the users it has
+    /// read with the coverage of a lazy cache for any stragglers. The cache
+    /// shares the snapshot’s `Arc`s, so nothing is copied, and takes the
+    /// current and effective IDs of the process from it as well.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::{Users, UsersCache, UsersSnapshot};
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::only_users(|u| u.uid() >= 1000) };
+    /// let cache = UsersCache::from_snapshot(&snapshot);
+    ///
+    /// // Answered from the snapshot’s entries, or else by the system.
+    /// let root = cache.get_user_by_uid(0);
+    /// ```
+    pub fn from_snapshot(snapshot: &UsersSnapshot<H>) -> Self {
+        let cache = Self::with_hasher(snapshot.users.forward.hasher().clone());
+        let now = cache.clock.instant();
+
+        {
+            let mut users = cache.users.borrow_mut();
+            let mut stored = cache.users_stored.borrow_mut();
+            for user in snapshot.users.forward.values().flatten() {
+                let name = Arc::clone(&user.name_arc);
+                users.insert(user.uid(), Arc::clone(&name), Arc::clone(user));
+                stored.ids.insert(user.uid(), now);
+                stored.names.insert(name, now);
+            }
+        }
 
-        if let Some(user) = super::get_user_by_uid(uid) {
-            let newsername = Arc::clone(&user.name_arc);
-            let user_arc = Arc::new(user);
+        {
+            let mut groups = cache.groups.borrow_mut();
+            let mut stored = cache.groups_stored.borrow_mut();
+            for group in snapshot.groups.forward.values().flatten() {
+                let name = Arc::clone(&group.name_arc);
+                groups.insert(group.gid(), Arc::clone(&name), Arc::clone(group));
+                stored.ids.insert(group.gid(), now);
+                stored.names.insert(name, now);
+            }
+        }
 
-            entry.insert(Some(Arc::clone(&user_arc)));
-            users.backward.insert(newsername, Some(uid));
+        cache.uid.set(Some(snapshot.uid));
+        cache.gid.set(Some(snapshot.gid));
+        cache.euid.set(Some(snapshot.euid));
+        cache.egid.set(Some(snapshot.egid));
+        cache
+    }
 
-            Some(user_arc)
-        } else {
-            entry.insert(None);
-            None
+    /// Creates a new empty cache out of everything it’s made of.
+    fn from_parts(policy: CachePolicy, clock: Box<dyn Clock + Send>, hasher: H) -> Self {
+        Self {
+            users: RefCell::new(IdNameMap::with_hasher(hasher.clone())),
+            groups: RefCell::new(IdNameMap::with_hasher(hasher.clone())),
+            users_stored: RefCell::new(StoredAt::with_hasher(hasher.clone())),
+            groups_stored: RefCell::new(StoredAt::with_hasher(hasher.clone())),
+            user_groups: RefCell::new(HashMap::with_hasher(hasher)),
+            policy,
+            refresh_before: Cell::default(),
+            clock,
+            source: None,
+            stats: Counters::default(),
+            #[cfg(all(feature = "watch", target_os = "linux"))]
+            watch: None,
+            uid: Cell::default(),
+            gid: Cell::default(),
+            euid: Cell::default(),
+            egid: Cell::default(),
         }
     }
 
-    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
-        let mut users = self.users.borrow_mut();
+    /// Makes the cache look entries up in the given source, instead of
+    /// through the top-level functions.
+    #[cfg(all(test, feature = "cache-sim"))]
+    pub(crate) fn with_source<S: UserDbSource + 'static>(mut self, source: S) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
 
-        let entry = match users.backward.entry(Arc::from(username.as_ref())) {
-            Vacant(e) => e,
-            Occupied(e) => return (*e.get()).and_then(|uid| users.forward[&uid].clone()),
-        };
+    /// Installs an observer that is told about every lookup the cache
+    /// answers, and every lookup it passes on to the system.
+    ///
+    /// See [`CacheObserver`] for an example.
+    pub fn with_observer<O: CacheObserver + 'static>(mut self, observer: O) -> Self {
+        self.stats.set_observer(Arc::new(observer));
+        self
+    }
 
-        if let Some(user) = super::get_user_by_name(username) {
-            let uid = user.uid();
-            let user_arc = Arc::new(user);
+    /// Makes the cache answer with the given IDs when asked for the current
+    /// and effective IDs of the process, instead of asking the system.
+    ///
+    /// The users and groups with those IDs are still looked up as usual, so
+    /// this lets code that asks for the current user be tested as another
+    /// user, without the process having to switch to that user.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{Groups, Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new().with_current_ids(0, 0, 0, 0);
+    /// assert_eq!(cache.get_current_uid(), 0);
+    /// assert_eq!(cache.get_effective_gid(), 0);
+    /// ```
+    pub fn with_current_ids(self, uid: uid_t, gid: gid_t, euid: uid_t, egid: gid_t) -> Self {
+        self.uid.set(Some(uid));
+        self.gid.set(Some(gid));
+        self.euid.set(Some(euid));
+        self.egid.set(Some(egid));
+        self
+    }
 
-            entry.insert(Some(uid));
-            users.forward.insert(uid, Some(Arc::clone(&user_arc)));
+    /// Returns the policy that decides how long entries stay valid.
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
 
-            Some(user_arc)
-        } else {
-            entry.insert(None);
-            None
-        }
+    /// Returns how many lookups the cache has answered itself, and how many
+    /// it had to pass on to the system.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
     }
 
-    fn get_current_uid(&self) -> uid_t {
-        self.uid.get().unwrap_or_else(|| {
-            let uid = super::get_current_uid();
-            self.uid.set(Some(uid));
-            uid
-        })
+    /// Sets the counts returned by [`stats`](UsersCache::stats) back to zero.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
     }
 
-    fn get_current_username(&self) -> Option<Arc<OsStr>> {
-        let uid = self.get_current_uid();
-        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    /// Returns a copy of everything the cache holds, including lookups that
+    /// found nothing and the IDs of the process it has asked for.
+    pub fn dump(&self) -> CacheDump {
+        let (users, user_names) = self.users.borrow().dump(|u| u.name());
+        let (groups, group_names) = self.groups.borrow().dump(|g| g.name());
+
+        CacheDump {
+            users,
+            user_names,
+            groups,
+            group_names,
+            current_uid: self.uid.get(),
+            effective_uid: self.euid.get(),
+            current_gid: self.gid.get(),
+            effective_gid: self.egid.get(),
+        }
     }
 
-    fn get_effective_uid(&self) -> uid_t {
-        self.euid.get().unwrap_or_else(|| {
-            let uid = super::get_effective_uid();
-            self.euid.set(Some(uid));
-            uid
-        })
+    /// Clears the cache if it’s [watching](UsersCache::with_watch) the
+    /// database files and they have changed.
+    #[cfg(all(feature = "watch", target_os = "linux"))]
+    fn clear_if_changed(&self) {
+        if self.watch.as_ref().is_some_and(Watch::changed) {
+            self.clear();
+        }
     }
 
-    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
-        let uid = self.get_effective_uid();
-        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    #[cfg(not(all(feature = "watch", target_os = "linux")))]
+    fn clear_if_changed(&self) {}
+
+    /// Returns whether an entry stored at the given time, if any, is still
+    /// valid.
+    ///
+    /// While [`refresh_older_than`](UsersCache::refresh_older_than) is under
+    /// way, entries stored before its cutoff are not valid either.
+    fn is_fresh(&self, stored_at: Option<&Instant>, found: bool) -> bool {
+        match stored_at {
+            Some(at) => {
+                self.refresh_before.get().is_none_or(|cutoff| *at >= cutoff)
+                    && self.policy.is_fresh(&*self.clock, *at, found)
+            }
+            None => true,
+        }
     }
-}
 
-impl Groups for UsersCache {
-    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
-        let mut groups = self.groups.borrow_mut();
+    /// Looks up the user with the given ID in the cache’s source, or through
+    /// the top-level functions if it has none.
+    fn fetch_user_by_uid(&self, uid: uid_t) -> io::Result<Option<User>> {
+        match &self.source {
+            Some(source) => Ok(source.user_by_uid(uid)),
+            None => super::try_get_user_by_uid(uid),
+        }
+    }
 
-        let entry = match groups.forward.entry(gid) {
-            Vacant(e) => e,
-            Occupied(e) => return e.get().clone(),
-        };
+    /// Looks up the user with the given name in the cache’s source.
+    fn fetch_user_by_name(&self, username: &OsStr) -> io::Result<Option<User>> {
+        match &self.source {
+            Some(source) => Ok(source.user_by_name(username)),
+            None => super::try_get_user_by_name(username),
+        }
+    }
 
-        if let Some(group) = super::get_group_by_gid(gid) {
-            let new_group_name = Arc::clone(&group.name_arc);
-            let group_arc = Arc::new(group);
+    /// Looks up the group with the given ID in the cache’s source.
+    fn fetch_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Group>> {
+        match &self.source {
+            Some(source) => Ok(source.group_by_gid(gid)),
+            None => super::try_get_group_by_gid(gid),
+        }
+    }
 
-            entry.insert(Some(Arc::clone(&group_arc)));
-            groups.backward.insert(new_group_name, Some(gid));
+    /// Looks up the group with the given name in the cache’s source.
+    fn fetch_group_by_name(&self, group_name: &OsStr) -> io::Result<Option<Group>> {
+        match &self.source {
+            Some(source) => Ok(source.group_by_name(group_name)),
+            None => super::try_get_group_by_name(group_name),
+        }
+    }
 
-            Some(group_arc)
-        } else {
-            entry.insert(None);
-            None
+    /// Looks up the groups of the given user in the cache’s source.
+    fn fetch_user_groups(&self, username: &OsStr, gid: gid_t) -> Option<Vec<Group>> {
+        match &self.source {
+            Some(source) => source.user_groups(username, gid),
+            None => super::get_user_groups(username, gid),
         }
     }
 
-    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
-        let mut groups = self.groups.borrow_mut();
+    /// Returns when the entry for the user with the given ID was looked up,
+    /// or `None` if it’s not cached.
+    ///
+    /// The time is read from the monotonic side of the cache’s clock, as
+    /// returned by [`Clock::instant`], so that it can be compared with
+    /// other instants but not turned into a date.
+    pub fn user_fetched_at(&self, uid: uid_t) -> Option<Instant> {
+        self.users_stored.borrow().ids.get(&uid).copied()
+    }
 
-        let entry = match groups.backward.entry(Arc::from(group_name.as_ref())) {
-            Vacant(e) => e,
-            Occupied(e) => {
-                return (*e.get()).and_then(|gid| groups.forward[&gid].as_ref().cloned())
-            }
+    /// Returns when the entry for the group with the given ID was looked up,
+    /// or `None` if it’s not cached.
+    ///
+    /// See [`user_fetched_at`](UsersCache::user_fetched_at).
+    pub fn group_fetched_at(&self, gid: gid_t) -> Option<Instant> {
+        self.groups_stored.borrow().ids.get(&gid).copied()
+    }
+
+    /// Looks up every cached user and group that was looked up more than
+    /// `age` ago again, leaving the newer entries alone.
+    ///
+    /// This gives control over how fresh the entries are to the program,
+    /// whatever the cache’s [policy](UsersCache::policy), without having to
+    /// [`clear`](UsersCache::clear) the whole cache. The lookups made count
+    /// as misses, and their entries as expired.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use uzers::{Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new();
+    /// cache.get_user_by_uid(1000);
+    ///
+    /// // ... a while later ...
+    ///
+    /// cache.refresh_older_than(Duration::from_secs(600));
+    /// ```
+    pub fn refresh_older_than(&self, age: Duration) {
+        let cutoff = match self.clock.instant().checked_sub(age) {
+            Some(cutoff) => cutoff,
+            None => return,
         };
 
-        if let Some(group) = super::get_group_by_name(group_name) {
-            let group_arc = Arc::new(group.clone());
-            let gid = group.gid();
+        fn older<K: Clone>(
+            stamps: &HashMap<K, Instant, impl BuildHasher>,
+            cutoff: Instant,
+        ) -> Vec<K> {
+            stamps
+                .iter()
+                .filter(|(_, at)| **at < cutoff)
+                .map(|(key, _)| key.clone())
+                .collect()
+        }
 
-            entry.insert(Some(gid));
-            groups.forward.insert(gid, Some(Arc::clone(&group_arc)));
+        let (uids, user_names) = {
+            let stored = self.users_stored.borrow();
+            (older(&stored.ids, cutoff), older(&stored.names, cutoff))
+        };
+        let (gids, group_names) = {
+            let stored = self.groups_stored.borrow();
+            (older(&stored.ids, cutoff), older(&stored.names, cutoff))
+        };
 
-            Some(group_arc)
-        } else {
-            entry.insert(None);
-            None
+        self.refresh_before.set(Some(cutoff));
+        for uid in uids {
+            self.get_user_by_uid(uid);
+        }
+        for name in user_names {
+            self.get_user_by_name(&*name);
+        }
+        for gid in gids {
+            self.get_group_by_gid(gid);
         }
+        for name in group_names {
+            self.get_group_by_name(&*name);
+        }
+        self.refresh_before.set(None);
     }
 
-    fn get_current_gid(&self) -> gid_t {
-        self.gid.get().unwrap_or_else(|| {
-            let gid = super::get_current_gid();
-            self.gid.set(Some(gid));
-            gid
-        })
-    }
+    /// Returns a snapshot of every user and group the cache has found so far.
+    ///
+    /// Unlike the cache, the snapshot can be iterated, and shared between
+    /// threads. It holds the same `Arc`s as the cache, so nothing is copied,
+    /// and the cache can still be used afterwards. Entries that have expired
+    /// according to the cache’s policy are left out.
+    ///
+    /// The snapshot only holds what the cache has looked up, so it’s not
+    /// [complete](UsersSnapshot::is_complete).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::Arc;
+    /// use std::thread;
+    /// use uzers::{AllUsers, Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new();
+    /// cache.preload_users(vec![0, 1000]);
+    ///
+    /// let snapshot = Arc::new(cache.freeze());
+    /// thread::spawn(move || {
+    ///     for user in snapshot.get_all_users() {
+    ///         println!("{:?}", user.name());
+    ///     }
+    /// });
+    /// ```
+    pub fn freeze(&self) -> UsersSnapshot<H> {
+        let mut snapshot = UsersSnapshot {
+            users: IdNameMap::with_hasher(self.users.borrow().forward.hasher().clone()),
+            groups: IdNameMap::with_hasher(self.groups.borrow().forward.hasher().clone()),
+            uid: self.get_current_uid(),
+            gid: self.get_current_gid(),
+            euid: self.get_effective_uid(),
+            egid: self.get_effective_gid(),
+            incomplete: true,
+            memberships: OnceLock::new(),
+        };
 
-    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
-        let gid = self.get_current_gid();
-        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+        let users = self.users.borrow();
+        let users_stored = self.users_stored.borrow();
+        let groups = self.groups.borrow();
+        let groups_stored = self.groups_stored.borrow();
+
+        for (uid, user) in &users.forward {
+            if let Some(user) = user {
+                if self.is_fresh(users_stored.ids.get(uid), true) {
+                    let name = Arc::clone(&user.name_arc);
+                    snapshot.users.insert(*uid, name, Arc::clone(user));
+                }
+            }
+        }
+
+        for (gid, group) in &groups.forward {
+            if let Some(group) = group {
+                if self.is_fresh(groups_stored.ids.get(gid), true) {
+                    let name = Arc::clone(&group.name_arc);
+                    snapshot.groups.insert(*gid, name, Arc::clone(group));
+                }
+            }
+        }
+
+        snapshot
     }
 
-    fn get_effective_gid(&self) -> gid_t {
+    /// Looks up every user with one of the given IDs that is not cached
+    /// yet, so that later lookups of them are answered from the cache.
+    ///
+    /// This is useful for gathering the latency of lookups in one place,
+    /// such as after scanning a directory for the owners of its files and
+    /// before printing them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::{Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new();
+    /// cache.preload_users(vec![0, 1000, 1001]);
+    ///
+    /// // Answered from the cache.
+    /// let root = cache.get_user_by_uid(0);
+    /// ```
+    pub fn preload_users<I: IntoIterator<Item = uid_t>>(&self, uids: I) {
+        for uid in uids {
+            self.get_user_by_uid(uid);
+        }
+    }
+
+    /// Looks up every group with one of the given IDs that is not cached
+    /// yet, so that later lookups of them are answered from the cache.
+    ///
+    /// See [`preload_users`](UsersCache::preload_users).
+    pub fn preload_groups<I: IntoIterator<Item = gid_t>>(&self, gids: I) {
+        for gid in gids {
+            self.get_group_by_gid(gid);
+        }
+    }
+
+    /// Drops the cached entry for the user with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    ///
+    /// Lookups of names that found nothing are not tied to any ID, so they
+    /// are only dropped by [`clear`](UsersCache::clear).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::{Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new();
+    /// let before = cache.get_user_by_uid(1000);
+    ///
+    /// // ... the administrator changes user #1000 ...
+    ///
+    /// cache.invalidate_user(1000);
+    /// let after = cache.get_user_by_uid(1000);
+    /// ```
+    pub fn invalidate_user(&self, uid: uid_t) {
+        let mut users = self.users.borrow_mut();
+        users.remove(uid);
+        self.users_stored.borrow_mut().retain(&users);
+        self.user_groups.borrow_mut().remove(&uid);
+    }
+
+    /// Drops the cached entry for the group with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    ///
+    /// Lookups of names that found nothing are not tied to any ID, so they
+    /// are only dropped by [`clear`](UsersCache::clear). The cached group
+    /// lists of users that are members of the group are dropped as well.
+    pub fn invalidate_group(&self, gid: gid_t) {
+        let mut groups = self.groups.borrow_mut();
+        groups.remove(gid);
+        self.groups_stored.borrow_mut().retain(&groups);
+        self.user_groups
+            .borrow_mut()
+            .retain(|_, (_, list)| !list.iter().flatten().any(|g| g.gid() == gid));
+    }
+
+    /// Drops every cached user and group, including lookups that found
+    /// nothing.
+    ///
+    /// The current and effective IDs of the process are kept, as they do not
+    /// come from the users database.
+    pub fn clear(&self) {
+        self.users.borrow_mut().clear();
+        self.groups.borrow_mut().clear();
+        self.users_stored.borrow_mut().clear();
+        self.groups_stored.borrow_mut().clear();
+        self.user_groups.borrow_mut().clear();
+    }
+
+    /// Returns every group the user with the given ID is a member of,
+    /// including their primary group, or `None` if there is no such user.
+    ///
+    /// Asking the system for a user’s groups with `getgrouplist` can be far
+    /// slower than looking up the user, especially when the groups come
+    /// from a directory service, so the list is cached by user ID like any
+    /// other entry and follows the cache’s policy. It’s dropped along with
+    /// the user by [`invalidate_user`](UsersCache::invalidate_user).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::{Users, UsersCache};
+    ///
+    /// let cache = UsersCache::new();
+    /// let uid = cache.get_current_uid();
+    /// for group in cache.get_user_groups(uid).unwrap_or_default() {
+    ///     println!("{:?}", group.name());
+    /// }
+    /// ```
+    pub fn get_user_groups(&self, uid: uid_t) -> Option<Vec<Arc<Group>>> {
+        self.clear_if_changed();
+        if let Some((stored_at, list)) = self.user_groups.borrow().get(&uid) {
+            if self.is_fresh(Some(stored_at), list.is_some()) {
+                return list.clone();
+            }
+        }
+
+        let list = self.get_user_by_uid(uid).and_then(|user| {
+            let groups = self.fetch_user_groups(user.name(), user.primary_group_id())?;
+            Some(groups.into_iter().map(Arc::new).collect::<Vec<_>>())
+        });
+
+        let mut user_groups = self.user_groups.borrow_mut();
+        if list.is_some() || self.policy.negative_caching {
+            user_groups.insert(uid, (self.clock.instant(), list.clone()));
+        } else {
+            user_groups.remove(&uid);
+        }
+        list
+    }
+}
+
+/// The trait’s `get_user_groups` takes a name and a primary group, and asks
+/// the system every time. [`groups_of`](SupplementaryGroups::groups_of) and
+/// [`identity_of`](SupplementaryGroups::identity_of) go through the cached
+/// lists of [`UsersCache::get_user_groups`], which takes a user ID.
+impl<H: BuildHasher + Clone> SupplementaryGroups for UsersCache<H> {
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>> {
+        let groups = self.fetch_user_groups(username.as_ref(), gid)?;
+        Some(groups.into_iter().map(Arc::new).collect())
+    }
+
+    fn groups_of(&self, uid: Uid) -> Option<Vec<Arc<Group>>> {
+        UsersCache::get_user_groups(self, uid.as_raw())
+    }
+}
+
+impl<H: BuildHasher + Clone> Users for UsersCache<H> {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.try_get_user_by_uid(uid).unwrap_or_default()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.try_get_user_by_name(username).unwrap_or_default()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.uid.get().unwrap_or_else(|| {
+            let uid = super::get_current_uid();
+            self.uid.set(Some(uid));
+            uid
+        })
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.euid.get().unwrap_or_else(|| {
+            let uid = super::get_effective_uid();
+            self.euid.set(Some(uid));
+            uid
+        })
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl<H: BuildHasher + Clone> Groups for UsersCache<H> {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.try_get_group_by_gid(gid).unwrap_or_default()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.try_get_group_by_name(group_name).unwrap_or_default()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.gid.get().unwrap_or_else(|| {
+            let gid = super::get_current_gid();
+            self.gid.set(Some(gid));
+            gid
+        })
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
         self.egid.get().unwrap_or_else(|| {
             let gid = super::get_effective_gid();
             self.egid.set(Some(gid));
@@ -384,6 +1209,174 @@ impl Groups for UsersCache {
     }
 }
 
+impl<H: BuildHasher + Clone> TryUsers for UsersCache<H> {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        self.clear_if_changed();
+
+        let mut users = self.users.borrow_mut();
+        let mut stored = self.users_stored.borrow_mut();
+
+        if let Some(entry) = users.forward.get(&uid) {
+            if self.is_fresh(stored.ids.get(&uid), entry.is_some()) {
+                self.stats.user_hit(entry.is_some());
+                return Ok(entry.clone());
+            }
+            self.stats.expired(EntryKind::User);
+        }
+        self.stats.user_miss();
+
+        let user = self.fetch_user_by_uid(uid)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        if let Some(user) = &user {
+            let newsername = Arc::clone(&user.name_arc);
+            users.backward.insert(Arc::clone(&newsername), Some(uid));
+            stored.names.insert(newsername, now);
+        }
+
+        if user.is_none() && !self.policy.negative_caching {
+            if users.forward.contains_key(&uid) {
+                users.remove(uid);
+                stored.ids.remove(&uid);
+            }
+            return Ok(None);
+        }
+
+        users.forward.insert(uid, user.clone());
+        stored.ids.insert(uid, now);
+        Ok(user)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        self.clear_if_changed();
+
+        let mut users = self.users.borrow_mut();
+        let mut stored = self.users_stored.borrow_mut();
+
+        if let Some(entry) = users.backward.get(username.as_ref()) {
+            if self.is_fresh(stored.names.get(username.as_ref()), entry.is_some()) {
+                self.stats.user_hit(entry.is_some());
+                return Ok((*entry).and_then(|uid| users.forward[&uid].clone()));
+            }
+            self.stats.expired(EntryKind::User);
+        }
+        self.stats.user_miss();
+
+        let user = self.fetch_user_by_name(username.as_ref())?.map(Arc::new);
+        let now = self.clock.instant();
+
+        match &user {
+            Some(user) => {
+                let uid = user.uid();
+                users.backward.insert(Arc::clone(&user.name_arc), Some(uid));
+                users.forward.insert(uid, Some(Arc::clone(user)));
+                stored.names.insert(Arc::clone(&user.name_arc), now);
+                stored.ids.insert(uid, now);
+            }
+            None if !self.policy.negative_caching => {
+                users.backward.remove(username.as_ref());
+                stored.names.remove(username.as_ref());
+            }
+            None => {
+                let name = Arc::from(username.as_ref());
+                users.backward.insert(Arc::clone(&name), None);
+                stored.names.insert(name, now);
+            }
+        }
+
+        Ok(user)
+    }
+}
+
+impl<H: BuildHasher + Clone> TryGroups for UsersCache<H> {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        self.clear_if_changed();
+
+        let mut groups = self.groups.borrow_mut();
+        let mut stored = self.groups_stored.borrow_mut();
+
+        if let Some(entry) = groups.forward.get(&gid) {
+            if self.is_fresh(stored.ids.get(&gid), entry.is_some()) {
+                self.stats.group_hit(entry.is_some());
+                return Ok(entry.clone());
+            }
+            self.stats.expired(EntryKind::Group);
+        }
+        self.stats.group_miss();
+
+        let group = self.fetch_group_by_gid(gid)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        if let Some(group) = &group {
+            let new_group_name = Arc::clone(&group.name_arc);
+            groups
+                .backward
+                .insert(Arc::clone(&new_group_name), Some(gid));
+            stored.names.insert(new_group_name, now);
+        }
+
+        if group.is_none() && !self.policy.negative_caching {
+            if groups.forward.contains_key(&gid) {
+                groups.remove(gid);
+                stored.ids.remove(&gid);
+            }
+            return Ok(None);
+        }
+
+        groups.forward.insert(gid, group.clone());
+        stored.ids.insert(gid, now);
+        Ok(group)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        self.clear_if_changed();
+
+        let mut groups = self.groups.borrow_mut();
+        let mut stored = self.groups_stored.borrow_mut();
+
+        if let Some(entry) = groups.backward.get(group_name.as_ref()) {
+            if self.is_fresh(stored.names.get(group_name.as_ref()), entry.is_some()) {
+                self.stats.group_hit(entry.is_some());
+                return Ok((*entry).and_then(|gid| groups.forward[&gid].as_ref().cloned()));
+            }
+            self.stats.expired(EntryKind::Group);
+        }
+        self.stats.group_miss();
+
+        let group = self.fetch_group_by_name(group_name.as_ref())?.map(Arc::new);
+        let now = self.clock.instant();
+
+        match &group {
+            Some(group) => {
+                let gid = group.gid();
+                groups
+                    .backward
+                    .insert(Arc::clone(&group.name_arc), Some(gid));
+                groups.forward.insert(gid, Some(Arc::clone(group)));
+                stored.names.insert(Arc::clone(&group.name_arc), now);
+                stored.ids.insert(gid, now);
+            }
+            None if !self.policy.negative_caching => {
+                groups.backward.remove(group_name.as_ref());
+                stored.names.remove(group_name.as_ref());
+            }
+            None => {
+                let name = Arc::from(group_name.as_ref());
+                groups.backward.insert(Arc::clone(&name), None);
+                stored.names.insert(name, now);
+            }
+        }
+
+        Ok(group)
+    }
+}
+
 /// A container of user and group instances.
 ///
 /// Included users and groups are determined by the method used to construct
@@ -397,6 +1390,11 @@ impl Groups for UsersCache {
 /// way after creation. Create a new `UsersSnapshot` to see changes in the
 /// underlying system database.
 ///
+/// With the `serde` feature, snapshots can be serialized and deserialized,
+/// so that a process that can read the users database can hand a snapshot
+/// of it to others that cannot. Users are serialized without their
+/// passwords.
+///
 /// ## Examples
 ///
 /// ```no_run
@@ -429,18 +1427,136 @@ impl Groups for UsersCache {
 /// For thread safety considerations, see the
 /// [`users::cache` module documentation](index.html#caching-multiple-threads-and-mutability).
 #[derive(Default)]
-pub struct UsersSnapshot {
-    users: IdNameMap<uid_t, Arc<OsStr>, Arc<User>>,
-    groups: IdNameMap<uid_t, Arc<OsStr>, Arc<Group>>,
+pub struct UsersSnapshot<H = RandomState> {
+    users: IdNameMap<uid_t, Arc<OsStr>, Arc<User>, H>,
+    groups: IdNameMap<uid_t, Arc<OsStr>, Arc<Group>, H>,
 
     uid: uid_t,
     gid: gid_t,
     euid: uid_t,
     egid: gid_t,
+
+    incomplete: bool,
+
+    memberships: OnceLock<Memberships>,
 }
 
-impl UsersSnapshot {
-    /// Creates a new snapshot containing provided users and groups.
+/// The error returned by [`UsersSnapshot::try_new`] when the users or groups
+/// cannot all be read, along with the snapshot of those that were read
+/// before the error.
+pub struct PartialSnapshot {
+    error: io::Error,
+    snapshot: Box<UsersSnapshot>,
+}
+
+impl PartialSnapshot {
+    /// Returns the error that stopped the users or groups from being read.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Returns the snapshot of the users and groups read before the error.
+    pub fn snapshot(&self) -> &UsersSnapshot {
+        &self.snapshot
+    }
+
+    /// Returns the snapshot of the users and groups read before the error,
+    /// which is not [complete](UsersSnapshot::is_complete).
+    pub fn into_snapshot(self) -> UsersSnapshot {
+        *self.snapshot
+    }
+
+    /// Returns the error and the snapshot, giving up neither.
+    pub fn into_parts(self) -> (io::Error, UsersSnapshot) {
+        (self.error, *self.snapshot)
+    }
+}
+
+impl fmt::Debug for PartialSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PartialSnapshot")
+            .field("error", &self.error)
+            .field("users", &self.snapshot.users.forward.len())
+            .field("groups", &self.snapshot.groups.forward.len())
+            .finish()
+    }
+}
+
+impl fmt::Display for PartialSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot read every user and group: {}", self.error)
+    }
+}
+
+impl error::Error for PartialSnapshot {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Lets `?` turn a partial snapshot into the error that caused it, in
+/// functions that return an `io::Result`.
+impl From<PartialSnapshot> for io::Error {
+    fn from(partial: PartialSnapshot) -> Self {
+        partial.error
+    }
+}
+
+/// Which groups each user of a snapshot is a member of, and which users
+/// each group has as members, worked out when it’s first needed.
+#[derive(Default)]
+struct Memberships {
+    /// The IDs of the groups of each user, sorted.
+    groups_of: HashMap<uid_t, Vec<gid_t>>,
+
+    /// The IDs of the members of each group, sorted.
+    members_of: HashMap<gid_t, Vec<uid_t>>,
+}
+
+impl Memberships {
+    /// Works out the memberships of the users and groups in the maps.
+    fn of<H: BuildHasher>(
+        users: &IdNameMap<uid_t, Arc<OsStr>, Arc<User>, H>,
+        groups: &IdNameMap<gid_t, Arc<OsStr>, Arc<Group>, H>,
+    ) -> Self {
+        let mut groups_of = HashMap::<uid_t, Vec<gid_t>>::new();
+
+        for group in groups.forward.values().flatten() {
+            for member in group.members() {
+                if let Some(Some(uid)) = users.backward.get(member.as_os_str()) {
+                    groups_of.entry(*uid).or_default().push(group.gid());
+                }
+            }
+        }
+        for user in users.forward.values().flatten() {
+            let gid = user.primary_group_id();
+            if let Some(Some(_)) = groups.forward.get(&gid) {
+                groups_of.entry(user.uid()).or_default().push(gid);
+            }
+        }
+
+        let mut members_of = HashMap::<gid_t, Vec<uid_t>>::new();
+        for (uid, gids) in &mut groups_of {
+            gids.sort_unstable();
+            gids.dedup();
+            for gid in gids.iter() {
+                members_of.entry(*gid).or_default().push(*uid);
+            }
+        }
+        for uids in members_of.values_mut() {
+            uids.sort_unstable();
+        }
+
+        Self {
+            groups_of,
+            members_of,
+        }
+    }
+}
+
+impl<H: BuildHasher + Clone> UsersSnapshot<H> {
+    /// Creates a new snapshot containing provided users and groups, whose
+    /// maps hash their keys with the given hasher.
     pub(crate) fn from<U, G>(
         users: U,
         groups: G,
@@ -448,35 +1564,628 @@ impl UsersSnapshot {
         current_gid: gid_t,
         effective_uid: uid_t,
         effective_gid: gid_t,
+        hasher: H,
     ) -> Self
     where
         U: Iterator<Item = User>,
         G: Iterator<Item = Group>,
     {
-        let mut user_map = IdNameMap::default();
+        let mut snapshot = Self {
+            users: IdNameMap::with_hasher(hasher.clone()),
+            groups: IdNameMap::with_hasher(hasher),
+            uid: current_uid,
+            gid: current_gid,
+            euid: effective_uid,
+            egid: effective_gid,
+            incomplete: false,
+            memberships: OnceLock::new(),
+        };
+        snapshot.insert_all(users, groups);
+        snapshot
+    }
 
+    /// Adds the users and groups to the snapshot.
+    fn insert_all<U, G>(&mut self, users: U, groups: G)
+    where
+        U: Iterator<Item = User>,
+        G: Iterator<Item = Group>,
+    {
         for user in users {
-            user_map.insert(user.uid(), Arc::clone(&user.name_arc), Arc::from(user));
+            self.users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::from(user));
         }
 
-        let mut group_map = IdNameMap::default();
-
         for group in groups {
-            group_map.insert(group.gid(), Arc::clone(&group.name_arc), Arc::from(group));
-        }
-
-        Self {
-            users: user_map,
-            groups: group_map,
-            uid: current_uid,
-            gid: current_gid,
-            euid: effective_uid,
-            egid: effective_gid,
+            self.groups
+                .insert(group.gid(), Arc::clone(&group.name_arc), Arc::from(group));
         }
     }
 
     /// Creates a new snapshot containing all system users and groups that pass
-    /// the filter.
+    /// the filter, whose maps hash their keys with the given hasher instead
+    /// of the default SipHash.
+    ///
+    /// See [`UsersCache::with_hasher`] for when this is worth it.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let hasher = BuildHasherDefault::<DefaultHasher>::default();
+    /// let snapshot = unsafe {
+    ///     UsersSnapshot::filtered_with_hasher(|u| u.uid() >= 1000, |_| true, hasher)
+    /// };
+    /// ```
+    pub unsafe fn filtered_with_hasher<U, G>(user_filter: U, group_filter: G, hasher: H) -> Self
+    where
+        U: FnMut(&User) -> bool,
+        G: FnMut(&Group) -> bool,
+    {
+        Self::read(user_filter, group_filter, hasher).0
+    }
+
+    /// Reads every system user and group that passes the filter, returning
+    /// the error that ended either enumeration early, if any.
+    ///
+    /// A snapshot that was not read in full is marked as incomplete.
+    unsafe fn read<U, G>(user_filter: U, group_filter: G, hasher: H) -> (Self, Option<io::Error>)
+    where
+        U: FnMut(&User) -> bool,
+        G: FnMut(&Group) -> bool,
+    {
+        let mut snapshot = Self::from(
+            iter::empty(),
+            iter::empty(),
+            super::get_current_uid(),
+            super::get_current_gid(),
+            super::get_effective_uid(),
+            super::get_effective_gid(),
+            hasher,
+        );
+
+        let mut users = enumerate_users();
+        snapshot.insert_all(users.by_ref().filter(user_filter), iter::empty());
+        let mut error = users.take_error();
+        drop(users);
+
+        let mut groups = enumerate_groups();
+        snapshot.insert_all(iter::empty(), groups.by_ref().filter(group_filter));
+        error = error.or(groups.take_error());
+        drop(groups);
+
+        snapshot.incomplete = error.is_some();
+        (snapshot, error)
+    }
+
+    /// Creates a new snapshot containing all system users and groups, whose
+    /// maps hash their keys with the given hasher.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    pub unsafe fn new_with_hasher(hasher: H) -> Self {
+        Self::filtered_with_hasher(|_| true, |_| true, hasher)
+    }
+
+    /// Creates a new snapshot holding the users and groups of both
+    /// snapshots, where those of `overlay` win over those of `base` with the
+    /// same ID or name. A user of `base` whose name is taken by a user of
+    /// `overlay` with another ID is left out, so that every name leads to
+    /// exactly one user, and the same goes for groups.
+    ///
+    /// This combines, say, the users of a host with those of a container or
+    /// a test fixture into one consistent view. The current and effective
+    /// IDs are taken from `overlay`, and the snapshot is only
+    /// [complete](UsersSnapshot::is_complete) if both of them are. The
+    /// entries are shared with both snapshots, so nothing is copied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{User, Users, UsersSnapshot};
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let mut host = MockUsers::with_current_uid(0);
+    /// host.add_user(User::new(1000, "fred", 100));
+    /// let mut container = MockUsers::with_current_uid(0);
+    /// container.add_user(User::new(1000, "app", 100));
+    ///
+    /// let merged = UsersSnapshot::merged(
+    ///     &UsersSnapshot::from_source(&host),
+    ///     &UsersSnapshot::from_source(&container),
+    /// );
+    /// assert_eq!(merged.get_user_by_uid(1000).unwrap().name(), "app");
+    /// assert!(merged.get_user_by_name("fred").is_none());
+    /// ```
+    pub fn merged(base: &Self, overlay: &Self) -> Self {
+        Self {
+            users: base.users.overlaid(&overlay.users),
+            groups: base.groups.overlaid(&overlay.groups),
+            uid: overlay.uid,
+            gid: overlay.gid,
+            euid: overlay.euid,
+            egid: overlay.egid,
+            incomplete: base.incomplete || overlay.incomplete,
+            memberships: OnceLock::new(),
+        }
+    }
+
+    /// Returns whether this snapshot contains every user and group that
+    /// passed its filters, or whether enumeration was stopped early.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// assert!(snapshot.is_complete());
+    /// ```
+    pub fn is_complete(&self) -> bool {
+        !self.incomplete
+    }
+}
+
+/// Collects the items that pass the filter, checking `stop` before reading
+/// each one, and returns whether it stopped the iteration early.
+fn collect_until<T, I, F, S>(iter: I, mut filter: F, mut stop: S, into: &mut Vec<T>) -> bool
+where
+    I: IntoIterator<Item = T>,
+    F: FnMut(&T) -> bool,
+    S: FnMut() -> bool,
+{
+    let mut iter = iter.into_iter();
+    loop {
+        if stop() {
+            return true;
+        }
+
+        match iter.next() {
+            Some(item) if filter(&item) => into.push(item),
+            Some(_) => {}
+            None => return false,
+        }
+    }
+}
+
+impl UsersSnapshot {
+    /// Creates a new snapshot containing all system users and groups that pass
+    /// the filter, stopping the enumeration as soon as `stop` returns `true`.
+    ///
+    /// `stop` is checked before every user and group is read, including the
+    /// last read of each, which finds that there are none left. If it ever
+    /// returns `true`, the snapshot is marked as incomplete.
+    unsafe fn filtered_until<U, G, S>(user_filter: U, group_filter: G, mut stop: S) -> Self
+    where
+        U: FnMut(&User) -> bool,
+        G: FnMut(&Group) -> bool,
+        S: FnMut() -> bool,
+    {
+        let mut users = Vec::new();
+        let mut groups = Vec::new();
+        let stopped = collect_until(all_users(), user_filter, &mut stop, &mut users)
+            || collect_until(all_groups(), group_filter, &mut stop, &mut groups);
+
+        let mut snapshot = Self::from(
+            users.into_iter(),
+            groups.into_iter(),
+            super::get_current_uid(),
+            super::get_current_gid(),
+            super::get_effective_uid(),
+            super::get_effective_gid(),
+            RandomState::new(),
+        );
+        snapshot.incomplete = stopped;
+        snapshot
+    }
+
+    /// Creates a new snapshot containing all system users and groups that pass
+    /// the filter.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// // Exclude Linux system users, include all groups
+    /// let snapshot = unsafe {
+    ///     UsersSnapshot::filtered(|u| u.uid() >= 1000, |_| true)
+    /// };
+    /// ```
+    ///
+    /// # See also
+    ///
+    /// - [`UsersSnapshot::only_users()`] - if only primary groups of users are
+    ///   needed
+    /// - [`UsersSnapshot::new()`] - if no filtering is needed
+    pub unsafe fn filtered<U, G>(user_filter: U, group_filter: G) -> Self
+    where
+        U: FnMut(&User) -> bool,
+        G: FnMut(&Group) -> bool,
+    {
+        Self::filtered_with_hasher(user_filter, group_filter, RandomState::new())
+    }
+
+    /// Creates a new snapshot containing all system users that pass the filter
+    /// and their primary groups.
+    ///
+    /// Note that some primary groups may be missing on the system.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// // Include Linux system users and their primary groups
+    /// let snapshot = unsafe { UsersSnapshot::only_users(|u| u.uid() < 1000) };
+    /// ```
+    ///
+    /// # See also
+    ///
+    /// - [`UsersSnapshot::filtered()`] - for more elaborate group filtering
+    /// - [`UsersSnapshot::new()`] - if no filtering is needed
+    pub unsafe fn only_users<F>(user_filter: F) -> Self
+    where
+        F: FnMut(&User) -> bool,
+    {
+        let users = all_users().filter(user_filter).collect::<Vec<_>>();
+        let primary_groups = users
+            .iter()
+            .map(User::primary_group_id)
+            .collect::<HashSet<_>>();
+        let groups = all_groups()
+            .filter(|g| primary_groups.contains(&g.gid()))
+            .collect::<Vec<_>>();
+
+        Self::from(
+            users.into_iter(),
+            groups.into_iter(),
+            super::get_current_uid(),
+            super::get_current_gid(),
+            super::get_effective_uid(),
+            super::get_effective_gid(),
+            RandomState::new(),
+        )
+    }
+
+    /// Creates a new snapshot containing all system groups that pass the
+    /// filter, along with their members: the users listed as members of a
+    /// group, and those whose primary group it is.
+    ///
+    /// Note that some members may be missing on the system.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// // Include the administrators’ groups and everyone in them
+    /// let snapshot = unsafe {
+    ///     UsersSnapshot::only_groups(|g| g.name() == "wheel" || g.name() == "sudo")
+    /// };
+    /// ```
+    ///
+    /// # See also
+    ///
+    /// - [`UsersSnapshot::only_users()`] - to filter users instead
+    /// - [`UsersSnapshot::filtered()`] - for more elaborate user filtering
+    pub unsafe fn only_groups<F>(group_filter: F) -> Self
+    where
+        F: FnMut(&Group) -> bool,
+    {
+        let groups = all_groups().filter(group_filter).collect::<Vec<_>>();
+        let gids = groups.iter().map(Group::gid).collect::<HashSet<_>>();
+        let members = groups
+            .iter()
+            .flat_map(|g| g.members())
+            .map(OsString::as_os_str)
+            .collect::<HashSet<_>>();
+        let users = all_users()
+            .filter(|u| gids.contains(&u.primary_group_id()) || members.contains(u.name()))
+            .collect::<Vec<_>>();
+
+        Self::from(
+            users.into_iter(),
+            groups.into_iter(),
+            super::get_current_uid(),
+            super::get_current_gid(),
+            super::get_effective_uid(),
+            super::get_effective_gid(),
+            RandomState::new(),
+        )
+    }
+
+    /// Creates a new snapshot containing all system users and groups.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// ```
+    ///
+    /// # See also
+    ///
+    /// [`UsersSnapshot::only_users()`], [`UsersSnapshot::filtered()`] provide
+    /// performance benefits if only some users and groups will be needed.
+    pub unsafe fn new() -> Self {
+        Self::filtered(|_| true, |_| true)
+    }
+
+    /// Creates a new snapshot holding every user and group of another
+    /// provider, along with the current and effective IDs it reports.
+    ///
+    /// This freezes a provider such as a [`MockUsers`](../mock/struct.MockUsers.html)
+    /// table or another snapshot into the standard immutable form. Unlike the
+    /// other constructors, it does not enumerate the system’s database, so
+    /// it’s not `unsafe`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{User, Users, UsersSnapshot};
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    ///
+    /// let snapshot = UsersSnapshot::from_source(&users);
+    /// assert_eq!(&*snapshot.get_current_username().unwrap(), "fred");
+    /// ```
+    pub fn from_source<S>(source: &S) -> Self
+    where
+        S: AllUsers + AllGroups + Users + Groups,
+    {
+        Self::from(
+            source.get_all_users().cloned(),
+            source.get_all_groups().cloned(),
+            source.get_current_uid(),
+            source.get_current_gid(),
+            source.get_effective_uid(),
+            source.get_effective_gid(),
+            RandomState::new(),
+        )
+    }
+
+    /// Returns a builder that gives more control over where a snapshot’s
+    /// users and groups come from, and which of them it keeps.
+    ///
+    /// See [`SnapshotBuilder`].
+    pub fn builder() -> SnapshotBuilder {
+        SnapshotBuilder::new()
+    }
+
+    /// Creates a new snapshot containing all system users and groups, or
+    /// returns the error that stopped them from being read.
+    ///
+    /// The other constructors treat an error from the system’s database,
+    /// such as a directory service that cannot be reached, as the end of the
+    /// users or groups, so they cannot tell a system with no users from one
+    /// whose users could not be read. The users and groups read before the
+    /// error are kept in the returned [`PartialSnapshot`], as a snapshot that
+    /// is not [complete](UsersSnapshot::is_complete).
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if reading the users or the groups
+    /// fails before the end of either is reached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = match unsafe { UsersSnapshot::try_new() } {
+    ///     Ok(snapshot) => snapshot,
+    ///     Err(partial) => {
+    ///         eprintln!("Cannot read every user: {}", partial.error());
+    ///         partial.into_snapshot()
+    ///     }
+    /// };
+    /// ```
+    pub unsafe fn try_new() -> Result<Self, PartialSnapshot> {
+        match Self::read(|_| true, |_| true, RandomState::new()) {
+            (snapshot, None) => Ok(snapshot),
+            (snapshot, Some(error)) => Err(PartialSnapshot {
+                error,
+                snapshot: Box::new(snapshot),
+            }),
+        }
+    }
+
+    /// Starts reading a new snapshot of all system users and groups on a
+    /// thread of its own, returning a handle to join for the snapshot.
+    ///
+    /// Enumerating a large directory can take a while, and this lets a
+    /// program get on with other work, such as drawing its interface, in the
+    /// meantime. The snapshot is read as by [`UsersSnapshot::new`].
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because the enumeration is still not safe to run at
+    /// the same time as another one. Moving it to another thread does not
+    /// change that: until the handle has been joined, nothing else in the
+    /// process may enumerate the users or groups, whether through
+    /// [`all_users`], [`all_groups`], or another snapshot. For more
+    /// information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let handle = unsafe { UsersSnapshot::new_on_thread() };
+    /// // ... other work that does not enumerate users or groups ...
+    /// let snapshot = handle.join().expect("Cannot read the users");
+    /// ```
+    pub unsafe fn new_on_thread() -> JoinHandle<Self> {
+        thread::spawn(|| unsafe { Self::new() })
+    }
+
+    /// Creates a new snapshot containing all system users and groups, with
+    /// room made for the given numbers of them up front, so that reading
+    /// that many does not have to grow the snapshot’s maps along the way.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new_with_capacity(10_000, 500) };
+    /// ```
+    pub unsafe fn new_with_capacity(users: usize, groups: usize) -> Self {
+        let mut snapshot = Self::from(
+            iter::empty(),
+            iter::empty(),
+            super::get_current_uid(),
+            super::get_current_gid(),
+            super::get_effective_uid(),
+            super::get_effective_gid(),
+            RandomState::new(),
+        );
+        snapshot.users.reserve(users);
+        snapshot.groups.reserve(groups);
+        snapshot.insert_all(all_users(), all_groups());
+        snapshot
+    }
+
+    /// Creates a new snapshot containing as many system users and groups as
+    /// can be read before the given deadline passes.
+    ///
+    /// Users are enumerated before groups, and the deadline is checked before
+    /// every entry is read. If it has passed, the enumeration stops and the
+    /// partial snapshot is returned with
+    /// [`is_complete()`](UsersSnapshot::is_complete) set to `false`. This lets
+    /// interactive programs show *something* quickly on hosts with slow
+    /// directory services.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new_with_deadline(Duration::from_millis(250)) };
+    /// if !snapshot.is_complete() {
+    ///     println!("Only showing some users");
+    /// }
+    /// ```
+    pub unsafe fn new_with_deadline(deadline: Duration) -> Self {
+        let start = Instant::now();
+        Self::filtered_until(|_| true, |_| true, || start.elapsed() > deadline)
+    }
+
+    /// Creates a new snapshot containing all system users and groups,
+    /// reporting the progress of the enumeration to the given callback.
+    ///
+    /// The callback is called before every user and group is read, with the
+    /// number of entries read so far (users first, then groups) and the time
+    /// elapsed since the enumeration started. It is also called before the
+    /// read that finds the end of the users, and before the one that finds
+    /// the end of the groups. If it returns [`ControlFlow::Break`], the
+    /// enumeration stops and the partial snapshot is returned with
+    /// [`is_complete()`](UsersSnapshot::is_complete) set to `false`.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because we cannot prevent data races if two caches
+    /// were attempted to be initialised on different threads at the same time.
+    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe {
+    ///     UsersSnapshot::new_with_progress(|progress| {
+    ///         eprint!("\rRead {} entries", progress.count());
+    ///         ControlFlow::Continue(())
+    ///     })
+    /// };
+    /// ```
+    pub unsafe fn new_with_progress<F>(progress: F) -> Self
+    where
+        F: FnMut(Progress) -> ControlFlow<()>,
+    {
+        let count = Cell::new(0);
+        let read = || {
+            count.set(count.get() + 1);
+            true
+        };
+        let mut tracker = Tracker::new(progress);
+        Self::filtered_until(|_| read(), |_| read(), || tracker.should_stop(count.get()))
+    }
+
+    /// Creates a new snapshot containing all system users and groups, which
+    /// stops early once the given token is cancelled.
+    ///
+    /// If the token is cancelled before the enumeration is over, the partial
+    /// snapshot is returned with [`is_complete()`](UsersSnapshot::is_complete)
+    /// set to `false`.
     ///
     /// # Safety
     ///
@@ -484,120 +2193,299 @@ impl UsersSnapshot {
     /// were attempted to be initialised on different threads at the same time.
     /// For more information, see the [`all_users` documentation](../fn.all_users.html).
     ///
-    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    /// use uzers::cancel::CancellationToken;
+    ///
+    /// let token = CancellationToken::new();
+    /// token.cancel();
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new_cancellable(&token) };
+    /// assert!(!snapshot.is_complete());
+    /// ```
+    #[cfg(feature = "cancellation")]
+    pub unsafe fn new_cancellable(token: &CancellationToken) -> Self {
+        Self::filtered_until(|_| true, |_| true, || token.is_cancelled())
+    }
+}
+
+impl<H: BuildHasher> UsersSnapshot<H> {
+    /// Returns the number of users in the snapshot.
     ///
     /// # Examples
     ///
     /// ```
+    /// use uzers::AllUsers;
     /// use uzers::cache::UsersSnapshot;
     ///
-    /// // Exclude Linux system users, include all groups
-    /// let snapshot = unsafe {
-    ///     UsersSnapshot::filtered(|u| u.uid() >= 1000, |_| true)
-    /// };
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// assert_eq!(snapshot.user_count(), snapshot.get_all_users().count());
     /// ```
+    pub fn user_count(&self) -> usize {
+        self.users.forward.len()
+    }
+
+    /// Returns the number of groups in the snapshot.
+    pub fn group_count(&self) -> usize {
+        self.groups.forward.len()
+    }
+
+    /// Returns whether the snapshot holds no users and no groups.
+    pub fn is_empty(&self) -> bool {
+        self.users.forward.is_empty() && self.groups.forward.is_empty()
+    }
+
+    /// Returns whether the snapshot holds a user with the given ID.
+    pub fn contains_uid(&self, uid: uid_t) -> bool {
+        self.users.forward.contains_key(&uid)
+    }
+
+    /// Returns whether the snapshot holds a group with the given ID.
+    pub fn contains_gid(&self, gid: gid_t) -> bool {
+        self.groups.forward.contains_key(&gid)
+    }
+
+    /// Checks the snapshot for the problems that `pwck` and `grpck` look
+    /// for, such as users whose primary group does not exist, or names and
+    /// IDs that are used more than once.
     ///
-    /// # See also
+    /// See [`ValidationReport`].
+    pub fn validate(&self) -> ValidationReport {
+        ValidationReport::of(self)
+    }
+
+    /// Returns every user in the snapshot, in order of their IDs.
     ///
-    /// - [`UsersSnapshot::only_users()`] - if only primary groups of users are
-    ///   needed
-    /// - [`UsersSnapshot::new()`] - if no filtering is needed
-    pub unsafe fn filtered<U, G>(user_filter: U, group_filter: G) -> Self
-    where
-        U: FnMut(&User) -> bool,
-        G: FnMut(&Group) -> bool,
-    {
-        Self::from(
-            all_users().filter(user_filter),
-            all_groups().filter(group_filter),
-            super::get_current_uid(),
-            super::get_current_gid(),
-            super::get_effective_uid(),
-            super::get_effective_gid(),
-        )
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// for user in snapshot.get_all_users_sorted_by_uid() {
+    ///     println!("{} {:?}", user.uid(), user.name());
+    /// }
+    /// ```
+    pub fn get_all_users_sorted_by_uid(&self) -> vec::IntoIter<&User> {
+        let mut users = self.get_all_users().collect::<Vec<_>>();
+        users.sort_unstable_by_key(|u| u.uid());
+        users.into_iter()
     }
 
-    /// Creates a new snapshot containing all system users that pass the filter
-    /// and their primary groups.
+    /// Returns every user in the snapshot, in order of their names, and of
+    /// their IDs for users with the same name.
+    pub fn get_all_users_sorted_by_name(&self) -> vec::IntoIter<&User> {
+        let mut users = self.get_all_users().collect::<Vec<_>>();
+        users.sort_unstable_by(|a, b| a.name().cmp(b.name()).then(a.uid().cmp(&b.uid())));
+        users.into_iter()
+    }
+
+    /// Returns every group in the snapshot, in order of their IDs.
+    pub fn get_all_groups_sorted_by_gid(&self) -> vec::IntoIter<&Group> {
+        let mut groups = self.get_all_groups().collect::<Vec<_>>();
+        groups.sort_unstable_by_key(|g| g.gid());
+        groups.into_iter()
+    }
+
+    /// Returns every group in the snapshot, in order of their names, and of
+    /// their IDs for groups with the same name.
+    pub fn get_all_groups_sorted_by_name(&self) -> vec::IntoIter<&Group> {
+        let mut groups = self.get_all_groups().collect::<Vec<_>>();
+        groups.sort_unstable_by(|a, b| a.name().cmp(b.name()).then(a.gid().cmp(&b.gid())));
+        groups.into_iter()
+    }
+
+    /// Returns every group in the snapshot that the user with the given ID
+    /// is a member of: the groups that list them as a member, and their
+    /// primary group. The groups are returned in order of their IDs.
     ///
-    /// Note that some primary groups may be missing on the system.
+    /// The memberships of every user are worked out the first time this is
+    /// called, so later calls do not have to search the groups again.
     ///
-    /// # Safety
+    /// # Examples
     ///
-    /// This is `unsafe` because we cannot prevent data races if two caches
-    /// were attempted to be initialised on different threads at the same time.
-    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    /// ```
+    /// use uzers::Users;
+    /// use uzers::cache::UsersSnapshot;
     ///
-    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// for group in snapshot.groups_of_user(snapshot.get_current_uid()) {
+    ///     println!("{:?}", group.name());
+    /// }
+    /// ```
+    pub fn groups_of_user(&self, uid: uid_t) -> impl Iterator<Item = &Group> + '_ {
+        let gids = self
+            .memberships()
+            .groups_of
+            .get(&uid)
+            .map_or(&[][..], Vec::as_slice);
+        gids.iter()
+            .filter_map(move |gid| self.groups.forward.get(gid)?.as_deref())
+    }
+
+    /// Returns every user in the snapshot that is a member of the group with
+    /// the given ID: the users it lists as members, and those whose primary
+    /// group it is. The users are returned in order of their IDs.
+    ///
+    /// Members whose names are not in the snapshot are left out.
     ///
     /// # Examples
     ///
     /// ```
     /// use uzers::cache::UsersSnapshot;
     ///
-    /// // Include Linux system users and their primary groups
-    /// let snapshot = unsafe { UsersSnapshot::only_users(|u| u.uid() < 1000) };
+    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// for user in snapshot.users_in_group(0) {
+    ///     println!("{:?}", user.name());
+    /// }
     /// ```
+    pub fn users_in_group(&self, gid: gid_t) -> impl Iterator<Item = &User> + '_ {
+        let uids = self
+            .memberships()
+            .members_of
+            .get(&gid)
+            .map_or(&[][..], Vec::as_slice);
+        uids.iter()
+            .filter_map(move |uid| self.users.forward.get(uid)?.as_deref())
+    }
+
+    /// Drops the users and groups that do not pass the filters from the
+    /// snapshot, without reading the system’s database again.
     ///
-    /// # See also
+    /// # Examples
     ///
-    /// - [`UsersSnapshot::filtered()`] - for more elaborate group filtering
-    /// - [`UsersSnapshot::new()`] - if no filtering is needed
-    pub unsafe fn only_users<F>(user_filter: F) -> Self
+    /// ```
+    /// use uzers::cache::UsersSnapshot;
+    ///
+    /// let mut snapshot = unsafe { UsersSnapshot::new() };
+    ///
+    /// // Drop Linux system users, but keep every group
+    /// snapshot.retain(|u| u.uid() >= 1000, |_| true);
+    /// ```
+    pub fn retain<U, G>(&mut self, mut user_filter: U, mut group_filter: G)
     where
-        F: FnMut(&User) -> bool,
+        U: FnMut(&User) -> bool,
+        G: FnMut(&Group) -> bool,
     {
-        let users = all_users().filter(user_filter).collect::<Vec<_>>();
-        let primary_groups = users
-            .iter()
-            .map(User::primary_group_id)
-            .collect::<HashSet<_>>();
-        let groups = all_groups()
-            .filter(|g| primary_groups.contains(&g.gid()))
-            .collect::<Vec<_>>();
-
-        Self::from(
-            users.into_iter(),
-            groups.into_iter(),
-            super::get_current_uid(),
-            super::get_current_gid(),
-            super::get_effective_uid(),
-            super::get_effective_gid(),
-        )
+        self.users.retain(|u| user_filter(u));
+        self.groups.retain(|g| group_filter(g));
+        self.memberships = OnceLock::new();
     }
 
-    /// Creates a new snapshot containing all system users and groups.
+    /// Returns a view of the snapshot that only shows the users and groups
+    /// that pass the filters, without copying any of them.
     ///
-    /// # Safety
-    ///
-    /// This is `unsafe` because we cannot prevent data races if two caches
-    /// were attempted to be initialised on different threads at the same time.
-    /// For more information, see the [`all_users` documentation](../fn.all_users.html).
+    /// See [`SnapshotView`].
+    pub fn filtered_view<U, G>(&self, user_filter: U, group_filter: G) -> SnapshotView<'_, U, G, H>
+    where
+        U: Fn(&User) -> bool,
+        G: Fn(&Group) -> bool,
+    {
+        SnapshotView::new(self, user_filter, group_filter)
+    }
+
+    /// Returns roughly how many bytes of memory the snapshot takes up.
     ///
-    /// Note that this method uses both [`all_users`] and [`all_groups`].
+    /// This counts the room allocated for the snapshot’s maps, whether or
+    /// not it’s used, along with the users and groups themselves and the
+    /// strings they hold. A name is counted once, even though it’s shared
+    /// between an entry and the map that finds it by name. The membership
+    /// index that [`groups_of_user`](UsersSnapshot::groups_of_user) builds
+    /// is not counted.
     ///
     /// # Examples
     ///
     /// ```
     /// use uzers::cache::UsersSnapshot;
     ///
-    /// let snapshot = unsafe { UsersSnapshot::new() };
+    /// let mut snapshot = unsafe { UsersSnapshot::new() };
+    /// snapshot.shrink_to_fit();
+    /// println!("{} KiB", snapshot.memory_usage() / 1024);
     /// ```
+    pub fn memory_usage(&self) -> usize {
+        let users = self.get_all_users().map(|u| {
+            mem::size_of::<User>()
+                + u.name().len()
+                + u.home_dir().as_os_str().len()
+                + u.shell().as_os_str().len()
+                + u.password().len()
+                + u.gecos().len()
+        });
+        let groups = self.get_all_groups().map(|g| {
+            let members = g.members();
+            mem::size_of::<Group>()
+                + g.name().len()
+                + mem::size_of_val(members)
+                + members.iter().map(|m| m.len()).sum::<usize>()
+        });
+
+        mem::size_of::<Self>()
+            + self.users.table_size()
+            + self.groups.table_size()
+            + users.sum::<usize>()
+            + groups.sum::<usize>()
+    }
+
+    /// Frees the room in the snapshot’s maps that its entries do not use.
     ///
-    /// # See also
-    ///
-    /// [`UsersSnapshot::only_users()`], [`UsersSnapshot::filtered()`] provide
-    /// performance benefits if only some users and groups will be needed.
-    pub unsafe fn new() -> Self {
-        Self::filtered(|_| true, |_| true)
+    /// Snapshots of large directories are read into maps that have grown
+    /// as they went, and can be left with much more room than they need.
+    /// Call this once a snapshot has been read, or has been narrowed down
+    /// with [`retain`](UsersSnapshot::retain).
+    pub fn shrink_to_fit(&mut self) {
+        self.users.shrink_to_fit();
+        self.groups.shrink_to_fit();
+    }
+
+    /// Returns the memberships of the users and groups, working them out if
+    /// this is the first time they are needed.
+    fn memberships(&self) -> &Memberships {
+        self.memberships
+            .get_or_init(|| Memberships::of(&self.users, &self.groups))
     }
 }
 
-impl AllUsers for UsersSnapshot {
-    type UserIter<'a> = std::iter::FilterMap<
+/// Returns a snapshot that is shared by the whole process.
+///
+/// Programs that only read the users and groups can use this one snapshot
+/// everywhere, instead of passing their own around, and still see the same
+/// view of the system from every function. The snapshot is read by the first
+/// call, as by [`UsersSnapshot::new`], and lives until the process ends.
+///
+/// This is only available with the `global-snapshot` feature.
+///
+/// # Safety
+///
+/// This is `unsafe` because the first call enumerates the system’s users
+/// and groups, and we cannot prevent data races with another enumeration
+/// happening on a different thread at the same time. Calls made once the
+/// snapshot has been read enumerate nothing. For more information, see the
+/// [`all_users` documentation](../fn.all_users.html).
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::{snapshot, AllUsers};
+///
+/// let users = unsafe { snapshot() };
+/// println!("{} users", users.get_all_users().count());
+/// ```
+#[cfg(feature = "global-snapshot")]
+pub unsafe fn snapshot() -> &'static UsersSnapshot {
+    static SNAPSHOT: OnceLock<UsersSnapshot> = OnceLock::new();
+    SNAPSHOT.get_or_init(|| unsafe { UsersSnapshot::new() })
+}
+
+impl<H: BuildHasher> AllUsers for UsersSnapshot<H> {
+    type UserIter<'a>
+        = std::iter::FilterMap<
         std::collections::hash_map::Values<'a, uid_t, Option<Arc<User>>>,
         for<'b> fn(&'b Option<Arc<User>>) -> Option<&'b User>,
-    >;
+    >
+    where
+        H: 'a;
 
     fn get_all_users(&self) -> Self::UserIter<'_> {
         fn get_user(x: &Option<Arc<User>>) -> Option<&User> {
@@ -608,14 +2496,22 @@ impl AllUsers for UsersSnapshot {
     }
 }
 
-impl Users for UsersSnapshot {
+impl<H: BuildHasher> AllUsersOwned for UsersSnapshot<H> {
+    type OwnedUserIter = vec::IntoIter<Arc<User>>;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        let users = self.users.forward.values().flatten().cloned();
+        users.collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<H: BuildHasher> Users for UsersSnapshot<H> {
     fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
         self.users.forward.get(&uid)?.as_ref().cloned()
     }
 
     fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
-        let name_arc = Arc::from(username.as_ref());
-        let uid = self.users.backward.get(&name_arc)?.as_ref()?;
+        let uid = self.users.backward.get(username.as_ref())?.as_ref()?;
         self.get_user_by_uid(*uid)
     }
 
@@ -638,11 +2534,46 @@ impl Users for UsersSnapshot {
     }
 }
 
-impl AllGroups for UsersSnapshot {
-    type GroupIter<'a> = std::iter::FilterMap<
+impl<H: BuildHasher> SupplementaryGroups for UsersSnapshot<H> {
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>> {
+        let username = username.as_ref();
+        let mut groups = self
+            .groups
+            .forward
+            .values()
+            .flatten()
+            .filter(|g| g.gid() == gid || g.members().iter().any(|m| m == username))
+            .cloned()
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|g| g.gid());
+        Some(groups)
+    }
+
+    fn groups_of(&self, uid: Uid) -> Option<Vec<Arc<Group>>> {
+        let uid = uid.as_raw();
+        self.get_user_by_uid(uid)?;
+        let gids = self.memberships().groups_of.get(&uid);
+        let groups = gids.into_iter().flatten();
+        Some(
+            groups
+                .filter_map(|gid| self.get_group_by_gid(*gid))
+                .collect(),
+        )
+    }
+}
+
+impl<H: BuildHasher> AllGroups for UsersSnapshot<H> {
+    type GroupIter<'a>
+        = std::iter::FilterMap<
         std::collections::hash_map::Values<'a, gid_t, Option<Arc<Group>>>,
         for<'b> fn(&'b Option<Arc<Group>>) -> Option<&'b Group>,
-    >;
+    >
+    where
+        H: 'a;
 
     fn get_all_groups(&self) -> Self::GroupIter<'_> {
         fn get_group(x: &Option<Arc<Group>>) -> Option<&Group> {
@@ -653,14 +2584,22 @@ impl AllGroups for UsersSnapshot {
     }
 }
 
-impl Groups for UsersSnapshot {
+impl<H: BuildHasher> AllGroupsOwned for UsersSnapshot<H> {
+    type OwnedGroupIter = vec::IntoIter<Arc<Group>>;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        let groups = self.groups.forward.values().flatten().cloned();
+        groups.collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<H: BuildHasher> Groups for UsersSnapshot<H> {
     fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
         self.groups.forward.get(&gid)?.as_ref().cloned()
     }
 
     fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
-        let name_arc = Arc::from(group_name.as_ref());
-        let gid = self.groups.backward.get(&name_arc)?.as_ref()?;
+        let gid = self.groups.backward.get(group_name.as_ref())?.as_ref()?;
         self.get_group_by_gid(*gid)
     }
 
@@ -682,3 +2621,611 @@ impl Groups for UsersSnapshot {
             .map(|g| Arc::clone(&g.name_arc))
     }
 }
+
+impl<H: BuildHasher> TryUsers for UsersSnapshot<H> {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_uid(uid))
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_name(username))
+    }
+}
+
+impl<H: BuildHasher> TryGroups for UsersSnapshot<H> {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_gid(gid))
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_name(group_name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::SystemTime;
+
+    use time::MockClock;
+
+    #[test]
+    fn remove_drops_names() {
+        let mut map = IdNameMap::<uid_t, &str, &str>::default();
+        map.insert(1, "one", "first");
+        map.insert(2, "two", "second");
+        map.backward.insert("uno", Some(1));
+        map.backward.insert("nobody", None);
+
+        map.remove(1);
+
+        assert!(!map.forward.contains_key(&1));
+        assert_eq!(map.backward.len(), 2);
+        assert_eq!(map.backward["two"], Some(2));
+        assert_eq!(map.backward["nobody"], None);
+    }
+
+    #[test]
+    fn invalidate_user() {
+        let cache = UsersCache::new();
+        let uid = cache.get_current_uid();
+        cache.get_user_by_uid(uid);
+        cache.get_user_by_name("user\u{0}name");
+
+        cache.invalidate_user(uid);
+        assert!(!cache.users.borrow().forward.contains_key(&uid));
+        assert_eq!(cache.users.borrow().backward.len(), 1);
+
+        cache.clear();
+        assert!(cache.users.borrow().backward.is_empty());
+        assert_eq!(cache.get_current_uid(), uid);
+    }
+
+    #[test]
+    fn expired_entries_are_fetched_again() {
+        let clock = MockClock::new(SystemTime::now());
+        let policy = CachePolicy {
+            positive_ttl: Some(Duration::from_secs(60)),
+            negative_ttl: Some(Duration::from_secs(5)),
+            ..CachePolicy::default()
+        };
+        let cache = UsersCache::with_policy_and_clock(policy, clock.clone());
+        let uid = cache.get_current_uid();
+
+        let first = cache.get_user_by_uid(uid);
+        cache.get_user_by_name("user\u{0}name");
+        assert!(cache
+            .users_stored
+            .borrow()
+            .names
+            .contains_key(OsStr::new("user\u{0}name")));
+
+        clock.advance(Duration::from_secs(10));
+        let stamp = cache.users_stored.borrow().ids[&uid];
+        let second = cache.get_user_by_uid(uid);
+        if let (Some(a), Some(b)) = (&first, &second) {
+            assert!(Arc::ptr_eq(a, b));
+        }
+        assert_eq!(cache.users_stored.borrow().ids[&uid], stamp);
+
+        cache.get_user_by_name("user\u{0}name");
+        assert_eq!(
+            cache.users_stored.borrow().names[OsStr::new("user\u{0}name")],
+            clock.instant()
+        );
+
+        clock.advance(Duration::from_secs(60));
+        cache.get_user_by_uid(uid);
+        assert_eq!(cache.users_stored.borrow().ids[&uid], clock.instant());
+    }
+
+    #[test]
+    fn refresh_older_than() {
+        let clock = MockClock::new(SystemTime::now());
+        let cache = UsersCache::with_policy_and_clock(CachePolicy::default(), clock.clone());
+        let uid = cache.get_current_uid();
+        cache.get_user_by_uid(uid);
+        assert_eq!(cache.user_fetched_at(uid), Some(clock.instant()));
+
+        clock.advance(Duration::from_secs(60));
+        cache.get_group_by_name("group\u{0}name");
+        cache.refresh_older_than(Duration::from_secs(30));
+        assert_eq!(cache.user_fetched_at(uid), Some(clock.instant()));
+        assert_eq!(cache.stats().expirations, 1);
+
+        cache.refresh_older_than(Duration::from_secs(30));
+        assert_eq!(cache.stats().misses(), 3);
+        assert!(cache.refresh_before.get().is_none());
+    }
+
+    #[test]
+    fn stats() {
+        let cache = UsersCache::new();
+        let uid = cache.get_current_uid();
+        cache.get_user_by_uid(uid);
+        cache.get_user_by_uid(uid);
+        cache.get_group_by_name("group\u{0}name");
+        cache.get_group_by_name("group\u{0}name");
+
+        let stats = cache.stats();
+        assert_eq!((stats.user_hits, stats.user_misses), (1, 1));
+        assert_eq!((stats.group_hits, stats.group_misses), (1, 1));
+        assert_eq!(stats.negative_hits, 1);
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn without_negative_caching() {
+        let cache = UsersCache::with_policy(CachePolicy {
+            negative_caching: false,
+            ..CachePolicy::default()
+        });
+
+        assert!(cache.get_user_by_name("user\u{0}name").is_none());
+        assert!(cache.get_group_by_gid(gid_t::MAX - 1).is_none());
+        assert!(cache.get_group_by_gid(gid_t::MAX - 1).is_none());
+        assert!(cache.users.borrow().backward.is_empty());
+        assert!(cache.groups.borrow().forward.is_empty());
+        assert_eq!(cache.stats().misses(), 3);
+    }
+
+    #[test]
+    fn preload() {
+        let cache = UsersCache::new();
+        let uid = cache.get_current_uid();
+        let gid = cache.get_current_gid();
+        cache.preload_users(vec![uid, uid]);
+        cache.preload_groups(Some(gid));
+        assert_eq!(cache.stats().misses(), 2);
+
+        cache.get_user_by_uid(uid);
+        cache.get_group_by_gid(gid);
+        assert_eq!(cache.stats().misses(), 2);
+    }
+
+    #[test]
+    fn dump() {
+        let cache = UsersCache::new();
+        assert_eq!(cache.dump(), CacheDump::default());
+
+        let uid = cache.get_current_uid();
+        let found = cache
+            .get_user_by_uid(uid)
+            .map(|u| u.name().to_string_lossy().into_owned());
+        cache.get_group_by_name("group\u{0}name");
+
+        let dump = cache.dump();
+        assert_eq!(dump.current_uid, Some(uid));
+        assert_eq!(dump.effective_uid, None);
+        assert_eq!(
+            dump.users,
+            vec![DumpedId {
+                id: uid,
+                name: found
+            }]
+        );
+        assert!(dump.groups.is_empty());
+        assert_eq!(
+            dump.group_names,
+            vec![DumpedName {
+                name: "group\u{0}name".into(),
+                id: None
+            }]
+        );
+    }
+
+    #[test]
+    fn freeze() {
+        let cache = UsersCache::new();
+        let uid = cache.get_current_uid();
+        let user = cache.get_user_by_uid(uid);
+        cache.get_user_by_name("user\u{0}name");
+
+        let snapshot = cache.freeze();
+        assert!(!snapshot.is_complete());
+        assert_eq!(snapshot.get_current_uid(), uid);
+        assert_eq!(
+            snapshot.get_all_users().count(),
+            usize::from(user.is_some())
+        );
+        if let Some(user) = user {
+            let frozen = snapshot.get_user_by_name(user.name()).unwrap();
+            assert!(Arc::ptr_eq(&user, &frozen));
+        }
+    }
+
+    #[test]
+    fn user_groups() {
+        let cache = UsersCache::new();
+        let uid = cache.get_current_uid();
+        let first = cache.get_user_groups(uid);
+        let second = cache.get_user_groups(uid);
+        assert_eq!(first.is_some(), second.is_some());
+        if let (Some(a), Some(b)) = (first, second) {
+            assert!(a.iter().zip(&b).all(|(a, b)| Arc::ptr_eq(a, b)));
+        }
+        assert!(cache.user_groups.borrow().contains_key(&uid));
+
+        cache.invalidate_user(uid);
+        assert!(cache.user_groups.borrow().is_empty());
+        let identity = SupplementaryGroups::identity_of(&cache, Uid::from_raw(uid));
+        assert_eq!(
+            cache.user_groups.borrow().contains_key(&uid),
+            identity.is_some()
+        );
+        cache.invalidate_user(uid);
+        assert!(cache.get_user_groups(uid_t::MAX - 1).is_none());
+        cache.clear();
+        assert!(cache.user_groups.borrow().is_empty());
+    }
+
+    #[test]
+    fn from_snapshot() {
+        let snapshot = UsersSnapshot::from(
+            vec![User::new(4242, "fred", 100)].into_iter(),
+            vec![Group::new(100, "staff")].into_iter(),
+            4242,
+            100,
+            4242,
+            100,
+            RandomState::new(),
+        );
+
+        let cache = UsersCache::from_snapshot(&snapshot);
+        assert_eq!(
+            cache.get_current_username().as_deref(),
+            Some(OsStr::new("fred"))
+        );
+        let fred = cache.get_user_by_name("fred").unwrap();
+        assert!(Arc::ptr_eq(&fred, &snapshot.get_user_by_uid(4242).unwrap()));
+        assert_eq!(cache.get_group_by_gid(100).unwrap().name(), "staff");
+        assert_eq!(cache.stats().misses(), 0);
+    }
+
+    #[test]
+    fn custom_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::BuildHasherDefault;
+
+        let cache = UsersCache::with_hasher(BuildHasherDefault::<DefaultHasher>::default());
+        let uid = cache.get_current_uid();
+        let user = cache.get_user_by_uid(uid);
+        assert!(cache.get_group_by_name("group\u{0}name").is_none());
+        cache.clear();
+
+        let user = user.map(|u| Arc::clone(&u.name_arc));
+        assert_eq!(cache.get_current_username(), user);
+        let snapshot: UsersSnapshot<BuildHasherDefault<DefaultHasher>> = cache.freeze();
+        assert_eq!(snapshot.get_current_username(), user);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let cache = UsersCache::with_capacity(1000, 100);
+        assert!(cache.users.borrow().forward.capacity() >= 1000);
+        assert!(cache.groups_stored.borrow().names.capacity() >= 100);
+        let uid = cache.get_current_uid();
+        assert_eq!(
+            cache.get_user_by_uid(uid).map(|u| u.uid()),
+            super::super::get_user_by_uid(uid).map(|u| u.uid())
+        );
+
+        let snapshot = unsafe { UsersSnapshot::new_with_capacity(1000, 100) };
+        assert!(snapshot.users.backward.capacity() >= 1000);
+        let everyone = unsafe { UsersSnapshot::new() };
+        assert_eq!(
+            snapshot.get_all_users().count(),
+            everyone.get_all_users().count()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "global-snapshot")]
+    fn global_snapshot() {
+        let first = unsafe { snapshot() };
+        let second = std::thread::spawn(|| unsafe { snapshot() }).join().unwrap();
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn new_on_thread() {
+        let snapshot = unsafe { UsersSnapshot::new_on_thread() }.join().unwrap();
+        assert_eq!(snapshot.get_current_uid(), super::super::get_current_uid());
+    }
+
+    #[test]
+    fn try_new() {
+        let snapshot = unsafe { UsersSnapshot::try_new() }.unwrap();
+        assert!(snapshot.is_complete());
+        let everyone = unsafe { UsersSnapshot::new() };
+        assert_eq!(
+            snapshot.get_all_groups().count(),
+            everyone.get_all_groups().count()
+        );
+    }
+
+    #[test]
+    fn partial_snapshot() {
+        let mut snapshot = UsersSnapshot::default();
+        let fred = Arc::new(User::new(1000, "fred", 100));
+        snapshot
+            .users
+            .insert(1000, Arc::clone(&fred.name_arc), fred);
+        snapshot.incomplete = true;
+        let error = io::Error::new(io::ErrorKind::TimedOut, "directory went away");
+        let partial = PartialSnapshot {
+            error,
+            snapshot: Box::new(snapshot),
+        };
+
+        assert_eq!(partial.error().kind(), io::ErrorKind::TimedOut);
+        assert!(partial.to_string().ends_with("directory went away"));
+        assert_eq!(partial.snapshot().get_all_users().count(), 1);
+
+        let (error, snapshot) = partial.into_parts();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(!snapshot.is_complete());
+    }
+
+    #[test]
+    fn stop_is_checked_before_reading() {
+        let read = Cell::new(0);
+        let items = (0..5).inspect(|_| read.set(read.get() + 1));
+
+        let mut kept = Vec::new();
+        let stopped = collect_until(items, |n| n % 2 == 0, || read.get() == 3, &mut kept);
+        assert!(stopped);
+        assert_eq!(read.get(), 3);
+        assert_eq!(kept, vec![0, 2]);
+
+        let mut checks = 0;
+        let stopped = collect_until(
+            0..3,
+            |_| true,
+            || {
+                checks += 1;
+                false
+            },
+            &mut kept,
+        );
+        assert!(!stopped);
+        assert_eq!(checks, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "mock")]
+    fn from_source() {
+        use mock::MockUsers;
+
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(1000, "fred", 100));
+        users.add_group(Group::new(100, "staff"));
+
+        let snapshot = UsersSnapshot::from_source(&users);
+        assert_eq!(snapshot.get_current_gid(), 1000);
+        assert_eq!(snapshot.get_user_by_name("fred").unwrap().uid(), 1000);
+        assert_eq!(snapshot.get_group_by_gid(100).unwrap().name(), "staff");
+
+        let copy = UsersSnapshot::from_source(&snapshot);
+        assert_eq!(copy.get_all_users().count(), 1);
+        assert_eq!(copy.get_all_groups().count(), 1);
+        assert!(copy.is_complete());
+    }
+
+    #[test]
+    fn merged() {
+        let fred = Arc::new(User::new(1000, "fred", 100));
+        let jane = Arc::new(User::new(1001, "jane", 100));
+        let app = Arc::new(User::new(1002, "fred", 100));
+        let staff = Arc::new(Group::new(100, "staff"));
+
+        let mut host: UsersSnapshot = UsersSnapshot::default();
+        host.users.insert(1000, Arc::clone(&fred.name_arc), fred);
+        host.users
+            .insert(1001, Arc::clone(&jane.name_arc), Arc::clone(&jane));
+        host.groups.insert(100, Arc::clone(&staff.name_arc), staff);
+        let mut container: UsersSnapshot = UsersSnapshot::default();
+        container
+            .users
+            .insert(1002, Arc::clone(&app.name_arc), Arc::clone(&app));
+        container.uid = 1002;
+        container.incomplete = true;
+
+        let merged = UsersSnapshot::merged(&host, &container);
+        assert!(Arc::ptr_eq(&merged.get_user_by_name("fred").unwrap(), &app));
+        assert!(Arc::ptr_eq(&merged.get_user_by_uid(1001).unwrap(), &jane));
+        assert!(merged.get_user_by_uid(1000).is_none());
+        assert_eq!(merged.get_all_users().count(), 2);
+        assert_eq!(merged.get_group_by_name("staff").unwrap().gid(), 100);
+        assert_eq!(merged.get_current_uid(), 1002);
+        assert!(!merged.is_complete());
+    }
+
+    #[test]
+    fn sorted() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        for user in [
+            User::new(1002, "amy", 100),
+            User::new(1000, "zoe", 100),
+            User::new(1001, "bob", 100),
+        ] {
+            snapshot
+                .users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+        }
+        for group in [Group::new(101, "admin"), Group::new(100, "staff")] {
+            snapshot
+                .groups
+                .insert(group.gid(), Arc::clone(&group.name_arc), Arc::new(group));
+        }
+
+        let uids = snapshot.get_all_users_sorted_by_uid().map(User::uid);
+        assert_eq!(uids.collect::<Vec<_>>(), [1000, 1001, 1002]);
+        let names = snapshot.get_all_users_sorted_by_name().map(User::name);
+        assert_eq!(names.collect::<Vec<_>>(), ["amy", "bob", "zoe"]);
+        let gids = snapshot.get_all_groups_sorted_by_gid().map(Group::gid);
+        assert_eq!(gids.collect::<Vec<_>>(), [100, 101]);
+        let names = snapshot.get_all_groups_sorted_by_name().map(Group::name);
+        assert_eq!(names.collect::<Vec<_>>(), ["admin", "staff"]);
+    }
+
+    #[test]
+    fn only_groups() {
+        let snapshot = unsafe { UsersSnapshot::only_groups(|g| g.gid() == 0) };
+        assert!(snapshot.get_all_groups().all(|g| g.gid() == 0));
+        let root = snapshot.get_group_by_gid(0);
+        for user in snapshot.get_all_users() {
+            let member = root
+                .as_ref()
+                .is_some_and(|g| g.members().contains(&user.name().into()));
+            assert!(user.primary_group_id() == 0 || member);
+        }
+
+        if let Some(root) = super::super::get_user_by_uid(0).filter(|u| u.primary_group_id() == 0) {
+            assert!(snapshot.get_user_by_name(root.name()).is_some());
+        }
+    }
+
+    #[test]
+    fn memberships() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        for user in [User::new(1000, "fred", 100), User::new(1001, "jane", 102)] {
+            snapshot
+                .users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+        }
+        for group in [
+            Group::new(100, "staff"),
+            Group::new(101, "wheel")
+                .add_member("fred")
+                .add_member("nobody"),
+            Group::new(99, "audio")
+                .add_member("fred")
+                .add_member("jane"),
+        ] {
+            snapshot
+                .groups
+                .insert(group.gid(), Arc::clone(&group.name_arc), Arc::new(group));
+        }
+
+        let gids = |uid| {
+            snapshot
+                .groups_of_user(uid)
+                .map(Group::gid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(gids(1000), [99, 100, 101]);
+        assert_eq!(gids(1001), [99]);
+        assert!(gids(1002).is_empty());
+
+        let uids = |gid| {
+            snapshot
+                .users_in_group(gid)
+                .map(User::uid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(uids(99), [1000, 1001]);
+        assert_eq!(uids(101), [1000]);
+        assert!(uids(102).is_empty());
+
+        let groups = |list: Option<Vec<Arc<Group>>>| {
+            list.map(|groups| groups.iter().map(|g| g.gid()).collect::<Vec<_>>())
+        };
+        assert_eq!(
+            groups(snapshot.groups_of(Uid::from_raw(1000))),
+            Some(vec![99, 100, 101])
+        );
+        assert_eq!(groups(snapshot.groups_of(Uid::from_raw(1002))), None);
+        assert_eq!(
+            groups(SupplementaryGroups::get_user_groups(&snapshot, "jane", 100)),
+            Some(vec![99, 100])
+        );
+    }
+
+    #[test]
+    fn retain() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        for user in [User::new(0, "root", 0), User::new(1000, "fred", 100)] {
+            snapshot
+                .users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+        }
+        for group in [Group::new(0, "root"), Group::new(100, "staff")] {
+            snapshot
+                .groups
+                .insert(group.gid(), Arc::clone(&group.name_arc), Arc::new(group));
+        }
+        assert_eq!(snapshot.users_in_group(0).count(), 1);
+
+        snapshot.retain(|u| u.uid() >= 1000, |g| g.gid() != 100);
+        assert!(snapshot.get_user_by_name("root").is_none());
+        assert!(snapshot.get_user_by_uid(1000).is_some());
+        assert!(snapshot.get_group_by_name("staff").is_none());
+        assert_eq!(snapshot.users.backward.len(), 1);
+        assert_eq!(snapshot.users_in_group(0).count(), 0);
+    }
+
+    #[test]
+    fn counts() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        assert!(snapshot.is_empty());
+
+        let staff = Group::new(100, "staff");
+        snapshot
+            .groups
+            .insert(100, Arc::clone(&staff.name_arc), Arc::new(staff));
+        assert!(!snapshot.is_empty());
+        assert_eq!(snapshot.user_count(), 0);
+        assert_eq!(snapshot.group_count(), 1);
+        assert!(snapshot.contains_gid(100));
+        assert!(!snapshot.contains_gid(101));
+        assert!(!snapshot.contains_uid(100));
+    }
+
+    #[test]
+    fn memory_usage() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        let empty = snapshot.memory_usage();
+        snapshot.users.reserve(1000);
+        let reserved = snapshot.memory_usage();
+        assert!(reserved > empty);
+
+        let fred = User::new(1000, "fred", 100);
+        snapshot
+            .users
+            .insert(1000, Arc::clone(&fred.name_arc), Arc::new(fred));
+        assert!(snapshot.memory_usage() > reserved);
+
+        snapshot.shrink_to_fit();
+        assert!(snapshot.memory_usage() < reserved);
+        assert!(snapshot.get_user_by_name("fred").is_some());
+    }
+
+    #[test]
+    fn with_current_ids() {
+        let cache = UsersCache::new().with_current_ids(0, 1, 2, 3);
+        let root = cache.get_user_by_uid(0).map(|u| Arc::clone(&u.name_arc));
+        assert_eq!(cache.get_current_username(), root);
+        assert_eq!(
+            (
+                cache.get_current_uid(),
+                cache.get_current_gid(),
+                cache.get_effective_uid(),
+                cache.get_effective_gid()
+            ),
+            (0, 1, 2, 3)
+        );
+
+        cache.clear();
+        assert_eq!(cache.get_effective_uid(), 2);
+    }
+}
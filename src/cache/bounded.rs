@@ -0,0 +1,356 @@
+//! A cache that holds a limited number of entries.
+
+use libc::{gid_t, uid_t};
+use std::borrow::Borrow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsStr;
+use std::hash::Hash;
+use std::io;
+use std::sync::Arc;
+
+use base::{self, Group, User};
+use traits::{Groups, TryGroups, TryUsers, Users};
+
+/// A producer of user and group instances that caches the results of the
+/// most recent lookups, evicting the least recently used ones once it’s
+/// full.
+///
+/// This works like [`UsersCache`](super::UsersCache), but its memory use is
+/// bounded, so it suits daemons that look up a great many distinct users
+/// over their lifetime. Each kind of lookup — users by ID, users by name,
+/// groups by ID, and groups by name — keeps at most `capacity` entries,
+/// including lookups that found nothing.
+///
+/// ## Example
+///
+/// ```no_run
+/// use uzers::Users;
+/// use uzers::cache::BoundedUsersCache;
+///
+/// let cache = BoundedUsersCache::with_capacity(1024);
+/// for uid in 1000..100_000 {
+///     cache.get_user_by_uid(uid);
+/// }
+/// assert!(cache.len() <= 1024 * 4);
+/// ```
+pub struct BoundedUsersCache {
+    users_by_id: RefCell<Lru<uid_t, Option<Arc<User>>>>,
+    users_by_name: RefCell<Lru<Arc<OsStr>, Option<Arc<User>>>>,
+    groups_by_id: RefCell<Lru<gid_t, Option<Arc<Group>>>>,
+    groups_by_name: RefCell<Lru<Arc<OsStr>, Option<Arc<Group>>>>,
+
+    uid: Cell<Option<uid_t>>,
+    gid: Cell<Option<gid_t>>,
+    euid: Cell<Option<uid_t>>,
+    egid: Cell<Option<gid_t>>,
+}
+
+impl BoundedUsersCache {
+    /// Creates a new empty cache that keeps at most `capacity` entries for
+    /// each kind of lookup.
+    ///
+    /// A capacity of zero caches nothing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::BoundedUsersCache;
+    ///
+    /// let cache = BoundedUsersCache::with_capacity(256);
+    /// assert_eq!(cache.capacity(), 256);
+    /// ```
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            users_by_id: RefCell::new(Lru::new(capacity)),
+            users_by_name: RefCell::new(Lru::new(capacity)),
+            groups_by_id: RefCell::new(Lru::new(capacity)),
+            groups_by_name: RefCell::new(Lru::new(capacity)),
+            uid: Cell::default(),
+            gid: Cell::default(),
+            euid: Cell::default(),
+            egid: Cell::default(),
+        }
+    }
+
+    /// Returns the most entries that each kind of lookup keeps.
+    pub fn capacity(&self) -> usize {
+        self.users_by_id.borrow().capacity
+    }
+
+    /// Returns the number of entries currently cached, across every kind of
+    /// lookup.
+    pub fn len(&self) -> usize {
+        self.users_by_id.borrow().len()
+            + self.users_by_name.borrow().len()
+            + self.groups_by_id.borrow().len()
+            + self.groups_by_name.borrow().len()
+    }
+
+    /// Returns whether nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops every cached user and group, including lookups that found
+    /// nothing. The current and effective IDs of the process are kept.
+    pub fn clear(&self) {
+        self.users_by_id.borrow_mut().clear();
+        self.users_by_name.borrow_mut().clear();
+        self.groups_by_id.borrow_mut().clear();
+        self.groups_by_name.borrow_mut().clear();
+    }
+}
+
+impl Users for BoundedUsersCache {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.try_get_user_by_uid(uid).unwrap_or_default()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.try_get_user_by_name(username).unwrap_or_default()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.uid.get().unwrap_or_else(|| {
+            let uid = base::get_current_uid();
+            self.uid.set(Some(uid));
+            uid
+        })
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.euid.get().unwrap_or_else(|| {
+            let uid = base::get_effective_uid();
+            self.euid.set(Some(uid));
+            uid
+        })
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl Groups for BoundedUsersCache {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.try_get_group_by_gid(gid).unwrap_or_default()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.try_get_group_by_name(group_name).unwrap_or_default()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.gid.get().unwrap_or_else(|| {
+            let gid = base::get_current_gid();
+            self.gid.set(Some(gid));
+            gid
+        })
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.egid.get().unwrap_or_else(|| {
+            let gid = base::get_effective_gid();
+            self.egid.set(Some(gid));
+            gid
+        })
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_effective_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl TryUsers for BoundedUsersCache {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        if let Some(entry) = self.users_by_id.borrow_mut().get(&uid) {
+            return Ok(entry.clone());
+        }
+
+        let user = base::try_get_user_by_uid(uid)?.map(Arc::new);
+        self.users_by_id.borrow_mut().insert(uid, user.clone());
+        Ok(user)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        if let Some(entry) = self.users_by_name.borrow_mut().get(username.as_ref()) {
+            return Ok(entry.clone());
+        }
+
+        let user = base::try_get_user_by_name(username)?.map(Arc::new);
+        self.users_by_name
+            .borrow_mut()
+            .insert(Arc::from(username.as_ref()), user.clone());
+        Ok(user)
+    }
+}
+
+impl TryGroups for BoundedUsersCache {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        if let Some(entry) = self.groups_by_id.borrow_mut().get(&gid) {
+            return Ok(entry.clone());
+        }
+
+        let group = base::try_get_group_by_gid(gid)?.map(Arc::new);
+        self.groups_by_id.borrow_mut().insert(gid, group.clone());
+        Ok(group)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        if let Some(entry) = self.groups_by_name.borrow_mut().get(group_name.as_ref()) {
+            return Ok(entry.clone());
+        }
+
+        let group = base::try_get_group_by_name(group_name)?.map(Arc::new);
+        self.groups_by_name
+            .borrow_mut()
+            .insert(Arc::from(group_name.as_ref()), group.clone());
+        Ok(group)
+    }
+}
+
+/// A map that holds at most `capacity` entries, evicting the least recently
+/// used one to make room for a new one.
+///
+/// Every entry is stamped with the tick at which it was last used, and
+/// `order` maps the ticks back to the keys, so the oldest entry is always
+/// the first one in `order`.
+struct Lru<K, V> {
+    entries: HashMap<K, (V, u64)>,
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    capacity: usize,
+}
+
+impl<K, V> Lru<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn new(capacity: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            capacity,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    /// Returns the value for the given key, marking it as the most recently
+    /// used.
+    fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let tick = self.next_tick();
+        let (value, used) = self.entries.get_mut(key)?;
+
+        let key = self.order.remove(used).expect("LRU order out of sync");
+        self.order.insert(tick, key);
+        *used = tick;
+
+        Some(value)
+    }
+
+    /// Inserts a value for the given key, evicting the least recently used
+    /// entry if the map is full.
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let tick = self.next_tick();
+        if let Some((_, used)) = self.entries.get(&key) {
+            self.order.remove(used);
+        } else if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, (value, tick));
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, "one");
+        lru.insert(2, "two");
+        assert_eq!(lru.get(&1), Some(&"one"));
+
+        lru.insert(3, "three");
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some(&"one"));
+        assert_eq!(lru.get(&3), Some(&"three"));
+    }
+
+    #[test]
+    fn replaces_existing() {
+        let mut lru = Lru::new(2);
+        lru.insert(1, "one");
+        lru.insert(1, "uno");
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.order.len(), 1);
+        assert_eq!(lru.get(&1), Some(&"uno"));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let cache = BoundedUsersCache::with_capacity(0);
+        let uid = cache.get_current_uid();
+        cache.get_user_by_uid(uid);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn bounded() {
+        let cache = BoundedUsersCache::with_capacity(3);
+        for n in 0..10 {
+            cache.get_user_by_name(&format!("user\u{0}{}", n));
+        }
+        assert_eq!(cache.len(), 3);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,269 @@
+//! Configuring how a snapshot is read.
+
+use libc::{gid_t, uid_t};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use base::{self, enumerate_groups, enumerate_users, Group, User};
+use files::{read_group_file_with, read_passwd_file_with, Dialect};
+
+use super::UsersSnapshot;
+
+/// A filter that decides which users or groups go into a snapshot.
+type Filter<T> = Box<dyn FnMut(&T) -> bool>;
+
+/// A builder of [`UsersSnapshot`]s, for when the constructors of the
+/// snapshot itself do not give enough control over what it contains.
+///
+/// Users and groups are read from the system unless a file is given for
+/// them, and every one of them is kept unless a filter is given. The current
+/// and effective IDs of the process are asked for unless they are given, so
+/// that a snapshot built only from files and given IDs does not depend on
+/// the system it’s built on at all.
+///
+/// ## Example
+///
+/// ```no_run
+/// use uzers::{Users, UsersSnapshot};
+///
+/// let snapshot = unsafe {
+///     UsersSnapshot::builder()
+///         .with_users_file("fixtures/passwd")
+///         .with_groups_file("fixtures/group")
+///         .with_user_filter(|u| u.uid() >= 1000)
+///         .with_current_ids(1000, 1000)
+///         .with_effective_ids(1000, 1000)
+///         .build()
+///         .expect("Cannot read the fixtures")
+/// };
+///
+/// assert_eq!(snapshot.get_current_uid(), 1000);
+/// ```
+#[derive(Default)]
+pub struct SnapshotBuilder {
+    users_file: Option<PathBuf>,
+    groups_file: Option<PathBuf>,
+    dialect: Dialect,
+    user_filter: Option<Filter<User>>,
+    group_filter: Option<Filter<Group>>,
+    include_current_user: bool,
+    current_ids: Option<(uid_t, gid_t)>,
+    effective_ids: Option<(uid_t, gid_t)>,
+}
+
+impl SnapshotBuilder {
+    /// Creates a builder of a snapshot holding every user and group of the
+    /// system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the users from the given `passwd` file instead of the system.
+    pub fn with_users_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.users_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Reads the groups from the given `group` file instead of the system.
+    pub fn with_groups_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.groups_file = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Reads the files in the given dialect instead of the classic format.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Keeps only the users that pass the filter.
+    pub fn with_user_filter<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&User) -> bool + 'static,
+    {
+        self.user_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Keeps only the groups that pass the filter.
+    pub fn with_group_filter<F>(mut self, filter: F) -> Self
+    where
+        F: FnMut(&Group) -> bool + 'static,
+    {
+        self.group_filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Keeps the current and effective users of the process, along with
+    /// their primary groups, even if the filters would leave them out.
+    pub fn include_current_user(mut self, include: bool) -> Self {
+        self.include_current_user = include;
+        self
+    }
+
+    /// Uses the given IDs as the current user and group IDs of the process,
+    /// instead of asking the system for them.
+    pub fn with_current_ids(mut self, uid: uid_t, gid: gid_t) -> Self {
+        self.current_ids = Some((uid, gid));
+        self
+    }
+
+    /// Uses the given IDs as the effective user and group IDs of the
+    /// process, instead of asking the system for them.
+    pub fn with_effective_ids(mut self, uid: uid_t, gid: gid_t) -> Self {
+        self.effective_ids = Some((uid, gid));
+        self
+    }
+
+    /// Reads the users and groups, and builds the snapshot.
+    ///
+    /// # Safety
+    ///
+    /// This is `unsafe` because the users or groups that are not read from
+    /// a file are enumerated from the system, and we cannot prevent data
+    /// races if that happens on two threads at the same time. For more
+    /// information, see the [`all_users` documentation](../fn.all_users.html).
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if one of the files cannot be read,
+    /// or if reading the users or groups of the system fails before the end
+    /// of either is reached. See [`UsersSnapshot::try_new`].
+    pub unsafe fn build(self) -> io::Result<UsersSnapshot> {
+        let (uid, gid) = self
+            .current_ids
+            .unwrap_or_else(|| (base::get_current_uid(), base::get_current_gid()));
+        let (euid, egid) = self
+            .effective_ids
+            .unwrap_or_else(|| (base::get_effective_uid(), base::get_effective_gid()));
+
+        let mut user_filter = self.user_filter;
+        let include = self.include_current_user;
+        let mut kept_groups = Vec::new();
+        let mut keep_user = |user: &User| {
+            let current = include && (user.uid() == uid || user.uid() == euid);
+            if current {
+                kept_groups.push(user.primary_group_id());
+            }
+            current || user_filter.as_mut().is_none_or(|filter| filter(user))
+        };
+
+        let users = match &self.users_file {
+            Some(path) => read_passwd_file_with(path, self.dialect)?
+                .into_iter()
+                .filter(|u| keep_user(u))
+                .collect::<Vec<_>>(),
+            None => {
+                let mut all = enumerate_users();
+                let users = all.by_ref().filter(|u| keep_user(u)).collect();
+                if let Some(error) = all.take_error() {
+                    return Err(error);
+                }
+                users
+            }
+        };
+
+        let mut group_filter = self.group_filter;
+        let mut keep_group = |group: &Group| {
+            kept_groups.contains(&group.gid())
+                || group_filter.as_mut().is_none_or(|filter| filter(group))
+        };
+
+        let groups = match &self.groups_file {
+            Some(path) => read_group_file_with(path, self.dialect)?
+                .into_iter()
+                .filter(|g| keep_group(g))
+                .collect::<Vec<_>>(),
+            None => {
+                let mut all = enumerate_groups();
+                let groups = all.by_ref().filter(|g| keep_group(g)).collect();
+                if let Some(error) = all.take_error() {
+                    return Err(error);
+                }
+                groups
+            }
+        };
+
+        Ok(UsersSnapshot::from(
+            users.into_iter(),
+            groups.into_iter(),
+            uid,
+            gid,
+            euid,
+            egid,
+            RandomState::new(),
+        ))
+    }
+}
+
+impl fmt::Debug for SnapshotBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SnapshotBuilder")
+            .field("users_file", &self.users_file)
+            .field("groups_file", &self.groups_file)
+            .field("dialect", &self.dialect)
+            .field("user_filter", &self.user_filter.is_some())
+            .field("group_filter", &self.group_filter.is_some())
+            .field("include_current_user", &self.include_current_user)
+            .field("current_ids", &self.current_ids)
+            .field("effective_ids", &self.effective_ids)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use traits::{AllGroups, AllUsers, Groups, Users};
+
+    #[test]
+    fn from_files() {
+        let snapshot = unsafe {
+            SnapshotBuilder::new()
+                .with_users_file("tests/fixtures/passwd")
+                .with_groups_file("tests/fixtures/group")
+                .with_group_filter(|g| g.gid() != 42)
+                .with_current_ids(1337, 42)
+                .with_effective_ids(0, 0)
+                .include_current_user(true)
+                .build()
+        }
+        .unwrap();
+
+        assert_eq!(snapshot.get_current_uid(), 1337);
+        assert_eq!(snapshot.get_effective_gid(), 0);
+        assert_eq!(snapshot.get_all_users().count(), 1);
+        assert_eq!(snapshot.get_all_groups().count(), 2);
+        assert!(snapshot.get_group_by_name("bosses").is_some());
+    }
+
+    #[test]
+    fn filtered_out() {
+        let snapshot = unsafe {
+            SnapshotBuilder::new()
+                .with_users_file("tests/fixtures/passwd")
+                .with_groups_file("tests/fixtures/group")
+                .with_user_filter(|_| false)
+                .with_group_filter(|_| false)
+                .with_current_ids(1337, 42)
+                .build()
+        }
+        .unwrap();
+
+        assert!(snapshot.get_user_by_uid(1337).is_none());
+        assert!(snapshot.get_group_by_gid(42).is_none());
+    }
+
+    #[test]
+    fn missing_file() {
+        let result = unsafe {
+            SnapshotBuilder::new()
+                .with_users_file("tests/fixtures/nothing-here")
+                .build()
+        };
+        assert!(result.is_err_and(|e| e.kind() == io::ErrorKind::NotFound));
+    }
+}
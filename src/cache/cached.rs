@@ -0,0 +1,441 @@
+//! Caching on top of any provider.
+
+use libc::{gid_t, uid_t};
+use std::cell::{Cell, RefCell};
+use std::ffi::OsStr;
+use std::io;
+use std::sync::Arc;
+
+use base::{Group, User};
+use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, TryGroups, TryUsers, Users,
+};
+
+use super::IdNameMap;
+
+/// A provider that caches the results of another provider.
+///
+/// [`UsersCache`](super::UsersCache) caches what the system returns; this
+/// does the same for any type that implements [`Users`] and [`Groups`], such
+/// as a [`MockUsers`](../mock/struct.MockUsers.html) table or a provider of
+/// your own that is slow to ask. Every lookup is passed on to the inner
+/// provider at most once, including lookups that find nothing, and the
+/// current and effective IDs are only asked for once.
+///
+/// Listing every user or group is passed straight on to the inner provider.
+/// Lookups through [`TryUsers`] and [`TryGroups`] pass the errors of the
+/// inner provider on without caching them.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::{Users, User};
+/// use uzers::cache::Cached;
+/// use uzers::mock::MockUsers;
+///
+/// let mut users = MockUsers::with_current_uid(1000);
+/// users.add_user(User::new(1000, "fred", 100));
+///
+/// let cached = Cached::new(users);
+/// assert_eq!(cached.get_user_by_uid(1000).unwrap().name(), "fred");
+/// ```
+pub struct Cached<U> {
+    inner: U,
+
+    users: RefCell<IdNameMap<uid_t, Arc<OsStr>, Arc<User>>>,
+    groups: RefCell<IdNameMap<gid_t, Arc<OsStr>, Arc<Group>>>,
+
+    uid: Cell<Option<uid_t>>,
+    gid: Cell<Option<gid_t>>,
+    euid: Cell<Option<uid_t>>,
+    egid: Cell<Option<gid_t>>,
+}
+
+impl<U> Cached<U> {
+    /// Creates a new empty cache on top of the given provider.
+    pub fn new(inner: U) -> Self {
+        Self {
+            inner,
+            users: RefCell::default(),
+            groups: RefCell::default(),
+            uid: Cell::default(),
+            gid: Cell::default(),
+            euid: Cell::default(),
+            egid: Cell::default(),
+        }
+    }
+
+    /// Returns the provider that lookups are passed on to.
+    pub fn inner(&self) -> &U {
+        &self.inner
+    }
+
+    /// Returns the provider that lookups are passed on to, dropping
+    /// everything that was cached.
+    pub fn into_inner(self) -> U {
+        self.inner
+    }
+
+    /// Drops every cached user and group, including lookups that found
+    /// nothing, and the current and effective IDs.
+    pub fn clear(&self) {
+        self.users.borrow_mut().clear();
+        self.groups.borrow_mut().clear();
+        self.uid.set(None);
+        self.gid.set(None);
+        self.euid.set(None);
+        self.egid.set(None);
+    }
+
+    /// Returns the cached user with the given ID, or asks `fetch` for it
+    /// and caches what it returns. Errors are passed on without being
+    /// cached.
+    fn user_by_uid(
+        &self,
+        uid: uid_t,
+        fetch: impl FnOnce() -> io::Result<Option<Arc<User>>>,
+    ) -> io::Result<Option<Arc<User>>> {
+        if let Some(entry) = self.users.borrow().forward.get(&uid) {
+            return Ok(entry.clone());
+        }
+
+        let user = fetch()?;
+
+        let mut users = self.users.borrow_mut();
+        if let Some(user) = &user {
+            users.backward.insert(Arc::clone(&user.name_arc), Some(uid));
+        }
+        users.forward.insert(uid, user.clone());
+        Ok(user)
+    }
+
+    /// Returns the cached user with the given name, or asks `fetch` for it
+    /// and caches what it returns. Errors are passed on without being
+    /// cached.
+    fn user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        fetch: impl FnOnce() -> io::Result<Option<Arc<User>>>,
+    ) -> io::Result<Option<Arc<User>>> {
+        {
+            let users = self.users.borrow();
+            if let Some(entry) = users.backward.get(username.as_ref()) {
+                return Ok((*entry).and_then(|uid| users.forward[&uid].clone()));
+            }
+        }
+
+        let user = fetch()?;
+
+        let mut users = self.users.borrow_mut();
+        match &user {
+            Some(user) => {
+                let uid = user.uid();
+                if user.name() != username.as_ref() {
+                    users
+                        .backward
+                        .insert(Arc::from(username.as_ref()), Some(uid));
+                }
+                users.insert(uid, Arc::clone(&user.name_arc), Arc::clone(user));
+            }
+            None => {
+                users.backward.insert(Arc::from(username.as_ref()), None);
+            }
+        }
+        Ok(user)
+    }
+
+    /// Returns the cached group with the given ID, or asks `fetch` for it
+    /// and caches what it returns. Errors are passed on without being
+    /// cached.
+    fn group_by_gid(
+        &self,
+        gid: gid_t,
+        fetch: impl FnOnce() -> io::Result<Option<Arc<Group>>>,
+    ) -> io::Result<Option<Arc<Group>>> {
+        if let Some(entry) = self.groups.borrow().forward.get(&gid) {
+            return Ok(entry.clone());
+        }
+
+        let group = fetch()?;
+
+        let mut groups = self.groups.borrow_mut();
+        if let Some(group) = &group {
+            groups
+                .backward
+                .insert(Arc::clone(&group.name_arc), Some(gid));
+        }
+        groups.forward.insert(gid, group.clone());
+        Ok(group)
+    }
+
+    /// Returns the cached group with the given name, or asks `fetch` for it
+    /// and caches what it returns. Errors are passed on without being
+    /// cached.
+    fn group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+        fetch: impl FnOnce() -> io::Result<Option<Arc<Group>>>,
+    ) -> io::Result<Option<Arc<Group>>> {
+        {
+            let groups = self.groups.borrow();
+            if let Some(entry) = groups.backward.get(group_name.as_ref()) {
+                return Ok((*entry).and_then(|gid| groups.forward[&gid].clone()));
+            }
+        }
+
+        let group = fetch()?;
+
+        let mut groups = self.groups.borrow_mut();
+        match &group {
+            Some(group) => {
+                let gid = group.gid();
+                if group.name() != group_name.as_ref() {
+                    groups
+                        .backward
+                        .insert(Arc::from(group_name.as_ref()), Some(gid));
+                }
+                groups.insert(gid, Arc::clone(&group.name_arc), Arc::clone(group));
+            }
+            None => {
+                groups.backward.insert(Arc::from(group_name.as_ref()), None);
+            }
+        }
+        Ok(group)
+    }
+}
+
+/// Returns the value of the cell, setting it with the function first if it’s
+/// empty.
+fn get_or_set<T: Copy>(cell: &Cell<Option<T>>, f: impl FnOnce() -> T) -> T {
+    cell.get().unwrap_or_else(|| {
+        let value = f();
+        cell.set(Some(value));
+        value
+    })
+}
+
+impl<U: Users> Users for Cached<U> {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.user_by_uid(uid, || Ok(self.inner.get_user_by_uid(uid)))
+            .unwrap_or_default()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.user_by_name(username, || Ok(self.inner.get_user_by_name(username)))
+            .unwrap_or_default()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        get_or_set(&self.uid, || self.inner.get_current_uid())
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        get_or_set(&self.euid, || self.inner.get_effective_uid())
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl<U: Groups> Groups for Cached<U> {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.group_by_gid(gid, || Ok(self.inner.get_group_by_gid(gid)))
+            .unwrap_or_default()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.group_by_name(group_name, || Ok(self.inner.get_group_by_name(group_name)))
+            .unwrap_or_default()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        get_or_set(&self.gid, || self.inner.get_current_gid())
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        get_or_set(&self.egid, || self.inner.get_effective_gid())
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_effective_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl<U: TryUsers> TryUsers for Cached<U> {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        self.user_by_uid(uid, || self.inner.try_get_user_by_uid(uid))
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        self.user_by_name(username, || self.inner.try_get_user_by_name(username))
+    }
+}
+
+impl<U: TryGroups> TryGroups for Cached<U> {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        self.group_by_gid(gid, || self.inner.try_get_group_by_gid(gid))
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        self.group_by_name(group_name, || self.inner.try_get_group_by_name(group_name))
+    }
+}
+
+impl<U: AllUsers> AllUsers for Cached<U> {
+    type UserIter<'a>
+        = U::UserIter<'a>
+    where
+        U: 'a;
+
+    fn get_all_users(&self) -> Self::UserIter<'_> {
+        self.inner.get_all_users()
+    }
+}
+
+impl<U: AllGroups> AllGroups for Cached<U> {
+    type GroupIter<'a>
+        = U::GroupIter<'a>
+    where
+        U: 'a;
+
+    fn get_all_groups(&self) -> Self::GroupIter<'_> {
+        self.inner.get_all_groups()
+    }
+}
+
+impl<U: AllUsersOwned> AllUsersOwned for Cached<U> {
+    type OwnedUserIter = U::OwnedUserIter;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        self.inner.get_all_users_owned()
+    }
+}
+
+impl<U: AllGroupsOwned> AllGroupsOwned for Cached<U> {
+    type OwnedGroupIter = U::OwnedGroupIter;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        self.inner.get_all_groups_owned()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::*;
+
+    use mock::MockUsers;
+
+    /// A provider that counts how often it’s asked.
+    struct Counting {
+        inner: MockUsers,
+        lookups: Cell<usize>,
+    }
+
+    impl Users for Counting {
+        fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.inner.get_user_by_uid(uid)
+        }
+
+        fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.inner.get_user_by_name(username)
+        }
+
+        fn get_current_uid(&self) -> uid_t {
+            self.inner.get_current_uid()
+        }
+
+        fn get_current_username(&self) -> Option<Arc<OsStr>> {
+            self.inner.get_current_username()
+        }
+
+        fn get_effective_uid(&self) -> uid_t {
+            self.inner.get_effective_uid()
+        }
+
+        fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+            self.inner.get_effective_username()
+        }
+    }
+
+    fn cached() -> Cached<Counting> {
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(1000, "fred", 100));
+        Cached::new(Counting {
+            inner: users,
+            lookups: Cell::new(0),
+        })
+    }
+
+    #[test]
+    fn asks_once() {
+        let cached = cached();
+        let first = cached.get_user_by_uid(1000).unwrap();
+        let second = cached.get_user_by_name("fred").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cached.inner().lookups.get(), 1);
+
+        assert!(cached.get_user_by_name("bob").is_none());
+        assert!(cached.get_user_by_name("bob").is_none());
+        assert_eq!(cached.inner().lookups.get(), 2);
+    }
+
+    #[test]
+    fn clear() {
+        let cached = cached();
+        cached.get_current_username();
+        cached.clear();
+        cached.get_current_username();
+        assert_eq!(cached.inner().lookups.get(), 2);
+    }
+
+    #[test]
+    fn groups_and_listing() {
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_group(Group::new(100, "staff"));
+        let cached = Cached::new(users);
+
+        assert_eq!(cached.get_group_by_name("staff").unwrap().gid(), 100);
+        assert_eq!(cached.get_current_groupname(), None);
+        assert_eq!(cached.get_all_groups().count(), 1);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        use mock::SharedMockUsers;
+
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(1000, "fred", 100));
+        users.fail_uid(1000, libc::EIO);
+        let shared = SharedMockUsers::new(users);
+        let cached = Cached::new(shared.clone());
+
+        assert!(cached.try_get_user_by_uid(1000).is_err());
+
+        shared.write().clear_failures();
+        assert_eq!(
+            cached.try_get_user_by_uid(1000).unwrap().unwrap().name(),
+            "fred"
+        );
+    }
+}
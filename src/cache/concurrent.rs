@@ -0,0 +1,329 @@
+//! A cache whose maps are sharded, for many threads looking up at once.
+
+extern crate dashmap;
+
+use self::dashmap::DashMap;
+use libc::{gid_t, uid_t};
+use std::ffi::OsStr;
+use std::io;
+use std::sync::{Arc, OnceLock};
+
+use base::{self, Group, User};
+use traits::{Groups, TryGroups, TryUsers, Users};
+
+use super::{CacheObserver, CacheStats, Counters};
+
+/// A producer of user and group instances that caches every result, and that
+/// many threads can look up through at the same time.
+///
+/// A [`SyncUsersCache`](super::SyncUsersCache) takes a write lock on all of
+/// its users or groups to store each answer, so threads that keep looking up
+/// entries it has not seen yet, such as those of a parallel file scanner,
+/// end up waiting for each other. This cache splits its maps into shards
+/// with their own locks, so storing an answer only holds up lookups of the
+/// entries in the same shard.
+///
+/// If two threads look up the same missing entry at the same time, both ask
+/// the system, and the first answer is kept.
+///
+/// This is only available with the `concurrent-cache` feature.
+///
+/// ## Example
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::thread;
+/// use uzers::Users;
+/// use uzers::cache::ConcurrentUsersCache;
+///
+/// let cache = Arc::new(ConcurrentUsersCache::new());
+///
+/// let workers = (0..8).map(|n| {
+///     let cache = Arc::clone(&cache);
+///     thread::spawn(move || cache.get_user_by_uid(1000 + n))
+/// });
+///
+/// for worker in workers {
+///     println!("{:?}", worker.join().unwrap());
+/// }
+/// ```
+#[derive(Default)]
+pub struct ConcurrentUsersCache {
+    users: DashMap<uid_t, Option<Arc<User>>>,
+    user_names: DashMap<Arc<OsStr>, Option<uid_t>>,
+    groups: DashMap<gid_t, Option<Arc<Group>>>,
+    group_names: DashMap<Arc<OsStr>, Option<gid_t>>,
+    stats: Counters,
+
+    uid: OnceLock<uid_t>,
+    gid: OnceLock<gid_t>,
+    euid: OnceLock<uid_t>,
+    egid: OnceLock<gid_t>,
+}
+
+impl ConcurrentUsersCache {
+    /// Creates a new empty cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::ConcurrentUsersCache;
+    ///
+    /// let cache = ConcurrentUsersCache::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty cache with room for at least the given numbers of
+    /// users and groups.
+    ///
+    /// See [`UsersCache::with_capacity`](super::UsersCache::with_capacity).
+    pub fn with_capacity(users: usize, groups: usize) -> Self {
+        Self {
+            users: DashMap::with_capacity(users),
+            user_names: DashMap::with_capacity(users),
+            groups: DashMap::with_capacity(groups),
+            group_names: DashMap::with_capacity(groups),
+            ..Self::default()
+        }
+    }
+
+    /// Installs an observer that is told about every lookup the cache
+    /// answers, and every lookup it passes on to the system.
+    ///
+    /// See [`CacheObserver`].
+    pub fn with_observer<O: CacheObserver + 'static>(mut self, observer: O) -> Self {
+        self.stats.set_observer(Arc::new(observer));
+        self
+    }
+
+    /// Returns how many lookups the cache has answered itself, and how many
+    /// it had to pass on to the system.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Sets the counts returned by [`stats`](ConcurrentUsersCache::stats)
+    /// back to zero.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Drops the cached entry for the user with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    pub fn invalidate_user(&self, uid: uid_t) {
+        self.users.remove(&uid);
+        self.user_names.retain(|_, entry| *entry != Some(uid));
+    }
+
+    /// Drops the cached entry for the group with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    pub fn invalidate_group(&self, gid: gid_t) {
+        self.groups.remove(&gid);
+        self.group_names.retain(|_, entry| *entry != Some(gid));
+    }
+
+    /// Drops every cached user and group, including lookups that found
+    /// nothing. The current and effective IDs of the process are kept.
+    pub fn clear(&self) {
+        self.users.clear();
+        self.user_names.clear();
+        self.groups.clear();
+        self.group_names.clear();
+    }
+}
+
+impl Users for ConcurrentUsersCache {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.try_get_user_by_uid(uid).unwrap_or_default()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.try_get_user_by_name(username).unwrap_or_default()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        *self.uid.get_or_init(base::get_current_uid)
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        *self.euid.get_or_init(base::get_effective_uid)
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl Groups for ConcurrentUsersCache {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.try_get_group_by_gid(gid).unwrap_or_default()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.try_get_group_by_name(group_name).unwrap_or_default()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        *self.gid.get_or_init(base::get_current_gid)
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        *self.egid.get_or_init(base::get_effective_gid)
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_effective_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl TryUsers for ConcurrentUsersCache {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        if let Some(entry) = self.users.get(&uid) {
+            self.stats.user_hit(entry.is_some());
+            return Ok(entry.clone());
+        }
+        self.stats.user_miss();
+
+        let user = base::try_get_user_by_uid(uid)?.map(Arc::new);
+        if let Some(user) = &user {
+            self.user_names
+                .entry(Arc::clone(&user.name_arc))
+                .or_insert(Some(uid));
+        }
+        Ok(self.users.entry(uid).or_insert(user).clone())
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        // The guard is dropped before the other map is locked.
+        let entry = self.user_names.get(username.as_ref()).map(|e| *e);
+        if let Some(entry) = entry {
+            self.stats.user_hit(entry.is_some());
+            return Ok(entry.and_then(|uid| self.users.get(&uid).and_then(|e| e.clone())));
+        }
+        self.stats.user_miss();
+
+        Ok(match base::try_get_user_by_name(username)?.map(Arc::new) {
+            Some(user) => {
+                let uid = user.uid();
+                self.user_names
+                    .insert(Arc::clone(&user.name_arc), Some(uid));
+                self.users.entry(uid).or_insert(Some(user)).clone()
+            }
+            None => {
+                self.user_names.insert(Arc::from(username.as_ref()), None);
+                None
+            }
+        })
+    }
+}
+
+impl TryGroups for ConcurrentUsersCache {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        if let Some(entry) = self.groups.get(&gid) {
+            self.stats.group_hit(entry.is_some());
+            return Ok(entry.clone());
+        }
+        self.stats.group_miss();
+
+        let group = base::try_get_group_by_gid(gid)?.map(Arc::new);
+        if let Some(group) = &group {
+            self.group_names
+                .entry(Arc::clone(&group.name_arc))
+                .or_insert(Some(gid));
+        }
+        Ok(self.groups.entry(gid).or_insert(group).clone())
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        // The guard is dropped before the other map is locked.
+        let entry = self.group_names.get(group_name.as_ref()).map(|e| *e);
+        if let Some(entry) = entry {
+            self.stats.group_hit(entry.is_some());
+            return Ok(entry.and_then(|gid| self.groups.get(&gid).and_then(|e| e.clone())));
+        }
+        self.stats.group_miss();
+
+        Ok(
+            match base::try_get_group_by_name(group_name)?.map(Arc::new) {
+                Some(group) => {
+                    let gid = group.gid();
+                    self.group_names
+                        .insert(Arc::clone(&group.name_arc), Some(gid));
+                    self.groups.entry(gid).or_insert(Some(group)).clone()
+                }
+                None => {
+                    self.group_names
+                        .insert(Arc::from(group_name.as_ref()), None);
+                    None
+                }
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    #[test]
+    fn shared_between_threads() {
+        let cache = Arc::new(ConcurrentUsersCache::new());
+        let uid = cache.get_current_uid();
+
+        let workers = (0..8)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || cache.get_user_by_uid(uid))
+            })
+            .collect::<Vec<_>>();
+
+        let first = cache.get_user_by_uid(uid);
+        for worker in workers {
+            let user = worker.join().unwrap();
+            match (&first, &user) {
+                (Some(a), Some(b)) => assert!(Arc::ptr_eq(a, b)),
+                (None, None) => {}
+                _ => panic!("Threads disagree about user #{}", uid),
+            }
+        }
+    }
+
+    #[test]
+    fn names() {
+        let cache = ConcurrentUsersCache::new();
+        assert!(cache.get_group_by_name("group\u{0}name").is_none());
+        assert!(cache.get_group_by_name("group\u{0}name").is_none());
+        assert_eq!(cache.stats().negative_hits, 1);
+
+        if let Some(name) = cache.get_current_username() {
+            let user = cache.get_user_by_name(&*name).unwrap();
+            cache.invalidate_user(user.uid());
+            assert!(cache.user_names.is_empty());
+        }
+
+        cache.clear();
+        assert!(cache.group_names.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+//! Dumping the contents of a cache.
+
+use std::ffi::OsStr;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+use libc::{gid_t, uid_t};
+
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+use self::serde::Serialize;
+
+use super::IdNameMap;
+
+/// A copy of everything a cache holds, for bug reports and for checking in
+/// tests what a cache has looked up.
+///
+/// Names are converted to strings lossily. Every list is sorted, so two
+/// dumps of caches that hold the same entries are equal. With the `serde`
+/// feature, dumps can be serialized.
+///
+/// ## Example
+///
+/// ```no_run
+/// use uzers::{Users, UsersCache};
+///
+/// let cache = UsersCache::new();
+/// cache.get_user_by_uid(0);
+/// cache.get_user_by_name("nobody-at-all");
+///
+/// println!("{:#?}", cache.dump());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CacheDump {
+    /// The users looked up by ID.
+    pub users: Vec<DumpedId>,
+
+    /// The users looked up by name, or found by ID.
+    pub user_names: Vec<DumpedName>,
+
+    /// The groups looked up by ID.
+    pub groups: Vec<DumpedId>,
+
+    /// The groups looked up by name, or found by ID.
+    pub group_names: Vec<DumpedName>,
+
+    /// The current user ID of the process, if it has been asked for.
+    pub current_uid: Option<uid_t>,
+
+    /// The effective user ID of the process, if it has been asked for.
+    pub effective_uid: Option<uid_t>,
+
+    /// The current group ID of the process, if it has been asked for.
+    pub current_gid: Option<gid_t>,
+
+    /// The effective group ID of the process, if it has been asked for.
+    pub effective_gid: Option<gid_t>,
+}
+
+/// A cached lookup by ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DumpedId {
+    /// The ID that was looked up.
+    pub id: u32,
+
+    /// The name of the user or group that was found, or `None` if the
+    /// lookup found nothing.
+    pub name: Option<String>,
+}
+
+/// A cached lookup by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct DumpedName {
+    /// The name that was looked up.
+    pub name: String,
+
+    /// The ID of the user or group that was found, or `None` if the lookup
+    /// found nothing.
+    pub id: Option<u32>,
+}
+
+impl<V, H: BuildHasher> IdNameMap<u32, Arc<OsStr>, V, H> {
+    /// Returns the lookups by ID and by name held in this map, sorted.
+    pub(crate) fn dump<F>(&self, name_of: F) -> (Vec<DumpedId>, Vec<DumpedName>)
+    where
+        F: Fn(&V) -> &OsStr,
+    {
+        let mut ids = self
+            .forward
+            .iter()
+            .map(|(id, entry)| DumpedId {
+                id: *id,
+                name: entry
+                    .as_ref()
+                    .map(|v| name_of(v).to_string_lossy().into_owned()),
+            })
+            .collect::<Vec<_>>();
+        ids.sort_by_key(|entry| entry.id);
+
+        let mut names = self
+            .backward
+            .iter()
+            .map(|(name, id)| DumpedName {
+                name: name.to_string_lossy().into_owned(),
+                id: *id,
+            })
+            .collect::<Vec<_>>();
+        names.sort_by(|a, b| a.name.cmp(&b.name));
+
+        (ids, names)
+    }
+}
@@ -0,0 +1,79 @@
+//! Watching what a cache does.
+
+use std::sync::Arc;
+
+/// Whether a cache event is about a user or a group.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    /// A user, looked up by ID or by name.
+    User,
+
+    /// A group, looked up by ID or by name.
+    Group,
+}
+
+/// Receives the events of a cache, so that they can be passed on to metrics
+/// or tracing.
+///
+/// Install an observer with `with_observer` on a
+/// [`UsersCache`](super::UsersCache) or a
+/// [`SyncUsersCache`](super::SyncUsersCache). Every method does nothing by
+/// default, so an observer only needs to implement the events it’s
+/// interested in. The methods are called while the lookup is under way, so
+/// they should return quickly.
+///
+/// ## Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use uzers::Users;
+/// use uzers::cache::{CacheObserver, EntryKind, UsersCache};
+///
+/// #[derive(Default)]
+/// struct Misses(AtomicU64);
+///
+/// impl CacheObserver for Misses {
+///     fn on_miss(&self, _kind: EntryKind) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let misses = Arc::new(Misses::default());
+/// let cache = UsersCache::new().with_observer(Arc::clone(&misses));
+/// cache.get_user_by_name("nobody-at-all");
+/// assert_eq!(misses.0.load(Ordering::Relaxed), 1);
+/// ```
+pub trait CacheObserver: Send + Sync {
+    /// Called when a lookup is answered with an entry from the cache.
+    fn on_hit(&self, _kind: EntryKind) {}
+
+    /// Called when a lookup is answered from the cache with a previous
+    /// lookup that found nothing.
+    fn on_negative(&self, _kind: EntryKind) {}
+
+    /// Called when a lookup is not in the cache, and has to ask the system.
+    fn on_miss(&self, _kind: EntryKind) {}
+
+    /// Called when an entry is looked up again because it has expired, or
+    /// because the cache is being refreshed.
+    fn on_refresh(&self, _kind: EntryKind) {}
+}
+
+impl<O: CacheObserver + ?Sized> CacheObserver for Arc<O> {
+    fn on_hit(&self, kind: EntryKind) {
+        (**self).on_hit(kind)
+    }
+
+    fn on_negative(&self, kind: EntryKind) {
+        (**self).on_negative(kind)
+    }
+
+    fn on_miss(&self, kind: EntryKind) {
+        (**self).on_miss(kind)
+    }
+
+    fn on_refresh(&self, kind: EntryKind) {
+        (**self).on_refresh(kind)
+    }
+}
@@ -0,0 +1,244 @@
+//! Saving a cache to a file, and loading it back.
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::hash::BuildHasher;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+
+use record::{escape, group_field, user_field, ReplayUsers};
+
+use super::UsersCache;
+
+impl<H: BuildHasher + Clone> UsersCache<H> {
+    /// Writes every entry of the cache, including lookups that found nothing
+    /// and the IDs of the process it has asked for, to a new file at the
+    /// given path, replacing any file there.
+    ///
+    /// The file is a lookup trace, in the format described in the
+    /// [`record`](../record/index.html#file-format) module, so it can also
+    /// be read by a [`ReplayUsers`](../record/struct.ReplayUsers.html).
+    /// [`load_from`](UsersCache::load_from) reads it back into a cache.
+    ///
+    /// Users are written with their password fields, so the file can only be
+    /// read and written by its owner. It’s written to a temporary file next
+    /// to the path first, and then renamed over it, so that a program loading
+    /// the cache at the same time sees either the old file or the new one,
+    /// never half of one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the file cannot be written.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let temp_path = temp_path_for(path);
+        let written = self.write_to(&temp_path);
+        let renamed = written.and_then(|()| fs::rename(&temp_path, path));
+        if renamed.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        renamed
+    }
+
+    /// Writes every entry of the cache to a new file at the given path,
+    /// readable only by its owner.
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(path)?;
+        let mut file = BufWriter::new(file);
+        let mut lines = Vec::new();
+
+        {
+            let users = self.users.borrow();
+            for (uid, user) in &users.forward {
+                lines.push(format!("uid {} {}", uid, user_field(user.as_deref())));
+            }
+            for (name, uid) in &users.backward {
+                let user = uid.and_then(|uid| users.forward.get(&uid).cloned().flatten());
+                let key = escape(name.as_bytes());
+                lines.push(format!("name {} {}", key, user_field(user.as_deref())));
+            }
+        }
+
+        {
+            let groups = self.groups.borrow();
+            for (gid, group) in &groups.forward {
+                lines.push(format!("gid {} {}", gid, group_field(group.as_deref())));
+            }
+            for (name, gid) in &groups.backward {
+                let group = gid.and_then(|gid| groups.forward.get(&gid).cloned().flatten());
+                let key = escape(name.as_bytes());
+                lines.push(format!("group {} {}", key, group_field(group.as_deref())));
+            }
+        }
+
+        let ids = [
+            ("current-uid", self.uid.get()),
+            ("effective-uid", self.euid.get()),
+            ("current-gid", self.gid.get()),
+            ("effective-gid", self.egid.get()),
+        ];
+        for (kind, id) in ids {
+            if let Some(id) = id {
+                lines.push(format!("{} - {}", kind, id));
+            }
+        }
+
+        lines.sort_unstable();
+        for line in lines {
+            writeln!(file, "{}", line)?;
+        }
+        file.into_inner().map_err(io::Error::from)?.sync_all()
+    }
+}
+
+/// Returns the path of the temporary file that a cache is written to before
+/// it’s renamed to the given path.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_else(|| OsStr::new("cache")));
+    name.push(format!(".{}.tmp", process::id()));
+    path.with_file_name(name)
+}
+
+impl UsersCache {
+    /// Creates a new cache holding the entries in a file written by
+    /// [`save_to`](UsersCache::save_to), which asks the system only for
+    /// entries that are not in the file.
+    ///
+    /// This lets a short-lived program that is run over and over skip the
+    /// system’s lookups entirely. Nothing checks whether the entries in the
+    /// file are still right: compare the modification time of the file with
+    /// that of `/etc/passwd` and `/etc/group`, or give the cache a
+    /// [policy](UsersCache::with_policy) after loading it, as suits the
+    /// program. The entries count as looked up when the file is loaded.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the file cannot be read, or an
+    /// `InvalidData` error if it’s not a valid lookup trace.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::{Users, UsersCache};
+    ///
+    /// let cache = UsersCache::load_from("users.cache").unwrap_or_default();
+    /// let user = cache.get_user_by_uid(1000);
+    /// cache.save_to("users.cache").expect("Cannot save the cache");
+    /// ```
+    pub fn load_from<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let replay = ReplayUsers::open(path)?;
+        let cache = Self::new();
+        let now = cache.clock.instant();
+
+        {
+            let mut users = cache.users.borrow_mut();
+            let mut stored = cache.users_stored.borrow_mut();
+            for (uid, user) in replay.users_by_uid {
+                if let Some(user) = &user {
+                    users.backward.insert(Arc::clone(&user.name_arc), Some(uid));
+                    stored.names.insert(Arc::clone(&user.name_arc), now);
+                }
+                users.forward.insert(uid, user);
+                stored.ids.insert(uid, now);
+            }
+            for (name, user) in replay.users_by_name {
+                let name = Arc::<OsStr>::from(name);
+                let uid = user.as_ref().map(|u| u.uid());
+                if let Some(user) = user {
+                    users.forward.entry(user.uid()).or_insert(Some(user));
+                }
+                users.backward.insert(Arc::clone(&name), uid);
+                stored.names.insert(name, now);
+            }
+        }
+
+        {
+            let mut groups = cache.groups.borrow_mut();
+            let mut stored = cache.groups_stored.borrow_mut();
+            for (gid, group) in replay.groups_by_gid {
+                if let Some(group) = &group {
+                    groups
+                        .backward
+                        .insert(Arc::clone(&group.name_arc), Some(gid));
+                    stored.names.insert(Arc::clone(&group.name_arc), now);
+                }
+                groups.forward.insert(gid, group);
+                stored.ids.insert(gid, now);
+            }
+            for (name, group) in replay.groups_by_name {
+                let name = Arc::<OsStr>::from(name);
+                let gid = group.as_ref().map(|g| g.gid());
+                if let Some(group) = group {
+                    groups.forward.entry(group.gid()).or_insert(Some(group));
+                }
+                groups.backward.insert(Arc::clone(&name), gid);
+                stored.names.insert(name, now);
+            }
+        }
+
+        let ids = &replay.ids;
+        cache.uid.set(ids.get("current-uid").copied());
+        cache.euid.set(ids.get("effective-uid").copied());
+        cache.gid.set(ids.get("current-gid").copied());
+        cache.egid.set(ids.get("effective-gid").copied());
+        Ok(cache)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    use base::os::unix::UserExt;
+    use base::{Group, User};
+    use traits::{Groups, Users};
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join(format!("uzers-cache-{}", std::process::id()));
+
+        let cache = UsersCache::new();
+        {
+            let mut users = cache.users.borrow_mut();
+            let fred = Arc::new(User::new(1000, "fred", 100).with_gecos("Santa: Fred"));
+            users.insert(1000, Arc::clone(&fred.name_arc), fred);
+            users
+                .backward
+                .insert(Arc::from(OsStr::new("nobody here")), None);
+
+            let mut groups = cache.groups.borrow_mut();
+            let staff = Arc::new(Group::new(200, "staff"));
+            groups.insert(200, Arc::clone(&staff.name_arc), staff);
+            groups.forward.insert(100, None);
+        }
+        cache.uid.set(Some(1000));
+        cache.save_to(&path).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(!temp_path_for(&path).exists());
+
+        let loaded = UsersCache::load_from(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.dump(), cache.dump());
+        let fred = loaded.get_user_by_name("fred").unwrap();
+        assert_eq!(fred.gecos(), "Santa: Fred");
+        assert!(loaded.get_user_by_name("nobody here").is_none());
+        assert!(loaded.get_group_by_gid(100).is_none());
+        assert_eq!(loaded.get_current_uid(), 1000);
+        assert_eq!(loaded.stats().misses(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+//! How long cached entries stay valid.
+
+use std::time::{Duration, Instant};
+
+use time::Clock;
+
+/// The rules that decide how long a [`UsersCache`](super::UsersCache) keeps
+/// using the entries it has cached.
+///
+/// By default, entries stay valid forever, including lookups that found
+/// nothing. Hosts whose users come from a directory service, such as LDAP,
+/// usually want a bound on how stale an answer can be, which a time-to-live
+/// gives:
+///
+/// ```
+/// use std::time::Duration;
+/// use uzers::cache::{CachePolicy, UsersCache};
+///
+/// let cache = UsersCache::with_policy(CachePolicy {
+///     positive_ttl: Some(Duration::from_secs(600)),
+///     negative_ttl: Some(Duration::from_secs(30)),
+///     ..CachePolicy::default()
+/// });
+/// ```
+///
+/// Programs that create users, and then look them up, may not want lookups
+/// that found nothing to be cached at all:
+///
+/// ```
+/// use uzers::cache::{CachePolicy, UsersCache};
+///
+/// let cache = UsersCache::with_policy(CachePolicy {
+///     negative_caching: false,
+///     ..CachePolicy::default()
+/// });
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// How long a lookup that found a user or group stays valid, or `None`
+    /// if it stays valid forever.
+    pub positive_ttl: Option<Duration>,
+
+    /// How long a lookup that found nothing stays valid, or `None` if it
+    /// stays valid forever.
+    pub negative_ttl: Option<Duration>,
+
+    /// Whether lookups that found nothing are cached at all. If not, every
+    /// lookup of a missing user or group asks the system again.
+    pub negative_caching: bool,
+
+    /// Whether an entry that has expired is returned right away, while it’s
+    /// looked up again in the background, instead of being looked up before
+    /// the lookup returns.
+    ///
+    /// Only a [`SyncUsersCache`](super::SyncUsersCache) whose
+    /// [refresher](super::SyncUsersCache::spawn_refresher) is running does
+    /// this; other caches always look expired entries up first.
+    pub stale_while_revalidate: bool,
+}
+
+impl Default for CachePolicy {
+    /// Returns a policy that keeps every entry forever, including lookups
+    /// that found nothing.
+    fn default() -> Self {
+        Self {
+            positive_ttl: None,
+            negative_ttl: None,
+            negative_caching: true,
+            stale_while_revalidate: false,
+        }
+    }
+}
+
+impl CachePolicy {
+    /// Returns whether an entry stored at the given monotonic time is still
+    /// valid according to the clock. `found` tells whether the lookup found
+    /// anything.
+    pub(crate) fn is_fresh<C>(&self, clock: &C, stored_at: Instant, found: bool) -> bool
+    where
+        C: Clock + ?Sized,
+    {
+        let ttl = if found {
+            self.positive_ttl
+        } else {
+            self.negative_ttl
+        };
+
+        match ttl {
+            Some(ttl) => clock.instant().saturating_duration_since(stored_at) < ttl,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+    use time::MockClock;
+
+    #[test]
+    fn forever_by_default() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let stored_at = clock.instant();
+        clock.advance(Duration::from_secs(1 << 40));
+        assert!(CachePolicy::default().is_fresh(&clock, stored_at, true));
+        assert!(CachePolicy::default().is_fresh(&clock, stored_at, false));
+    }
+
+    #[test]
+    fn separate_ttls() {
+        let policy = CachePolicy {
+            positive_ttl: Some(Duration::from_secs(60)),
+            negative_ttl: Some(Duration::from_secs(5)),
+            ..CachePolicy::default()
+        };
+        let clock = MockClock::new(UNIX_EPOCH);
+        let stored_at = clock.instant();
+        clock.advance(Duration::from_secs(10));
+
+        assert!(policy.is_fresh(&clock, stored_at, true));
+        assert!(!policy.is_fresh(&clock, stored_at, false));
+
+        clock.advance(Duration::from_secs(50));
+        assert!(!policy.is_fresh(&clock, stored_at, true));
+    }
+
+    #[test]
+    fn setting_the_clock_back() {
+        let policy = CachePolicy {
+            positive_ttl: Some(Duration::from_secs(60)),
+            ..CachePolicy::default()
+        };
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(3600));
+        let stored_at = clock.instant();
+
+        clock.set(UNIX_EPOCH);
+        clock.advance(Duration::from_secs(61));
+        assert!(!policy.is_fresh(&clock, stored_at, true));
+    }
+}
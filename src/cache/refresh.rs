@@ -0,0 +1,306 @@
+//! Refreshing a shared cache in the background.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use base::{self, Group, User};
+
+use super::{IdNameMap, StoredAt, SyncUsersCache};
+
+/// A message to the thread of a [`Refresher`].
+pub(super) enum Message {
+    /// Look up the entries that have been queued by stale lookups.
+    Revalidate,
+
+    /// Stop the thread.
+    Stop,
+}
+
+/// A thread that refreshes a [`SyncUsersCache`] at a fixed interval,
+/// returned by [`SyncUsersCache::spawn_refresher`].
+///
+/// The thread stops when this handle is dropped or [stopped](Refresher::stop),
+/// or once every other reference to the cache has been dropped. It only
+/// holds a weak reference to the cache, so it does not keep the cache alive.
+#[derive(Debug)]
+pub struct Refresher {
+    sender: Sender<Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Refresher {
+    /// Starts a thread that calls [`SyncUsersCache::refresh`] on the given
+    /// cache every `interval`, and looks up the entries that the cache
+    /// queues for it in between.
+    pub(super) fn spawn(cache: &Arc<SyncUsersCache>, interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        *cache.wake.lock().unwrap() = Some(sender.clone());
+        let cache = Arc::downgrade(cache);
+
+        let thread = thread::spawn(move || loop {
+            let message = receiver.recv_timeout(interval);
+            let cache = match Weak::upgrade(&cache) {
+                Some(cache) => cache,
+                None => break,
+            };
+
+            match message {
+                Err(RecvTimeoutError::Timeout) => cache.refresh(),
+                Ok(Message::Revalidate) => cache.revalidate(),
+                Ok(Message::Stop) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        });
+
+        Self {
+            sender,
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops the thread, waiting for a refresh that is under way to finish.
+    pub fn stop(self) {
+        drop(self);
+    }
+}
+
+impl Drop for Refresher {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+        if let Some(thread) = self.thread.take() {
+            // A refresh that panicked has nothing left to clean up.
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Returns the IDs and names that the map holds entries for, so that they
+/// can be looked up again once the lock on the map has been released.
+pub(super) fn keys<V>(map: &IdNameMap<u32, Arc<OsStr>, V>) -> (Vec<u32>, Vec<Arc<OsStr>>) {
+    let ids = map.forward.keys().copied().collect();
+    let names = map.backward.keys().cloned().collect();
+    (ids, names)
+}
+
+/// Where a refresh looks users and groups up again.
+pub(super) trait Lookups {
+    /// Looks up a user by ID.
+    fn user_by_uid(&self, uid: u32) -> io::Result<Option<User>>;
+
+    /// Looks up a user by name.
+    fn user_by_name(&self, username: &OsStr) -> io::Result<Option<User>>;
+
+    /// Looks up a group by ID.
+    fn group_by_gid(&self, gid: u32) -> io::Result<Option<Group>>;
+
+    /// Looks up a group by name.
+    fn group_by_name(&self, group_name: &OsStr) -> io::Result<Option<Group>>;
+}
+
+/// The system’s users database.
+pub(super) struct System;
+
+impl Lookups for System {
+    fn user_by_uid(&self, uid: u32) -> io::Result<Option<User>> {
+        base::try_get_user_by_uid(uid)
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> io::Result<Option<User>> {
+        base::try_get_user_by_name(username)
+    }
+
+    fn group_by_gid(&self, gid: u32) -> io::Result<Option<Group>> {
+        base::try_get_group_by_gid(gid)
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> io::Result<Option<Group>> {
+        base::try_get_group_by_name(group_name)
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+impl Lookups for ::mock::MockUsers {
+    fn user_by_uid(&self, uid: u32) -> io::Result<Option<User>> {
+        use traits::TryUsers;
+        let user = self.try_get_user_by_uid(uid)?;
+        Ok(user.map(|u| User::clone(&u)))
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> io::Result<Option<User>> {
+        use traits::TryUsers;
+        let user = self.try_get_user_by_name(username)?;
+        Ok(user.map(|u| User::clone(&u)))
+    }
+
+    fn group_by_gid(&self, gid: u32) -> io::Result<Option<Group>> {
+        use traits::TryGroups;
+        let group = self.try_get_group_by_gid(gid)?;
+        Ok(group.map(|g| Group::clone(&g)))
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> io::Result<Option<Group>> {
+        use traits::TryGroups;
+        let group = self.try_get_group_by_name(group_name)?;
+        Ok(group.map(|g| Group::clone(&g)))
+    }
+}
+
+/// The answers to looking up the entries of a map again, along with the IDs
+/// and names whose lookups failed.
+pub(super) struct Refreshed<V> {
+    map: IdNameMap<u32, Arc<OsStr>, Arc<V>>,
+    failed_ids: HashSet<u32>,
+    failed_names: Vec<Arc<OsStr>>,
+    key_of: fn(&V) -> (u32, Arc<OsStr>),
+}
+
+impl<V> Refreshed<V> {
+    /// Returns the entry found for an ID, if there was one.
+    pub(super) fn get(&self, id: u32) -> Option<&Arc<V>> {
+        self.map.forward.get(&id)?.as_ref()
+    }
+
+    /// Returns whether looking up an ID failed.
+    pub(super) fn failed(&self, id: u32) -> bool {
+        self.failed_ids.contains(&id)
+    }
+
+    /// Replaces the old map with the new one, stamped with the given time,
+    /// keeping the old entries and times for the IDs and names whose lookups
+    /// failed, so that a directory service going away for a moment does not
+    /// empty the cache.
+    pub(super) fn merge_into(
+        mut self,
+        old: &mut IdNameMap<u32, Arc<OsStr>, Arc<V>>,
+        old_stored: &mut StoredAt<u32, Arc<OsStr>>,
+        now: Instant,
+    ) {
+        let mut stored = stamped(&self.map, now);
+
+        for &id in &self.failed_ids {
+            if self.map.forward.contains_key(&id) {
+                continue;
+            }
+            if let Some(entry) = old.forward.get(&id) {
+                self.map.forward.insert(id, entry.clone());
+                if let Some(&at) = old_stored.ids.get(&id) {
+                    stored.ids.insert(id, at);
+                }
+            }
+        }
+
+        for name in self.failed_names {
+            if self.map.backward.contains_key(&name) {
+                continue;
+            }
+            let entry = match old.backward.get(&name) {
+                Some(&entry) => entry,
+                None => continue,
+            };
+
+            // A name is only kept while it leads to an entry with that name.
+            if let Some(id) = entry {
+                let key_of = self.key_of;
+                let value = self.map.forward.get(&id).and_then(Option::as_ref);
+                if value.is_none_or(|value| key_of(value).1 != name) {
+                    continue;
+                }
+            }
+
+            self.map.backward.insert(Arc::clone(&name), entry);
+            if let Some(&at) = old_stored.names.get(&name) {
+                stored.names.insert(name, at);
+            }
+        }
+
+        *old = self.map;
+        *old_stored = stored;
+    }
+}
+
+/// Looks up every given ID and name again, returning the answers.
+///
+/// Every ID is looked up by ID, and every name that is not the name of one
+/// of the entries found by ID is looked up by name. `looked_up` is called
+/// before each lookup. Lookups that found nothing are only kept if
+/// `negative_caching` is set, and lookups that failed are left out, to be
+/// [merged](Refreshed::merge_into) back in from the old map.
+pub(super) fn refreshed<V, I, N, L>(
+    (ids, names): (Vec<u32>, Vec<Arc<OsStr>>),
+    by_id: I,
+    by_name: N,
+    key_of: fn(&V) -> (u32, Arc<OsStr>),
+    looked_up: L,
+    negative_caching: bool,
+) -> Refreshed<V>
+where
+    I: Fn(u32) -> io::Result<Option<V>>,
+    N: Fn(&OsStr) -> io::Result<Option<V>>,
+    L: Fn(),
+{
+    let mut fresh = Refreshed {
+        map: IdNameMap::default(),
+        failed_ids: HashSet::new(),
+        failed_names: Vec::new(),
+        key_of,
+    };
+
+    for id in ids {
+        looked_up();
+        match by_id(id) {
+            Ok(Some(value)) => {
+                let (id, name) = key_of(&value);
+                fresh.map.insert(id, name, Arc::new(value));
+            }
+            Ok(None) => {
+                if negative_caching {
+                    fresh.map.forward.insert(id, None);
+                }
+            }
+            Err(_) => {
+                fresh.failed_ids.insert(id);
+            }
+        }
+    }
+
+    for name in names {
+        if fresh.map.backward.contains_key(&name) {
+            continue;
+        }
+
+        looked_up();
+        match by_name(&name) {
+            Ok(Some(value)) => {
+                let (id, name) = key_of(&value);
+                fresh.map.insert(id, name, Arc::new(value));
+            }
+            Ok(None) => {
+                if negative_caching {
+                    fresh.map.backward.insert(name, None);
+                }
+            }
+            Err(_) => {
+                fresh.failed_names.push(name);
+            }
+        }
+    }
+
+    fresh
+}
+
+/// Returns a record of every entry in the map being stored at the given
+/// time.
+fn stamped<V>(map: &IdNameMap<u32, Arc<OsStr>, V>, now: Instant) -> StoredAt<u32, Arc<OsStr>> {
+    StoredAt {
+        ids: map.forward.keys().map(|&id| (id, now)).collect(),
+        names: map
+            .backward
+            .keys()
+            .map(|name| (Arc::clone(name), now))
+            .collect(),
+    }
+}
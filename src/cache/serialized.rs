@@ -0,0 +1,131 @@
+//! Serializing snapshots, to hand them to processes that cannot read the
+//! users database themselves.
+
+extern crate serde;
+
+use self::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use libc::{gid_t, uid_t};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+use base::{Group, User};
+use encoding::Unchecked;
+
+use super::UsersSnapshot;
+
+/// The form a snapshot is serialized in.
+///
+/// Every user and group is written the way a [`User`] or [`Group`] is
+/// serialized on its own, so names that are not valid UTF-8 survive any
+/// format, and passwords are left out. Fields may hold any byte but NUL,
+/// as the system’s database may hand out such entries.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "UsersSnapshot")]
+struct Serialized<U, G> {
+    users: Vec<U>,
+    groups: Vec<G>,
+    current_uid: uid_t,
+    current_gid: gid_t,
+    effective_uid: uid_t,
+    effective_gid: gid_t,
+    complete: bool,
+}
+
+/// Snapshots are serialized with their users and groups sorted by ID, so
+/// that two snapshots holding the same entries serialize the same.
+impl<H: BuildHasher> Serialize for UsersSnapshot<H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let users = self.users.forward.values().flatten();
+        let mut users = users.map(|u| &**u).collect::<Vec<&User>>();
+        users.sort_unstable_by_key(|u| u.uid());
+        let groups = self.groups.forward.values().flatten();
+        let mut groups = groups.map(|g| &**g).collect::<Vec<&Group>>();
+        groups.sort_unstable_by_key(|g| g.gid());
+
+        Serialized {
+            users,
+            groups,
+            current_uid: self.uid,
+            current_gid: self.gid,
+            effective_uid: self.euid,
+            effective_gid: self.egid,
+            complete: !self.incomplete,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for UsersSnapshot {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized: Serialized<Unchecked<User>, Unchecked<Group>> =
+            Serialized::deserialize(deserializer)?;
+
+        let mut snapshot = Self::from(
+            serialized.users.into_iter().map(|Unchecked(user)| user),
+            serialized.groups.into_iter().map(|Unchecked(group)| group),
+            serialized.current_uid,
+            serialized.current_gid,
+            serialized.effective_uid,
+            serialized.effective_gid,
+            RandomState::new(),
+        );
+        snapshot.incomplete = !serialized.complete;
+        Ok(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate serde_json;
+
+    use super::*;
+
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::sync::Arc;
+
+    use os::unix::{GroupExt, UserExt};
+    use traits::{AllUsers, Groups, Users};
+
+    #[test]
+    fn round_trip() {
+        let odd = User::new(1001, OsStr::from_bytes(b"caf\xe9 user"), 100);
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        let fred = User::new(1000, "fred", 100)
+            .with_gecos("Fred: the first, and\nonly")
+            .with_password("$6$secret");
+        for user in [fred, odd] {
+            snapshot
+                .users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+        }
+        let staff = Group::new(100, "staff").add_member("fred");
+        snapshot
+            .groups
+            .insert(100, Arc::clone(&staff.name_arc), Arc::new(staff));
+        snapshot.uid = 1000;
+        snapshot.incomplete = true;
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(json, serde_json::to_string(&snapshot).unwrap());
+        assert!(!json.contains("secret"));
+        let copy: UsersSnapshot = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(copy.get_all_users().count(), 2);
+        assert_eq!(&*copy.get_current_username().unwrap(), "fred");
+        let fred = copy.get_user_by_uid(1000).unwrap();
+        assert_eq!(fred.gecos(), "Fred: the first, and\nonly");
+        assert!(copy
+            .get_user_by_name(OsStr::from_bytes(b"caf\xe9 user"))
+            .is_some());
+        assert_eq!(copy.get_group_by_gid(100).unwrap().members(), ["fred"]);
+        assert!(!copy.is_complete());
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let json = r#"{"users":[{"name":"fred"}],"groups":[],"current_uid":0,"current_gid":0,
+            "effective_uid":0,"effective_gid":0,"complete":true}"#;
+        assert!(serde_json::from_str::<UsersSnapshot>(json).is_err());
+    }
+}
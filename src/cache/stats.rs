@@ -0,0 +1,240 @@
+//! Counting how often a cache answers lookups itself.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use super::{CacheObserver, EntryKind};
+
+/// How many lookups a cache has answered from its entries, and how many it
+/// had to pass on to the system.
+///
+/// The counts start at zero when the cache is created, and can be reset with
+/// the cache’s `reset_stats` method. Clearing or invalidating entries does
+/// not change them.
+///
+/// ## Example
+///
+/// ```no_run
+/// use uzers::{Users, UsersCache};
+///
+/// let cache = UsersCache::new();
+/// cache.get_user_by_uid(1000);
+/// cache.get_user_by_uid(1000);
+///
+/// let stats = cache.stats();
+/// assert_eq!(stats.user_misses, 1);
+/// assert_eq!(stats.user_hits, 1);
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of user lookups answered by the cache.
+    pub user_hits: u64,
+
+    /// The number of user lookups that had to ask the system.
+    pub user_misses: u64,
+
+    /// The number of group lookups answered by the cache.
+    pub group_hits: u64,
+
+    /// The number of group lookups that had to ask the system.
+    pub group_misses: u64,
+
+    /// The number of hits, of users or groups, that found a cached lookup
+    /// that had found nothing.
+    pub negative_hits: u64,
+
+    /// The number of misses caused by an entry that was cached, but had
+    /// expired.
+    pub expirations: u64,
+}
+
+impl CacheStats {
+    /// Returns the number of lookups answered by the cache.
+    pub fn hits(&self) -> u64 {
+        self.user_hits + self.group_hits
+    }
+
+    /// Returns the number of lookups that had to ask the system.
+    pub fn misses(&self) -> u64 {
+        self.user_misses + self.group_misses
+    }
+
+    /// Returns the fraction of lookups answered by the cache, between 0 and
+    /// 1, or 0 if there were no lookups.
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits() + self.misses();
+        if lookups == 0 {
+            0.0
+        } else {
+            self.hits() as f64 / lookups as f64
+        }
+    }
+}
+
+/// The counters behind a cache’s `CacheStats`, which can be updated through
+/// a shared reference from any thread, along with the cache’s observer, which
+/// is told about every event that is counted.
+#[derive(Default)]
+pub(crate) struct Counters {
+    user_hits: AtomicU64,
+    user_misses: AtomicU64,
+    group_hits: AtomicU64,
+    group_misses: AtomicU64,
+    negative_hits: AtomicU64,
+    expirations: AtomicU64,
+    observer: Option<Arc<dyn CacheObserver>>,
+}
+
+fn bump(counter: &AtomicU64) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+impl Counters {
+    /// Sets the observer that is told about every event.
+    pub(crate) fn set_observer(&mut self, observer: Arc<dyn CacheObserver>) {
+        self.observer = Some(observer);
+    }
+
+    /// Counts a user lookup answered by the cache, which found a user if
+    /// `found` is true.
+    pub(crate) fn user_hit(&self, found: bool) {
+        bump(&self.user_hits);
+        self.hit(EntryKind::User, found);
+    }
+
+    /// Counts a user lookup that had to ask the system.
+    pub(crate) fn user_miss(&self) {
+        bump(&self.user_misses);
+        self.notify(|o| o.on_miss(EntryKind::User));
+    }
+
+    /// Counts a group lookup answered by the cache, which found a group if
+    /// `found` is true.
+    pub(crate) fn group_hit(&self, found: bool) {
+        bump(&self.group_hits);
+        self.hit(EntryKind::Group, found);
+    }
+
+    /// Counts a group lookup that had to ask the system.
+    pub(crate) fn group_miss(&self) {
+        bump(&self.group_misses);
+        self.notify(|o| o.on_miss(EntryKind::Group));
+    }
+
+    /// Counts an entry that was found to have expired.
+    pub(crate) fn expired(&self, kind: EntryKind) {
+        bump(&self.expirations);
+        self.refreshed(kind);
+    }
+
+    /// Tells the observer about an entry that is being looked up again. This
+    /// is not counted.
+    pub(crate) fn refreshed(&self, kind: EntryKind) {
+        self.notify(|o| o.on_refresh(kind));
+    }
+
+    fn hit(&self, kind: EntryKind, found: bool) {
+        if found {
+            self.notify(|o| o.on_hit(kind));
+        } else {
+            bump(&self.negative_hits);
+            self.notify(|o| o.on_negative(kind));
+        }
+    }
+
+    fn notify<F: FnOnce(&dyn CacheObserver)>(&self, event: F) {
+        if let Some(observer) = &self.observer {
+            event(&**observer);
+        }
+    }
+
+    /// Returns the current counts.
+    pub(crate) fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            user_hits: self.user_hits.load(Ordering::Relaxed),
+            user_misses: self.user_misses.load(Ordering::Relaxed),
+            group_hits: self.group_hits.load(Ordering::Relaxed),
+            group_misses: self.group_misses.load(Ordering::Relaxed),
+            negative_hits: self.negative_hits.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets every count back to zero.
+    pub(crate) fn reset(&self) {
+        for counter in &[
+            &self.user_hits,
+            &self.user_misses,
+            &self.group_hits,
+            &self.group_misses,
+            &self.negative_hits,
+            &self.expirations,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        let counters = Counters::default();
+        counters.user_hit(true);
+        counters.user_hit(false);
+        counters.user_miss();
+        counters.group_miss();
+        counters.expired(EntryKind::User);
+
+        let stats = counters.snapshot();
+        assert_eq!(stats.hits(), 2);
+        assert_eq!(stats.misses(), 2);
+        assert_eq!(stats.negative_hits, 1);
+        assert_eq!(stats.expirations, 1);
+        assert_eq!(stats.hit_rate(), 0.5);
+
+        counters.reset();
+        assert_eq!(counters.snapshot(), CacheStats::default());
+    }
+
+    #[test]
+    fn observer() {
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Events(Mutex<Vec<(&'static str, EntryKind)>>);
+
+        impl CacheObserver for Events {
+            fn on_hit(&self, kind: EntryKind) {
+                self.0.lock().unwrap().push(("hit", kind));
+            }
+
+            fn on_negative(&self, kind: EntryKind) {
+                self.0.lock().unwrap().push(("negative", kind));
+            }
+
+            fn on_refresh(&self, kind: EntryKind) {
+                self.0.lock().unwrap().push(("refresh", kind));
+            }
+        }
+
+        let events = Arc::new(Events::default());
+        let mut counters = Counters::default();
+        counters.set_observer(events.clone());
+        counters.user_hit(true);
+        counters.group_hit(false);
+        counters.group_miss();
+        counters.expired(EntryKind::User);
+
+        assert_eq!(
+            *events.0.lock().unwrap(),
+            vec![
+                ("hit", EntryKind::User),
+                ("negative", EntryKind::Group),
+                ("refresh", EntryKind::User),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1010 @@
+//! A cache that can be shared between threads.
+
+use libc::{gid_t, uid_t};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io;
+use std::mem;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+use base::{self, Group, User};
+use ids::Uid;
+use time::{Clock, SystemClock};
+use traits::{Groups, SupplementaryGroups, TryGroups, TryUsers, Users};
+
+use super::refresh::{keys, refreshed, Lookups, Message, Refresher, System};
+use super::{
+    CacheDump, CacheObserver, CachePolicy, CacheStats, Counters, EntryKind, GroupList, IdNameMap,
+    StoredAt,
+};
+
+/// A producer of user and group instances that caches every result, and that
+/// can be shared between threads.
+///
+/// This works like [`UsersCache`](super::UsersCache), except that the cached
+/// entries are kept behind `RwLock`s, so the cache is `Send` and `Sync` and
+/// can be put behind an `Arc`. Lookups of entries that are already cached
+/// only take a read lock, and the system is asked without holding any lock,
+/// so a slow lookup does not hold up the other threads.
+///
+/// If two threads look up the same missing entry at the same time, both ask
+/// the system, and the first answer is kept.
+///
+/// Entries are kept for as long as the cache’s [policy](CachePolicy) says.
+/// With [`stale_while_revalidate`](CachePolicy::stale_while_revalidate) set
+/// and a [refresher](SyncUsersCache::spawn_refresher) running, a lookup of an
+/// expired entry returns it straight away, and leaves it to the refresher to
+/// look it up again, so that no lookup waits for a slow directory service
+/// once the entry has been cached.
+///
+/// ## Example
+///
+/// ```no_run
+/// use std::sync::Arc;
+/// use std::thread;
+/// use uzers::Users;
+/// use uzers::cache::SyncUsersCache;
+///
+/// let cache = Arc::new(SyncUsersCache::new());
+///
+/// let workers = (0..4).map(|_| {
+///     let cache = Arc::clone(&cache);
+///     thread::spawn(move || cache.get_user_by_uid(502))
+/// });
+///
+/// for worker in workers {
+///     println!("{:?}", worker.join().unwrap());
+/// }
+/// ```
+pub struct SyncUsersCache {
+    users: RwLock<IdNameMap<uid_t, Arc<OsStr>, Arc<User>>>,
+    groups: RwLock<IdNameMap<gid_t, Arc<OsStr>, Arc<Group>>>,
+    user_groups: RwLock<HashMap<uid_t, (Instant, GroupList)>>,
+
+    // Always locked after the map they belong to.
+    users_stored: RwLock<StoredAt<uid_t, Arc<OsStr>>>,
+    groups_stored: RwLock<StoredAt<gid_t, Arc<OsStr>>>,
+    policy: CachePolicy,
+    clock: Box<dyn Clock + Send + Sync>,
+    stale: Mutex<HashSet<Stale>>,
+    pub(super) wake: Mutex<Option<Sender<Message>>>,
+    stats: Counters,
+
+    uid: OnceLock<uid_t>,
+    gid: OnceLock<gid_t>,
+    euid: OnceLock<uid_t>,
+    egid: OnceLock<gid_t>,
+}
+
+impl Default for SyncUsersCache {
+    fn default() -> Self {
+        Self::with_policy(CachePolicy::default())
+    }
+}
+
+impl SyncUsersCache {
+    /// Creates a new empty cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::cache::SyncUsersCache;
+    ///
+    /// let cache = SyncUsersCache::new();
+    /// ```
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new empty cache with room for at least the given numbers of
+    /// users and groups.
+    ///
+    /// See [`UsersCache::with_capacity`](super::UsersCache::with_capacity).
+    pub fn with_capacity(users: usize, groups: usize) -> Self {
+        let cache = Self::new();
+        cache.users.write().unwrap().reserve(users);
+        cache.users_stored.write().unwrap().reserve(users);
+        cache.groups.write().unwrap().reserve(groups);
+        cache.groups_stored.write().unwrap().reserve(groups);
+        cache
+    }
+
+    /// Creates a new empty cache that keeps entries for as long as the given
+    /// policy says.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use uzers::cache::{CachePolicy, SyncUsersCache};
+    ///
+    /// let cache = SyncUsersCache::with_policy(CachePolicy {
+    ///     positive_ttl: Some(Duration::from_secs(300)),
+    ///     stale_while_revalidate: true,
+    ///     ..CachePolicy::default()
+    /// });
+    /// ```
+    pub fn with_policy(policy: CachePolicy) -> Self {
+        Self::with_policy_and_clock(policy, SystemClock)
+    }
+
+    /// Creates a new empty cache that keeps entries for as long as the given
+    /// policy says, measuring their age with the given clock.
+    ///
+    /// See [`UsersCache::with_policy_and_clock`](super::UsersCache::with_policy_and_clock).
+    pub fn with_policy_and_clock<C>(policy: CachePolicy, clock: C) -> Self
+    where
+        C: Clock + Send + Sync + 'static,
+    {
+        Self {
+            users: RwLock::default(),
+            groups: RwLock::default(),
+            user_groups: RwLock::default(),
+            users_stored: RwLock::default(),
+            groups_stored: RwLock::default(),
+            policy,
+            clock: Box::new(clock),
+            stale: Mutex::default(),
+            wake: Mutex::default(),
+            stats: Counters::default(),
+            uid: OnceLock::new(),
+            gid: OnceLock::new(),
+            euid: OnceLock::new(),
+            egid: OnceLock::new(),
+        }
+    }
+
+    /// Returns the policy this cache keeps entries by.
+    pub fn policy(&self) -> CachePolicy {
+        self.policy
+    }
+
+    /// Installs an observer that is told about every lookup the cache
+    /// answers, every lookup it passes on to the system, and every entry it
+    /// looks up again when it’s [refreshed](SyncUsersCache::refresh).
+    ///
+    /// See [`CacheObserver`].
+    pub fn with_observer<O: CacheObserver + 'static>(mut self, observer: O) -> Self {
+        self.stats.set_observer(Arc::new(observer));
+        self
+    }
+
+    /// Returns how many lookups the cache has answered itself, and how many
+    /// it had to pass on to the system.
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+
+    /// Sets the counts returned by [`stats`](SyncUsersCache::stats) back to
+    /// zero.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+
+    /// Returns a copy of everything the cache holds.
+    ///
+    /// See [`UsersCache::dump`](super::UsersCache::dump).
+    pub fn dump(&self) -> CacheDump {
+        let (users, user_names) = self.users.read().unwrap().dump(|u| u.name());
+        let (groups, group_names) = self.groups.read().unwrap().dump(|g| g.name());
+
+        CacheDump {
+            users,
+            user_names,
+            groups,
+            group_names,
+            current_uid: self.uid.get().copied(),
+            effective_uid: self.euid.get().copied(),
+            current_gid: self.gid.get().copied(),
+            effective_gid: self.egid.get().copied(),
+        }
+    }
+
+    /// Drops the cached entry for the user with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    ///
+    /// See [`UsersCache::invalidate_user`](super::UsersCache::invalidate_user).
+    pub fn invalidate_user(&self, uid: uid_t) {
+        let mut users = self.users.write().unwrap();
+        users.remove(uid);
+        self.users_stored.write().unwrap().retain(&users);
+        drop(users);
+        self.user_groups.write().unwrap().remove(&uid);
+    }
+
+    /// Drops the cached entry for the group with the given ID, along with the
+    /// names that lead to it, so that the next lookup asks the system again.
+    ///
+    /// See [`UsersCache::invalidate_group`](super::UsersCache::invalidate_group).
+    pub fn invalidate_group(&self, gid: gid_t) {
+        let mut groups = self.groups.write().unwrap();
+        groups.remove(gid);
+        self.groups_stored.write().unwrap().retain(&groups);
+        drop(groups);
+        self.user_groups
+            .write()
+            .unwrap()
+            .retain(|_, (_, list)| !list.iter().flatten().any(|g| g.gid() == gid));
+    }
+
+    /// Drops every cached user and group, including lookups that found
+    /// nothing. The current and effective IDs of the process are kept.
+    pub fn clear(&self) {
+        let mut users = self.users.write().unwrap();
+        users.clear();
+        self.users_stored.write().unwrap().clear();
+        drop(users);
+
+        let mut groups = self.groups.write().unwrap();
+        groups.clear();
+        self.groups_stored.write().unwrap().clear();
+        drop(groups);
+
+        self.user_groups.write().unwrap().clear();
+    }
+
+    /// Returns every group the user with the given ID is a member of,
+    /// including their primary group, or `None` if there is no such user.
+    ///
+    /// See [`UsersCache::get_user_groups`](super::UsersCache::get_user_groups).
+    pub fn get_user_groups(&self, uid: uid_t) -> Option<Vec<Arc<Group>>> {
+        if let Some((stored_at, list)) = self.user_groups.read().unwrap().get(&uid) {
+            if self.is_fresh(Some(stored_at), list.is_some()) {
+                return list.clone();
+            }
+        }
+
+        let list = self.get_user_by_uid(uid).and_then(|user| {
+            let groups = base::get_user_groups(user.name(), user.primary_group_id())?;
+            Some(groups.into_iter().map(Arc::new).collect::<Vec<_>>())
+        });
+
+        let mut user_groups = self.user_groups.write().unwrap();
+        if let Some((stored_at, kept)) = user_groups.get(&uid) {
+            if self.is_fresh(Some(stored_at), kept.is_some()) {
+                return kept.clone();
+            }
+        }
+        if list.is_some() || self.policy.negative_caching {
+            user_groups.insert(uid, (self.clock.instant(), list.clone()));
+        } else {
+            user_groups.remove(&uid);
+        }
+        list
+    }
+
+    /// Looks up every cached user, group and group list again, replacing
+    /// the cached entries with the answers.
+    ///
+    /// The system is asked without holding any lock, so lookups carry on
+    /// being answered from the old entries until the refresh is done. Entries
+    /// that are first looked up while a refresh is under way may be dropped
+    /// by it, and are looked up again when they are next asked for.
+    pub fn refresh(&self) {
+        self.refresh_from(&System);
+    }
+
+    /// Refreshes the cache from the given users database.
+    ///
+    /// Entries whose lookups fail are kept as they were, along with the
+    /// times they were stored at.
+    fn refresh_from<S: Lookups>(&self, source: &S) {
+        // The keys are copied out so that no lock is held while the system
+        // is asked.
+        let user_keys = keys(&self.users.read().unwrap());
+        let group_keys = keys(&self.groups.read().unwrap());
+        let negative_caching = self.policy.negative_caching;
+
+        let users = refreshed(
+            user_keys,
+            |uid| source.user_by_uid(uid),
+            |name| source.user_by_name(name),
+            |u| (u.uid(), Arc::clone(&u.name_arc)),
+            || self.stats.refreshed(EntryKind::User),
+            negative_caching,
+        );
+        let groups = refreshed(
+            group_keys,
+            |gid| source.group_by_gid(gid),
+            |name| source.group_by_name(name),
+            |g| (g.gid(), Arc::clone(&g.name_arc)),
+            || self.stats.refreshed(EntryKind::Group),
+            negative_caching,
+        );
+
+        let old_user_groups = self.user_groups.read().unwrap().clone();
+        let now = self.clock.instant();
+        let user_groups = old_user_groups
+            .into_iter()
+            .map(|(uid, old)| {
+                if users.failed(uid) {
+                    return (uid, old);
+                }
+                let list = users.get(uid).and_then(|user| {
+                    let groups = base::get_user_groups(user.name(), user.primary_group_id())?;
+                    Some(groups.into_iter().map(Arc::new).collect::<Vec<_>>())
+                });
+                (uid, (now, list))
+            })
+            .filter(|(_, (_, list))| list.is_some() || negative_caching)
+            .collect();
+
+        let mut users_lock = self.users.write().unwrap();
+        let mut users_stored = self.users_stored.write().unwrap();
+        users.merge_into(&mut users_lock, &mut users_stored, now);
+        drop(users_stored);
+        drop(users_lock);
+
+        let mut groups_lock = self.groups.write().unwrap();
+        let mut groups_stored = self.groups_stored.write().unwrap();
+        groups.merge_into(&mut groups_lock, &mut groups_stored, now);
+        drop(groups_stored);
+        drop(groups_lock);
+
+        *self.user_groups.write().unwrap() = user_groups;
+    }
+
+    /// Starts a thread that [refreshes](SyncUsersCache::refresh) the cache
+    /// every `interval`, so that lookups stay as fast as ever while the
+    /// cached entries follow changes to the users database.
+    ///
+    /// The thread also looks up the expired entries that lookups have left
+    /// to it, if the cache’s policy says to
+    /// [serve stale entries](CachePolicy::stale_while_revalidate).
+    ///
+    /// The thread runs until the returned [`Refresher`] is dropped, or until
+    /// the cache itself is.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    /// use uzers::cache::SyncUsersCache;
+    ///
+    /// let cache = Arc::new(SyncUsersCache::new());
+    /// let refresher = cache.spawn_refresher(Duration::from_secs(60));
+    ///
+    /// // ... use the cache ...
+    ///
+    /// refresher.stop();
+    /// ```
+    pub fn spawn_refresher(self: &Arc<Self>, interval: Duration) -> Refresher {
+        Refresher::spawn(self, interval)
+    }
+
+    /// Looks up the expired entries that lookups have left to the refresher.
+    pub(super) fn revalidate(&self) {
+        let stale = mem::take(&mut *self.stale.lock().unwrap());
+        for entry in stale {
+            match entry {
+                Stale::Uid(uid) => drop(self.fetch_user_by_uid(uid)),
+                Stale::UserName(name) => drop(self.fetch_user_by_name(&name)),
+                Stale::Gid(gid) => drop(self.fetch_group_by_gid(gid)),
+                Stale::GroupName(name) => drop(self.fetch_group_by_name(&name)),
+            }
+        }
+    }
+
+    /// Returns whether an entry stored at the given time can still be used.
+    fn is_fresh(&self, stored_at: Option<&Instant>, found: bool) -> bool {
+        match stored_at {
+            Some(at) => self.policy.is_fresh(&*self.clock, *at, found),
+            None => true,
+        }
+    }
+
+    /// Leaves an expired entry for the refresher to look up again, returning
+    /// whether it will. If not, the entry has to be looked up before it’s
+    /// returned.
+    fn revalidate_later(&self, entry: Stale) -> bool {
+        if !self.policy.stale_while_revalidate {
+            return false;
+        }
+        let wake = self.wake.lock().unwrap();
+        let wake = match &*wake {
+            Some(wake) => wake,
+            None => return false,
+        };
+
+        self.stale.lock().unwrap().insert(entry.clone());
+        if wake.send(Message::Revalidate).is_ok() {
+            true
+        } else {
+            // The refresher has stopped.
+            self.stale.lock().unwrap().remove(&entry);
+            false
+        }
+    }
+
+    /// Asks the system for the user with the given ID, and caches the
+    /// answer, unless another thread has cached one in the meantime.
+    fn fetch_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        let user = base::try_get_user_by_uid(uid)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        let mut users = self.users.write().unwrap();
+        let mut stored = self.users_stored.write().unwrap();
+        if let Some(entry) = users.forward.get(&uid) {
+            if self.is_fresh(stored.ids.get(&uid), entry.is_some()) {
+                return Ok(entry.clone());
+            }
+        }
+
+        if let Some(user) = &user {
+            users.backward.insert(Arc::clone(&user.name_arc), Some(uid));
+            stored.names.insert(Arc::clone(&user.name_arc), now);
+        }
+
+        if user.is_none() && !self.policy.negative_caching {
+            if users.forward.contains_key(&uid) {
+                users.remove(uid);
+                stored.retain(&users);
+            }
+            return Ok(None);
+        }
+
+        users.forward.insert(uid, user.clone());
+        stored.ids.insert(uid, now);
+        Ok(user)
+    }
+
+    /// Asks the system for the user with the given name, and caches the
+    /// answer, unless another thread has cached one in the meantime.
+    fn fetch_user_by_name(&self, username: &OsStr) -> io::Result<Option<Arc<User>>> {
+        let user = base::try_get_user_by_name(username)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        let mut users = self.users.write().unwrap();
+        let mut stored = self.users_stored.write().unwrap();
+        if let Some(entry) = users.backward.get(username) {
+            if self.is_fresh(stored.names.get(username), entry.is_some()) {
+                return Ok((*entry).and_then(|uid| users.forward.get(&uid).cloned().flatten()));
+            }
+        }
+
+        Ok(match user {
+            Some(user) => {
+                let uid = user.uid();
+                users.backward.insert(Arc::clone(&user.name_arc), Some(uid));
+                stored.names.insert(Arc::clone(&user.name_arc), now);
+                if let Some(Some(cached)) = users.forward.get(&uid) {
+                    if self.is_fresh(stored.ids.get(&uid), true) {
+                        return Ok(Some(Arc::clone(cached)));
+                    }
+                }
+                users.forward.insert(uid, Some(Arc::clone(&user)));
+                stored.ids.insert(uid, now);
+                Some(user)
+            }
+            None if !self.policy.negative_caching => {
+                users.backward.remove(username);
+                stored.names.remove(username);
+                None
+            }
+            None => {
+                let name = Arc::<OsStr>::from(username);
+                users.backward.insert(Arc::clone(&name), None);
+                stored.names.insert(name, now);
+                None
+            }
+        })
+    }
+
+    /// Asks the system for the group with the given ID, and caches the
+    /// answer, unless another thread has cached one in the meantime.
+    fn fetch_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        let group = base::try_get_group_by_gid(gid)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        let mut groups = self.groups.write().unwrap();
+        let mut stored = self.groups_stored.write().unwrap();
+        if let Some(entry) = groups.forward.get(&gid) {
+            if self.is_fresh(stored.ids.get(&gid), entry.is_some()) {
+                return Ok(entry.clone());
+            }
+        }
+
+        if let Some(group) = &group {
+            groups
+                .backward
+                .insert(Arc::clone(&group.name_arc), Some(gid));
+            stored.names.insert(Arc::clone(&group.name_arc), now);
+        }
+
+        if group.is_none() && !self.policy.negative_caching {
+            if groups.forward.contains_key(&gid) {
+                groups.remove(gid);
+                stored.retain(&groups);
+            }
+            return Ok(None);
+        }
+
+        groups.forward.insert(gid, group.clone());
+        stored.ids.insert(gid, now);
+        Ok(group)
+    }
+
+    /// Asks the system for the group with the given name, and caches the
+    /// answer, unless another thread has cached one in the meantime.
+    fn fetch_group_by_name(&self, group_name: &OsStr) -> io::Result<Option<Arc<Group>>> {
+        let group = base::try_get_group_by_name(group_name)?.map(Arc::new);
+        let now = self.clock.instant();
+
+        let mut groups = self.groups.write().unwrap();
+        let mut stored = self.groups_stored.write().unwrap();
+        if let Some(entry) = groups.backward.get(group_name) {
+            if self.is_fresh(stored.names.get(group_name), entry.is_some()) {
+                return Ok((*entry).and_then(|gid| groups.forward.get(&gid).cloned().flatten()));
+            }
+        }
+
+        Ok(match group {
+            Some(group) => {
+                let gid = group.gid();
+                groups
+                    .backward
+                    .insert(Arc::clone(&group.name_arc), Some(gid));
+                stored.names.insert(Arc::clone(&group.name_arc), now);
+                if let Some(Some(cached)) = groups.forward.get(&gid) {
+                    if self.is_fresh(stored.ids.get(&gid), true) {
+                        return Ok(Some(Arc::clone(cached)));
+                    }
+                }
+                groups.forward.insert(gid, Some(Arc::clone(&group)));
+                stored.ids.insert(gid, now);
+                Some(group)
+            }
+            None if !self.policy.negative_caching => {
+                groups.backward.remove(group_name);
+                stored.names.remove(group_name);
+                None
+            }
+            None => {
+                let name = Arc::<OsStr>::from(group_name);
+                groups.backward.insert(Arc::clone(&name), None);
+                stored.names.insert(name, now);
+                None
+            }
+        })
+    }
+}
+
+/// An expired entry that a lookup has left to the refresher.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Stale {
+    Uid(uid_t),
+    UserName(Arc<OsStr>),
+    Gid(gid_t),
+    GroupName(Arc<OsStr>),
+}
+
+/// Returns a cache that is shared by the whole process.
+///
+/// Libraries that each create a cache of their own end up asking the system
+/// for the same users again and again. Those that use this one share their
+/// lookups with every other library and with the program itself. The cache
+/// is created when it’s first asked for, and lives until the process ends.
+///
+/// This is only available with the `global-cache` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::{global_cache, Users};
+///
+/// let uid = global_cache().get_current_uid();
+/// let user = global_cache().get_user_by_uid(uid);
+/// ```
+#[cfg(feature = "global-cache")]
+pub fn global_cache() -> &'static SyncUsersCache {
+    static CACHE: OnceLock<SyncUsersCache> = OnceLock::new();
+    CACHE.get_or_init(SyncUsersCache::new)
+}
+
+impl Users for SyncUsersCache {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.try_get_user_by_uid(uid).unwrap_or_default()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.try_get_user_by_name(username).unwrap_or_default()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        *self.uid.get_or_init(base::get_current_uid)
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        *self.euid.get_or_init(base::get_effective_uid)
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+/// As with [`UsersCache`](super::UsersCache), the trait’s `get_user_groups`
+/// asks the system every time, while
+/// [`groups_of`](SupplementaryGroups::groups_of) and
+/// [`identity_of`](SupplementaryGroups::identity_of) go through the cached
+/// lists of [`SyncUsersCache::get_user_groups`].
+impl SupplementaryGroups for SyncUsersCache {
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>> {
+        let groups = base::get_user_groups(username, gid)?;
+        Some(groups.into_iter().map(Arc::new).collect())
+    }
+
+    fn groups_of(&self, uid: Uid) -> Option<Vec<Arc<Group>>> {
+        SyncUsersCache::get_user_groups(self, uid.as_raw())
+    }
+}
+
+impl Groups for SyncUsersCache {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.try_get_group_by_gid(gid).unwrap_or_default()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.try_get_group_by_name(group_name).unwrap_or_default()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        *self.gid.get_or_init(base::get_current_gid)
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        *self.egid.get_or_init(base::get_effective_gid)
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_effective_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl TryUsers for SyncUsersCache {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        {
+            let users = self.users.read().unwrap();
+            if let Some(entry) = users.forward.get(&uid) {
+                let stored = self.users_stored.read().unwrap();
+                if self.is_fresh(stored.ids.get(&uid), entry.is_some()) {
+                    self.stats.user_hit(entry.is_some());
+                    return Ok(entry.clone());
+                }
+                self.stats.expired(EntryKind::User);
+                if self.revalidate_later(Stale::Uid(uid)) {
+                    self.stats.user_hit(entry.is_some());
+                    return Ok(entry.clone());
+                }
+            }
+        }
+        self.stats.user_miss();
+        self.fetch_user_by_uid(uid)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        let username = username.as_ref();
+        {
+            let users = self.users.read().unwrap();
+            if let Some(&entry) = users.backward.get(username) {
+                let user = || entry.and_then(|uid| users.forward.get(&uid).cloned().flatten());
+                let stored = self.users_stored.read().unwrap();
+                if self.is_fresh(stored.names.get(username), entry.is_some()) {
+                    self.stats.user_hit(entry.is_some());
+                    return Ok(user());
+                }
+                self.stats.expired(EntryKind::User);
+                if self.revalidate_later(Stale::UserName(Arc::from(username))) {
+                    self.stats.user_hit(entry.is_some());
+                    return Ok(user());
+                }
+            }
+        }
+        self.stats.user_miss();
+        self.fetch_user_by_name(username)
+    }
+}
+
+impl TryGroups for SyncUsersCache {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        {
+            let groups = self.groups.read().unwrap();
+            if let Some(entry) = groups.forward.get(&gid) {
+                let stored = self.groups_stored.read().unwrap();
+                if self.is_fresh(stored.ids.get(&gid), entry.is_some()) {
+                    self.stats.group_hit(entry.is_some());
+                    return Ok(entry.clone());
+                }
+                self.stats.expired(EntryKind::Group);
+                if self.revalidate_later(Stale::Gid(gid)) {
+                    self.stats.group_hit(entry.is_some());
+                    return Ok(entry.clone());
+                }
+            }
+        }
+        self.stats.group_miss();
+        self.fetch_group_by_gid(gid)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        let group_name = group_name.as_ref();
+        {
+            let groups = self.groups.read().unwrap();
+            if let Some(&entry) = groups.backward.get(group_name) {
+                let group = || entry.and_then(|gid| groups.forward.get(&gid).cloned().flatten());
+                let stored = self.groups_stored.read().unwrap();
+                if self.is_fresh(stored.names.get(group_name), entry.is_some()) {
+                    self.stats.group_hit(entry.is_some());
+                    return Ok(group());
+                }
+                self.stats.expired(EntryKind::Group);
+                if self.revalidate_later(Stale::GroupName(Arc::from(group_name))) {
+                    self.stats.group_hit(entry.is_some());
+                    return Ok(group());
+                }
+            }
+        }
+        self.stats.group_miss();
+        self.fetch_group_by_name(group_name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+    use std::time::SystemTime;
+
+    use time::MockClock;
+
+    #[test]
+    fn send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SyncUsersCache>();
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let cache = Arc::new(SyncUsersCache::new());
+        let uid = cache.get_current_uid();
+
+        let workers = (0..4)
+            .map(|_| {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || cache.get_user_by_uid(uid))
+            })
+            .collect::<Vec<_>>();
+
+        let first = cache.get_user_by_uid(uid);
+        for worker in workers {
+            let user = worker.join().unwrap();
+            match (&first, &user) {
+                (Some(a), Some(b)) => assert!(Arc::ptr_eq(a, b)),
+                (None, None) => {}
+                _ => panic!("Threads disagree about user #{}", uid),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_names_are_cached() {
+        let cache = SyncUsersCache::new();
+        assert!(cache.get_user_by_name("user\u{0}name").is_none());
+        assert_eq!(cache.users.read().unwrap().backward.len(), 1);
+
+        assert!(cache.get_user_by_name("user\u{0}name").is_none());
+        assert_eq!(cache.stats().negative_hits, 1);
+
+        cache.clear();
+        assert!(cache.users.read().unwrap().backward.is_empty());
+    }
+
+    #[test]
+    fn refresh() {
+        let cache = SyncUsersCache::new();
+        let uid = cache.get_current_uid();
+        let before = cache.get_user_by_uid(uid);
+        cache.get_group_by_name("group\u{0}name");
+
+        cache.refresh();
+        let after = cache.get_user_by_uid(uid);
+        assert_eq!(before.is_some(), after.is_some());
+        if let (Some(a), Some(b)) = (before, after) {
+            assert!(!Arc::ptr_eq(&a, &b));
+            assert_eq!(a.name(), b.name());
+        }
+        assert_eq!(cache.groups.read().unwrap().backward.len(), 1);
+        assert_eq!(cache.stats().user_misses, 1);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn failed_refresh_keeps_entries() {
+        use mock::MockUsers;
+
+        let clock = MockClock::new(SystemTime::now());
+        let cache = SyncUsersCache::with_policy_and_clock(CachePolicy::default(), clock.clone());
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+
+        let stored = clock.instant();
+        let fred = Arc::new(User::new(1337, "fred", 101));
+        cache
+            .users
+            .write()
+            .unwrap()
+            .insert(1337, Arc::clone(&fred.name_arc), fred);
+        cache.users_stored.write().unwrap().ids.insert(1337, stored);
+        let staff = Arc::new(Group::new(101, "staff"));
+        cache
+            .groups
+            .write()
+            .unwrap()
+            .insert(101, Arc::clone(&staff.name_arc), staff);
+
+        clock.advance(Duration::from_secs(60));
+        users.fail_after(0, libc::ETIMEDOUT);
+        cache.refresh_from(&users);
+
+        assert!(cache.get_user_by_uid(1337).is_some());
+        assert!(cache.get_user_by_name("fred").is_some());
+        assert!(cache.get_group_by_gid(101).is_some());
+        assert_eq!(cache.users_stored.read().unwrap().ids[&1337], stored);
+        assert_eq!(cache.stats().misses(), 0);
+
+        users.clear_failures();
+        cache.refresh_from(&users);
+        assert!(cache.users_stored.read().unwrap().ids[&1337] > stored);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn refresh_without_negative_caching() {
+        use mock::MockUsers;
+
+        let cache = SyncUsersCache::with_policy(CachePolicy {
+            negative_caching: false,
+            ..CachePolicy::default()
+        });
+        let fred = Arc::new(User::new(1337, "fred", 101));
+        cache
+            .users
+            .write()
+            .unwrap()
+            .insert(1337, Arc::clone(&fred.name_arc), fred);
+
+        cache.refresh_from(&MockUsers::with_current_uid(1337));
+        let users = cache.users.read().unwrap();
+        assert!(users.forward.is_empty());
+        assert!(users.backward.is_empty());
+    }
+
+    #[test]
+    fn refresher_stops_with_the_cache() {
+        let cache = Arc::new(SyncUsersCache::new());
+        let uid = cache.get_current_uid();
+        cache.get_user_by_uid(uid);
+
+        let refresher = cache.spawn_refresher(Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(20));
+        drop(cache);
+        refresher.stop();
+    }
+
+    #[test]
+    fn stale_while_revalidate() {
+        let cache = Arc::new(SyncUsersCache::with_policy(CachePolicy {
+            positive_ttl: Some(Duration::ZERO),
+            negative_ttl: Some(Duration::ZERO),
+            stale_while_revalidate: true,
+            ..CachePolicy::default()
+        }));
+        let uid = cache.get_current_uid();
+        let first = cache.get_user_by_uid(uid);
+
+        let refresher = cache.spawn_refresher(Duration::from_secs(3600));
+        let stale = cache.get_user_by_uid(uid);
+        match (&first, &stale) {
+            (Some(a), Some(b)) => assert!(Arc::ptr_eq(a, b)),
+            (None, None) => {}
+            _ => panic!("The stale entry for user #{} was not returned", uid),
+        }
+        assert_eq!(cache.stats().user_misses, 1);
+        assert_eq!(cache.stats().expirations, 1);
+
+        refresher.stop();
+        assert!(cache.stale.lock().unwrap().is_empty());
+        let refreshed = cache.users.read().unwrap().forward[&uid].clone();
+        if let (Some(a), Some(b)) = (first, refreshed) {
+            assert!(!Arc::ptr_eq(&a, &b));
+        }
+    }
+
+    #[test]
+    fn expired_entries_without_a_refresher() {
+        let cache = SyncUsersCache::with_policy(CachePolicy {
+            negative_ttl: Some(Duration::ZERO),
+            stale_while_revalidate: true,
+            ..CachePolicy::default()
+        });
+        cache.get_group_by_name("group\u{0}name");
+        cache.get_group_by_name("group\u{0}name");
+        assert_eq!(cache.stats().group_misses, 2);
+        assert_eq!(cache.groups_stored.read().unwrap().names.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "global-cache")]
+    fn global() {
+        let uid = global_cache().get_current_uid();
+        global_cache().get_user_by_uid(uid);
+        let cache = thread::spawn(global_cache).join().unwrap();
+        assert!(std::ptr::eq(cache, global_cache()));
+        assert!(cache.users.read().unwrap().forward.contains_key(&uid));
+    }
+
+    #[test]
+    fn user_groups() {
+        let cache = SyncUsersCache::new();
+        let uid = cache.get_current_uid();
+        let first = cache.get_user_groups(uid);
+        assert_eq!(
+            cache.get_user_groups(uid).map(|l| l.len()),
+            first.map(|l| l.len())
+        );
+        assert_eq!(cache.user_groups.read().unwrap().len(), 1);
+
+        cache.invalidate_user(uid);
+        assert!(cache.user_groups.read().unwrap().is_empty());
+        let identity = cache.identity_of(Uid::from_raw(uid));
+        assert_eq!(
+            cache.user_groups.read().unwrap().contains_key(&uid),
+            identity.is_some()
+        );
+    }
+
+    #[test]
+    fn user_groups_expire() {
+        let clock = MockClock::new(SystemTime::now());
+        let policy = CachePolicy {
+            positive_ttl: Some(Duration::from_secs(60)),
+            negative_caching: false,
+            ..CachePolicy::default()
+        };
+        let cache = SyncUsersCache::with_policy_and_clock(policy, clock.clone());
+        let uid = uid_t::MAX - 1;
+        let made_up = (clock.instant(), Some(Vec::new()));
+        cache.user_groups.write().unwrap().insert(uid, made_up);
+        clock.advance(Duration::from_secs(3600));
+
+        assert!(cache.get_user_groups(uid).is_none());
+        assert!(cache.user_groups.read().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Checking a snapshot for inconsistent entries.
+
+use libc::{gid_t, uid_t};
+use std::collections::BTreeSet;
+use std::ffi::OsString;
+use std::hash::BuildHasher;
+
+use base::os::unix::GroupExt;
+
+use super::UsersSnapshot;
+
+/// A problem with the users or groups of a snapshot, found by
+/// [`UsersSnapshot::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ValidationIssue {
+    /// A user’s primary group is not in the snapshot.
+    DanglingPrimaryGroup {
+        /// The ID of the user.
+        uid: uid_t,
+
+        /// The ID of the group the user names as its primary group.
+        gid: gid_t,
+    },
+
+    /// More than one user has this ID. Only one of them can be looked up
+    /// by it.
+    DuplicateUid(uid_t),
+
+    /// More than one group has this ID. Only one of them can be looked up
+    /// by it.
+    DuplicateGid(gid_t),
+
+    /// More than one user has this name. Only one of them can be looked up
+    /// by it.
+    DuplicateUserName(OsString),
+
+    /// More than one group has this name. Only one of them can be looked up
+    /// by it.
+    DuplicateGroupName(OsString),
+
+    /// A group lists a member that is not a user in the snapshot.
+    UnknownMember {
+        /// The ID of the group.
+        gid: gid_t,
+
+        /// The name of the member.
+        member: OsString,
+    },
+
+    /// The user with this ID has an empty name.
+    EmptyUserName(uid_t),
+
+    /// The group with this ID has an empty name.
+    EmptyGroupName(gid_t),
+}
+
+/// The problems found with a snapshot by [`UsersSnapshot::validate`].
+///
+/// These are the problems that `pwck` and `grpck` report for the `passwd`
+/// and `group` files, found in the snapshot instead, so they are reported
+/// for every source of users and groups the system has. The issues are
+/// sorted, so two reports about the same problems are equal.
+///
+/// Duplicates are found from the entries that a snapshot still holds. When
+/// two users share an ID, the snapshot only keeps the one read last, and the
+/// other is only known by the name that now leads to the wrong user.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::cache::UsersSnapshot;
+///
+/// let snapshot = unsafe { UsersSnapshot::new() };
+/// for issue in snapshot.validate().issues() {
+///     println!("{:?}", issue);
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Checks every user and group of the snapshot.
+    pub(super) fn of<H: BuildHasher>(snapshot: &UsersSnapshot<H>) -> Self {
+        let mut issues = BTreeSet::new();
+        let users = &snapshot.users;
+        let groups = &snapshot.groups;
+
+        for (&uid, user) in &users.forward {
+            let Some(user) = user else { continue };
+
+            if user.name().is_empty() {
+                issues.insert(ValidationIssue::EmptyUserName(uid));
+            }
+            if users.backward.get(user.name()) != Some(&Some(uid)) {
+                issues.insert(ValidationIssue::DuplicateUserName(user.name().to_owned()));
+            }
+
+            let gid = user.primary_group_id();
+            if !groups.forward.contains_key(&gid) {
+                issues.insert(ValidationIssue::DanglingPrimaryGroup { uid, gid });
+            }
+        }
+
+        for (name, &uid) in &users.backward {
+            let Some(uid) = uid else { continue };
+            if let Some(Some(user)) = users.forward.get(&uid) {
+                if user.name() != &**name {
+                    issues.insert(ValidationIssue::DuplicateUid(uid));
+                }
+            }
+        }
+
+        for (&gid, group) in &groups.forward {
+            let Some(group) = group else { continue };
+
+            if group.name().is_empty() {
+                issues.insert(ValidationIssue::EmptyGroupName(gid));
+            }
+            if groups.backward.get(group.name()) != Some(&Some(gid)) {
+                issues.insert(ValidationIssue::DuplicateGroupName(group.name().to_owned()));
+            }
+
+            for member in group.members() {
+                if !matches!(users.backward.get(member.as_os_str()), Some(Some(_))) {
+                    issues.insert(ValidationIssue::UnknownMember {
+                        gid,
+                        member: member.clone(),
+                    });
+                }
+            }
+        }
+
+        for (name, &gid) in &groups.backward {
+            let Some(gid) = gid else { continue };
+            if let Some(Some(group)) = groups.forward.get(&gid) {
+                if group.name() != &**name {
+                    issues.insert(ValidationIssue::DuplicateGid(gid));
+                }
+            }
+        }
+
+        Self {
+            issues: issues.into_iter().collect(),
+        }
+    }
+
+    /// Returns whether no problems were found.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the problems that were found, in order.
+    pub fn issues(&self) -> &[ValidationIssue] {
+        &self.issues
+    }
+}
+
+impl IntoIterator for ValidationReport {
+    type Item = ValidationIssue;
+    type IntoIter = ::std::vec::IntoIter<ValidationIssue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.issues.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use base::{Group, User};
+    use std::sync::Arc;
+
+    fn add_user(snapshot: &mut UsersSnapshot, user: User) {
+        snapshot
+            .users
+            .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+    }
+
+    fn add_group(snapshot: &mut UsersSnapshot, group: Group) {
+        snapshot
+            .groups
+            .insert(group.gid(), Arc::clone(&group.name_arc), Arc::new(group));
+    }
+
+    #[test]
+    fn consistent() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        add_user(&mut snapshot, User::new(1000, "fred", 100));
+        add_group(&mut snapshot, Group::new(100, "staff").add_member("fred"));
+        assert!(snapshot.validate().is_ok());
+    }
+
+    #[test]
+    fn issues() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        add_user(&mut snapshot, User::new(1000, "fred", 100));
+        add_user(&mut snapshot, User::new(1000, "frederick", 100));
+        add_user(&mut snapshot, User::new(1001, "frederick", 100));
+        add_user(&mut snapshot, User::new(1002, "", 42));
+        add_group(&mut snapshot, Group::new(100, "staff").add_member("wilma"));
+        add_group(&mut snapshot, Group::new(101, ""));
+
+        assert_eq!(
+            snapshot.validate().issues(),
+            [
+                ValidationIssue::DanglingPrimaryGroup { uid: 1002, gid: 42 },
+                ValidationIssue::DuplicateUid(1000),
+                ValidationIssue::DuplicateUserName("frederick".into()),
+                ValidationIssue::UnknownMember {
+                    gid: 100,
+                    member: "wilma".into()
+                },
+                ValidationIssue::EmptyUserName(1002),
+                ValidationIssue::EmptyGroupName(101),
+            ]
+        );
+    }
+}
@@ -0,0 +1,271 @@
+//! Borrowed views of a snapshot that leave some entries out.
+
+use libc::{gid_t, uid_t};
+use std::collections::hash_map::RandomState;
+use std::ffi::OsStr;
+use std::hash::BuildHasher;
+use std::io;
+use std::sync::Arc;
+use std::vec;
+
+use base::{Group, User};
+use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, TryGroups, TryUsers, Users,
+};
+
+use super::UsersSnapshot;
+
+/// A view of a [`UsersSnapshot`] that only shows the users and groups that
+/// pass its filters, returned by
+/// [`filtered_view`](UsersSnapshot::filtered_view).
+///
+/// The view borrows the snapshot, so narrowing a snapshot down this way
+/// copies nothing. It answers lookups as if the filtered-out entries were
+/// not there, and reports the same current and effective IDs as the
+/// snapshot.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::AllUsers;
+/// use uzers::cache::UsersSnapshot;
+///
+/// let snapshot = unsafe { UsersSnapshot::new() };
+/// let people = snapshot.filtered_view(|u| u.uid() >= 1000, |g| g.gid() >= 1000);
+///
+/// for user in people.get_all_users() {
+///     println!("{:?}", user.name());
+/// }
+/// ```
+pub struct SnapshotView<'a, U, G, H = RandomState> {
+    snapshot: &'a UsersSnapshot<H>,
+    user_filter: U,
+    group_filter: G,
+}
+
+impl<'a, U, G, H> SnapshotView<'a, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    /// Creates a view of the snapshot through the filters.
+    pub(super) fn new(snapshot: &'a UsersSnapshot<H>, user_filter: U, group_filter: G) -> Self {
+        Self {
+            snapshot,
+            user_filter,
+            group_filter,
+        }
+    }
+}
+
+impl<U, G, H> AllUsers for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    type UserIter<'b>
+        = Box<dyn Iterator<Item = &'b User> + 'b>
+    where
+        Self: 'b;
+
+    fn get_all_users(&self) -> Self::UserIter<'_> {
+        Box::new(
+            self.snapshot
+                .get_all_users()
+                .filter(move |u| (self.user_filter)(u)),
+        )
+    }
+}
+
+impl<U, G, H> AllUsersOwned for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    type OwnedUserIter = vec::IntoIter<Arc<User>>;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        let users = self.snapshot.get_all_users_owned();
+        let users = users.filter(|u| (self.user_filter)(u));
+        users.collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<U, G, H> Users for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.snapshot
+            .get_user_by_uid(uid)
+            .filter(|u| (self.user_filter)(u))
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.snapshot
+            .get_user_by_name(username)
+            .filter(|u| (self.user_filter)(u))
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.snapshot.get_current_uid()
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        self.get_user_by_uid(self.get_current_uid())
+            .map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.snapshot.get_effective_uid()
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        self.get_user_by_uid(self.get_effective_uid())
+            .map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl<U, G, H> AllGroups for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    type GroupIter<'b>
+        = Box<dyn Iterator<Item = &'b Group> + 'b>
+    where
+        Self: 'b;
+
+    fn get_all_groups(&self) -> Self::GroupIter<'_> {
+        Box::new(
+            self.snapshot
+                .get_all_groups()
+                .filter(move |g| (self.group_filter)(g)),
+        )
+    }
+}
+
+impl<U, G, H> AllGroupsOwned for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    type OwnedGroupIter = vec::IntoIter<Arc<Group>>;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        let groups = self.snapshot.get_all_groups_owned();
+        let groups = groups.filter(|g| (self.group_filter)(g));
+        groups.collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<U, G, H> Groups for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.snapshot
+            .get_group_by_gid(gid)
+            .filter(|g| (self.group_filter)(g))
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.snapshot
+            .get_group_by_name(group_name)
+            .filter(|g| (self.group_filter)(g))
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.snapshot.get_current_gid()
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        self.get_group_by_gid(self.get_current_gid())
+            .map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.snapshot.get_effective_gid()
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        self.get_group_by_gid(self.get_effective_gid())
+            .map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl<U, G, H> TryUsers for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_uid(uid))
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_name(username))
+    }
+}
+
+impl<U, G, H> TryGroups for SnapshotView<'_, U, G, H>
+where
+    U: Fn(&User) -> bool,
+    G: Fn(&Group) -> bool,
+    H: BuildHasher,
+{
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_gid(gid))
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_name(group_name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        let mut snapshot: UsersSnapshot = UsersSnapshot::default();
+        for user in [User::new(0, "root", 0), User::new(1000, "fred", 100)] {
+            snapshot
+                .users
+                .insert(user.uid(), Arc::clone(&user.name_arc), Arc::new(user));
+        }
+        for group in [Group::new(0, "root"), Group::new(100, "staff")] {
+            snapshot
+                .groups
+                .insert(group.gid(), Arc::clone(&group.name_arc), Arc::new(group));
+        }
+
+        let view = snapshot.filtered_view(|u| u.uid() >= 1000, |g| g.gid() == 100);
+        assert_eq!(view.get_all_users().count(), 1);
+        assert!(view.get_user_by_name("root").is_none());
+        assert!(view.get_current_username().is_none());
+        assert_eq!(view.get_user_by_uid(1000).unwrap().name(), "fred");
+        assert_eq!(view.get_all_groups().count(), 1);
+        assert!(view.get_group_by_gid(0).is_none());
+        assert_eq!(view.get_group_by_name("staff").unwrap().gid(), 100);
+        assert_eq!(view.get_all_users_owned().count(), 1);
+        assert_eq!(view.get_all_groups_owned().count(), 1);
+    }
+}
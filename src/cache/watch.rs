@@ -0,0 +1,129 @@
+//! Noticing changes to the users database files.
+
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+/// The directory holding the files that the users database is read from.
+pub(super) const ETC: &str = "/etc";
+
+/// The files in [`ETC`] whose changes invalidate a cache.
+pub(super) const FILES: &[&str] = &["passwd", "group", "nsswitch.conf"];
+
+/// An inotify watch on some of the files in a directory.
+///
+/// The directory is watched rather than the files themselves, because the
+/// tools that edit the database write a new file and rename it over the old
+/// one, which would leave a watch on the old file behind.
+pub(super) struct Watch {
+    fd: OwnedFd,
+    names: Vec<Vec<u8>>,
+}
+
+impl Watch {
+    /// Starts watching the files with the given names in the directory.
+    pub(super) fn new(dir: &Path, names: &[&str]) -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let path = CString::new(dir.as_os_str().as_bytes())?;
+        let mask = libc::IN_CLOSE_WRITE
+            | libc::IN_CREATE
+            | libc::IN_DELETE
+            | libc::IN_MOVED_FROM
+            | libc::IN_MOVED_TO;
+        if unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd,
+            names: names.iter().map(|n| n.as_bytes().to_vec()).collect(),
+        })
+    }
+
+    /// Returns whether any of the watched files has changed since this was
+    /// last called, without waiting for a change.
+    ///
+    /// If the events cannot be read, or some were lost, this assumes that a
+    /// change happened.
+    pub(super) fn changed(&self) -> bool {
+        let mut changed = false;
+        let mut buffer = [0_u8; 4096];
+
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            if read <= 0 {
+                let error = io::Error::last_os_error();
+                return changed || (read < 0 && error.kind() != io::ErrorKind::WouldBlock);
+            }
+
+            changed |= self.any_watched(&buffer[..read as usize]);
+        }
+    }
+
+    /// Returns whether any of the events in the buffer is about one of the
+    /// watched files.
+    fn any_watched(&self, mut events: &[u8]) -> bool {
+        // `struct inotify_event`: wd, mask, cookie and len, followed by a
+        // name of `len` bytes padded with NULs.
+        const HEADER: usize = 16;
+
+        let mut found = false;
+        while events.len() >= HEADER {
+            let field = |at: usize| {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(&events[at..at + 4]);
+                u32::from_ne_bytes(bytes)
+            };
+            let mask = field(4);
+            let len = field(12) as usize;
+
+            let end = (HEADER + len).min(events.len());
+            let name = &events[HEADER..end];
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+
+            found |= mask & libc::IN_Q_OVERFLOW != 0 || self.names.iter().any(|n| n == name);
+            events = &events[end..];
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn notices_watched_files() {
+        let dir = std::env::temp_dir().join(format!("uzers-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let watch = Watch::new(&dir, &["passwd"]).unwrap();
+        assert!(!watch.changed());
+
+        fs::write(dir.join("passwd-"), "").unwrap();
+        assert!(!watch.changed());
+
+        fs::rename(dir.join("passwd-"), dir.join("passwd")).unwrap();
+        assert!(watch.changed());
+        assert!(!watch.changed());
+
+        fs::write(dir.join("passwd"), "root:x:0:0::/root:/bin/sh\n").unwrap();
+        assert!(watch.changed());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
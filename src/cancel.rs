@@ -0,0 +1,82 @@
+//! Cancellation of long-running operations.
+//!
+//! Enumerating the users database can take a long time, or even hang, when
+//! it’s served by a slow or stuck network backend. A [`CancellationToken`]
+//! lets another thread ask such an operation to stop, so that a server that
+//! is shutting down is not blocked by it.
+//!
+//! Cancellation is cooperative: it is checked between entries, so an
+//! operation stops as soon as the entry it’s currently reading arrives.
+//!
+//! ```no_run
+//! use std::thread;
+//! use uzers::cancel::CancellationToken;
+//! use uzers::cache::UsersSnapshot;
+//!
+//! let token = CancellationToken::new();
+//! let worker_token = token.clone();
+//! let worker = thread::spawn(move || unsafe { UsersSnapshot::new_cancellable(&worker_token) });
+//!
+//! // Shutting down...
+//! token.cancel();
+//! let snapshot = worker.join().unwrap();
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A shared flag that signals operations to stop.
+///
+/// Clones of a token share the same flag, so cancelling any of them cancels
+/// all of them.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a new token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every operation that uses this token or one of its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether this token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Creates a token that shares an existing flag, which is set when the
+    /// token is cancelled.
+    fn from(cancelled: Arc<AtomicBool>) -> Self {
+        Self { cancelled }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn clones_share_state() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+
+    #[test]
+    fn from_flag() {
+        let flag = Arc::new(AtomicBool::new(false));
+        CancellationToken::from(Arc::clone(&flag)).cancel();
+        assert!(flag.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,231 @@
+//! Canonical encoding and hashing of users and groups.
+//!
+//! Programs that watch the users database for changes want to compare
+//! records cheaply, and to get the same answer on every platform and with
+//! every version of this crate. The encoding here writes the fields that are
+//! common to every Unix in a fixed order, so that two records have the same
+//! encoding exactly when they hold the same information. It is returned by
+//! [`User::canonical_bytes`](../struct.User.html#method.canonical_bytes) and
+//! [`Group::canonical_bytes`](../struct.Group.html#method.canonical_bytes),
+//! so that other systems, such as content-addressed stores, can build on it.
+//!
+//!
+//! ## Format
+//!
+//! Numbers are written as four bytes, most significant first. Strings are
+//! written as their length, as a number, followed by their bytes, without
+//! any escaping.
+//!
+//! A user is written as:
+//!
+//! 1. the [`VERSION`] byte, then the byte `U`;
+//! 2. the user ID, then the primary group ID;
+//! 3. the name, the password, the GECOS field, the home directory, and the
+//!    shell, as strings.
+//!
+//! A group is written as:
+//!
+//! 1. the [`VERSION`] byte, then the byte `G`;
+//! 2. the group ID;
+//! 3. the name, as a string;
+//! 4. the number of members, then each member’s name as a string, sorted by
+//!    their bytes.
+//!
+//! Fields that only some platforms have are not written. The format of a
+//! version never changes: if a field is ever added, the version goes up.
+//!
+//! With the `content-hash` feature, `content_hash` returns the SHA-256 hash
+//! of the encoding.
+
+#[cfg(feature = "content-hash")]
+extern crate sha2;
+
+use std::os::unix::ffi::OsStrExt;
+
+#[cfg(feature = "content-hash")]
+use self::sha2::{Digest, Sha256};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+
+/// The version of the encoding, which is its first byte.
+pub const VERSION: u8 = 1;
+
+/// Appends a number as four big-endian bytes.
+fn push_u32(bytes: &mut Vec<u8>, number: u32) {
+    bytes.extend_from_slice(&number.to_be_bytes());
+}
+
+/// Appends a field, prefixed with its length.
+fn push_field(bytes: &mut Vec<u8>, field: &[u8]) {
+    push_u32(bytes, field.len() as u32);
+    bytes.extend_from_slice(field);
+}
+
+/// Returns the canonical encoding of a user.
+fn user_bytes(user: &User) -> Vec<u8> {
+    let mut bytes = vec![VERSION, b'U'];
+    push_u32(&mut bytes, user.uid());
+    push_u32(&mut bytes, user.primary_group_id());
+    push_field(&mut bytes, user.name().as_bytes());
+    push_field(&mut bytes, user.password().as_bytes());
+    push_field(&mut bytes, user.gecos().as_bytes());
+    push_field(&mut bytes, user.home_dir().as_os_str().as_bytes());
+    push_field(&mut bytes, user.shell().as_os_str().as_bytes());
+    bytes
+}
+
+/// Returns the canonical encoding of a group. Members are sorted, as their
+/// order carries no meaning.
+fn group_bytes(group: &Group) -> Vec<u8> {
+    let mut members = group
+        .members()
+        .iter()
+        .map(|m| m.as_bytes())
+        .collect::<Vec<_>>();
+    members.sort_unstable();
+
+    let mut bytes = vec![VERSION, b'G'];
+    push_u32(&mut bytes, group.gid());
+    push_field(&mut bytes, group.name().as_bytes());
+    push_u32(&mut bytes, members.len() as u32);
+    for member in members {
+        push_field(&mut bytes, member);
+    }
+    bytes
+}
+
+impl User {
+    /// Returns the canonical encoding of this user’s information, which is
+    /// the same on every platform and with every version of this crate that
+    /// uses the same [`VERSION`](canonical/constant.VERSION.html).
+    ///
+    /// See the [`canonical`](canonical/index.html) module for the format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::User;
+    ///
+    /// let bytes = User::new(1000, "fred", 100).canonical_bytes();
+    /// assert_eq!(&bytes[..2], &[uzers::canonical::VERSION, b'U']);
+    /// ```
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        user_bytes(self)
+    }
+}
+
+#[cfg(feature = "content-hash")]
+impl User {
+    /// Returns a SHA-256 hash of this user’s information, which is the same
+    /// on every platform and with every version of this crate.
+    ///
+    /// Two users have the same hash exactly when their name, ID, primary
+    /// group, password, GECOS field, home directory and shell are the same,
+    /// so the hash can be stored and compared to find users that have
+    /// changed. Fields that only some platforms have are not included.
+    ///
+    /// This method is only available with the `content-hash` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::User;
+    /// use uzers::os::unix::UserExt;
+    ///
+    /// let before = User::new(1000, "fred", 100);
+    /// let after = before.clone().with_shell("/bin/zsh");
+    /// assert_ne!(before.content_hash(), after.content_hash());
+    /// ```
+    pub fn content_hash(&self) -> [u8; 32] {
+        Sha256::digest(self.canonical_bytes()).into()
+    }
+}
+
+impl Group {
+    /// Returns the canonical encoding of this group’s information, which is
+    /// the same on every platform and with every version of this crate that
+    /// uses the same [`VERSION`](canonical/constant.VERSION.html). Members
+    /// are sorted, so their order does not matter.
+    ///
+    /// See the [`canonical`](canonical/index.html) module for the format.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        group_bytes(self)
+    }
+}
+
+#[cfg(feature = "content-hash")]
+impl Group {
+    /// Returns a SHA-256 hash of this group’s information, which is the same
+    /// on every platform and with every version of this crate.
+    ///
+    /// Two groups have the same hash exactly when their name, ID and members
+    /// are the same, in any order.
+    ///
+    /// This method is only available with the `content-hash` feature.
+    pub fn content_hash(&self) -> [u8; 32] {
+        Sha256::digest(self.canonical_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_encoding() {
+        let user = User::new(1000, "fred", 100)
+            .with_password("x")
+            .with_gecos("")
+            .with_home_dir("/h")
+            .with_shell("/s");
+
+        let mut expected = vec![1, b'U', 0, 0, 3, 232, 0, 0, 0, 100];
+        expected.extend_from_slice(&[0, 0, 0, 4]);
+        expected.extend_from_slice(b"fred");
+        expected.extend_from_slice(&[0, 0, 0, 1, b'x']);
+        expected.extend_from_slice(&[0, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 0, 0, 2]);
+        expected.extend_from_slice(b"/h");
+        expected.extend_from_slice(&[0, 0, 0, 2]);
+        expected.extend_from_slice(b"/s");
+
+        assert_eq!(user_bytes(&user), expected);
+    }
+
+    #[test]
+    fn member_order_is_ignored() {
+        let one = Group::new(43, "contributors")
+            .add_member("bob")
+            .add_member("martha");
+        let two = Group::new(43, "contributors")
+            .add_member("martha")
+            .add_member("bob");
+
+        assert_eq!(group_bytes(&one), group_bytes(&two));
+    }
+
+    #[test]
+    fn fields_do_not_run_together() {
+        let one = Group::new(1, "ab").add_member("c");
+        let two = Group::new(1, "a").add_member("bc");
+        assert_ne!(group_bytes(&one), group_bytes(&two));
+    }
+
+    #[cfg(feature = "content-hash")]
+    #[test]
+    fn stable_hash() {
+        let group = Group::new(43, "contributors").add_member("bob");
+        let hash = group.content_hash();
+
+        // This value must never change.
+        let hex = hash
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert_eq!(
+            hex,
+            "c171a4f37fc9419aa9bd0e82bba1b916b2648d41bca508439ee6203d7afd7262"
+        );
+    }
+}
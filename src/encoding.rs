@@ -0,0 +1,347 @@
+//! Serializing users and groups.
+//!
+//! With the `serde` feature, [`User`] and [`Group`] implement `Serialize`
+//! and `Deserialize`. Their names and paths are `OsStr`s, which need not be
+//! valid UTF-8, so how they are written is chosen with an [`OsStrEncoding`].
+//! Users and groups serialize with [`OsStrEncoding::Native`] by default;
+//! wrap them in an [`Encoded`] to use another one:
+//!
+//! ```
+//! # extern crate serde_json;
+//! # extern crate uzers;
+//! use uzers::User;
+//! use uzers::encoding::{Encoded, OsStrEncoding};
+//!
+//! let user = User::new(1000, "fred", 100);
+//! let json = serde_json::to_string(&Encoded::new(&user, OsStrEncoding::Lossy)).unwrap();
+//! assert!(json.contains(r#""name":"fred""#));
+//!
+//! let back: User = serde_json::from_str(&json).unwrap();
+//! assert_eq!(back.uid(), 1000);
+//! ```
+//!
+//! A user’s password field is not serialized, so that a password hash in it
+//! does not end up in logs or API responses. Deserialized users and groups
+//! are checked as a [`UserBuilder`](../struct.UserBuilder.html) or
+//! [`GroupBuilder`](../struct.GroupBuilder.html) would check them.
+
+extern crate serde;
+
+use self::serde::de::{self, Error as _, SeqAccess, Visitor};
+use self::serde::ser::SerializeStruct;
+use self::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use libc::{gid_t, uid_t};
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, GroupBuilder, User, UserBuilder};
+
+/// How the names and paths of users and groups are serialized.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OsStrEncoding {
+    /// As a string if it is valid UTF-8, and as bytes otherwise. Nothing is
+    /// lost, and most names are as readable as they can be.
+    #[default]
+    Native,
+
+    /// As a string, with bytes that are not valid UTF-8 replaced by
+    /// `U+FFFD`. This is for output meant to be read rather than
+    /// deserialized, as the replaced bytes are lost.
+    Lossy,
+
+    /// Always as bytes.
+    Bytes,
+}
+
+/// A user or group to be serialized with the given [`OsStrEncoding`].
+#[derive(Copy, Clone, Debug)]
+pub struct Encoded<'a, T> {
+    value: &'a T,
+    encoding: OsStrEncoding,
+}
+
+impl<'a, T> Encoded<'a, T> {
+    /// Wraps the user or group so that it’s serialized with the encoding.
+    pub fn new(value: &'a T, encoding: OsStrEncoding) -> Self {
+        Self { value, encoding }
+    }
+}
+
+/// One name or path, serialized with an encoding.
+struct EncodedOsStr<'a>(&'a OsStr, OsStrEncoding);
+
+impl Serialize for EncodedOsStr<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let EncodedOsStr(value, encoding) = *self;
+        match (encoding, value.to_str()) {
+            (OsStrEncoding::Native, Some(string)) => serializer.serialize_str(string),
+            (OsStrEncoding::Lossy, _) => serializer.serialize_str(&value.to_string_lossy()),
+            _ => serializer.serialize_bytes(value.as_bytes()),
+        }
+    }
+}
+
+impl Serialize for Encoded<'_, User> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let user = self.value;
+        let encode = |value| EncodedOsStr(value, self.encoding);
+
+        let mut state = serializer.serialize_struct("User", 8)?;
+        state.serialize_field("uid", &user.uid())?;
+        state.serialize_field("name", &encode(user.name()))?;
+        state.serialize_field("primary_group", &user.primary_group_id())?;
+        state.serialize_field("home_dir", &encode(user.home_dir().as_os_str()))?;
+        state.serialize_field("shell", &encode(user.shell().as_os_str()))?;
+        state.serialize_field("gecos", &encode(user.gecos()))?;
+
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        {
+            use base::os::bsd::UserExt;
+            state.serialize_field("password_change_time", &user.password_change_time())?;
+            state.serialize_field("password_expire_time", &user.password_expire_time())?;
+        }
+
+        state.end()
+    }
+}
+
+impl Serialize for Encoded<'_, Group> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let group = self.value;
+        let members = group
+            .members()
+            .iter()
+            .map(|m| EncodedOsStr(m, self.encoding))
+            .collect::<Vec<_>>();
+
+        let mut state = serializer.serialize_struct("Group", 3)?;
+        state.serialize_field("gid", &group.gid())?;
+        state.serialize_field("name", &EncodedOsStr(group.name(), self.encoding))?;
+        state.serialize_field("members", &members)?;
+        state.end()
+    }
+}
+
+impl Serialize for User {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Encoded::new(self, OsStrEncoding::Native).serialize(serializer)
+    }
+}
+
+impl Serialize for Group {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Encoded::new(self, OsStrEncoding::Native).serialize(serializer)
+    }
+}
+
+/// A name or path read back from any of the encodings.
+#[derive(Default)]
+struct DecodedOsStr(OsString);
+
+impl<'de> Deserialize<'de> for DecodedOsStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecodedVisitor;
+
+        impl<'de> Visitor<'de> for DecodedVisitor {
+            type Value = DecodedOsStr;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string or bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(DecodedOsStr(value.into()))
+            }
+
+            fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<Self::Value, E> {
+                Ok(DecodedOsStr(OsStr::from_bytes(value).to_owned()))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(DecodedOsStr(OsString::from_vec(bytes)))
+            }
+        }
+
+        deserializer.deserialize_any(DecodedVisitor)
+    }
+}
+
+/// The fields of a serialized user.
+#[derive(Deserialize)]
+#[serde(rename = "User")]
+struct UserFields {
+    uid: uid_t,
+    name: DecodedOsStr,
+    primary_group: gid_t,
+    #[serde(default)]
+    home_dir: DecodedOsStr,
+    #[serde(default)]
+    shell: DecodedOsStr,
+    #[serde(default)]
+    gecos: DecodedOsStr,
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    #[serde(default)]
+    password_change_time: libc::time_t,
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    #[serde(default)]
+    password_expire_time: libc::time_t,
+}
+
+/// The fields of a serialized group.
+#[derive(Deserialize)]
+#[serde(rename = "Group")]
+struct GroupFields {
+    gid: gid_t,
+    name: DecodedOsStr,
+    #[serde(default)]
+    members: Vec<DecodedOsStr>,
+}
+
+impl UserFields {
+    /// Builds the user these fields describe with the given build method.
+    fn build_with(self, build: fn(UserBuilder) -> io::Result<User>) -> io::Result<User> {
+        let builder = User::builder()
+            .uid(self.uid)
+            .name(&self.name.0)
+            .primary_group(self.primary_group)
+            .home_dir(&self.home_dir.0)
+            .shell(&self.shell.0)
+            .gecos(&self.gecos.0);
+
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        let builder = builder
+            .password_change_time(self.password_change_time)
+            .password_expire_time(self.password_expire_time);
+
+        build(builder)
+    }
+}
+
+impl GroupFields {
+    /// Builds the group these fields describe with the given build method.
+    fn build_with(self, build: fn(GroupBuilder) -> io::Result<Group>) -> io::Result<Group> {
+        build(
+            Group::builder()
+                .gid(self.gid)
+                .name(&self.name.0)
+                .members(self.members.iter().map(|m| &m.0)),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for User {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = UserFields::deserialize(deserializer)?;
+        fields
+            .build_with(UserBuilder::build)
+            .map_err(D::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Group {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = GroupFields::deserialize(deserializer)?;
+        fields
+            .build_with(GroupBuilder::build)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A user or group deserialized without the checks that keep it writable
+/// to a `passwd` or `group` file, for entries that came from the system’s
+/// database in the first place, such as those of a serialized snapshot.
+#[cfg(feature = "cache")]
+pub(crate) struct Unchecked<T>(pub(crate) T);
+
+#[cfg(feature = "cache")]
+impl<'de> Deserialize<'de> for Unchecked<User> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = UserFields::deserialize(deserializer)?;
+        let user = fields.build_with(UserBuilder::build_unchecked);
+        user.map(Unchecked).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(feature = "cache")]
+impl<'de> Deserialize<'de> for Unchecked<Group> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = GroupFields::deserialize(deserializer)?;
+        let group = fields.build_with(GroupBuilder::build_unchecked);
+        group.map(Unchecked).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn encodings() {
+        let odd = OsStr::from_bytes(b"caf\xe9");
+        let user = User::new(1000, odd, 100).with_shell("/bin/sh");
+
+        let native = serde_json::to_value(&user).unwrap();
+        assert_eq!(native["name"], serde_json::json!([99, 97, 102, 233]));
+        assert_eq!(native["shell"], "/bin/sh");
+        assert!(native.get("password").is_none());
+
+        let lossy = serde_json::to_value(Encoded::new(&user, OsStrEncoding::Lossy)).unwrap();
+        assert_eq!(lossy["name"], "caf\u{FFFD}");
+
+        let bytes = serde_json::to_value(Encoded::new(&user, OsStrEncoding::Bytes)).unwrap();
+        assert_eq!(bytes["shell"].as_array().unwrap().len(), 7);
+
+        let back: User = serde_json::from_value(native).unwrap();
+        assert_eq!(back.name(), odd);
+        assert_eq!(back.shell().as_os_str(), "/bin/sh");
+    }
+
+    #[test]
+    fn groups() {
+        let group = Group::new(100, "staff").with_members(["fred", "wilma"]);
+        let json = serde_json::to_string(&group).unwrap();
+        assert_eq!(
+            json,
+            r#"{"gid":100,"name":"staff","members":["fred","wilma"]}"#
+        );
+
+        let back: Group = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.members(), ["fred", "wilma"]);
+
+        let invalid = r#"{"gid":100,"name":"st:aff"}"#;
+        assert!(serde_json::from_str::<Group>(invalid).is_err());
+    }
+}
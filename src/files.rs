@@ -0,0 +1,548 @@
+//! Reading users and groups from `passwd` and `group` files.
+//!
+//! The C library functions used by the rest of this crate read the users and
+//! groups databases through whichever services are configured on the system.
+//! Sometimes, though, you want to read one particular file: a fixture for
+//! tests, the `/etc/passwd` of a container image, or a backup.
+//!
+//! The functions in this module parse the classic colon-separated formats:
+//!
+//! ```text
+//! fred:x:1337:42:Fred Santa:/home/fred:/usr/bin/nologin
+//! contributors:x:43:bob,martha
+//! ```
+//!
+//! Blank lines, comments starting with `#`, and NIS compatibility entries
+//! starting with `+` or `-` are skipped, as are lines that cannot be parsed.
+//! Entries with the sentinel ID `(uid_t) -1` (see the [`ids`](../ids/index.html)
+//! module) cannot be parsed, whether it’s written as `-1` or `4294967295`.
+//!
+//! Files from other systems do not always follow that format to the letter.
+//! The functions ending in `_with` take a [`Dialect`] that says which
+//! deviations to accept, so that their entries are not dropped:
+//!
+//! ```
+//! use uzers::files::{parse_passwd_line_with, Dialect};
+//!
+//! let line = b"fred:*:1337:42:staff:0:0:Fred Santa:/home/fred:/bin/sh";
+//! let user = parse_passwd_line_with(line, Dialect::MasterPasswd).unwrap();
+//! assert_eq!(user.uid(), 1337);
+//! ```
+//!
+//! ```
+//! use uzers::files::parse_passwd_line;
+//!
+//! let user = parse_passwd_line(b"fred:x:1337:42:Fred Santa:/home/fred:/bin/sh").unwrap();
+//! assert_eq!(user.uid(), 1337);
+//! ```
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::str;
+
+#[cfg(feature = "logging")]
+extern crate log;
+#[cfg(feature = "logging")]
+use self::log::trace;
+
+use libc::{gid_t, uid_t};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+use ids::NO_UID;
+
+/// The environment variable that overrides the `passwd` file read by the
+/// top-level functions, with the `test-override` feature.
+#[cfg(feature = "test-override")]
+pub const PASSWD_FILE_VAR: &str = "UZERS_PASSWD_FILE";
+
+/// The environment variable that overrides the `group` file read by the
+/// top-level functions, with the `test-override` feature.
+#[cfg(feature = "test-override")]
+pub const GROUP_FILE_VAR: &str = "UZERS_GROUP_FILE";
+
+/// The variant of the `passwd` and `group` formats that a file is written
+/// in.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// The classic format: seven fields for users, and four for groups. This
+    /// is what the functions without a dialect use.
+    #[default]
+    Strict,
+
+    /// The classic format, also accepting files written by less careful
+    /// tools, such as some versions of busybox:
+    ///
+    /// - users may leave out the gecos, home directory and shell fields,
+    ///   which then keep the defaults of [`User::new`];
+    /// - groups may leave out the members field;
+    /// - fields after the last known one are ignored;
+    /// - trailing whitespace, including carriage returns, is ignored, as are
+    ///   spaces around the names of members.
+    Lenient,
+
+    /// The BSD `master.passwd` format, in which users have ten fields: the
+    /// login class, password change time and account expiry time come after
+    /// the group ID. Groups use the classic format.
+    MasterPasswd,
+}
+
+/// Parses a numeric ID field, rejecting the “no ID” sentinel.
+fn parse_id(field: &[u8]) -> Option<u32> {
+    match str::from_utf8(field).ok()?.parse().ok()? {
+        NO_UID => None,
+        id => Some(id),
+    }
+}
+
+/// Returns whether a line holds no entry.
+fn is_skipped(line: &[u8]) -> bool {
+    match line.first() {
+        None | Some(b'#') | Some(b'+') | Some(b'-') => true,
+        _ => line.iter().all(u8::is_ascii_whitespace),
+    }
+}
+
+/// Removes the trailing whitespace from a line or field.
+fn trim_end(mut bytes: &[u8]) -> &[u8] {
+    while let Some((last, rest)) = bytes.split_last() {
+        if !last.is_ascii_whitespace() {
+            break;
+        }
+        bytes = rest;
+    }
+    bytes
+}
+
+/// Removes the leading and trailing whitespace from a field.
+fn trim(mut bytes: &[u8]) -> &[u8] {
+    while let Some((first, rest)) = bytes.split_first() {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        bytes = rest;
+    }
+    trim_end(bytes)
+}
+
+/// Parses one line of a `passwd` file in the classic format.
+///
+/// Returns `None` if the line is blank, a comment, or not a valid entry.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::files::parse_passwd_line;
+/// use uzers::os::unix::UserExt;
+///
+/// let user = parse_passwd_line(b"fred:x:1337:42:Fred Santa:/home/fred:/bin/sh").unwrap();
+/// assert_eq!(user.name(), "fred");
+/// assert_eq!(user.primary_group_id(), 42);
+/// assert_eq!(user.gecos(), "Fred Santa");
+///
+/// assert!(parse_passwd_line(b"# a comment").is_none());
+/// ```
+pub fn parse_passwd_line(line: &[u8]) -> Option<User> {
+    parse_passwd_line_with(line, Dialect::Strict)
+}
+
+/// Parses one line of a `passwd` file in the given dialect.
+///
+/// Returns `None` if the line is blank, a comment, or not a valid entry.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::files::{parse_passwd_line_with, Dialect};
+/// use uzers::os::unix::UserExt;
+///
+/// let user = parse_passwd_line_with(b"fred:x:1337:42\r", Dialect::Lenient).unwrap();
+/// assert_eq!(user.name(), "fred");
+/// assert_eq!(user.shell().to_str(), Some("/bin/false"));
+/// ```
+pub fn parse_passwd_line_with(line: &[u8], dialect: Dialect) -> Option<User> {
+    let line = match dialect {
+        Dialect::Lenient => trim_end(line),
+        Dialect::Strict | Dialect::MasterPasswd => line,
+    };
+
+    if is_skipped(line) {
+        return None;
+    }
+
+    let fields = line.split(|b| *b == b':').collect::<Vec<_>>();
+    let rest = match dialect {
+        Dialect::Strict if fields.len() == 7 => [Some(fields[4]), Some(fields[5]), Some(fields[6])],
+        Dialect::MasterPasswd if fields.len() == 10 => {
+            [Some(fields[7]), Some(fields[8]), Some(fields[9])]
+        }
+        Dialect::Lenient if fields.len() >= 4 => {
+            [fields.get(4), fields.get(5), fields.get(6)].map(|field| field.copied())
+        }
+        _ => return None,
+    };
+
+    if fields[0].is_empty() {
+        return None;
+    }
+
+    let uid: uid_t = parse_id(fields[2])?;
+    let gid: gid_t = parse_id(fields[3])?;
+
+    let mut user = User::new(uid, OsStr::from_bytes(fields[0]), gid)
+        .with_password(OsStr::from_bytes(fields[1]));
+
+    if let [Some(gecos), home_dir, shell] = rest {
+        user = user.with_gecos(OsStr::from_bytes(gecos));
+        if let Some(home_dir) = home_dir {
+            user = user.with_home_dir(OsStr::from_bytes(home_dir));
+        }
+        if let Some(shell) = shell {
+            user = user.with_shell(OsStr::from_bytes(shell));
+        }
+    }
+
+    Some(user)
+}
+
+/// Parses one line of a `group` file in the classic format.
+///
+/// Returns `None` if the line is blank, a comment, or not a valid entry.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::files::parse_group_line;
+/// use uzers::os::unix::GroupExt;
+///
+/// let group = parse_group_line(b"contributors:x:43:bob,martha").unwrap();
+/// assert_eq!(group.gid(), 43);
+/// assert_eq!(group.members(), &["bob", "martha"]);
+/// ```
+pub fn parse_group_line(line: &[u8]) -> Option<Group> {
+    parse_group_line_with(line, Dialect::Strict)
+}
+
+/// Parses one line of a `group` file in the given dialect.
+///
+/// Returns `None` if the line is blank, a comment, or not a valid entry.
+pub fn parse_group_line_with(line: &[u8], dialect: Dialect) -> Option<Group> {
+    let lenient = dialect == Dialect::Lenient;
+    let line = if lenient { trim_end(line) } else { line };
+
+    if is_skipped(line) {
+        return None;
+    }
+
+    let fields = line.split(|b| *b == b':').collect::<Vec<_>>();
+    let valid = if lenient {
+        fields.len() >= 3
+    } else {
+        fields.len() == 4
+    };
+    if !valid || fields[0].is_empty() {
+        return None;
+    }
+
+    let gid: gid_t = parse_id(fields[2])?;
+
+    let mut group = Group::new(gid, OsStr::from_bytes(fields[0]));
+    let members = fields.get(3).copied().unwrap_or_default();
+    for member in members.split(|b| *b == b',') {
+        let member = if lenient { trim(member) } else { member };
+        if !member.is_empty() {
+            group = group.add_member(OsStr::from_bytes(member));
+        }
+    }
+
+    Some(group)
+}
+
+/// Parses the contents of a `passwd` file in the classic format, skipping
+/// lines that do not hold a valid entry.
+pub fn parse_passwd(contents: &[u8]) -> Vec<User> {
+    parse_passwd_with(contents, Dialect::Strict)
+}
+
+/// Parses the contents of a `passwd` file in the given dialect, skipping
+/// lines that do not hold a valid entry.
+pub fn parse_passwd_with(contents: &[u8], dialect: Dialect) -> Vec<User> {
+    contents
+        .split(|b| *b == b'\n')
+        .filter_map(|line| parse_passwd_line_with(line, dialect))
+        .collect()
+}
+
+/// Parses the contents of a `group` file in the classic format, skipping
+/// lines that do not hold a valid entry.
+pub fn parse_group(contents: &[u8]) -> Vec<Group> {
+    parse_group_with(contents, Dialect::Strict)
+}
+
+/// Parses the contents of a `group` file in the given dialect, skipping
+/// lines that do not hold a valid entry.
+pub fn parse_group_with(contents: &[u8], dialect: Dialect) -> Vec<Group> {
+    contents
+        .split(|b| *b == b'\n')
+        .filter_map(|line| parse_group_line_with(line, dialect))
+        .collect()
+}
+
+/// Formats a user as a line of a `passwd` file, without the trailing
+/// newline.
+///
+/// The format has no way to escape anything, so fields are written as they
+/// are, and a field holding a colon or a newline gives a line that does not
+/// read back as the same user. Users built by a
+/// [`UserBuilder`](../struct.UserBuilder.html) never hold one.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::User;
+/// use uzers::files::format_passwd_line;
+/// use uzers::os::unix::UserExt;
+///
+/// let user = User::new(1337, "fred", 42).with_shell("/bin/sh");
+/// assert_eq!(format_passwd_line(&user), b"fred:*:1337:42::/var/empty:/bin/sh");
+/// ```
+pub fn format_passwd_line(user: &User) -> Vec<u8> {
+    let mut line = Vec::new();
+    line.extend_from_slice(user.name().as_bytes());
+    line.push(b':');
+    line.extend_from_slice(user.password().as_bytes());
+    line.extend_from_slice(format!(":{}:{}:", user.uid(), user.primary_group_id()).as_bytes());
+    line.extend_from_slice(user.gecos().as_bytes());
+    line.push(b':');
+    line.extend_from_slice(user.home_dir().as_os_str().as_bytes());
+    line.push(b':');
+    line.extend_from_slice(user.shell().as_os_str().as_bytes());
+    line
+}
+
+/// Formats a group as a line of a `group` file, without the trailing
+/// newline. Groups do not hold a password, so the password field is `x`.
+///
+/// As with [`format_passwd_line`], fields are written as they are, so a
+/// name holding a colon or a newline, or a member holding a comma, gives a
+/// line that does not read back as the same group. Groups built by a
+/// [`GroupBuilder`](../struct.GroupBuilder.html) never hold one.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::Group;
+/// use uzers::files::format_group_line;
+/// use uzers::os::unix::GroupExt;
+///
+/// let group = Group::new(43, "contributors").add_member("bob").add_member("martha");
+/// assert_eq!(format_group_line(&group), b"contributors:x:43:bob,martha");
+/// ```
+pub fn format_group_line(group: &Group) -> Vec<u8> {
+    let mut line = Vec::new();
+    line.extend_from_slice(group.name().as_bytes());
+    line.extend_from_slice(format!(":x:{}:", group.gid()).as_bytes());
+    for (index, member) in group.members().iter().enumerate() {
+        if index > 0 {
+            line.push(b',');
+        }
+        line.extend_from_slice(member.as_bytes());
+    }
+    line
+}
+
+/// Reads every user from the `passwd` file at the given path.
+///
+/// # Errors
+///
+/// This function will return `Err` if the file cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::files::read_passwd_file;
+///
+/// for user in read_passwd_file("/etc/passwd").expect("Cannot read passwd") {
+///     println!("User #{} ({:?})", user.uid(), user.name());
+/// }
+/// ```
+pub fn read_passwd_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<User>> {
+    read_passwd_file_with(path, Dialect::Strict)
+}
+
+/// Reads every user from the `passwd` file at the given path, written in
+/// the given dialect.
+///
+/// # Errors
+///
+/// This function will return `Err` if the file cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::files::{read_passwd_file_with, Dialect};
+///
+/// let users = read_passwd_file_with("/etc/master.passwd", Dialect::MasterPasswd)
+///     .expect("Cannot read master.passwd");
+/// ```
+pub fn read_passwd_file_with<P: AsRef<Path>>(path: P, dialect: Dialect) -> io::Result<Vec<User>> {
+    #[cfg(feature = "logging")]
+    trace!("Reading passwd file {:?} ({:?})", path.as_ref(), dialect);
+
+    Ok(parse_passwd_with(&fs::read(path)?, dialect))
+}
+
+/// Reads every group from the `group` file at the given path.
+///
+/// # Errors
+///
+/// This function will return `Err` if the file cannot be read.
+pub fn read_group_file<P: AsRef<Path>>(path: P) -> io::Result<Vec<Group>> {
+    read_group_file_with(path, Dialect::Strict)
+}
+
+/// Reads every group from the `group` file at the given path, written in
+/// the given dialect.
+///
+/// # Errors
+///
+/// This function will return `Err` if the file cannot be read.
+pub fn read_group_file_with<P: AsRef<Path>>(path: P, dialect: Dialect) -> io::Result<Vec<Group>> {
+    #[cfg(feature = "logging")]
+    trace!("Reading group file {:?} ({:?})", path.as_ref(), dialect);
+
+    Ok(parse_group_with(&fs::read(path)?, dialect))
+}
+
+/// Returns the users of the file named by [`PASSWD_FILE_VAR`], if it is set.
+///
+/// If the file cannot be read, the override still applies, and there are no
+/// users at all.
+#[cfg(feature = "test-override")]
+pub(crate) fn overridden_users() -> Option<Vec<User>> {
+    let path = std::env::var_os(PASSWD_FILE_VAR)?;
+    Some(read_passwd_file(path).unwrap_or_default())
+}
+
+/// Returns the groups of the file named by [`GROUP_FILE_VAR`], if it is set.
+///
+/// If the file cannot be read, the override still applies, and there are no
+/// groups at all.
+#[cfg(feature = "test-override")]
+pub(crate) fn overridden_groups() -> Option<Vec<Group>> {
+    let path = std::env::var_os(GROUP_FILE_VAR)?;
+    Some(read_group_file(path).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path::Path;
+
+    #[test]
+    fn passwd_line() {
+        let user =
+            parse_passwd_line(b"fred:x:1337:42:Fred Santa:/home/fred:/usr/bin/nologin").unwrap();
+
+        assert_eq!(user.uid(), 1337);
+        assert_eq!(user.name(), "fred");
+        assert_eq!(user.primary_group_id(), 42);
+        assert_eq!(user.password(), "x");
+        assert_eq!(user.home_dir(), Path::new("/home/fred"));
+        assert_eq!(user.shell(), Path::new("/usr/bin/nologin"));
+    }
+
+    #[test]
+    fn invalid_passwd_lines() {
+        assert!(parse_passwd_line(b"").is_none());
+        assert!(parse_passwd_line(b"   ").is_none());
+        assert!(parse_passwd_line(b"+nisuser::::::").is_none());
+        assert!(parse_passwd_line(b"fred:x:notanumber:42::/:/bin/sh").is_none());
+        assert!(parse_passwd_line(b"fred:x:1337:42").is_none());
+    }
+
+    #[test]
+    fn sentinel_ids() {
+        assert!(parse_passwd_line(b"fred:x:4294967295:42::/:/bin/sh").is_none());
+        assert!(parse_passwd_line(b"fred:x:-1:42::/:/bin/sh").is_none());
+        assert!(parse_passwd_line(b"fred:x:1337:4294967295::/:/bin/sh").is_none());
+        assert!(parse_group_line(b"bosses:x:4294967295:").is_none());
+        assert!(parse_passwd_line(b"fred:x:65535:42::/:/bin/sh").is_some());
+    }
+
+    #[test]
+    fn group_without_members() {
+        let group = parse_group_line(b"bosses:x:42:").unwrap();
+        assert_eq!(group.name(), "bosses");
+        assert!(group.members().is_empty());
+    }
+
+    #[test]
+    fn whole_files() {
+        let users =
+            parse_passwd(b"# users\nroot:x:0:0:root:/root:/bin/sh\n\nfred:x:1337:42::/:/bin/sh\n");
+        assert_eq!(
+            users.iter().map(User::uid).collect::<Vec<_>>(),
+            vec![0, 1337]
+        );
+
+        let groups = parse_group(b"root:x:0:\nbosses:x:42:fred\n");
+        assert_eq!(
+            groups.iter().map(Group::gid).collect::<Vec<_>>(),
+            vec![0, 42]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let line = b"fred:x:1337:42:Fred Santa:/home/fred:/usr/bin/nologin";
+        assert_eq!(format_passwd_line(&parse_passwd_line(line).unwrap()), line);
+
+        let line = b"contributors:x:43:bob,martha";
+        assert_eq!(format_group_line(&parse_group_line(line).unwrap()), line);
+    }
+
+    #[test]
+    fn lenient() {
+        let user = parse_passwd_line_with(b"fred:x:1337:42:Fred\r", Dialect::Lenient).unwrap();
+        assert_eq!(user.gecos(), "Fred");
+        assert_eq!(user.home_dir(), Path::new("/var/empty"));
+        assert!(parse_passwd_line(b"fred:x:1337:42:Fred").is_none());
+
+        let user = parse_passwd_line_with(b"fred:x:1:2:::/bin/sh:extra", Dialect::Lenient).unwrap();
+        assert_eq!(user.shell(), Path::new("/bin/sh"));
+        assert!(parse_passwd_line_with(b"fred:x:1337", Dialect::Lenient).is_none());
+
+        let group = parse_group_line_with(b"staff:x:50: bob , ,martha ", Dialect::Lenient).unwrap();
+        assert_eq!(group.members(), &["bob", "martha"]);
+        let group = parse_group_line_with(b"staff:x:50", Dialect::Lenient).unwrap();
+        assert!(group.members().is_empty());
+    }
+
+    #[test]
+    fn master_passwd() {
+        let line = b"fred:$2b$08$hash:1337:42:staff:0:0:Fred Santa:/home/fred:/bin/sh";
+        let user = parse_passwd_line_with(line, Dialect::MasterPasswd).unwrap();
+        assert_eq!(user.gecos(), "Fred Santa");
+        assert_eq!(user.home_dir(), Path::new("/home/fred"));
+        assert_eq!(user.shell(), Path::new("/bin/sh"));
+
+        assert!(parse_passwd_line(line).is_none());
+        assert!(parse_passwd_line_with(b"fred:x:1:2::/:/bin/sh", Dialect::MasterPasswd).is_none());
+    }
+
+    #[test]
+    fn fixture_files() {
+        let users = read_passwd_file("tests/fixtures/passwd").unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].name(), "fred");
+
+        let groups = read_group_file("tests/fixtures/group").unwrap();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[1].members(), &["bob", "martha"]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Resolving the owners of files.
+//!
+//! The metadata of a file only holds the IDs of the user and group that own
+//! it. [`MetadataOwnerExt`] looks them up in one call:
+//!
+//! ```no_run
+//! use std::fs;
+//! use uzers::UsersCache;
+//! use uzers::fs::MetadataOwnerExt;
+//!
+//! let cache = UsersCache::new();
+//! let metadata = fs::metadata("/etc/passwd").unwrap();
+//! if let Some(owner) = metadata.owner(&cache) {
+//!     println!("Owned by {:?}", owner.name());
+//! }
+//! ```
+
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::sync::Arc;
+
+use base::{Group, User};
+use traits::{Groups, Users};
+
+/// Extension to `std::fs::Metadata` to look up the user and group that own a
+/// file.
+pub trait MetadataOwnerExt {
+    /// Returns the user that owns the file, or `None` if there is no user
+    /// with its ID.
+    fn owner<U: Users + ?Sized>(&self, users: &U) -> Option<Arc<User>>;
+
+    /// Returns the group that owns the file, or `None` if there is no group
+    /// with its ID.
+    fn group<G: Groups + ?Sized>(&self, groups: &G) -> Option<Arc<Group>>;
+}
+
+impl MetadataOwnerExt for Metadata {
+    fn owner<U: Users + ?Sized>(&self, users: &U) -> Option<Arc<User>> {
+        users.get_user_by_uid(self.uid())
+    }
+
+    fn group<G: Groups + ?Sized>(&self, groups: &G) -> Option<Arc<Group>> {
+        groups.get_group_by_gid(self.gid())
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::*;
+
+    use mock::MockUsers;
+    use std::fs;
+
+    #[test]
+    fn owner_and_group() {
+        let metadata = fs::metadata("Cargo.toml").unwrap();
+        let mut users = MockUsers::with_current_uid(0);
+        assert!(metadata.owner(&users).is_none());
+
+        users.add_user(User::new(metadata.uid(), "owner", metadata.gid()));
+        users.add_group(Group::new(metadata.gid(), "owners"));
+        assert_eq!(metadata.owner(&users).unwrap().name(), "owner");
+        assert_eq!(metadata.group(&users).unwrap().name(), "owners");
+    }
+}
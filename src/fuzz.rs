@@ -0,0 +1,131 @@
+//! Implementations of [`Arbitrary`] for users, groups, and mock tables, so
+//! that code that consumes them can be fuzzed.
+//!
+//! The users and groups produced hold names and fields made of any bytes
+//! but NUL, as those are the only ones the C library can store, and any
+//! IDs, including the sentinel `(uid_t) -1`. A [`MockUsers`] table holds
+//! some of each, and its current and effective users are picked from the
+//! users it holds when it holds any.
+//!
+//! This is only available with the `arbitrary` feature.
+//!
+//! [`Arbitrary`]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+
+extern crate arbitrary;
+
+use self::arbitrary::{Arbitrary, Result, Unstructured};
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+
+use libc::{gid_t, uid_t};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+#[cfg(feature = "mock")]
+use mock::MockUsers;
+
+/// Returns a field made of any bytes but NUL.
+fn field(u: &mut Unstructured<'_>) -> Result<OsString> {
+    let mut bytes = Vec::<u8>::arbitrary(u)?;
+    bytes.retain(|&byte| byte != 0);
+    Ok(OsString::from_vec(bytes))
+}
+
+impl<'a> Arbitrary<'a> for User {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let uid = uid_t::arbitrary(u)?;
+        let name = field(u)?;
+        let primary_group = gid_t::arbitrary(u)?;
+
+        Ok(User::new(uid, &name, primary_group)
+            .with_home_dir(&field(u)?)
+            .with_shell(&field(u)?)
+            .with_gecos(&field(u)?))
+    }
+}
+
+impl<'a> Arbitrary<'a> for Group {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut group = Group::new(gid_t::arbitrary(u)?, &field(u)?);
+        for _ in 0..u.arbitrary_len::<u8>()? {
+            group = group.add_member(&field(u)?);
+        }
+
+        Ok(group)
+    }
+}
+
+#[cfg(feature = "mock")]
+impl<'a> Arbitrary<'a> for MockUsers {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let users = Vec::<User>::arbitrary(u)?;
+        let groups = Vec::<Group>::arbitrary(u)?;
+
+        // Pick the current and effective users from the ones in the table,
+        // so that lookups of them are not always misses.
+        let pick = |u: &mut Unstructured<'a>| -> Result<(uid_t, gid_t)> {
+            if users.is_empty() {
+                Ok((uid_t::arbitrary(u)?, gid_t::arbitrary(u)?))
+            } else {
+                let user = u.choose(&users)?;
+                Ok((user.uid(), user.primary_group_id()))
+            }
+        };
+        let (uid, gid) = pick(u)?;
+        let (euid, egid) = pick(u)?;
+
+        let mut table = MockUsers::builder()
+            .current_uid(uid)
+            .current_gid(gid)
+            .effective_uid(euid)
+            .effective_gid(egid)
+            .build();
+        for user in users {
+            table.add_user(user);
+        }
+        for group in groups {
+            table.add_group(group);
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::arbitrary::{Arbitrary, Unstructured};
+    use base::os::unix::GroupExt;
+    use base::{Group, User};
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn no_nul_bytes() {
+        let data: Vec<u8> = (0..=u8::MAX).cycle().take(4096).collect();
+        let mut u = Unstructured::new(&data);
+
+        while !u.is_empty() {
+            let user = User::arbitrary(&mut u).unwrap();
+            assert!(!user.name().as_bytes().contains(&0));
+
+            let group = Group::arbitrary(&mut u).unwrap();
+            assert!(group
+                .members()
+                .iter()
+                .all(|member| !member.as_bytes().contains(&0)));
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn current_user_is_in_table() {
+        use mock::MockUsers;
+        use traits::{AllUsers, Users};
+
+        let data: Vec<u8> = (0..=u8::MAX).rev().cycle().take(1024).collect();
+        let users = MockUsers::arbitrary(&mut Unstructured::new(&data)).unwrap();
+
+        if users.get_all_users().next().is_some() {
+            assert!(users.get_user_by_uid(users.get_current_uid()).is_some());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Looking up everything `id` prints about a user at once.
+
+use std::sync::Arc;
+
+use base::{get_group_by_gid, get_user_by_uid, get_user_groups, Group, User};
+use ids::Uid;
+
+/// A user along with the groups they belong to, as printed by `id`.
+///
+/// This is returned by [`identity_of`] and [`current_identity`], which look
+/// up the user and their groups from the system, and by
+/// [`SupplementaryGroups::identity_of`](trait.SupplementaryGroups.html#method.identity_of),
+/// which looks them up from a producer.
+#[derive(Clone, Debug)]
+pub struct Identity {
+    /// The user.
+    pub user: Arc<User>,
+
+    /// The user’s primary group, or `None` if there is no group with its ID.
+    pub primary_group: Option<Arc<Group>>,
+
+    /// The other groups the user is a member of, in the order they were
+    /// returned, without the primary group.
+    pub supplementary_groups: Vec<Arc<Group>>,
+}
+
+impl Identity {
+    /// Puts the user and their groups together, leaving the primary group
+    /// out of the other groups.
+    pub(crate) fn new(
+        user: Arc<User>,
+        primary_group: Option<Arc<Group>>,
+        groups: Vec<Arc<Group>>,
+    ) -> Self {
+        let gid = user.primary_group_id();
+        let supplementary_groups = groups.into_iter().filter(|g| g.gid() != gid).collect();
+        Self {
+            user,
+            primary_group,
+            supplementary_groups,
+        }
+    }
+}
+
+/// Returns the user with the given ID along with their primary and
+/// supplementary groups, or `None` if there is no such user.
+///
+/// If the user’s groups cannot be looked up, the identity is returned
+/// without supplementary groups.
+///
+/// # libc functions used
+///
+/// - [`getpwuid_r`](https://docs.rs/libc/*/libc/fn.getpwuid_r.html)
+/// - [`getgrgid_r`](https://docs.rs/libc/*/libc/fn.getgrgid_r.html)
+/// - [`getgrouplist`](https://docs.rs/libc/*/libc/fn.getgrouplist.html)
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::{identity_of, Uid};
+///
+/// let identity = identity_of(Uid::from_raw(1000)).expect("No such user");
+/// println!("uid={} ({:?})", identity.user.uid(), identity.user.name());
+/// for group in &identity.supplementary_groups {
+///     println!("group {} ({:?})", group.gid(), group.name());
+/// }
+/// ```
+pub fn identity_of(uid: Uid) -> Option<Identity> {
+    let user = Arc::new(get_user_by_uid(uid.as_raw())?);
+    let gid = user.primary_group_id();
+    let primary_group = get_group_by_gid(gid).map(Arc::new);
+    let groups = get_user_groups(user.name(), gid).unwrap_or_default();
+    let groups = groups.into_iter().map(Arc::new).collect();
+    Some(Identity::new(user, primary_group, groups))
+}
+
+/// Returns the user running the process along with their primary and
+/// supplementary groups, or `None` if there is no user with its ID.
+///
+/// See [`identity_of`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::current_identity;
+///
+/// let identity = current_identity().expect("No current user");
+/// println!("{:?}", identity.user.name());
+/// ```
+pub fn current_identity() -> Option<Identity> {
+    identity_of(Uid::current())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use base::get_current_uid;
+
+    #[test]
+    fn current() {
+        if let Some(identity) = current_identity() {
+            assert_eq!(identity.user.uid(), get_current_uid());
+            let gid = identity.user.primary_group_id();
+            assert!(identity.supplementary_groups.iter().all(|g| g.gid() != gid));
+        }
+    }
+
+    #[test]
+    fn primary_group_left_out() {
+        let user = Arc::new(User::new(1000, "fred", 100));
+        let groups = vec![
+            Arc::new(Group::new(100, "staff")),
+            Arc::new(Group::new(10, "wheel")),
+        ];
+        let identity = Identity::new(user, None, groups);
+        assert_eq!(identity.supplementary_groups.len(), 1);
+        assert_eq!(identity.supplementary_groups[0].gid(), 10);
+    }
+}
@@ -0,0 +1,277 @@
+//! Sentinel user and group IDs.
+//!
+//! Some ID values do not name a user or group at all, but stand for “no ID”
+//! or “unknown”. The C library uses `(uid_t) -1`, which is `0xFFFFFFFF` as an
+//! unsigned 32-bit number, as the argument to `chown` and `setreuid` that
+//! leaves an ID unchanged, and old 16-bit systems used `65535` in the same
+//! way. Archives and network file systems pass these values on, and treating
+//! them as ordinary IDs leads to lookups of users that cannot exist.
+//!
+//! The top-level lookups return `None` for the ID `(uid_t) -1` without asking
+//! the system, and the parsers in the [`files`](../files/index.html) module
+//! skip entries that use it. Other sources, such as the mock provider or a
+//! caller's own [`Users`](../trait.Users.html) implementation, may still hand
+//! out sentinel IDs, so the functions here let callers check for them
+//! themselves:
+//!
+//! ```
+//! use uzers::ids::{display_uid, is_sentinel_uid};
+//!
+//! let uid = u32::MAX;
+//! assert!(is_sentinel_uid(uid));
+//! assert_eq!(display_uid(uid).to_string(), "-1");
+//! ```
+//!
+//!
+//! ## Typed IDs
+//!
+//! User and group IDs are both `u32`, so nothing stops a user ID being
+//! passed where a group ID is expected. The [`Uid`] and [`Gid`] types wrap
+//! them so that the compiler tells them apart, for code that wants to opt
+//! in:
+//!
+//! ```
+//! use uzers::{Gid, Uid};
+//!
+//! fn chown_args(uid: Uid, gid: Gid) -> String {
+//!     format!("{}:{}", uid, gid)
+//! }
+//!
+//! assert_eq!(chown_args(Uid::ROOT, Gid::from_raw(100)), "0:100");
+//! ```
+
+use std::fmt;
+
+use libc::{gid_t, uid_t};
+
+use base::{self, Group, User};
+
+/// The user ID that means “no user”, `(uid_t) -1`.
+pub const NO_UID: uid_t = !0;
+
+/// The group ID that means “no group”, `(gid_t) -1`.
+pub const NO_GID: gid_t = !0;
+
+/// The user ID that meant “no user” on systems with 16-bit IDs.
+pub const LEGACY_NO_UID: uid_t = 0xFFFF;
+
+/// The group ID that meant “no group” on systems with 16-bit IDs.
+pub const LEGACY_NO_GID: gid_t = 0xFFFF;
+
+/// Returns whether the given user ID is a sentinel, [`NO_UID`] or
+/// [`LEGACY_NO_UID`], rather than the ID of a user.
+///
+/// Only `NO_UID` is rejected by the lookups of this crate: some systems do
+/// have a user with the ID 65535, so that value is still looked up.
+pub fn is_sentinel_uid(uid: uid_t) -> bool {
+    uid == NO_UID || uid == LEGACY_NO_UID
+}
+
+/// Returns whether the given group ID is a sentinel, [`NO_GID`] or
+/// [`LEGACY_NO_GID`], rather than the ID of a group.
+///
+/// Only `NO_GID` is rejected by the lookups of this crate: some systems do
+/// have a group with the ID 65535, so that value is still looked up.
+pub fn is_sentinel_gid(gid: gid_t) -> bool {
+    gid == NO_GID || gid == LEGACY_NO_GID
+}
+
+/// An ID that is displayed as `-1` if it’s the “no ID” sentinel, and as a
+/// number otherwise. Returned by [`display_uid`] and [`display_gid`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DisplayId(u32);
+
+impl fmt::Display for DisplayId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == !0 {
+            f.pad("-1")
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// Returns a value that displays the given user ID, writing [`NO_UID`] as
+/// `-1` instead of `4294967295`.
+pub fn display_uid(uid: uid_t) -> DisplayId {
+    DisplayId(uid)
+}
+
+/// Returns a value that displays the given group ID, writing [`NO_GID`] as
+/// `-1` instead of `4294967295`.
+pub fn display_gid(gid: gid_t) -> DisplayId {
+    DisplayId(gid)
+}
+
+/// A user ID, kept apart from group IDs.
+///
+/// This converts to and from a `uid_t` with [`from_raw`](Uid::from_raw) and
+/// [`as_raw`](Uid::as_raw), which are spelled out so that a group ID cannot
+/// slip in through a conversion: `uid_t` and `gid_t` are the same type. It’s
+/// displayed as the number it wraps, with [`NO_UID`] as `-1`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uid(uid_t);
+
+impl Uid {
+    /// The ID of the superuser.
+    pub const ROOT: Self = Self(0);
+
+    /// The ID that means “no user”. See [`NO_UID`].
+    pub const NONE: Self = Self(NO_UID);
+
+    /// Wraps the given user ID.
+    pub const fn from_raw(uid: uid_t) -> Self {
+        Self(uid)
+    }
+
+    /// Returns the user ID this wraps.
+    pub const fn as_raw(self) -> uid_t {
+        self.0
+    }
+
+    /// Returns the user ID of the running process.
+    pub fn current() -> Self {
+        Self(base::get_current_uid())
+    }
+
+    /// Returns the effective user ID of the running process.
+    pub fn effective() -> Self {
+        Self(base::get_effective_uid())
+    }
+
+    /// Returns whether this is the ID of the superuser.
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
+
+    /// Returns whether this is a sentinel rather than the ID of a user. See
+    /// [`is_sentinel_uid`].
+    pub fn is_sentinel(self) -> bool {
+        is_sentinel_uid(self.0)
+    }
+
+    /// Looks up the user with this ID. See
+    /// [`get_user_by_uid`](../fn.get_user_by_uid.html).
+    pub fn user(self) -> Option<User> {
+        base::get_user_by_uid(self.0)
+    }
+}
+
+impl From<&User> for Uid {
+    fn from(user: &User) -> Self {
+        Self(user.uid())
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_uid(self.0).fmt(f)
+    }
+}
+
+/// A group ID, kept apart from user IDs.
+///
+/// This converts to and from a `gid_t` with [`from_raw`](Gid::from_raw) and
+/// [`as_raw`](Gid::as_raw), as with [`Uid`]. It’s displayed as the number
+/// it wraps, with [`NO_GID`] as `-1`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Gid(gid_t);
+
+impl Gid {
+    /// The ID of the superuser’s group.
+    pub const ROOT: Self = Self(0);
+
+    /// The ID that means “no group”. See [`NO_GID`].
+    pub const NONE: Self = Self(NO_GID);
+
+    /// Wraps the given group ID.
+    pub const fn from_raw(gid: gid_t) -> Self {
+        Self(gid)
+    }
+
+    /// Returns the group ID this wraps.
+    pub const fn as_raw(self) -> gid_t {
+        self.0
+    }
+
+    /// Returns the group ID of the running process.
+    pub fn current() -> Self {
+        Self(base::get_current_gid())
+    }
+
+    /// Returns the effective group ID of the running process.
+    pub fn effective() -> Self {
+        Self(base::get_effective_gid())
+    }
+
+    /// Returns whether this is the ID of the superuser’s group.
+    pub fn is_root(self) -> bool {
+        self == Self::ROOT
+    }
+
+    /// Returns whether this is a sentinel rather than the ID of a group. See
+    /// [`is_sentinel_gid`].
+    pub fn is_sentinel(self) -> bool {
+        is_sentinel_gid(self.0)
+    }
+
+    /// Looks up the group with this ID. See
+    /// [`get_group_by_gid`](../fn.get_group_by_gid.html).
+    pub fn group(self) -> Option<Group> {
+        base::get_group_by_gid(self.0)
+    }
+}
+
+impl From<&Group> for Gid {
+    fn from(group: &Group) -> Self {
+        Self(group.gid())
+    }
+}
+
+impl fmt::Display for Gid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_gid(self.0).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sentinels() {
+        assert!(is_sentinel_uid(0xFFFF_FFFF));
+        assert!(is_sentinel_uid(65535));
+        assert!(!is_sentinel_uid(65534));
+        assert!(!is_sentinel_gid(0));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(display_uid(NO_UID).to_string(), "-1");
+        assert_eq!(display_gid(LEGACY_NO_GID).to_string(), "65535");
+        assert_eq!(format!("{:>5}", display_uid(42)), "   42");
+        assert_eq!(format!("{:>5}", display_uid(NO_UID)), "   -1");
+        assert_eq!(format!("{:<3}|", display_gid(NO_GID)), "-1 |");
+    }
+
+    #[test]
+    fn typed() {
+        let uid = Uid::from_raw(1000);
+        assert_eq!(uid.as_raw(), 1000);
+        assert!(Uid::ROOT < uid);
+        assert!(Uid::ROOT.is_root());
+        assert!(Gid::NONE.is_sentinel());
+        assert_eq!(Gid::NONE.to_string(), "-1");
+        assert_eq!(Uid::from(&User::new(1000, "fred", 100)), uid);
+        assert_eq!(Gid::from(&Group::new(100, "staff")).as_raw(), 100);
+        assert_eq!(Uid::current().as_raw(), base::get_current_uid());
+        assert!(Uid::NONE.user().is_none());
+    }
+
+    #[test]
+    fn lookups() {
+        assert!(::get_user_by_uid(NO_UID).is_none());
+        assert!(::get_group_by_gid(NO_GID).is_none());
+    }
+}
@@ -114,6 +114,68 @@
 //! ```
 //!
 //!
+//! ## Sessions
+//!
+//! The `utmp` feature, which is off by default, adds the
+//! [`sessions`](sessions/index.html) module for listing the users that are
+//! currently logged in to the system.
+//!
+//!
+//! ## Replacing the database
+//!
+//! The top-level functions can be routed through another database for the
+//! whole process with [`set_global_source`]. See the
+//! [`source`](source/index.html) module.
+//!
+//!
+//! ## Testing with fixture files
+//!
+//! The `test-override` feature, which is off by default, makes the top-level
+//! functions read users from the file named by the `UZERS_PASSWD_FILE`
+//! environment variable, and groups from the file named by
+//! `UZERS_GROUP_FILE`, instead of asking the system. This lets integration
+//! tests run against fixtures without preloading a library such as
+//! `nss_wrapper`. The [`files`](files/index.html) module does the parsing.
+//!
+//!
+//! ## Interoperating with `nix`
+//!
+//! The `nix` feature, which is off by default, adds `TryFrom` conversions
+//! both ways between the [`User`] and [`Group`] of this crate and
+//! `nix::unistd::User` and `nix::unistd::Group`, and `From` conversions
+//! between the [`Uid`] and [`Gid`] types of the two crates. Users and
+//! groups from `nix` are checked as a [`UserBuilder`] or [`GroupBuilder`]
+//! would check them, and ours cannot be converted if a name is not valid
+//! UTF-8 or a field holds a NUL byte.
+//!
+//!
+//! ## Fuzzing
+//!
+//! The `arbitrary` feature, which is off by default, implements the
+//! `arbitrary` crate’s `Arbitrary` trait for [`User`], [`Group`], and
+//! [`MockUsers`](mock/struct.MockUsers.html), so that code that consumes
+//! them can be fuzzed with `cargo fuzz`. Their names and fields are made of
+//! any bytes but NUL.
+//!
+//!
+//! ## Zeroizing passwords
+//!
+//! The `zeroize` feature, which is off by default, implements the `zeroize`
+//! crate’s `Zeroize` and `ZeroizeOnDrop` traits for [`User`] and for the
+//! entries of the shadow database, so that the encrypted passwords they
+//! hold are overwritten rather than left in memory once they are dropped.
+//! The buffers that the C library copies entries into are overwritten too,
+//! as is the old password that
+//! [`with_password`](os/unix/trait.UserExt.html#tymethod.with_password)
+//! replaces.
+//!
+//! Overwriting on drop takes a `Drop` implementation, which changes how the
+//! types can be used: with the feature, the fields of a
+//! [`Shadow`](shadow/struct.Shadow.html) entry cannot be moved out of it,
+//! and one entry cannot be built from another with the `..other` struct
+//! update syntax. Code that does either only compiles without the feature.
+//!
+//!
 //! ## Logging
 //!
 //! The `logging` feature, which is on by default, uses the `log` crate to
@@ -133,7 +195,9 @@ extern crate libc;
 pub use libc::{gid_t, uid_t};
 
 mod base;
-pub use base::{all_groups, all_users};
+pub use base::{all_groups, all_groups_with_progress, all_users, all_users_with_progress};
+#[cfg(feature = "cancellation")]
+pub use base::{all_groups_cancellable, all_users_cancellable};
 pub use base::{get_current_gid, get_current_groupname};
 pub use base::{get_current_uid, get_current_username};
 pub use base::{get_effective_gid, get_effective_groupname};
@@ -141,18 +205,93 @@ pub use base::{get_effective_uid, get_effective_username};
 pub use base::{get_group_by_gid, get_group_by_name};
 pub use base::{get_user_by_name, get_user_by_uid};
 pub use base::{get_user_groups, group_access_list};
-pub use base::{os, Group, User};
+pub use base::{os, Group, GroupBuilder, User, UserBuilder};
+pub use base::{try_get_group_by_gid, try_get_group_by_name};
+pub use base::{try_get_user_by_name, try_get_user_by_uid};
+
+pub mod audit;
+
+pub mod bulk;
+
+pub mod canonical;
 
 #[cfg(feature = "cache")]
 pub mod cache;
 
+#[cfg(feature = "serde")]
+pub mod encoding;
+
+pub mod files;
+
+pub mod fs;
+
+mod identity;
+pub use identity::{current_identity, identity_of, Identity};
+
+pub mod ids;
+pub use ids::{is_sentinel_gid, is_sentinel_uid, Gid, Uid};
+
+#[cfg(feature = "cancellation")]
+pub mod cancel;
+
+#[cfg(feature = "global-cache")]
+pub use cache::global_cache;
+#[cfg(feature = "global-snapshot")]
+pub use cache::snapshot;
 #[cfg(feature = "cache")]
-pub use cache::{UsersCache, UsersSnapshot};
+pub use cache::{BoundedUsersCache, SyncUsersCache, UsersCache, UsersSnapshot};
 
 #[cfg(feature = "mock")]
 pub mod mock;
 
+#[cfg(all(
+    feature = "utmp",
+    any(target_os = "linux", target_os = "macos", target_os = "freebsd")
+))]
+pub mod sessions;
+
+#[cfg(target_os = "linux")]
+pub mod shadow;
+
+#[cfg(all(test, feature = "cache-sim"))]
+mod sim;
+
+mod progress;
+pub use progress::Progress;
+
+pub mod nsswitch;
+
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+
+#[cfg(feature = "nix")]
+mod nix_compat;
+
+mod null;
+pub use null::NullUsers;
+
+pub mod prelude;
+
+pub mod record;
+
+pub mod source;
+pub use source::{clear_global_source, set_global_source, UserDbSource};
+
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
 pub mod switch;
 
+pub mod time;
+
+#[cfg(all(feature = "userdb", target_os = "linux"))]
+pub mod userdb;
+
+#[cfg(feature = "v1-preview")]
+pub mod v1;
+
 mod traits;
-pub use traits::{AllGroups, AllUsers, Groups, Users};
+pub use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, DynAllGroups, DynAllUsers, DynGroups,
+    DynUsers, Groups, SupplementaryGroups, TryGroups, TryUsers, UserDatabase, Users,
+};
@@ -29,6 +29,41 @@
 //!
 //! The exports get re-exported into the mock module, for simpler `use` lines.
 //!
+//! A table made with `with_current_uid` reports the same ID as the current and
+//! effective user and group IDs. To test code that behaves differently when
+//! these differ, such as a setuid program, build the table with
+//! [`MockUsers::builder`] instead:
+//!
+//! ```
+//! use uzers::Users;
+//! use uzers::mock::MockUsers;
+//!
+//! let users = MockUsers::builder()
+//!     .current_uid(1000)
+//!     .current_gid(100)
+//!     .effective_uid(0)
+//!     .build();
+//!
+//! assert_eq!(users.get_effective_uid(), 0);
+//! ```
+//!
+//! The current and effective IDs do not have to belong to a user in the
+//! table. A process can keep running after its user has been deleted, and
+//! programs that show the owner of something should fall back to the number
+//! when there is no name for it. To test that, leave the current user out
+//! of the table, or remove it:
+//!
+//! ```
+//! use uzers::Users;
+//! use uzers::mock::MockUsers;
+//!
+//! let mut users = MockUsers::with_realistic_defaults(1000, "fred");
+//! users.remove_user(1000);
+//!
+//! assert_eq!(users.get_current_uid(), 1000);
+//! assert_eq!(users.get_current_username(), None);
+//! ```
+//!
 //!
 //! ## Using Mock Users
 //!
@@ -77,29 +112,237 @@
 //! print_all_users(&users);
 
 use std::collections::HashMap;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::iter::FromIterator;
 use std::ops::Deref;
-use std::sync::Arc;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::vec;
+
+use base::os::unix::{GroupExt, UserExt};
+#[cfg(feature = "cache")]
+use cache::{UsersSnapshot, ValidationReport};
+use files::{parse_group, parse_passwd, read_group_file, read_passwd_file};
 
 pub use base::{Group, User};
 pub use libc::{gid_t, uid_t};
-pub use traits::{AllGroups, AllUsers, Groups, Users};
+pub use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, SupplementaryGroups, TryGroups,
+    TryUsers, Users,
+};
+
+/// The ID of the `nobody` user and the `nogroup` group.
+const NOBODY: u32 = 65534;
 
 /// A mocking users table that you can add your own users and groups to.
+#[derive(Debug)]
 pub struct MockUsers {
     users: HashMap<uid_t, Arc<User>>,
     groups: HashMap<gid_t, Arc<Group>>,
     uid: uid_t,
+    gid: gid_t,
+    euid: uid_t,
+    egid: gid_t,
+    faults: Faults,
+}
+
+/// The lookups of a [`MockUsers`] table that have been set up to fail, and
+/// the error numbers they fail with.
+#[derive(Debug, Default)]
+struct Faults {
+    uids: HashMap<uid_t, i32>,
+    usernames: HashMap<OsString, i32>,
+    gids: HashMap<gid_t, i32>,
+    groupnames: HashMap<OsString, i32>,
+    after: Option<(usize, i32)>,
+    lookups: AtomicUsize,
+}
+
+impl Faults {
+    /// Counts a lookup, returning the error it should fail with, if any.
+    fn check(&self, errno: Option<&i32>) -> io::Result<()> {
+        let lookups = self.lookups.fetch_add(1, Ordering::Relaxed);
+        let after = self
+            .after
+            .filter(|&(limit, _)| lookups >= limit)
+            .map(|(_, errno)| errno);
+
+        match errno.copied().or(after) {
+            Some(errno) => Err(io::Error::from_raw_os_error(errno)),
+            None => Ok(()),
+        }
+    }
 }
 
 impl MockUsers {
     /// Create a new, empty mock users table.
+    ///
+    /// The given ID is used as the current and effective user ID, and also
+    /// as the current and effective group ID.
     pub fn with_current_uid(current_uid: uid_t) -> Self {
-        Self {
-            users: HashMap::new(),
-            groups: HashMap::new(),
-            uid: current_uid,
+        Self::builder()
+            .current_uid(current_uid)
+            .current_gid(current_uid)
+            .build()
+    }
+
+    /// Create a mock users table holding the accounts that every system has,
+    /// along with a human user who is the current user.
+    ///
+    /// The table holds:
+    ///
+    /// - `root`, with ID 0, in the `root` group with ID 0;
+    /// - `nobody`, with ID 65534, in the `nogroup` group with ID 65534;
+    /// - the given user, in a group of the same name and ID, with a home
+    ///   directory under `/home` and `/bin/sh` as its shell.
+    ///
+    /// The given user and its group are the current and effective user and
+    /// group of the table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{Groups, Users};
+    /// use uzers::mock::MockUsers;
+    /// use uzers::os::unix::UserExt;
+    ///
+    /// let users = MockUsers::with_realistic_defaults(1000, "fred");
+    /// let fred = users.get_user_by_uid(users.get_current_uid()).unwrap();
+    /// assert_eq!(fred.home_dir().to_str(), Some("/home/fred"));
+    /// assert_eq!(&*users.get_current_groupname().unwrap(), "fred");
+    /// assert!(users.get_user_by_name("nobody").is_some());
+    /// ```
+    pub fn with_realistic_defaults<S: AsRef<OsStr> + ?Sized>(uid: uid_t, username: &S) -> Self {
+        let username = username.as_ref();
+        let mut users = Self::with_current_uid(uid);
+
+        users.add_user(
+            User::new(0, "root", 0)
+                .with_home_dir("/root")
+                .with_shell("/bin/sh"),
+        );
+        users.add_group(Group::new(0, "root"));
+
+        users.add_user(
+            User::new(NOBODY, "nobody", NOBODY)
+                .with_home_dir("/nonexistent")
+                .with_shell("/usr/sbin/nologin"),
+        );
+        users.add_group(Group::new(NOBODY, "nogroup"));
+
+        users.add_user(
+            User::new(uid, username, uid)
+                .with_home_dir(&Path::new("/home").join(username))
+                .with_shell("/bin/sh"),
+        );
+        users.add_group(Group::new(uid, username));
+
+        users
+    }
+
+    /// Create a mock users table holding the users of the given contents of
+    /// a `passwd` file, and no groups.
+    ///
+    /// This lets unit tests use the same fixtures as the tests that run
+    /// against `nss_wrapper`. Lines that cannot be parsed are skipped, as
+    /// with [`parse_passwd`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::Users;
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let users = MockUsers::from_passwd_str("fred:x:1337:42::/home/fred:/bin/sh", 1337);
+    /// assert_eq!(&*users.get_current_username().unwrap(), "fred");
+    /// ```
+    pub fn from_passwd_str(passwd: &str, current_uid: uid_t) -> Self {
+        let mut users = Self::with_current_uid(current_uid);
+        for user in parse_passwd(passwd.as_bytes()) {
+            users.add_user(user);
+        }
+        users
+    }
+
+    /// Create a mock users table holding the groups of the given contents of
+    /// a `group` file, and no users.
+    ///
+    /// Lines that cannot be parsed are skipped, as with [`parse_group`]. To
+    /// read both files into one table, use
+    /// [`from_passwd_str`](MockUsers::from_passwd_str) and add the groups
+    /// parsed by [`parse_group`] to it.
+    pub fn from_group_str(group: &str, current_uid: uid_t) -> Self {
+        let mut users = Self::with_current_uid(current_uid);
+        for group in parse_group(group.as_bytes()) {
+            users.add_group(group);
+        }
+        users
+    }
+
+    /// Create a mock users table holding the users and groups of the given
+    /// `passwd` and `group` files.
+    ///
+    /// The files are read with [`read_passwd_file`] and [`read_group_file`],
+    /// so lines that cannot be parsed are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if either file cannot be read.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let users = MockUsers::from_files("tests/fixtures/passwd", "tests/fixtures/group", 1337)
+    ///     .expect("Cannot read the fixtures");
+    /// ```
+    pub fn from_files<P, G>(passwd: P, group: G, current_uid: uid_t) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        G: AsRef<Path>,
+    {
+        let mut users = Self::with_current_uid(current_uid);
+        for user in read_passwd_file(passwd)? {
+            users.add_user(user);
         }
+        for group in read_group_file(group)? {
+            users.add_group(group);
+        }
+        Ok(users)
+    }
+
+    /// Uses the given ID as the current and effective group ID of the table,
+    /// instead of the current user ID.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{Group, Groups};
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000).with_current_gid(100);
+    /// users.add_group(Group::new(100, "staff"));
+    /// assert_eq!(&*users.get_current_groupname().unwrap(), "staff");
+    /// ```
+    pub fn with_current_gid(mut self, current_gid: gid_t) -> Self {
+        self.gid = current_gid;
+        self.egid = current_gid;
+        self
+    }
+
+    /// Changes the effective group ID of the table, leaving the current
+    /// group ID as it is.
+    pub fn set_effective_gid(&mut self, effective_gid: gid_t) {
+        self.egid = effective_gid;
+    }
+
+    /// Returns a builder for a new, empty mock users table whose current and
+    /// effective user and group IDs can be set independently.
+    pub fn builder() -> MockUsersBuilder {
+        MockUsersBuilder::default()
     }
 
     /// Add a user to the users table.
@@ -111,18 +354,465 @@ impl MockUsers {
     pub fn add_group(&mut self, group: Group) -> Option<Arc<Group>> {
         self.groups.insert(group.gid(), Arc::new(group))
     }
+
+    /// Returns the user with the given ID, to be changed in place.
+    ///
+    /// Users that have been looked up are shared with whoever looked them
+    /// up, so the first change after a lookup copies the user, and those who
+    /// hold on to it keep seeing it as it was.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{User, Users};
+    /// use uzers::mock::MockUsers;
+    /// use uzers::os::unix::UserExt;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    ///
+    /// let fred = users.user_mut(1000).unwrap();
+    /// *fred = fred.clone().with_shell("/bin/zsh");
+    /// assert_eq!(users.get_user_by_uid(1000).unwrap().shell().to_str(), Some("/bin/zsh"));
+    /// ```
+    pub fn user_mut(&mut self, uid: uid_t) -> Option<&mut User> {
+        self.users.get_mut(&uid).map(Arc::make_mut)
+    }
+
+    /// Returns the group with the given ID, to be changed in place.
+    ///
+    /// See [`user_mut`](MockUsers::user_mut).
+    pub fn group_mut(&mut self, gid: gid_t) -> Option<&mut Group> {
+        self.groups.get_mut(&gid).map(Arc::make_mut)
+    }
+
+    /// Remove the user with the given ID from the users table, returning it
+    /// if it was there.
+    pub fn remove_user(&mut self, uid: uid_t) -> Option<Arc<User>> {
+        self.users.remove(&uid)
+    }
+
+    /// Remove the user with the given name from the users table, returning
+    /// it if it was there.
+    pub fn remove_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &mut self,
+        username: &S,
+    ) -> Option<Arc<User>> {
+        let uid = self.find_user_by_name(username.as_ref())?.uid();
+        self.users.remove(&uid)
+    }
+
+    /// Remove the group with the given ID from the groups table, returning
+    /// it if it was there.
+    pub fn remove_group(&mut self, gid: gid_t) -> Option<Arc<Group>> {
+        self.groups.remove(&gid)
+    }
+
+    /// Adds the user with the given ID to the members of the group with the
+    /// given ID, returning whether both were in the table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{Group, SupplementaryGroups, User};
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    /// users.add_group(Group::new(100, "staff"));
+    /// users.add_group(Group::new(27, "sudo"));
+    /// users.add_user_to_group(1000, 27);
+    ///
+    /// let groups = users.get_user_groups("fred", 100).unwrap();
+    /// assert_eq!(groups.iter().map(|g| g.gid()).collect::<Vec<_>>(), vec![27, 100]);
+    /// ```
+    pub fn add_user_to_group(&mut self, uid: uid_t, gid: gid_t) -> bool {
+        let (Some(user), Some(group)) = (self.users.get(&uid), self.groups.get_mut(&gid)) else {
+            return false;
+        };
+
+        if !group.members().iter().any(|m| m == user.name()) {
+            let group = Arc::make_mut(group);
+            *group = group.clone().add_member(user.name());
+        }
+        true
+    }
+
+    /// Remove every user and group, leaving the current and effective IDs
+    /// as they are.
+    pub fn clear(&mut self) {
+        self.users.clear();
+        self.groups.clear();
+    }
+
+    /// Checks the table for the mistakes that fixtures tend to have: users
+    /// whose primary group is not in the table, names used by more than one
+    /// user or group, and groups listing members that are not users in the
+    /// table.
+    ///
+    /// The table is checked as a [`UsersSnapshot`](../cache/struct.UsersSnapshot.html)
+    /// of it would be; see [`ValidationReport`]. This is only available with
+    /// the `cache` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{Group, User};
+    /// use uzers::mock::MockUsers;
+    /// use uzers::os::unix::GroupExt;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    /// users.add_group(Group::new(100, "staff").add_member("fred"));
+    /// assert!(users.validate().is_ok());
+    /// ```
+    #[cfg(feature = "cache")]
+    pub fn validate(&self) -> ValidationReport {
+        UsersSnapshot::from_source(self).validate()
+    }
+
+    /// Makes every lookup of the user with the given ID fail with the given
+    /// error number, such as `libc::EIO`.
+    ///
+    /// Failed lookups return `Err` through the [`TryUsers`] and
+    /// [`TryGroups`] traits, and `None` through the [`Users`] and [`Groups`]
+    /// traits, the same as the top-level functions do when the C library
+    /// reports an error. The
+    /// current and effective users and groups are not looked up this way,
+    /// so they never fail.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::{TryUsers, User};
+    /// use uzers::mock::MockUsers;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    /// users.fail_uid(1000, libc::EIO);
+    ///
+    /// let error = users.try_get_user_by_uid(1000).unwrap_err();
+    /// assert_eq!(error.raw_os_error(), Some(libc::EIO));
+    /// ```
+    pub fn fail_uid(&mut self, uid: uid_t, errno: i32) {
+        self.faults.uids.insert(uid, errno);
+    }
+
+    /// Makes every lookup of the user with the given name fail with the
+    /// given error number.
+    ///
+    /// See [`fail_uid`](MockUsers::fail_uid).
+    pub fn fail_username<S: AsRef<OsStr> + ?Sized>(&mut self, username: &S, errno: i32) {
+        self.faults
+            .usernames
+            .insert(username.as_ref().to_owned(), errno);
+    }
+
+    /// Makes every lookup of the group with the given ID fail with the given
+    /// error number.
+    ///
+    /// See [`fail_uid`](MockUsers::fail_uid).
+    pub fn fail_gid(&mut self, gid: gid_t, errno: i32) {
+        self.faults.gids.insert(gid, errno);
+    }
+
+    /// Makes every lookup of the group with the given name fail with the
+    /// given error number.
+    ///
+    /// See [`fail_uid`](MockUsers::fail_uid).
+    pub fn fail_groupname<S: AsRef<OsStr> + ?Sized>(&mut self, group_name: &S, errno: i32) {
+        self.faults
+            .groupnames
+            .insert(group_name.as_ref().to_owned(), errno);
+    }
+
+    /// Lets the given number of lookups through, counting from now, and
+    /// makes every lookup after them fail with the given error number, such
+    /// as `libc::ETIMEDOUT` for a directory service that has gone away.
+    ///
+    /// See [`fail_uid`](MockUsers::fail_uid).
+    pub fn fail_after(&mut self, lookups: usize, errno: i32) {
+        self.faults.lookups = AtomicUsize::new(0);
+        self.faults.after = Some((lookups, errno));
+    }
+
+    /// Makes every lookup succeed again.
+    pub fn clear_failures(&mut self) {
+        self.faults = Faults::default();
+    }
+
+    /// Returns the user with the given name, without counting it as a
+    /// lookup.
+    fn find_user_by_name(&self, username: &OsStr) -> Option<&Arc<User>> {
+        self.users.values().find(|u| u.name() == username)
+    }
+}
+
+impl Extend<User> for MockUsers {
+    fn extend<T: IntoIterator<Item = User>>(&mut self, users: T) {
+        for user in users {
+            self.add_user(user);
+        }
+    }
+}
+
+impl Extend<Group> for MockUsers {
+    fn extend<T: IntoIterator<Item = Group>>(&mut self, groups: T) {
+        for group in groups {
+            self.add_group(group);
+        }
+    }
+}
+
+/// Collects users into a table whose current and effective IDs are all 0,
+/// as with a [`MockUsersBuilder`] that has none of them set.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::{User, Users};
+/// use uzers::mock::MockUsers;
+///
+/// let users = vec![User::new(1000, "fred", 100), User::new(1001, "wilma", 100)];
+/// let people = users.into_iter().collect::<MockUsers>();
+/// assert!(people.get_user_by_name("wilma").is_some());
+/// assert_eq!(people.get_current_uid(), 0);
+/// ```
+impl FromIterator<User> for MockUsers {
+    fn from_iter<T: IntoIterator<Item = User>>(users: T) -> Self {
+        let mut table = Self::builder().build();
+        table.extend(users);
+        table
+    }
+}
+
+/// Collects groups into a table whose current and effective IDs are all 0,
+/// as with a [`MockUsersBuilder`] that has none of them set.
+impl FromIterator<Group> for MockUsers {
+    fn from_iter<T: IntoIterator<Item = Group>>(groups: T) -> Self {
+        let mut table = Self::builder().build();
+        table.extend(groups);
+        table
+    }
+}
+
+/// A [`MockUsers`] table that can be shared between threads, and changed
+/// while they hold on to it.
+///
+/// Every clone refers to the same table, so a test can hand clones to the
+/// code under test, then add, remove or break users through its own clone
+/// between assertions. Each lookup takes a read lock on the table for as
+/// long as it takes to answer.
+///
+/// Iterating over every user or group has to hold the lock for as long as
+/// the iterator lives, so this does not implement [`AllUsers`] or
+/// [`AllGroups`] itself. Iterate over the table returned by
+/// [`read`](SharedMockUsers::read) instead, or use [`AllUsersOwned`] and
+/// [`AllGroupsOwned`], whose iterators go over the users and groups in the
+/// table when they were created, and do not hold the lock.
+///
+/// ## Example
+///
+/// ```
+/// use std::thread;
+/// use uzers::{AllUsers, User, Users};
+/// use uzers::mock::{MockUsers, SharedMockUsers};
+///
+/// let users = SharedMockUsers::new(MockUsers::with_current_uid(1000));
+/// users.write().add_user(User::new(1000, "fred", 100));
+///
+/// let clone = users.clone();
+/// let name = thread::spawn(move || clone.get_current_username()).join().unwrap();
+/// assert_eq!(&*name.unwrap(), "fred");
+///
+/// users.write().remove_user(1000);
+/// assert_eq!(users.read().get_all_users().count(), 0);
+/// ```
+#[derive(Clone)]
+pub struct SharedMockUsers {
+    table: Arc<RwLock<MockUsers>>,
+}
+
+impl SharedMockUsers {
+    /// Shares the given table.
+    pub fn new(table: MockUsers) -> Self {
+        Self {
+            table: Arc::new(RwLock::new(table)),
+        }
+    }
+
+    /// Locks the table for reading, blocking until no clone is writing to
+    /// it.
+    pub fn read(&self) -> RwLockReadGuard<'_, MockUsers> {
+        self.table.read().unwrap()
+    }
+
+    /// Locks the table for writing, blocking until no clone is reading
+    /// from or writing to it.
+    pub fn write(&self) -> RwLockWriteGuard<'_, MockUsers> {
+        self.table.write().unwrap()
+    }
+}
+
+impl From<MockUsers> for SharedMockUsers {
+    fn from(table: MockUsers) -> Self {
+        Self::new(table)
+    }
+}
+
+impl Users for SharedMockUsers {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.read().get_user_by_uid(uid)
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.read().get_user_by_name(username)
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.read().get_current_uid()
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        self.read().get_current_username()
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.read().get_effective_uid()
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        self.read().get_effective_username()
+    }
+}
+
+impl Groups for SharedMockUsers {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.read().get_group_by_gid(gid)
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.read().get_group_by_name(group_name)
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.read().get_current_gid()
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        self.read().get_current_groupname()
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.read().get_effective_gid()
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        self.read().get_effective_groupname()
+    }
+}
+
+impl TryUsers for SharedMockUsers {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        self.read().try_get_user_by_uid(uid)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        self.read().try_get_user_by_name(username)
+    }
+}
+
+impl TryGroups for SharedMockUsers {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        self.read().try_get_group_by_gid(gid)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        self.read().try_get_group_by_name(group_name)
+    }
+}
+
+impl AllUsersOwned for SharedMockUsers {
+    type OwnedUserIter = vec::IntoIter<Arc<User>>;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        self.read().get_all_users_owned()
+    }
+}
+
+impl AllGroupsOwned for SharedMockUsers {
+    type OwnedGroupIter = vec::IntoIter<Arc<Group>>;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        self.read().get_all_groups_owned()
+    }
+}
+
+/// A builder of [`MockUsers`] tables, returned by [`MockUsers::builder`].
+///
+/// Every ID that is not set defaults to another one: the effective user ID
+/// to the current user ID, the effective group ID to the current group ID,
+/// and the current user and group IDs to 0.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MockUsersBuilder {
+    uid: uid_t,
+    gid: gid_t,
+    euid: Option<uid_t>,
+    egid: Option<gid_t>,
+}
+
+impl MockUsersBuilder {
+    /// Sets the current user ID of the table.
+    pub fn current_uid(mut self, uid: uid_t) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    /// Sets the current group ID of the table.
+    pub fn current_gid(mut self, gid: gid_t) -> Self {
+        self.gid = gid;
+        self
+    }
+
+    /// Sets the effective user ID of the table.
+    pub fn effective_uid(mut self, uid: uid_t) -> Self {
+        self.euid = Some(uid);
+        self
+    }
+
+    /// Sets the effective group ID of the table.
+    pub fn effective_gid(mut self, gid: gid_t) -> Self {
+        self.egid = Some(gid);
+        self
+    }
+
+    /// Creates the table, with no users or groups in it.
+    pub fn build(self) -> MockUsers {
+        MockUsers {
+            users: HashMap::new(),
+            groups: HashMap::new(),
+            uid: self.uid,
+            gid: self.gid,
+            euid: self.euid.unwrap_or(self.uid),
+            egid: self.egid.unwrap_or(self.gid),
+            faults: Faults::default(),
+        }
+    }
 }
 
 impl Users for MockUsers {
     fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
-        self.users.get(&uid).cloned()
+        self.try_get_user_by_uid(uid).ok().flatten()
     }
 
     fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
-        self.users
-            .values()
-            .find(|u| u.name() == username.as_ref())
-            .cloned()
+        self.try_get_user_by_name(username).ok().flatten()
     }
 
     fn get_current_uid(&self) -> uid_t {
@@ -137,12 +827,12 @@ impl Users for MockUsers {
     }
 
     fn get_effective_uid(&self) -> uid_t {
-        self.uid
+        self.euid
     }
 
     fn get_effective_username(&self) -> Option<Arc<OsStr>> {
         self.users
-            .get(&self.uid)
+            .get(&self.euid)
             .cloned()
             .map(|u| Arc::clone(&u.name_arc))
     }
@@ -150,39 +840,98 @@ impl Users for MockUsers {
 
 impl Groups for MockUsers {
     fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
-        self.groups.get(&gid).cloned()
+        self.try_get_group_by_gid(gid).ok().flatten()
     }
 
     fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
-        self.groups
-            .values()
-            .find(|g| g.name() == group_name.as_ref())
-            .cloned()
+        self.try_get_group_by_name(group_name).ok().flatten()
     }
 
-    fn get_current_gid(&self) -> uid_t {
-        self.uid
+    fn get_current_gid(&self) -> gid_t {
+        self.gid
     }
 
     fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
         self.groups
-            .get(&self.uid)
+            .get(&self.gid)
             .cloned()
             .map(|u| Arc::clone(&u.name_arc))
     }
 
-    fn get_effective_gid(&self) -> uid_t {
-        self.uid
+    fn get_effective_gid(&self) -> gid_t {
+        self.egid
     }
 
     fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
         self.groups
-            .get(&self.uid)
+            .get(&self.egid)
             .cloned()
             .map(|u| Arc::clone(&u.name_arc))
     }
 }
 
+/// Lookups fail with the errors they have been set up to fail with by
+/// [`fail_uid`](MockUsers::fail_uid) and the other `fail_` methods.
+impl TryUsers for MockUsers {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        self.faults.check(self.faults.uids.get(&uid))?;
+        Ok(self.users.get(&uid).cloned())
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        let username = username.as_ref();
+        self.faults.check(self.faults.usernames.get(username))?;
+        Ok(self.find_user_by_name(username).cloned())
+    }
+}
+
+/// Lookups fail with the errors they have been set up to fail with by
+/// [`fail_gid`](MockUsers::fail_gid) and the other `fail_` methods.
+impl TryGroups for MockUsers {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        self.faults.check(self.faults.gids.get(&gid))?;
+        Ok(self.groups.get(&gid).cloned())
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        let group_name = group_name.as_ref();
+        self.faults.check(self.faults.groupnames.get(group_name))?;
+        Ok(self
+            .groups
+            .values()
+            .find(|g| g.name() == group_name)
+            .cloned())
+    }
+}
+
+impl SupplementaryGroups for MockUsers {
+    /// Returns the groups of the user with the given name and primary group
+    /// ID, as [`get_user_groups`](../fn.get_user_groups.html) does for the
+    /// system: the primary group, if it’s in the table, along with every
+    /// group that lists the user as a member, in order of their IDs.
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>> {
+        let username = username.as_ref();
+        let mut groups = self
+            .groups
+            .values()
+            .filter(|g| g.gid() == gid || g.members().iter().any(|m| m == username))
+            .cloned()
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|g| g.gid());
+        Some(groups)
+    }
+}
+
 impl AllUsers for MockUsers {
     type UserIter<'a> = std::iter::Map<
         std::collections::hash_map::Values<'a, uid_t, Arc<User>>,
@@ -205,11 +954,34 @@ impl AllGroups for MockUsers {
     }
 }
 
+impl AllUsersOwned for MockUsers {
+    type OwnedUserIter = vec::IntoIter<Arc<User>>;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        let users = self.users.values().cloned();
+        users.collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl AllGroupsOwned for MockUsers {
+    type OwnedGroupIter = vec::IntoIter<Arc<Group>>;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        let groups = self.groups.values().cloned();
+        groups.collect::<Vec<_>>().into_iter()
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::MockUsers;
+    use super::{MockUsers, SharedMockUsers};
+    use base::os::unix::GroupExt;
     use base::{Group, User};
-    use traits::{AllGroups, AllUsers, Groups, Users};
+    use ids::Uid;
+    use traits::{
+        AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, SupplementaryGroups, TryGroups,
+        TryUsers, Users,
+    };
 
     use std::ffi::OsStr;
     use std::sync::Arc;
@@ -230,6 +1002,315 @@ mod test {
         assert_eq!(None, users.get_current_username())
     }
 
+    #[test]
+    fn deleted_current_user() {
+        let mut users = MockUsers::builder()
+            .current_uid(1337)
+            .current_gid(101)
+            .build();
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+        users.remove_user_by_name("fred");
+
+        assert_eq!(1337, users.get_current_uid());
+        assert_eq!(None, users.get_current_username());
+        assert_eq!(None, users.get_effective_username());
+        assert_eq!(
+            Some(Arc::from(OsStr::new("staff"))),
+            users.get_current_groupname()
+        );
+    }
+
+    #[test]
+    fn builder() {
+        let mut users = MockUsers::builder()
+            .current_uid(1000)
+            .current_gid(100)
+            .effective_uid(0)
+            .build();
+        users.add_user(User::new(0, "root", 0));
+        users.add_user(User::new(1000, "fred", 100));
+        users.add_group(Group::new(100, "staff"));
+
+        assert_eq!(users.get_current_uid(), 1000);
+        assert_eq!(users.get_effective_uid(), 0);
+        assert_eq!(users.get_current_gid(), 100);
+        assert_eq!(users.get_effective_gid(), 100);
+        assert_eq!(
+            Some(Arc::from(OsStr::new("root"))),
+            users.get_effective_username()
+        );
+        assert_eq!(
+            Some(Arc::from(OsStr::new("staff"))),
+            users.get_effective_groupname()
+        );
+    }
+
+    #[test]
+    fn gids() {
+        let mut users = MockUsers::with_current_uid(1000).with_current_gid(100);
+        users.add_group(Group::new(100, "staff"));
+        users.add_group(Group::new(0, "wheel"));
+        users.set_effective_gid(0);
+
+        assert_eq!(users.get_current_uid(), 1000);
+        assert_eq!(users.get_current_gid(), 100);
+        assert_eq!(users.get_effective_gid(), 0);
+        assert_eq!(
+            Some(Arc::from(OsStr::new("staff"))),
+            users.get_current_groupname()
+        );
+        assert_eq!(
+            Some(Arc::from(OsStr::new("wheel"))),
+            users.get_effective_groupname()
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_user(User::new(1338, "wilma", 101));
+        users.add_group(Group::new(101, "staff"));
+
+        assert_eq!(Some(1337), users.remove_user(1337).map(|u| u.uid()));
+        assert!(users.remove_user(1337).is_none());
+        assert!(users.get_current_username().is_none());
+        assert_eq!(
+            Some(1338),
+            users.remove_user_by_name("wilma").map(|u| u.uid())
+        );
+        assert!(users.remove_user_by_name("wilma").is_none());
+        assert_eq!(Some(101), users.remove_group(101).map(|g| g.gid()));
+        assert_eq!(0, users.get_all_groups().count());
+    }
+
+    #[test]
+    fn clear() {
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+        users.clear();
+
+        assert_eq!(0, users.get_all_users().count());
+        assert_eq!(0, users.get_all_groups().count());
+        assert_eq!(1337, users.get_current_uid());
+    }
+
+    #[test]
+    fn from_strs() {
+        let users = MockUsers::from_passwd_str(include_str!("../tests/fixtures/passwd"), 1337);
+        assert_eq!(
+            Some(42),
+            users.get_user_by_name("fred").map(|u| u.primary_group_id())
+        );
+        assert_eq!(0, users.get_all_groups().count());
+
+        let groups = MockUsers::from_group_str(include_str!("../tests/fixtures/group"), 1337);
+        assert_eq!(
+            Some(43),
+            groups.get_group_by_name("contributors").map(|g| g.gid())
+        );
+        assert_eq!(2, groups.get_all_groups().count());
+        assert_eq!(0, groups.get_all_users().count());
+    }
+
+    #[test]
+    fn from_files() {
+        let users =
+            MockUsers::from_files("tests/fixtures/passwd", "tests/fixtures/group", 1337).unwrap();
+        assert_eq!(
+            Some(Arc::from(OsStr::new("fred"))),
+            users.get_current_username()
+        );
+        assert_eq!(Some(42), users.get_group_by_name("bosses").map(|g| g.gid()));
+
+        let missing =
+            MockUsers::from_files("tests/fixtures/passwd", "tests/fixtures/nothing-here", 0);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn collect() {
+        let mut users = vec![User::new(1337, "fred", 101), User::new(0, "root", 0)]
+            .into_iter()
+            .filter(|u| u.uid() != 0)
+            .collect::<MockUsers>();
+        users.extend(vec![Group::new(101, "staff")]);
+
+        assert_eq!(0, users.get_current_uid());
+        assert_eq!(
+            vec![1337],
+            users.get_all_users().map(|u| u.uid()).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(101), users.get_group_by_name("staff").map(|g| g.gid()));
+
+        let groups = vec![Group::new(101, "staff")]
+            .into_iter()
+            .collect::<MockUsers>();
+        assert_eq!(1, groups.get_all_groups().count());
+    }
+
+    #[test]
+    fn failures() {
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+        users.fail_uid(1337, libc::EIO);
+        users.fail_groupname("staff", libc::ERANGE);
+
+        let error = users.try_get_user_by_uid(1337).unwrap_err();
+        assert_eq!(Some(libc::EIO), error.raw_os_error());
+        assert!(users.get_user_by_uid(1337).is_none());
+        assert!(users.get_user_by_name("fred").is_some());
+        assert!(users.get_current_username().is_some());
+
+        let error = users.try_get_group_by_name("staff").unwrap_err();
+        assert_eq!(Some(libc::ERANGE), error.raw_os_error());
+        assert!(users.try_get_group_by_gid(101).unwrap().is_some());
+
+        users.clear_failures();
+        assert!(users.try_get_user_by_uid(1337).unwrap().is_some());
+    }
+
+    #[test]
+    fn fail_after() {
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.fail_after(2, libc::ETIMEDOUT);
+
+        assert!(users.try_get_user_by_uid(1337).is_ok());
+        assert!(users.try_get_group_by_gid(101).is_ok());
+        let error = users.try_get_user_by_name("fred").unwrap_err();
+        assert_eq!(Some(libc::ETIMEDOUT), error.raw_os_error());
+        assert!(users.get_user_by_uid(1337).is_none());
+    }
+
+    #[test]
+    fn shared() {
+        let users = SharedMockUsers::from(MockUsers::with_current_uid(1337));
+        let clone = users.clone();
+        let worker = std::thread::spawn(move || {
+            while clone.get_user_by_uid(1337).is_none() {
+                std::thread::yield_now();
+            }
+            clone.write().remove_user(1337);
+        });
+
+        users.write().add_user(User::new(1337, "fred", 101));
+        worker.join().unwrap();
+        assert!(users.get_current_username().is_none());
+        assert_eq!(0, users.read().get_all_users().count());
+    }
+
+    #[test]
+    fn shared_owned_iterators() {
+        let users = SharedMockUsers::from(MockUsers::with_current_uid(1337));
+        users.write().add_user(User::new(1337, "fred", 101));
+        users.write().add_group(Group::new(101, "staff"));
+
+        let all_users = users.get_all_users_owned();
+        let all_groups = users.get_all_groups_owned();
+        users.write().clear();
+
+        assert_eq!(all_users.map(|u| u.uid()).collect::<Vec<_>>(), [1337]);
+        assert_eq!(all_groups.count(), 1);
+        assert_eq!(users.get_all_users_owned().count(), 0);
+    }
+
+    #[test]
+    fn user_groups() {
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+        users.add_group(Group::new(27, "sudo"));
+        users.add_group(Group::new(1, "other"));
+
+        assert!(users.add_user_to_group(1337, 27));
+        assert!(users.add_user_to_group(1337, 27));
+        assert!(!users.add_user_to_group(1337, 28));
+        assert!(!users.add_user_to_group(1338, 27));
+
+        let gids = |groups: Vec<Arc<Group>>| groups.iter().map(|g| g.gid()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![27, 101],
+            gids(users.get_user_groups("fred", 101).unwrap())
+        );
+        assert_eq!(
+            vec![101],
+            gids(users.get_user_groups("wilma", 101).unwrap())
+        );
+        assert_eq!(1, users.get_group_by_gid(27).unwrap().members().len());
+
+        let groups = users.groups_of(Uid::from_raw(1337)).unwrap();
+        assert_eq!(
+            vec![27, 101],
+            groups.iter().map(|g| g.gid()).collect::<Vec<_>>()
+        );
+        assert!(users.groups_of(Uid::from_raw(1338)).is_none());
+    }
+
+    #[test]
+    fn realistic_defaults() {
+        let users = MockUsers::with_realistic_defaults(1337, "fred");
+        assert_eq!(3, users.get_all_users().count());
+        assert_eq!(3, users.get_all_groups().count());
+        assert_eq!(Some(0), users.get_user_by_name("root").map(|u| u.uid()));
+        assert_eq!(
+            Some(65534),
+            users.get_group_by_name("nogroup").map(|g| g.gid())
+        );
+        assert_eq!(1337, users.get_current_gid());
+        assert_eq!(
+            Some(Arc::from(OsStr::new("fred"))),
+            users.get_effective_username()
+        );
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn validate() {
+        use cache::ValidationIssue;
+
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_user(User::new(1338, "fred", 42));
+        users.add_group(Group::new(101, "staff").add_member("wilma"));
+
+        let issues = users.validate().into_iter().collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            [
+                ValidationIssue::DanglingPrimaryGroup { uid: 1338, gid: 42 },
+                ValidationIssue::DuplicateUserName("fred".into()),
+                ValidationIssue::UnknownMember {
+                    gid: 101,
+                    member: "wilma".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn mutate() {
+        use base::os::unix::UserExt;
+
+        let mut users = MockUsers::with_current_uid(1337);
+        users.add_user(User::new(1337, "fred", 101));
+        users.add_group(Group::new(101, "staff"));
+        let before = users.get_user_by_uid(1337).unwrap();
+
+        let fred = users.user_mut(1337).unwrap();
+        *fred = fred.clone().with_gecos("Fred Santa");
+        let staff = users.group_mut(101).unwrap();
+        *staff = staff.clone().add_member("fred");
+
+        assert_eq!("", before.gecos());
+        assert_eq!("Fred Santa", users.get_user_by_uid(1337).unwrap().gecos());
+        assert_eq!(1, users.get_group_by_gid(101).unwrap().members().len());
+        assert!(users.user_mut(1338).is_none());
+    }
+
     #[test]
     fn uid() {
         let mut users = MockUsers::with_current_uid(0);
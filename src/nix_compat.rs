@@ -0,0 +1,226 @@
+//! Conversions between the users and groups of this crate and those of the
+//! `nix` crate.
+//!
+//! Users and groups of `nix` are checked as they would be by a
+//! [`UserBuilder`] or [`GroupBuilder`] when they are converted, and users and
+//! groups of this crate cannot be converted if their names are not valid
+//! UTF-8 or one of their fields holds a NUL byte, as `nix` cannot hold them.
+//!
+//! ```
+//! # extern crate nix;
+//! # extern crate uzers;
+//! use std::convert::TryFrom;
+//! use uzers::User;
+//!
+//! let user = User::new(1000, "fred", 100);
+//! let converted = nix::unistd::User::try_from(&user).unwrap();
+//! assert_eq!(converted.name, "fred");
+//! assert_eq!(User::try_from(converted).unwrap().uid(), 1000);
+//! ```
+//!
+//! This is only available with the `nix` feature.
+
+extern crate nix;
+
+use self::nix::unistd::{self, Gid, Uid};
+use std::convert::TryFrom;
+use std::ffi::{CString, OsStr};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, GroupBuilder, User, UserBuilder};
+use ids;
+
+/// Returns the value as a `String`, or an error saying which field is not
+/// valid UTF-8.
+fn utf8(field: &str, value: &OsStr) -> io::Result<String> {
+    value.to_str().map(String::from).ok_or_else(|| {
+        let message = format!("{} {:?} is not valid UTF-8", field, value);
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    })
+}
+
+/// Returns the value as a `CString`, or an error saying which field holds a
+/// NUL byte.
+fn c_string(field: &str, value: &OsStr) -> io::Result<CString> {
+    CString::new(value.as_bytes()).map_err(|_| {
+        let message = format!("{} {:?} contains a NUL byte", field, value);
+        io::Error::new(io::ErrorKind::InvalidData, message)
+    })
+}
+
+impl From<ids::Uid> for Uid {
+    fn from(uid: ids::Uid) -> Self {
+        Self::from_raw(uid.as_raw())
+    }
+}
+
+impl From<Uid> for ids::Uid {
+    fn from(uid: Uid) -> Self {
+        Self::from_raw(uid.as_raw())
+    }
+}
+
+impl From<ids::Gid> for Gid {
+    fn from(gid: ids::Gid) -> Self {
+        Self::from_raw(gid.as_raw())
+    }
+}
+
+impl From<Gid> for ids::Gid {
+    fn from(gid: Gid) -> Self {
+        Self::from_raw(gid.as_raw())
+    }
+}
+
+impl TryFrom<&User> for unistd::User {
+    type Error = io::Error;
+
+    fn try_from(user: &User) -> io::Result<Self> {
+        Ok(Self {
+            name: utf8("user name", user.name())?,
+            passwd: c_string("password", user.password())?,
+            uid: Uid::from_raw(user.uid()),
+            gid: Gid::from_raw(user.primary_group_id()),
+            #[cfg(not(all(target_os = "android", target_pointer_width = "32")))]
+            gecos: c_string("GECOS comment", user.gecos())?,
+            dir: user.home_dir().to_path_buf(),
+            shell: user.shell().to_path_buf(),
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            ))]
+            class: CString::default(),
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            ))]
+            change: ::base::os::bsd::UserExt::password_change_time(user),
+            #[cfg(any(
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "dragonfly",
+                target_os = "openbsd",
+                target_os = "netbsd"
+            ))]
+            expire: ::base::os::bsd::UserExt::password_expire_time(user),
+        })
+    }
+}
+
+impl TryFrom<unistd::User> for User {
+    type Error = io::Error;
+
+    fn try_from(user: unistd::User) -> io::Result<Self> {
+        let builder = UserBuilder::default()
+            .uid(user.uid.as_raw())
+            .name(&user.name)
+            .primary_group(user.gid.as_raw())
+            .home_dir(&user.dir)
+            .shell(&user.shell)
+            .password(OsStr::from_bytes(user.passwd.as_bytes()));
+
+        #[cfg(not(all(target_os = "android", target_pointer_width = "32")))]
+        let builder = builder.gecos(OsStr::from_bytes(user.gecos.as_bytes()));
+
+        #[cfg(any(
+            target_os = "macos",
+            target_os = "freebsd",
+            target_os = "dragonfly",
+            target_os = "openbsd",
+            target_os = "netbsd"
+        ))]
+        let builder = builder
+            .password_change_time(user.change)
+            .password_expire_time(user.expire);
+
+        builder.build()
+    }
+}
+
+impl TryFrom<&Group> for unistd::Group {
+    type Error = io::Error;
+
+    fn try_from(group: &Group) -> io::Result<Self> {
+        let mem = group
+            .members()
+            .iter()
+            .map(|member| utf8("member name", member))
+            .collect::<io::Result<_>>()?;
+
+        Ok(Self {
+            name: utf8("group name", group.name())?,
+            passwd: CString::default(),
+            gid: Gid::from_raw(group.gid()),
+            mem,
+        })
+    }
+}
+
+impl TryFrom<unistd::Group> for Group {
+    type Error = io::Error;
+
+    fn try_from(group: unistd::Group) -> io::Result<Self> {
+        GroupBuilder::default()
+            .gid(group.gid.as_raw())
+            .name(&group.name)
+            .members(&group.mem)
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    #[test]
+    fn users() {
+        let user = User::new(1000, "fred", 100)
+            .with_home_dir("/home/fred")
+            .with_shell("/bin/sh")
+            .with_gecos("Fred");
+
+        let converted = unistd::User::try_from(&user).unwrap();
+        assert_eq!(converted.uid, Uid::from_raw(1000));
+        assert_eq!(converted.gid, Gid::from_raw(100));
+        assert_eq!(converted.dir.as_os_str(), "/home/fred");
+
+        let back = User::try_from(converted).unwrap();
+        assert_eq!(back.name(), "fred");
+        assert_eq!(back.shell().as_os_str(), "/bin/sh");
+        assert_eq!(back.gecos(), "Fred");
+
+        let name = OsString::from_vec(b"fr\xffd".to_vec());
+        let user = User::new(1000, &name, 100);
+        let error = unistd::User::try_from(&user).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn groups() {
+        let group = Group::new(100, "staff").with_members(["fred", "wilma"]);
+        let converted = unistd::Group::try_from(&group).unwrap();
+        assert_eq!(converted.mem, ["fred", "wilma"]);
+
+        let back = Group::try_from(converted).unwrap();
+        assert_eq!(back.gid(), 100);
+        assert_eq!(back.members(), ["fred", "wilma"]);
+
+        assert_eq!(ids::Gid::from(Gid::from_raw(100)), ids::Gid::from_raw(100));
+        assert_eq!(Uid::from(ids::Uid::ROOT), Uid::from_raw(0));
+
+        let mut invalid = unistd::Group::try_from(&group).unwrap();
+        invalid.name = "st:aff".into();
+        assert!(Group::try_from(invalid).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Reading the Name Service Switch configuration.
+//!
+//! The C library does not look users and groups up in one fixed place: the
+//! `/etc/nsswitch.conf` file lists, for every database, the *services* that
+//! are asked in turn, such as `files` for `/etc/passwd`, `sss` for SSSD, or
+//! `systemd` for systemd’s user database. This module reads that file, so
+//! that diagnostic tools can explain where a lookup result came from.
+//!
+//! ```no_run
+//! use uzers::nsswitch::sources_for;
+//!
+//! for source in sources_for("passwd").expect("Cannot read nsswitch.conf") {
+//!     println!("{}", source.service());
+//! }
+//! ```
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The path to the Name Service Switch configuration file.
+pub const NSSWITCH_PATH: &str = "/etc/nsswitch.conf";
+
+/// A service that is asked for entries of a database, along with the
+/// actions that decide whether the next service is asked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Source {
+    service: String,
+    actions: Vec<(String, String)>,
+}
+
+impl Source {
+    /// Returns the name of the service, such as `files` or `sss`.
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Returns the actions that follow the service in square brackets, as
+    /// pairs of a status and an action. For `[NOTFOUND=return]`, this is
+    /// `("NOTFOUND", "return")`. A negated status keeps its `!`.
+    pub fn actions(&self) -> &[(String, String)] {
+        &self.actions
+    }
+}
+
+/// Returns the services configured for the given database, such as
+/// `passwd` or `group`, in the order they are asked.
+///
+/// This reads the file at [`NSSWITCH_PATH`]. An empty list is returned if
+/// the database is not configured, in which case the C library falls back to
+/// its built-in default.
+///
+/// # Errors
+///
+/// This function will return `Err` if the configuration file cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::nsswitch::sources_for;
+///
+/// let sources = sources_for("group").expect("Cannot read nsswitch.conf");
+/// if !sources.iter().any(|s| s.service() == "files") {
+///     eprintln!("Warning: /etc/group is not consulted");
+/// }
+/// ```
+pub fn sources_for(database: &str) -> io::Result<Vec<Source>> {
+    sources_for_in(NSSWITCH_PATH, database)
+}
+
+/// Returns the services configured for the given database in the
+/// configuration file at the given path.
+///
+/// # Errors
+///
+/// This function will return `Err` if the configuration file cannot be read.
+pub fn sources_for_in<P: AsRef<Path>>(path: P, database: &str) -> io::Result<Vec<Source>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(parse_sources(&contents, database))
+}
+
+/// Returns the services configured for the given database in the given
+/// configuration text.
+///
+/// If the database appears on more than one line, the first one is used,
+/// like the C library does.
+///
+/// # Examples
+///
+/// ```
+/// use uzers::nsswitch::parse_sources;
+///
+/// let sources = parse_sources("passwd: files systemd\n", "passwd");
+/// let services = sources.iter().map(|s| s.service()).collect::<Vec<_>>();
+/// assert_eq!(services, vec!["files", "systemd"]);
+/// ```
+pub fn parse_sources(contents: &str, database: &str) -> Vec<Source> {
+    for line in contents.lines() {
+        let line = match line.find('#') {
+            Some(index) => &line[..index],
+            None => line,
+        };
+
+        let (name, services) = match line.find(':') {
+            Some(index) => (line[..index].trim(), &line[index + 1..]),
+            None => continue,
+        };
+
+        if name == database {
+            return parse_services(services);
+        }
+    }
+
+    Vec::new()
+}
+
+/// Parses the list of services and actions of one database.
+fn parse_services(services: &str) -> Vec<Source> {
+    let mut sources: Vec<Source> = Vec::new();
+    let mut rest = services.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(criteria) = rest.strip_prefix('[') {
+            let end = criteria.find(']').unwrap_or(criteria.len());
+            if let Some(source) = sources.last_mut() {
+                source
+                    .actions
+                    .extend(criteria[..end].split_whitespace().filter_map(|item| {
+                        let mut parts = item.splitn(2, '=');
+                        let status = parts.next()?.to_string();
+                        let action = parts.next()?.to_string();
+                        Some((status, action))
+                    }));
+            }
+            rest = criteria.get(end + 1..).unwrap_or("");
+        } else {
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '[')
+                .unwrap_or(rest.len());
+            sources.push(Source {
+                service: rest[..end].to_string(),
+                actions: Vec::new(),
+            });
+            rest = &rest[end..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    sources
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = "\
+# /etc/nsswitch.conf
+passwd:         files systemd   # local first
+group:          sss [NOTFOUND=return !UNAVAIL=continue] files
+shadow:files
+passwd:         ldap
+";
+
+    fn services(sources: &[Source]) -> Vec<&str> {
+        sources.iter().map(Source::service).collect()
+    }
+
+    #[test]
+    fn simple_list() {
+        let sources = parse_sources(CONFIG, "passwd");
+        assert_eq!(services(&sources), vec!["files", "systemd"]);
+        assert!(sources.iter().all(|s| s.actions().is_empty()));
+    }
+
+    #[test]
+    fn actions() {
+        let sources = parse_sources(CONFIG, "group");
+        assert_eq!(services(&sources), vec!["sss", "files"]);
+        assert_eq!(
+            sources[0].actions(),
+            &[
+                ("NOTFOUND".to_string(), "return".to_string()),
+                ("!UNAVAIL".to_string(), "continue".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_whitespace() {
+        assert_eq!(services(&parse_sources(CONFIG, "shadow")), vec!["files"]);
+    }
+
+    #[test]
+    fn missing_database() {
+        assert!(parse_sources(CONFIG, "hosts").is_empty());
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(sources_for_in("/nonexistent/nsswitch.conf", "passwd").is_err());
+    }
+}
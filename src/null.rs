@@ -0,0 +1,209 @@
+//! A producer of users and groups that knows of none.
+
+use libc::{gid_t, uid_t};
+use std::ffi::OsStr;
+use std::io;
+use std::iter;
+use std::sync::Arc;
+
+use base::{Group, User};
+use traits::{
+    AllGroups, AllGroupsOwned, AllUsers, AllUsersOwned, Groups, SupplementaryGroups, TryGroups,
+    TryUsers, Users,
+};
+
+/// The ID of the `nobody` user and group on most systems.
+const NOBODY: u32 = 65534;
+
+/// A producer of users and groups with nothing in it.
+///
+/// Every lookup finds nothing, and there are no users or groups to iterate
+/// over. The current and effective IDs are those of `nobody` unless others
+/// are given. This is a default for places that take a producer but do not
+/// always have one, and a way to test how code copes with a system where no
+/// users or groups can be found, such as a minimal container.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::{NullUsers, Users};
+///
+/// let users = NullUsers::with_current_uid(1000);
+/// assert_eq!(users.get_current_uid(), 1000);
+/// assert!(users.get_current_username().is_none());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct NullUsers {
+    uid: uid_t,
+    gid: gid_t,
+}
+
+impl NullUsers {
+    /// Creates a producer whose current and effective IDs are those of
+    /// `nobody`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a producer whose current and effective user ID is the given
+    /// one. The group IDs are set to the same number, as with
+    /// [`MockUsers::with_current_uid`](mock/struct.MockUsers.html#method.with_current_uid).
+    pub fn with_current_uid(uid: uid_t) -> Self {
+        Self { uid, gid: uid }
+    }
+
+    /// Sets the current and effective group ID.
+    pub fn with_current_gid(mut self, gid: gid_t) -> Self {
+        self.gid = gid;
+        self
+    }
+}
+
+impl Default for NullUsers {
+    fn default() -> Self {
+        Self::with_current_uid(NOBODY)
+    }
+}
+
+impl Users for NullUsers {
+    fn get_user_by_uid(&self, _uid: uid_t) -> Option<Arc<User>> {
+        None
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, _username: &S) -> Option<Arc<User>> {
+        None
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.uid
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        None
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.uid
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        None
+    }
+}
+
+impl Groups for NullUsers {
+    fn get_group_by_gid(&self, _gid: gid_t) -> Option<Arc<Group>> {
+        None
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, _group_name: &S) -> Option<Arc<Group>> {
+        None
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.gid
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        None
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.gid
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        None
+    }
+}
+
+impl TryUsers for NullUsers {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_uid(uid))
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_name(username))
+    }
+}
+
+impl TryGroups for NullUsers {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_gid(gid))
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_name(group_name))
+    }
+}
+
+impl AllUsers for NullUsers {
+    type UserIter<'a> = iter::Empty<&'a User>;
+
+    fn get_all_users(&self) -> Self::UserIter<'_> {
+        iter::empty()
+    }
+}
+
+impl AllGroups for NullUsers {
+    type GroupIter<'a> = iter::Empty<&'a Group>;
+
+    fn get_all_groups(&self) -> Self::GroupIter<'_> {
+        iter::empty()
+    }
+}
+
+impl AllUsersOwned for NullUsers {
+    type OwnedUserIter = iter::Empty<Arc<User>>;
+
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter {
+        iter::empty()
+    }
+}
+
+impl AllGroupsOwned for NullUsers {
+    type OwnedGroupIter = iter::Empty<Arc<Group>>;
+
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter {
+        iter::empty()
+    }
+}
+
+impl SupplementaryGroups for NullUsers {
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        _username: &S,
+        _gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ids::Uid;
+
+    #[test]
+    fn knows_nothing() {
+        let users = NullUsers::new();
+        assert_eq!(users.get_current_uid(), NOBODY);
+        assert_eq!(users.get_effective_gid(), NOBODY);
+        assert!(users.get_user_by_uid(0).is_none());
+        assert!(users.get_group_by_name("root").is_none());
+        assert!(users.groups_of(Uid::from_raw(NOBODY)).is_none());
+        assert_eq!(users.get_all_users().count(), 0);
+        assert_eq!(users.get_all_groups().count(), 0);
+
+        let users = NullUsers::with_current_uid(1000).with_current_gid(100);
+        assert_eq!(users.get_effective_uid(), 1000);
+        assert_eq!(users.get_current_gid(), 100);
+    }
+}
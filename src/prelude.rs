@@ -0,0 +1,23 @@
+//! The traits and types that most users of this crate need.
+//!
+//! Looking up users and groups through a cache or a mock table takes the
+//! traits that provide the lookups, and reading a user’s home directory or a
+//! group’s members takes the extension traits too. Importing this module
+//! brings all of them into scope at once:
+//!
+//! ```
+//! use uzers::prelude::*;
+//!
+//! fn home_of<U: Users>(users: &U, uid: uid_t) -> Option<std::path::PathBuf> {
+//!     users.get_user_by_uid(uid).map(|u| u.home_dir().to_path_buf())
+//! }
+//! ```
+
+pub use base::os::unix::{GroupExt, UserExt};
+pub use base::{Group, User};
+pub use fs::MetadataOwnerExt;
+pub use libc::{gid_t, uid_t};
+pub use traits::{AllGroups, AllUsers, Groups, SupplementaryGroups, UserDatabase, Users};
+
+#[cfg(feature = "cache")]
+pub use cache::{UsersCache, UsersSnapshot};
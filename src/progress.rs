@@ -0,0 +1,136 @@
+//! Progress reporting for long-running enumerations.
+
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+/// How far an enumeration of the users or groups database has got.
+///
+/// A value of this type is passed to progress callbacks before every attempt
+/// to read an entry, so a callback can render a progress indicator and decide
+/// whether to carry on. That includes the last attempt, which finds that
+/// there are no entries left, so an enumeration that runs to the end calls
+/// its callback once more than the number of entries it reads.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Progress {
+    count: usize,
+    elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns the number of entries that have been read so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Returns the time elapsed since the enumeration started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Keeps track of an enumeration and reports its progress to a callback.
+pub(crate) struct Tracker<F> {
+    callback: F,
+    start: Instant,
+}
+
+impl<F> Tracker<F>
+where
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    /// Starts tracking a new enumeration.
+    pub(crate) fn new(callback: F) -> Self {
+        Self {
+            callback,
+            start: Instant::now(),
+        }
+    }
+
+    /// Reports that the given number of entries have been read, and returns
+    /// `true` if the callback asked for the enumeration to stop.
+    pub(crate) fn should_stop(&mut self, count: usize) -> bool {
+        let progress = Progress {
+            count,
+            elapsed: self.start.elapsed(),
+        };
+
+        (self.callback)(progress).is_break()
+    }
+}
+
+/// An iterator that reports its progress before yielding every item, and
+/// stops early if asked to.
+pub(crate) struct WithProgress<I, F> {
+    inner: I,
+    tracker: Tracker<F>,
+    count: usize,
+    stopped: bool,
+}
+
+impl<I, F> WithProgress<I, F>
+where
+    I: Iterator,
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    /// Wraps an iterator so it reports its progress to the callback.
+    pub(crate) fn new(inner: I, callback: F) -> Self {
+        Self {
+            inner,
+            tracker: Tracker::new(callback),
+            count: 0,
+            stopped: false,
+        }
+    }
+}
+
+impl<I, F> Iterator for WithProgress<I, F>
+where
+    I: Iterator,
+    F: FnMut(Progress) -> ControlFlow<()>,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.stopped || self.tracker.should_stop(self.count) {
+            self.stopped = true;
+            return None;
+        }
+
+        let item = self.inner.next()?;
+        self.count += 1;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts_entries() {
+        let mut counts = Vec::new();
+        let items = WithProgress::new(0..3, |p: Progress| {
+            counts.push(p.count());
+            ControlFlow::Continue(())
+        })
+        .collect::<Vec<_>>();
+
+        // The last call comes before the read that finds the end.
+        assert_eq!(items, vec![0, 1, 2]);
+        assert_eq!(counts, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn stops_when_asked() {
+        let items = WithProgress::new(0.., |p: Progress| {
+            if p.count() == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(items, vec![0, 1]);
+    }
+}
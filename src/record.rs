@@ -0,0 +1,830 @@
+//! Recording and replaying lookups.
+//!
+//! Bugs in programs that resolve users and groups often depend on the
+//! contents of one particular system’s databases, which the developer trying
+//! to fix them cannot see. [`RecordingUsers`] wraps a provider and writes
+//! every lookup it answers, along with the result, to a file:
+//!
+//! ```no_run
+//! use uzers::{Users, UsersCache};
+//! use uzers::record::RecordingUsers;
+//!
+//! let users = RecordingUsers::create(UsersCache::new(), "lookups.trace").unwrap();
+//! users.get_user_by_uid(1000);
+//! users.finish().unwrap();
+//! ```
+//!
+//! That file can then be attached to a bug report, and [`ReplayUsers`]
+//! answers the same lookups with the same results, without touching the
+//! system:
+//!
+//! ```no_run
+//! use uzers::Users;
+//! use uzers::record::ReplayUsers;
+//!
+//! let users = ReplayUsers::open("lookups.trace").unwrap();
+//! let user = users.get_user_by_uid(1000);
+//! ```
+//!
+//!
+//! ## File format
+//!
+//! Every lookup is written as one line of three fields separated by spaces:
+//! the kind of lookup, its key, and its result. Users are written as lines of
+//! a `passwd` file and groups as lines of a `group` file, as read by the
+//! [`files`](../files/index.html) module.
+//!
+//! ```text
+//! uid 1000 fred:x:1000:100:Fred%20Santa:/home/fred:/bin/sh
+//! name nobody-here -
+//! current-uid - 1000
+//! ```
+//!
+//! A field of `-` stands for nothing. Bytes that are not printable ASCII, as
+//! well as spaces, `%`, `:` and `,`, are written as `%` followed by two hex
+//! digits, and an empty string is written as a lone `%`. Within a user or
+//! group, each of its fields is escaped on its own, so a colon in a GECOS
+//! comment or a comma in a member’s name is read back as it was.
+//!
+//!
+//! ## Checking lookups in tests
+//!
+//! A recording kept in memory can be read back while the provider is still
+//! in use, so tests can check which lookups the code under test made:
+//!
+//! ```
+//! use uzers::Users;
+//! use uzers::mock::MockUsers;
+//! use uzers::record::RecordingUsers;
+//!
+//! let users = RecordingUsers::in_memory(MockUsers::with_current_uid(1000));
+//! users.get_user_by_uid(0);
+//! assert_eq!(users.count("uid", "0"), 1);
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::str;
+use std::sync::Arc;
+
+#[cfg(feature = "logging")]
+extern crate log;
+#[cfg(feature = "logging")]
+use self::log::trace;
+
+use libc::{gid_t, uid_t};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+use ids::{NO_GID, NO_UID};
+use traits::{Groups, TryGroups, TryUsers, Users};
+
+/// Escapes a field so that it holds no spaces or newlines.
+pub(crate) fn escape(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::from("%");
+    }
+    if bytes == b"-" {
+        return String::from("%2D");
+    }
+
+    let mut field = String::with_capacity(bytes.len());
+    escape_into(&mut field, bytes);
+    field
+}
+
+/// Escapes the bytes onto the end of the string, along with the colons and
+/// commas that separate the fields of a user or group.
+fn escape_into(field: &mut String, bytes: &[u8]) {
+    for &byte in bytes {
+        if byte.is_ascii_graphic() && !b"%:,".contains(&byte) {
+            field.push(byte as char);
+        } else {
+            let _ = write!(field, "%{:02X}", byte);
+        }
+    }
+}
+
+/// Returns the value field of a lookup that found the given user, or `-`.
+pub(crate) fn user_field(user: Option<&User>) -> String {
+    let user = match user {
+        Some(user) => user,
+        None => return "-".into(),
+    };
+
+    let mut field = String::new();
+    escape_into(&mut field, user.name().as_bytes());
+    field.push(':');
+    escape_into(&mut field, user.password().as_bytes());
+    let _ = write!(field, ":{}:{}:", user.uid(), user.primary_group_id());
+    escape_into(&mut field, user.gecos().as_bytes());
+    field.push(':');
+    escape_into(&mut field, user.home_dir().as_os_str().as_bytes());
+    field.push(':');
+    escape_into(&mut field, user.shell().as_os_str().as_bytes());
+    field
+}
+
+/// Returns the value field of a lookup that found the given group, or `-`.
+pub(crate) fn group_field(group: Option<&Group>) -> String {
+    let group = match group {
+        Some(group) => group,
+        None => return "-".into(),
+    };
+
+    let mut field = String::new();
+    escape_into(&mut field, group.name().as_bytes());
+    let _ = write!(field, ":x:{}:", group.gid());
+    for (index, member) in group.members().iter().enumerate() {
+        if index > 0 {
+            field.push(',');
+        }
+        escape_into(&mut field, member.as_bytes());
+    }
+    field
+}
+
+/// Reads a user written by [`user_field`], returning `None` if the field
+/// is not valid.
+pub(crate) fn parse_user_field(field: &str) -> Option<User> {
+    let fields = field.split(':').map(unescape).collect::<Option<Vec<_>>>()?;
+    let [name, password, uid, gid, gecos, home_dir, shell] =
+        <[Vec<u8>; 7]>::try_from(fields).ok()?;
+
+    let uid = parse_id(&uid).filter(|&uid| uid != NO_UID)?;
+    let gid = parse_id(&gid)?;
+    if name.is_empty() {
+        return None;
+    }
+
+    let user = User::new(uid, OsStr::from_bytes(&name), gid)
+        .with_password(OsStr::from_bytes(&password))
+        .with_gecos(OsStr::from_bytes(&gecos))
+        .with_home_dir(OsStr::from_bytes(&home_dir))
+        .with_shell(OsStr::from_bytes(&shell));
+    Some(user)
+}
+
+/// Reads a group written by [`group_field`], returning `None` if the field
+/// is not valid.
+pub(crate) fn parse_group_field(field: &str) -> Option<Group> {
+    let mut fields = field.split(':');
+    let (name, _password, gid, members) = (
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+        fields.next()?,
+    );
+    if fields.next().is_some() {
+        return None;
+    }
+
+    let name = unescape(name).filter(|name| !name.is_empty())?;
+    let gid = parse_id(&unescape(gid)?).filter(|&gid| gid != NO_GID)?;
+    let mut group = Group::new(gid, OsStr::from_bytes(&name));
+    for member in members.split(',').filter(|m| !m.is_empty()) {
+        group = group.add_member(OsStr::from_bytes(&unescape(member)?));
+    }
+    Some(group)
+}
+
+/// Reads a decimal ID.
+fn parse_id(field: &[u8]) -> Option<u32> {
+    str::from_utf8(field).ok()?.parse().ok()
+}
+
+/// Reverses [`escape`], returning `None` if the field is not valid.
+pub(crate) fn unescape(field: &str) -> Option<Vec<u8>> {
+    if field == "%" {
+        return Some(Vec::new());
+    }
+
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(bytes)
+}
+
+/// One lookup written by a [`RecordingUsers`], as read back by
+/// [`lookups`](RecordingUsers::lookups).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedLookup {
+    kind: String,
+    key: Option<OsString>,
+    value: Option<OsString>,
+}
+
+impl RecordedLookup {
+    /// Reads one line of a trace, returning `None` if it is not valid.
+    pub(crate) fn parse(line: &str) -> Option<Self> {
+        let field = |field: &str| match field {
+            "-" => Some(None),
+            field => unescape(field).map(|bytes| Some(OsString::from_vec(bytes))),
+        };
+
+        let mut fields = line.split(' ');
+        let (kind, key, value) = (fields.next()?, fields.next()?, fields.next()?);
+        if fields.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            kind: kind.to_string(),
+            key: field(key)?,
+            value: field(value)?,
+        })
+    }
+
+    /// Returns the kind of lookup, such as `uid` or `current-username`, as
+    /// written in the trace.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    /// Returns what was looked up, such as an ID or a name, or `None` for
+    /// the lookups that take nothing.
+    pub fn key(&self) -> Option<&OsStr> {
+        self.key.as_deref()
+    }
+
+    /// Returns the result of the lookup, or `None` if it found nothing.
+    ///
+    /// Users are returned as lines of a `passwd` file, groups as lines of a
+    /// `group` file, and IDs as numbers.
+    pub fn value(&self) -> Option<&OsStr> {
+        self.value.as_deref()
+    }
+}
+
+/// A provider that writes every lookup it answers, along with its result,
+/// to a writer.
+///
+/// Lookups are answered by the wrapped provider. Errors writing the trace do
+/// not affect the lookups; the first one is returned by
+/// [`finish`](RecordingUsers::finish).
+pub struct RecordingUsers<P, W: Write = BufWriter<File>> {
+    inner: P,
+    writer: RefCell<W>,
+    error: RefCell<Option<io::Error>>,
+}
+
+impl<P> RecordingUsers<P> {
+    /// Creates a provider that records the lookups answered by the given
+    /// provider to a new file at the given path, replacing any file there.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the file cannot be created.
+    pub fn create<Q: AsRef<Path>>(inner: P, path: Q) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::new(inner, BufWriter::new(file)))
+    }
+}
+
+impl<P> RecordingUsers<P, Vec<u8>> {
+    /// Creates a provider that records the lookups answered by the given
+    /// provider in memory, where they can be read back with
+    /// [`lookups`](RecordingUsers::lookups).
+    pub fn in_memory(inner: P) -> Self {
+        Self::new(inner, Vec::new())
+    }
+
+    /// Returns every lookup recorded so far, in the order they were made.
+    pub fn lookups(&self) -> Vec<RecordedLookup> {
+        let writer = self.writer.borrow();
+        String::from_utf8_lossy(&writer)
+            .lines()
+            .filter_map(RecordedLookup::parse)
+            .collect()
+    }
+
+    /// Returns how many lookups of the given kind were made for the given
+    /// key, such as `count("uid", "0")` for the lookups of the user with ID
+    /// 0. Use `-` as the key of the lookups that take nothing.
+    pub fn count<S: AsRef<OsStr> + ?Sized>(&self, kind: &str, key: &S) -> usize {
+        let key = Some(key.as_ref()).filter(|k| *k != "-");
+        self.lookups()
+            .iter()
+            .filter(|l| l.kind() == kind && l.key() == key)
+            .count()
+    }
+
+    /// Forgets every lookup recorded so far.
+    pub fn clear_lookups(&self) {
+        self.writer.borrow_mut().clear();
+    }
+}
+
+impl<P, W: Write> RecordingUsers<P, W> {
+    /// Creates a provider that records the lookups answered by the given
+    /// provider to the given writer.
+    pub fn new(inner: P, writer: W) -> Self {
+        Self {
+            inner,
+            writer: RefCell::new(writer),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Returns the provider that answers the lookups.
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Flushes the trace, and returns the writer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return the first error that happened while writing
+    /// the trace, or while flushing it.
+    pub fn finish(self) -> io::Result<W> {
+        if let Some(error) = self.error.into_inner() {
+            return Err(error);
+        }
+
+        let mut writer = self.writer.into_inner();
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    /// Writes one line of the trace, keeping the first error.
+    fn record(&self, kind: &str, key: &str, value: &str) {
+        if self.error.borrow().is_some() {
+            return;
+        }
+
+        let line = format!("{} {} {}\n", kind, key, value);
+        if let Err(e) = self.writer.borrow_mut().write_all(line.as_bytes()) {
+            #[cfg(feature = "logging")]
+            trace!("Cannot write lookup trace: {}", e);
+
+            *self.error.borrow_mut() = Some(e);
+        }
+    }
+
+    fn record_user(&self, kind: &str, key: &str, user: Option<&Arc<User>>) {
+        self.record(kind, key, &user_field(user.map(|u| &**u)));
+    }
+
+    fn record_group(&self, kind: &str, key: &str, group: Option<&Arc<Group>>) {
+        self.record(kind, key, &group_field(group.map(|g| &**g)));
+    }
+
+    fn record_name(&self, kind: &str, name: Option<&Arc<OsStr>>) {
+        let value = name.map_or_else(|| "-".into(), |n| escape(n.as_bytes()));
+        self.record(kind, "-", &value);
+    }
+}
+
+impl<P: Users, W: Write> Users for RecordingUsers<P, W> {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        let user = self.inner.get_user_by_uid(uid);
+        self.record_user("uid", &uid.to_string(), user.as_ref());
+        user
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        let user = self.inner.get_user_by_name(username);
+        let key = escape(username.as_ref().as_bytes());
+        self.record_user("name", &key, user.as_ref());
+        user
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        let uid = self.inner.get_current_uid();
+        self.record("current-uid", "-", &uid.to_string());
+        uid
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let name = self.inner.get_current_username();
+        self.record_name("current-username", name.as_ref());
+        name
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        let uid = self.inner.get_effective_uid();
+        self.record("effective-uid", "-", &uid.to_string());
+        uid
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let name = self.inner.get_effective_username();
+        self.record_name("effective-username", name.as_ref());
+        name
+    }
+}
+
+impl<P: Groups, W: Write> Groups for RecordingUsers<P, W> {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        let group = self.inner.get_group_by_gid(gid);
+        self.record_group("gid", &gid.to_string(), group.as_ref());
+        group
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        let group = self.inner.get_group_by_name(group_name);
+        let key = escape(group_name.as_ref().as_bytes());
+        self.record_group("group", &key, group.as_ref());
+        group
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        let gid = self.inner.get_current_gid();
+        self.record("current-gid", "-", &gid.to_string());
+        gid
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let name = self.inner.get_current_groupname();
+        self.record_name("current-groupname", name.as_ref());
+        name
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        let gid = self.inner.get_effective_gid();
+        self.record("effective-gid", "-", &gid.to_string());
+        gid
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let name = self.inner.get_effective_groupname();
+        self.record_name("effective-groupname", name.as_ref());
+        name
+    }
+}
+
+/// Failed lookups are passed on without being recorded, as a trace can only
+/// hold what a lookup found.
+impl<P: TryUsers, W: Write> TryUsers for RecordingUsers<P, W> {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        let user = self.inner.try_get_user_by_uid(uid)?;
+        self.record_user("uid", &uid.to_string(), user.as_ref());
+        Ok(user)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        let user = self.inner.try_get_user_by_name(username)?;
+        let key = escape(username.as_ref().as_bytes());
+        self.record_user("name", &key, user.as_ref());
+        Ok(user)
+    }
+}
+
+/// Failed lookups are passed on without being recorded.
+impl<P: TryGroups, W: Write> TryGroups for RecordingUsers<P, W> {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        let group = self.inner.try_get_group_by_gid(gid)?;
+        self.record_group("gid", &gid.to_string(), group.as_ref());
+        Ok(group)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        let group = self.inner.try_get_group_by_name(group_name)?;
+        let key = escape(group_name.as_ref().as_bytes());
+        self.record_group("group", &key, group.as_ref());
+        Ok(group)
+    }
+}
+
+/// A provider that answers lookups from a trace written by
+/// [`RecordingUsers`].
+///
+/// Lookups that were not recorded find nothing. If a lookup was recorded
+/// more than once, the last result is used. A current or effective user or
+/// group ID that the trace does not record is returned as the sentinel
+/// [`NO_UID`](../ids/constant.NO_UID.html) or
+/// [`NO_GID`](../ids/constant.NO_GID.html), which no user or group has, and
+/// a name it does not record as `None`.
+#[derive(Clone, Debug, Default)]
+pub struct ReplayUsers {
+    pub(crate) users_by_uid: HashMap<uid_t, Option<Arc<User>>>,
+    pub(crate) users_by_name: HashMap<OsString, Option<Arc<User>>>,
+    pub(crate) groups_by_gid: HashMap<gid_t, Option<Arc<Group>>>,
+    pub(crate) groups_by_name: HashMap<OsString, Option<Arc<Group>>>,
+    pub(crate) ids: HashMap<String, u32>,
+    names: HashMap<String, Option<Arc<OsStr>>>,
+}
+
+impl ReplayUsers {
+    /// Reads the trace in the file at the given path.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the file cannot be read, or an
+    /// `InvalidData` error if it is not a valid trace.
+    pub fn open<Q: AsRef<Path>>(path: Q) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads a trace from text.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an `InvalidData` error naming the first line
+    /// that is not valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::Users;
+    /// use uzers::record::ReplayUsers;
+    ///
+    /// let users = ReplayUsers::parse("uid 1000 fred:x:1000:100::/home/fred:/bin/sh\n").unwrap();
+    /// assert_eq!(users.get_user_by_uid(1000).unwrap().name(), "fred");
+    /// ```
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut replay = Self::default();
+
+        for (number, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+
+            if replay.parse_line(line).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid trace line {}: {:?}", number + 1, line),
+                ));
+            }
+        }
+
+        Ok(replay)
+    }
+
+    /// Reads one line of a trace, returning `None` if it is not valid.
+    fn parse_line(&mut self, line: &str) -> Option<()> {
+        let mut fields = line.split(' ');
+        let (kind, key, value) = (fields.next()?, fields.next()?, fields.next()?);
+        if fields.next().is_some() {
+            return None;
+        }
+
+        let raw = Some(value).filter(|v| *v != "-");
+        let value = match raw {
+            None => None,
+            Some(value) => Some(unescape(value)?),
+        };
+
+        match kind {
+            "uid" | "name" => {
+                let user = match raw {
+                    Some(field) => Some(Arc::new(parse_user_field(field)?)),
+                    None => None,
+                };
+                if kind == "uid" {
+                    self.users_by_uid.insert(key.parse().ok()?, user);
+                } else {
+                    let name = OsString::from_vec(unescape(key)?);
+                    self.users_by_name.insert(name, user);
+                }
+            }
+            "gid" | "group" => {
+                let group = match raw {
+                    Some(field) => Some(Arc::new(parse_group_field(field)?)),
+                    None => None,
+                };
+                if kind == "gid" {
+                    self.groups_by_gid.insert(key.parse().ok()?, group);
+                } else {
+                    let name = OsString::from_vec(unescape(key)?);
+                    self.groups_by_name.insert(name, group);
+                }
+            }
+            "current-uid" | "effective-uid" | "current-gid" | "effective-gid" => {
+                let id = parse_id(&value?)?;
+                self.ids.insert(kind.to_string(), id);
+            }
+            "current-username"
+            | "effective-username"
+            | "current-groupname"
+            | "effective-groupname" => {
+                let name = value.map(|v| Arc::from(OsStr::from_bytes(&v)));
+                self.names.insert(kind.to_string(), name);
+            }
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn id(&self, kind: &str, sentinel: u32) -> u32 {
+        self.ids.get(kind).copied().unwrap_or(sentinel)
+    }
+
+    fn name(&self, kind: &str) -> Option<Arc<OsStr>> {
+        self.names.get(kind).cloned().flatten()
+    }
+}
+
+impl Users for ReplayUsers {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.users_by_uid.get(&uid).cloned().flatten()
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.users_by_name.get(username.as_ref()).cloned().flatten()
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.id("current-uid", NO_UID)
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        self.name("current-username")
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.id("effective-uid", NO_UID)
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        self.name("effective-username")
+    }
+}
+
+impl Groups for ReplayUsers {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.groups_by_gid.get(&gid).cloned().flatten()
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.groups_by_name
+            .get(group_name.as_ref())
+            .cloned()
+            .flatten()
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.id("current-gid", NO_GID)
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        self.name("current-groupname")
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.id("effective-gid", NO_GID)
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        self.name("effective-groupname")
+    }
+}
+
+impl TryUsers for ReplayUsers {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_uid(uid))
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        Ok(self.get_user_by_name(username))
+    }
+}
+
+impl TryGroups for ReplayUsers {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_gid(gid))
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        Ok(self.get_group_by_name(group_name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        for field in [&b""[..], b"-", b"fred", b"Fred Santa", b"100%", b"\xFF\n"] {
+            assert_eq!(unescape(&escape(field)).unwrap(), field);
+        }
+        assert_eq!(escape(b"Fred Santa"), "Fred%20Santa");
+        assert!(unescape("%G0").is_none());
+        assert!(unescape("%4").is_none());
+    }
+
+    #[test]
+    fn invalid_traces() {
+        assert!(ReplayUsers::parse("uid 1000").is_err());
+        assert!(ReplayUsers::parse("uid fred -").is_err());
+        assert!(ReplayUsers::parse("shell - /bin/sh").is_err());
+        assert!(ReplayUsers::parse("uid 1000 not-a-passwd-line").is_err());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn in_memory() {
+        use mock::MockUsers;
+
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(0, "root", 0));
+
+        let recording = RecordingUsers::in_memory(mock);
+        recording.get_user_by_uid(0);
+        recording.get_user_by_name("Fred Santa");
+        recording.get_current_uid();
+        recording.get_user_by_uid(0);
+
+        let lookups = recording.lookups();
+        assert_eq!(lookups.len(), 4);
+        assert_eq!(lookups[1].kind(), "name");
+        assert_eq!(lookups[1].key(), Some(OsStr::new("Fred Santa")));
+        assert_eq!(lookups[1].value(), None);
+        assert_eq!(lookups[2].value(), Some(OsStr::new("1000")));
+        assert_eq!(recording.count("uid", "0"), 2);
+        assert_eq!(recording.count("current-uid", "-"), 1);
+
+        recording.clear_lookups();
+        assert!(recording.lookups().is_empty());
+    }
+
+    #[test]
+    fn unrecorded_current_ids() {
+        let replay = ReplayUsers::default();
+        assert_eq!(replay.get_current_uid(), NO_UID);
+        assert_eq!(replay.get_effective_gid(), NO_GID);
+        assert!(replay.get_current_username().is_none());
+    }
+
+    #[test]
+    fn separators_in_fields() {
+        let user = User::new(1000, "fred", 100).with_gecos("Santa, Fred: boss");
+        let field = user_field(Some(&user));
+        assert_eq!(
+            field,
+            "fred:*:1000:100:Santa%2C%20Fred%3A%20boss:/var/empty:/bin/false"
+        );
+        assert_eq!(
+            parse_user_field(&field).unwrap().gecos(),
+            "Santa, Fred: boss"
+        );
+
+        let group = Group::new(100, "staff")
+            .add_member("odd,one")
+            .add_member("wilma");
+        let group = parse_group_field(&group_field(Some(&group))).unwrap();
+        assert_eq!(group.members(), ["odd,one", "wilma"]);
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn round_trip() {
+        use base::os::unix::UserExt;
+        use mock::MockUsers;
+
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(1000, "fred", 100).with_gecos("Fred Santa"));
+        mock.add_group(Group::new(100, "bosses"));
+
+        let recording = RecordingUsers::new(mock, Vec::new());
+        recording.get_user_by_uid(1000);
+        recording.get_user_by_name("nobody here");
+        recording.get_group_by_name("bosses");
+        recording.get_current_uid();
+        recording.get_current_username();
+        let trace = String::from_utf8(recording.finish().unwrap()).unwrap();
+
+        let replay = ReplayUsers::parse(&trace).unwrap();
+        let fred = replay.get_user_by_uid(1000).unwrap();
+        assert_eq!(fred.name(), "fred");
+        assert_eq!(fred.gecos(), "Fred Santa");
+        assert!(replay.get_user_by_name("nobody here").is_none());
+        assert_eq!(replay.get_group_by_name("bosses").unwrap().gid(), 100);
+        assert_eq!(replay.get_current_uid(), 1000);
+        assert_eq!(replay.get_current_username().unwrap().as_ref(), "fred");
+
+        // Never looked up, so never recorded.
+        assert!(replay.get_group_by_gid(100).is_none());
+    }
+}
@@ -0,0 +1,860 @@
+//! Information about the users currently logged in to the system.
+//!
+//! The C library keeps a record of every login session in the `utmpx`
+//! database. This module reads that database and joins each session with the
+//! [`User`](../struct.User.html) record for its username, which gives you
+//! everything that `who` or `w` print about a session.
+//!
+//! On Linux, the historical `wtmp` database can be read as well, which is
+//! what `last` uses to print the login history of each user, and the
+//! `lastlog` database tells when each user last logged in.
+//!
+//! ```no_run
+//! use uzers::sessions::logged_in_users;
+//!
+//! for session in unsafe { logged_in_users() } {
+//!     println!("{:?} on {:?}", session.username(), session.tty());
+//! }
+//! ```
+
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+use std::ffi::{OsStr, OsString};
+#[cfg(target_os = "linux")]
+use std::fs::File;
+#[cfg(target_os = "linux")]
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+#[cfg(target_os = "linux")]
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(all(feature = "lastlog2", target_os = "linux"))]
+extern crate rusqlite;
+#[cfg(all(feature = "lastlog2", target_os = "linux"))]
+use self::rusqlite::{Connection, OpenFlags, OptionalExtension};
+
+#[cfg(feature = "logging")]
+extern crate log;
+#[cfg(feature = "logging")]
+use self::log::trace;
+
+#[cfg(target_os = "linux")]
+use libc::uid_t;
+use libc::{c_char, pid_t, utmpx};
+
+use base::{get_user_by_name, User};
+
+/// A login session read from the `utmpx` database.
+#[derive(Clone, Debug)]
+pub struct Session {
+    username: OsString,
+    user: Option<User>,
+    tty: OsString,
+    host: OsString,
+    login_time: SystemTime,
+    pid: pid_t,
+}
+
+impl Session {
+    /// Returns the name of the user that owns this session.
+    pub fn username(&self) -> &OsStr {
+        &self.username
+    }
+
+    /// Returns the `User` record for this session’s username.
+    ///
+    /// This is `None` when the user does not exist in the users database,
+    /// which can happen if they were deleted while still logged in.
+    pub fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    /// Returns the terminal line of this session, such as `pts/0`.
+    pub fn tty(&self) -> &OsStr {
+        &self.tty
+    }
+
+    /// Returns the remote host this session was opened from, or `None` for
+    /// local logins.
+    pub fn host(&self) -> Option<&OsStr> {
+        if self.host.is_empty() {
+            None
+        } else {
+            Some(&self.host)
+        }
+    }
+
+    /// Returns the time this session was opened.
+    pub fn login_time(&self) -> SystemTime {
+        self.login_time
+    }
+
+    /// Returns the process ID of this session’s login process.
+    pub fn pid(&self) -> pid_t {
+        self.pid
+    }
+}
+
+/// Reads a fixed-size, possibly unterminated `char` array from a `utmpx`
+/// record.
+pub(crate) fn from_char_array(chars: &[c_char]) -> OsString {
+    let bytes = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect::<Vec<_>>();
+
+    OsStr::from_bytes(&bytes).to_os_string()
+}
+
+/// Reads a fixed-size, possibly unterminated byte array from a `lastlog`
+/// record.
+#[cfg(target_os = "linux")]
+fn from_byte_array(bytes: &[u8]) -> OsString {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    OsStr::from_bytes(&bytes[..len]).to_os_string()
+}
+
+/// Converts the `ut_tv` field of a `utmpx` record to a `SystemTime`.
+pub(crate) fn record_time(record: &utmpx) -> SystemTime {
+    let secs = i64::from(record.ut_tv.tv_sec);
+    let micros = i64::from(record.ut_tv.tv_usec).max(0) as u32;
+
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::new(secs as u64, micros.saturating_mul(1000))
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    }
+}
+
+/// Reads a `utmpx` user process record and returns it as a `Session`.
+fn record_to_session(record: &utmpx) -> Session {
+    let username = from_char_array(&record.ut_user);
+    let user = get_user_by_name(&username);
+
+    Session {
+        username,
+        user,
+        tty: from_char_array(&record.ut_line),
+        host: from_char_array(&record.ut_host),
+        login_time: record_time(record),
+        pid: record.ut_pid,
+    }
+}
+
+/// Returns every session that is currently logged in to the system.
+///
+/// Only *user process* entries are returned; boot, run level and dead
+/// process entries are skipped.
+///
+/// # libc functions used
+///
+/// - [`getutxent`](https://docs.rs/libc/*/libc/fn.getutxent.html)
+/// - [`setutxent`](https://docs.rs/libc/*/libc/fn.setutxent.html)
+/// - [`endutxent`](https://docs.rs/libc/*/libc/fn.endutxent.html)
+/// - [`getpwnam_r`](https://docs.rs/libc/*/libc/fn.getpwnam_r.html)
+///
+/// # Safety
+///
+/// This function is marked as `unsafe` for the same reason as
+/// [`all_users`](../fn.all_users.html): the underlying C functions,
+/// `getutxent`/`setutxent`/`endutxent`, iterate over the `utmpx` database
+/// using a global state, and we cannot guarantee that nothing else calls them
+/// at the same time.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::sessions::logged_in_users;
+///
+/// for session in unsafe { logged_in_users() } {
+///     match session.host() {
+///         Some(host) => println!("{:?} from {:?}", session.username(), host),
+///         None       => println!("{:?} (local)", session.username()),
+///     }
+/// }
+/// ```
+pub unsafe fn logged_in_users() -> Vec<Session> {
+    let mut sessions = Vec::new();
+
+    #[cfg(feature = "logging")]
+    trace!("Running setutxent");
+
+    libc::setutxent();
+
+    loop {
+        #[cfg(feature = "logging")]
+        trace!("Running getutxent");
+
+        let result = libc::getutxent();
+
+        if result.is_null() {
+            break;
+        }
+
+        let record = &*result;
+        if record.ut_type == libc::USER_PROCESS {
+            sessions.push(record_to_session(record));
+        }
+    }
+
+    #[cfg(feature = "logging")]
+    trace!("Running endutxent");
+
+    libc::endutxent();
+    sessions
+}
+
+/// The path to the system’s login history database.
+#[cfg(target_os = "linux")]
+pub const WTMP_PATH: &str = "/var/log/wtmp";
+
+/// The path to the system’s current sessions database, which is restored
+/// once a history iterator is dropped.
+#[cfg(target_os = "linux")]
+const UTMP_PATH: &str = "/var/run/utmp";
+
+/// The kinds of entries in the login history.
+#[cfg(target_os = "linux")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoginEventKind {
+    /// A user logged in.
+    Login,
+
+    /// A user logged out.
+    Logout,
+
+    /// The system was booted.
+    Boot,
+}
+
+/// An entry in the login history read from the `wtmp` database.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug)]
+pub struct LoginRecord {
+    kind: LoginEventKind,
+    username: OsString,
+    user: Option<User>,
+    tty: OsString,
+    host: OsString,
+    time: SystemTime,
+    pid: pid_t,
+}
+
+#[cfg(target_os = "linux")]
+impl LoginRecord {
+    /// Returns what kind of event this entry records.
+    pub fn kind(&self) -> LoginEventKind {
+        self.kind
+    }
+
+    /// Returns the name of the user this entry is about.
+    ///
+    /// Logout entries do not contain a username in the database, so it is
+    /// filled in from the most recent login on the same terminal line, if one
+    /// was seen. Boot entries hold the placeholder name `reboot`, as `last`
+    /// prints them.
+    pub fn username(&self) -> &OsStr {
+        &self.username
+    }
+
+    /// Returns the `User` record for this entry’s username, if that user
+    /// still exists.
+    pub fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    /// Returns the terminal line of this entry, such as `pts/0`.
+    pub fn tty(&self) -> &OsStr {
+        &self.tty
+    }
+
+    /// Returns the remote host of this entry, or `None` for local logins.
+    ///
+    /// For boot entries, this is the release of the kernel that was booted.
+    pub fn host(&self) -> Option<&OsStr> {
+        if self.host.is_empty() {
+            None
+        } else {
+            Some(&self.host)
+        }
+    }
+
+    /// Returns the time of this entry.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the process ID of the login process of this entry.
+    pub fn pid(&self) -> pid_t {
+        self.pid
+    }
+}
+
+/// An iterator over the entries of a login history database.
+#[cfg(target_os = "linux")]
+struct LoginHistory {
+    /// Users that have already been looked up, by name.
+    users: HashMap<OsString, Option<User>>,
+
+    /// The username of the last login on every terminal line.
+    open: HashMap<OsString, OsString>,
+}
+
+#[cfg(target_os = "linux")]
+impl LoginHistory {
+    fn user_named(&mut self, username: &OsStr) -> Option<User> {
+        self.users
+            .entry(username.to_os_string())
+            .or_insert_with(|| get_user_by_name(username))
+            .clone()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for LoginHistory {
+    fn drop(&mut self) {
+        #[cfg(feature = "logging")]
+        trace!("Running endutxent");
+
+        unsafe { libc::endutxent() };
+
+        let path = CString::new(UTMP_PATH).unwrap();
+        unsafe { libc::utmpxname(path.as_ptr()) };
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Iterator for LoginHistory {
+    type Item = LoginRecord;
+
+    fn next(&mut self) -> Option<LoginRecord> {
+        loop {
+            #[cfg(feature = "logging")]
+            trace!("Running getutxent");
+
+            let result = unsafe { libc::getutxent() };
+
+            if result.is_null() {
+                return None;
+            }
+
+            let record = unsafe { &*result };
+            let kind = match record.ut_type {
+                libc::USER_PROCESS => LoginEventKind::Login,
+                libc::DEAD_PROCESS => LoginEventKind::Logout,
+                libc::BOOT_TIME => LoginEventKind::Boot,
+                _ => continue,
+            };
+
+            let tty = from_char_array(&record.ut_line);
+            let mut username = from_char_array(&record.ut_user);
+
+            match kind {
+                LoginEventKind::Login => {
+                    self.open.insert(tty.clone(), username.clone());
+                }
+                LoginEventKind::Logout => {
+                    if let Some(name) = self.open.remove(&tty) {
+                        if username.is_empty() {
+                            username = name;
+                        }
+                    }
+                }
+                LoginEventKind::Boot => {
+                    self.open.clear();
+                }
+            }
+
+            let user = if kind == LoginEventKind::Boot || username.is_empty() {
+                None
+            } else {
+                self.user_named(&username)
+            };
+
+            return Some(LoginRecord {
+                kind,
+                username,
+                user,
+                tty,
+                host: from_char_array(&record.ut_host),
+                time: record_time(record),
+                pid: record.ut_pid,
+            });
+        }
+    }
+}
+
+/// Creates a new iterator over the system’s login history, oldest entry
+/// first.
+///
+/// This reads the database at [`WTMP_PATH`]. See
+/// [`login_history_from`](fn.login_history_from.html) for details.
+///
+/// # Safety
+///
+/// This function is `unsafe` for the same reasons as
+/// [`login_history_from`](fn.login_history_from.html).
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::sessions::{login_history, LoginEventKind};
+///
+/// let history = unsafe { login_history() }.expect("Could not read wtmp");
+/// for record in history.filter(|r| r.kind() == LoginEventKind::Login) {
+///     println!("{:?} logged in at {:?}", record.username(), record.time());
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub unsafe fn login_history() -> io::Result<impl Iterator<Item = LoginRecord>> {
+    login_history_from(WTMP_PATH)
+}
+
+/// Creates a new iterator over the login history stored in the given
+/// `wtmp`-format file, oldest entry first.
+///
+/// Login, logout and boot entries are returned; every other kind of entry is
+/// skipped. Each entry is joined with the `User` record for its username,
+/// and users are only looked up once per iterator.
+///
+/// # libc functions used
+///
+/// - [`utmpxname`](https://docs.rs/libc/*/libc/fn.utmpxname.html)
+/// - [`getutxent`](https://docs.rs/libc/*/libc/fn.getutxent.html)
+/// - [`setutxent`](https://docs.rs/libc/*/libc/fn.setutxent.html)
+/// - [`endutxent`](https://docs.rs/libc/*/libc/fn.endutxent.html)
+/// - [`getpwnam_r`](https://docs.rs/libc/*/libc/fn.getpwnam_r.html)
+///
+/// # Errors
+///
+/// This function will return `Err` if the file cannot be read.
+///
+/// # Safety
+///
+/// This function is marked as `unsafe` for the same reason as
+/// [`all_users`](../fn.all_users.html): the underlying C functions use a
+/// global state. Additionally, while the iterator is alive, the `utmpx`
+/// functions read from the given file instead of the current sessions
+/// database, so [`logged_in_users`] must not be called until it is dropped.
+#[cfg(target_os = "linux")]
+pub unsafe fn login_history_from<P: AsRef<Path>>(
+    path: P,
+) -> io::Result<impl Iterator<Item = LoginRecord>> {
+    let path = path.as_ref();
+    let _ = std::fs::File::open(path)?;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    #[cfg(feature = "logging")]
+    trace!("Running utmpxname for {:?}", path);
+
+    if libc::utmpxname(c_path.as_ptr()) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[cfg(feature = "logging")]
+    trace!("Running setutxent");
+
+    libc::setutxent();
+
+    Ok(LoginHistory {
+        users: HashMap::new(),
+        open: HashMap::new(),
+    })
+}
+
+/// The path to the database of every user’s last login.
+#[cfg(target_os = "linux")]
+pub const LASTLOG_PATH: &str = "/var/log/lastlog";
+
+/// The size of the time field of a `lastlog` record, which glibc keeps at
+/// 32 bits on every platform for compatibility.
+#[cfg(target_os = "linux")]
+const LASTLOG_TIME_SIZE: usize = 4;
+
+/// The size of the line field of a `lastlog` record.
+#[cfg(target_os = "linux")]
+const LASTLOG_LINE_SIZE: usize = 32;
+
+/// The size of the host field of a `lastlog` record.
+#[cfg(target_os = "linux")]
+const LASTLOG_HOST_SIZE: usize = 256;
+
+/// The size of a whole `lastlog` record.
+#[cfg(target_os = "linux")]
+const LASTLOG_RECORD_SIZE: usize = LASTLOG_TIME_SIZE + LASTLOG_LINE_SIZE + LASTLOG_HOST_SIZE;
+
+/// When and where a user last logged in.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastLogin {
+    time: SystemTime,
+    tty: OsString,
+    host: OsString,
+}
+
+#[cfg(target_os = "linux")]
+impl LastLogin {
+    /// Returns the time of the login.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Returns the terminal line of the login, such as `pts/0`.
+    pub fn tty(&self) -> &OsStr {
+        &self.tty
+    }
+
+    /// Returns the remote host of the login, or `None` for local logins.
+    pub fn host(&self) -> Option<&OsStr> {
+        if self.host.is_empty() {
+            None
+        } else {
+            Some(&self.host)
+        }
+    }
+}
+
+/// Reads the record for the given user ID from a `lastlog`-format file.
+///
+/// The file is indexed by user ID, and users that have never logged in have
+/// an empty record, or no record at all if the file ends before it.
+#[cfg(target_os = "linux")]
+fn read_lastlog<P: AsRef<Path>>(path: P, uid: uid_t) -> io::Result<Option<LastLogin>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(u64::from(uid) * LASTLOG_RECORD_SIZE as u64))?;
+
+    let mut buf = [0; LASTLOG_RECORD_SIZE];
+    match file.read_exact(&mut buf) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let (time, rest) = buf.split_at(LASTLOG_TIME_SIZE);
+    let (line, host) = rest.split_at(LASTLOG_LINE_SIZE);
+
+    let mut time_bytes = [0; LASTLOG_TIME_SIZE];
+    time_bytes.copy_from_slice(time);
+    let time = i32::from_ne_bytes(time_bytes);
+
+    if time == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(LastLogin {
+        time: UNIX_EPOCH + Duration::from_secs(u64::from(time as u32)),
+        tty: from_byte_array(line),
+        host: from_byte_array(host),
+    }))
+}
+
+/// The path to the SQLite database that replaces `lastlog` on newer systems.
+#[cfg(all(feature = "lastlog2", target_os = "linux"))]
+pub const LASTLOG2_PATH: &str = "/var/lib/lastlog/lastlog2.db";
+
+/// Opens a `lastlog2` database for reading, returning a `NotFound` error if
+/// it does not exist.
+#[cfg(all(feature = "lastlog2", target_os = "linux"))]
+fn open_lastlog2<P: AsRef<Path>>(path: P) -> io::Result<Connection> {
+    // Opening a database that does not exist would create it.
+    std::fs::metadata(path.as_ref())?;
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(io::Error::other)
+}
+
+/// Reads the record for the given username from a `lastlog2` database.
+///
+/// Users that have never logged in have no record, or one whose time is
+/// zero.
+#[cfg(all(feature = "lastlog2", target_os = "linux"))]
+fn read_lastlog2(db: &Connection, username: &OsStr) -> io::Result<Option<LastLogin>> {
+    let username = match username.to_str() {
+        Some(username) => username,
+        None => return Ok(None),
+    };
+
+    let record = db
+        .query_row(
+            "SELECT Time, TTY, RemoteHost FROM Lastlog2 WHERE Name = ?1",
+            [username],
+            |row| {
+                let time: Option<i64> = row.get(0)?;
+                let tty: Option<String> = row.get(1)?;
+                let host: Option<String> = row.get(2)?;
+                Ok((time.unwrap_or_default(), tty, host))
+            },
+        )
+        .optional()
+        .map_err(io::Error::other)?;
+
+    Ok(record
+        .filter(|(time, _, _)| *time > 0)
+        .map(|(time, tty, host)| LastLogin {
+            time: UNIX_EPOCH + Duration::from_secs(time as u64),
+            tty: tty.unwrap_or_default().into(),
+            host: host.unwrap_or_default().into(),
+        }))
+}
+
+/// Scans a `wtmp`-format file for the most recent login of every user,
+/// keyed by username.
+#[cfg(target_os = "linux")]
+fn read_wtmp<P: AsRef<Path>>(path: P) -> io::Result<HashMap<OsString, LastLogin>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut buf = vec![0; mem::size_of::<utmpx>()];
+    let mut logins = HashMap::new();
+
+    loop {
+        match reader.read_exact(&mut buf) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+
+        // The file is a plain array of `utmpx` structs.
+        let record = unsafe { (buf.as_ptr() as *const utmpx).read_unaligned() };
+        if record.ut_type == libc::USER_PROCESS {
+            let login = LastLogin {
+                time: record_time(&record),
+                tty: from_char_array(&record.ut_line),
+                host: from_char_array(&record.ut_host),
+            };
+            logins.insert(from_char_array(&record.ut_user), login);
+        }
+    }
+
+    Ok(logins)
+}
+
+/// Looks up the last logins of many users, opening the `lastlog2` database
+/// and reading the `wtmp` database at most once.
+#[cfg(target_os = "linux")]
+#[derive(Default)]
+pub(crate) struct LastLogins {
+    #[cfg(feature = "lastlog2")]
+    lastlog2: Option<Option<Connection>>,
+    wtmp: Option<HashMap<OsString, LastLogin>>,
+}
+
+#[cfg(target_os = "linux")]
+impl LastLogins {
+    /// Returns when and where the given user last logged in, as
+    /// [`last_login_of`] does.
+    pub(crate) fn of(&mut self, user: &User) -> io::Result<Option<LastLogin>> {
+        #[cfg(feature = "lastlog2")]
+        {
+            let lastlog2 = match &mut self.lastlog2 {
+                Some(lastlog2) => lastlog2,
+                lastlog2 => match open_lastlog2(LASTLOG2_PATH) {
+                    Ok(db) => lastlog2.insert(Some(db)),
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => lastlog2.insert(None),
+                    Err(e) => return Err(e),
+                },
+            };
+            if let Some(db) = lastlog2 {
+                if let Some(login) = read_lastlog2(db, user.name())? {
+                    return Ok(Some(login));
+                }
+            }
+        }
+
+        match read_lastlog(LASTLOG_PATH, user.uid()) {
+            Ok(Some(login)) => return Ok(Some(login)),
+            Ok(None) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        let wtmp = match &mut self.wtmp {
+            Some(wtmp) => wtmp,
+            wtmp => match read_wtmp(WTMP_PATH) {
+                Ok(logins) => wtmp.insert(logins),
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => wtmp.insert(HashMap::new()),
+                Err(e) => return Err(e),
+            },
+        };
+        Ok(wtmp.get(user.name()).cloned())
+    }
+}
+
+/// Returns when and where the given user last logged in, or `None` if they
+/// never have.
+///
+/// The `lastlog` database at [`LASTLOG_PATH`] is checked first. If it does
+/// not exist or has no entry for the user, the login history at
+/// [`WTMP_PATH`] is scanned instead.
+///
+/// Systems that have replaced `lastlog` with the SQLite-based `lastlog2`
+/// keep it at `/var/lib/lastlog/lastlog2.db`. With the `lastlog2` feature,
+/// that database is checked before the other two; without it, such systems
+/// are only covered by the `wtmp` fallback.
+///
+/// # Errors
+///
+/// This function will return `Err` if one of the databases exists but
+/// cannot be read.
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::{get_user_by_name, sessions::last_login_of};
+///
+/// let user = get_user_by_name("stevedore").expect("User not found");
+/// match last_login_of(&user).expect("Could not read login databases") {
+///     Some(login) => println!("Last login on {:?} at {:?}", login.tty(), login.time()),
+///     None        => println!("Never logged in"),
+/// }
+/// ```
+#[cfg(target_os = "linux")]
+pub fn last_login_of(user: &User) -> io::Result<Option<LastLogin>> {
+    LastLogins::default().of(user)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn char_array() {
+        let chars = [
+            b'p' as c_char,
+            b't' as c_char,
+            b's' as c_char,
+            0,
+            b'x' as c_char,
+        ];
+        assert_eq!(from_char_array(&chars), OsString::from("pts"));
+    }
+
+    #[test]
+    fn unterminated_char_array() {
+        let chars = [
+            b'r' as c_char,
+            b'o' as c_char,
+            b'o' as c_char,
+            b't' as c_char,
+        ];
+        assert_eq!(from_char_array(&chars), OsString::from("root"));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn missing_history() {
+        assert!(unsafe { login_history_from("/nonexistent/wtmp") }.is_err());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn lastlog_records() {
+        use std::io::Write;
+
+        let path = std::env::temp_dir().join(format!("uzers-lastlog-{}", std::process::id()));
+        let mut record = vec![0; LASTLOG_RECORD_SIZE];
+        record[..LASTLOG_TIME_SIZE].copy_from_slice(&1_000_000i32.to_ne_bytes());
+        record[LASTLOG_TIME_SIZE..LASTLOG_TIME_SIZE + 5].copy_from_slice(b"pts/3");
+
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&vec![0; LASTLOG_RECORD_SIZE]).unwrap();
+        file.write_all(&record).unwrap();
+        drop(file);
+
+        let never = read_lastlog(&path, 0).unwrap();
+        let login = read_lastlog(&path, 1).unwrap().unwrap();
+        let missing = read_lastlog(&path, 2).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(never, None);
+        assert_eq!(login.time(), UNIX_EPOCH + Duration::from_secs(1_000_000));
+        assert_eq!(login.tty(), OsStr::new("pts/3"));
+        assert_eq!(login.host(), None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    #[cfg(all(feature = "lastlog2", target_os = "linux"))]
+    fn lastlog2_records() {
+        let path = std::env::temp_dir().join(format!("uzers-lastlog2-{}.db", std::process::id()));
+        assert_eq!(
+            open_lastlog2(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        let db = Connection::open(&path).unwrap();
+        db.execute_batch(
+            "CREATE TABLE Lastlog2 (Name TEXT PRIMARY KEY, Time INTEGER NOT NULL, \
+             TTY TEXT, RemoteHost TEXT, Service TEXT);
+             INSERT INTO Lastlog2 VALUES ('fred', 1000000, 'pts/3', 'example.org', 'sshd');
+             INSERT INTO Lastlog2 VALUES ('bob', 0, NULL, NULL, NULL);",
+        )
+        .unwrap();
+        drop(db);
+
+        let db = open_lastlog2(&path).unwrap();
+        let login = read_lastlog2(&db, OsStr::new("fred")).unwrap().unwrap();
+        let never = read_lastlog2(&db, OsStr::new("bob")).unwrap();
+        let missing = read_lastlog2(&db, OsStr::new("alice")).unwrap();
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(login.time(), UNIX_EPOCH + Duration::from_secs(1_000_000));
+        assert_eq!(login.tty(), OsStr::new("pts/3"));
+        assert_eq!(login.host(), Some(OsStr::new("example.org")));
+        assert_eq!(never, None);
+        assert_eq!(missing, None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn wtmp_logins() {
+        use std::io::Write;
+        use std::slice;
+
+        fn record(kind: libc::c_short, user: &[u8], line: &[u8], time: u16) -> utmpx {
+            let mut record: utmpx = unsafe { mem::zeroed() };
+            record.ut_type = kind;
+            for (c, &b) in record.ut_user.iter_mut().zip(user) {
+                *c = b as c_char;
+            }
+            for (c, &b) in record.ut_line.iter_mut().zip(line) {
+                *c = b as c_char;
+            }
+            record.ut_tv.tv_sec = time.into();
+            record
+        }
+
+        let records = [
+            record(libc::USER_PROCESS, b"fred", b"pts/0", 100),
+            record(libc::USER_PROCESS, b"bob", b"pts/1", 200),
+            record(libc::BOOT_TIME, b"reboot", b"~", 300),
+            record(libc::USER_PROCESS, b"fred", b"pts/2", 400),
+        ];
+        let bytes = unsafe {
+            slice::from_raw_parts(records.as_ptr() as *const u8, mem::size_of_val(&records))
+        };
+
+        let path = std::env::temp_dir().join(format!("uzers-wtmp-{}", std::process::id()));
+        File::create(&path).unwrap().write_all(bytes).unwrap();
+        let logins = read_wtmp(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(logins.len(), 2);
+        let fred = &logins[OsStr::new("fred")];
+        assert_eq!(fred.time(), UNIX_EPOCH + Duration::from_secs(400));
+        assert_eq!(fred.tty(), OsStr::new("pts/2"));
+        assert_eq!(logins[OsStr::new("bob")].tty(), OsStr::new("pts/1"));
+    }
+
+    #[test]
+    fn sessions_have_usernames() {
+        for session in unsafe { logged_in_users() } {
+            assert!(!session.username().is_empty());
+        }
+    }
+}
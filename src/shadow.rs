@@ -0,0 +1,388 @@
+//! Integration with the shadow password database.
+//!
+//! On Linux, password hashes and password aging information are not stored
+//! in `/etc/passwd`, but in the separate *shadow* database, which is only
+//! readable by privileged processes. This module reads entries from it and
+//! answers the aging questions that `login` and `sshd` ask before letting a
+//! user in: has the account expired, and does the password need changing?
+//!
+//! All dates in the shadow database are counted in whole days since the Unix
+//! epoch.
+//!
+//! ```no_run
+//! use uzers::shadow::get_shadow_by_name;
+//!
+//! let entry = get_shadow_by_name("root").expect("Cannot read shadow entry");
+//! if entry.is_password_expired() {
+//!     println!("The root password needs to be changed");
+//! }
+//! ```
+//!
+//! With the `zeroize` feature, [`Shadow`] entries implement `Zeroize` and
+//! `ZeroizeOnDrop`, so their password hashes are overwritten once they are
+//! no longer needed, as is the buffer the C library copies an entry into.
+//! This gives `Shadow` a `Drop` implementation, so with the feature, its
+//! fields cannot be moved out of it, and an entry cannot be built with the
+//! `..other` struct update syntax: set the fields of a copy instead.
+
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "logging")]
+extern crate log;
+#[cfg(feature = "logging")]
+use self::log::trace;
+
+#[cfg(feature = "zeroize")]
+extern crate zeroize;
+#[cfg(feature = "zeroize")]
+use self::zeroize::{Zeroize, ZeroizeOnDrop};
+
+#[cfg(feature = "zeroize")]
+use base::zeroize_os_string;
+use base::EntryBuffer;
+use libc::{c_long, spwd};
+
+/// The number of seconds in a day, the unit of every shadow date.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// An entry in the shadow password database.
+#[derive(Clone)]
+pub struct Shadow {
+    name: OsString,
+    password: OsString,
+    last_change: Option<i64>,
+    min_days: Option<i64>,
+    max_days: Option<i64>,
+    warn_days: Option<i64>,
+    inactive_days: Option<i64>,
+    expire: Option<i64>,
+}
+
+/// Converts a shadow field, which uses `-1` for “not set”, to an option.
+// allow useless conversion: on 64-bit platforms c_long is already i64
+#[allow(clippy::useless_conversion)]
+fn field(value: c_long) -> Option<i64> {
+    if value < 0 {
+        None
+    } else {
+        Some(i64::from(value))
+    }
+}
+
+/// Returns the number of whole days between the Unix epoch and `date`.
+fn days_since_epoch(date: SystemTime) -> i64 {
+    match date.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() / SECONDS_PER_DAY) as i64,
+        Err(e) => -(e.duration().as_secs().div_ceil(SECONDS_PER_DAY) as i64),
+    }
+}
+
+impl Shadow {
+    /// Returns the name of the user this entry belongs to.
+    pub fn name(&self) -> &OsStr {
+        &self.name
+    }
+
+    /// Returns the user’s encrypted password.
+    pub fn password(&self) -> &OsStr {
+        &self.password
+    }
+
+    /// Returns the day the password was last changed, in days since the
+    /// epoch. A value of `0` means the password has to be changed at the next
+    /// login.
+    pub fn last_change(&self) -> Option<i64> {
+        self.last_change
+    }
+
+    /// Returns the number of days the user has to wait before changing their
+    /// password again.
+    pub fn min_days(&self) -> Option<i64> {
+        self.min_days
+    }
+
+    /// Returns the number of days after which the password has to be changed.
+    pub fn max_days(&self) -> Option<i64> {
+        self.max_days
+    }
+
+    /// Returns the number of days before password expiry that the user is
+    /// warned.
+    pub fn warn_days(&self) -> Option<i64> {
+        self.warn_days
+    }
+
+    /// Returns the number of days after password expiry that the password is
+    /// still accepted.
+    pub fn inactive_days(&self) -> Option<i64> {
+        self.inactive_days
+    }
+
+    /// Returns the day the account expires, in days since the epoch.
+    pub fn expire(&self) -> Option<i64> {
+        self.expire
+    }
+
+    /// Returns whether the account has expired at the current date.
+    pub fn is_account_expired(&self) -> bool {
+        self.is_account_expired_at(SystemTime::now())
+    }
+
+    /// Returns whether the account has expired at the given date.
+    ///
+    /// An account expires at the start of its expiry day.
+    pub fn is_account_expired_at(&self, date: SystemTime) -> bool {
+        match self.expire {
+            Some(expire) => days_since_epoch(date) >= expire,
+            None => false,
+        }
+    }
+
+    /// Returns whether the password has expired at the current date.
+    pub fn is_password_expired(&self) -> bool {
+        self.is_password_expired_at(SystemTime::now())
+    }
+
+    /// Returns whether the password has expired at the given date.
+    ///
+    /// This is the case when the password has been marked as having to be
+    /// changed at the next login, or when it is older than its maximum age.
+    pub fn is_password_expired_at(&self, date: SystemTime) -> bool {
+        match (self.last_change, self.max_days) {
+            (Some(0), _) => true,
+            (Some(last_change), Some(max_days)) => {
+                days_since_epoch(date) > last_change.saturating_add(max_days)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns how long ago the password was last changed, at the current
+    /// date.
+    pub fn password_age(&self) -> Option<Duration> {
+        self.password_age_at(SystemTime::now())
+    }
+
+    /// Returns how long ago the password was last changed, at the given date.
+    ///
+    /// This is `None` if the change date is not set or lies in the future.
+    pub fn password_age_at(&self, date: SystemTime) -> Option<Duration> {
+        let days = days_since_epoch(date) - self.last_change?;
+        if days < 0 {
+            None
+        } else {
+            Some(Duration::from_secs(days as u64 * SECONDS_PER_DAY))
+        }
+    }
+
+    /// Returns the number of days until the account expires, at the current
+    /// date.
+    pub fn days_until_expiry(&self) -> Option<i64> {
+        self.days_until_expiry_at(SystemTime::now())
+    }
+
+    /// Returns the number of days until the account expires, at the given
+    /// date. This is negative if the account has already expired, and `None`
+    /// if it never expires.
+    pub fn days_until_expiry_at(&self, date: SystemTime) -> Option<i64> {
+        Some(self.expire? - days_since_epoch(date))
+    }
+}
+
+impl std::fmt::Debug for Shadow {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Never print the password hash
+        f.debug_struct("Shadow")
+            .field("name", &self.name)
+            .field("last_change", &self.last_change)
+            .field("min_days", &self.min_days)
+            .field("max_days", &self.max_days)
+            .field("warn_days", &self.warn_days)
+            .field("inactive_days", &self.inactive_days)
+            .field("expire", &self.expire)
+            .finish()
+    }
+}
+
+/// Zeroizing an entry overwrites its password hash. With the `zeroize`
+/// feature, the hash is also overwritten when the entry is dropped.
+#[cfg(feature = "zeroize")]
+impl Zeroize for Shadow {
+    fn zeroize(&mut self) {
+        zeroize_os_string(&mut self.password);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl ZeroizeOnDrop for Shadow {}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Shadow {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Reads data from the `spwd` and returns it as a `Shadow`.
+unsafe fn spwd_to_shadow(spwd: spwd) -> Shadow {
+    #[cfg(feature = "logging")]
+    trace!("Loading shadow entry");
+
+    Shadow {
+        name: OsStr::from_bytes(CStr::from_ptr(spwd.sp_namp).to_bytes()).to_os_string(),
+        password: OsStr::from_bytes(CStr::from_ptr(spwd.sp_pwdp).to_bytes()).to_os_string(),
+        last_change: field(spwd.sp_lstchg),
+        min_days: field(spwd.sp_min),
+        max_days: field(spwd.sp_max),
+        warn_days: field(spwd.sp_warn),
+        inactive_days: field(spwd.sp_inact),
+        expire: field(spwd.sp_expire),
+    }
+}
+
+/// Searches for the shadow entry of the user with the given name. Returns it
+/// if one is found, otherwise returns `None`.
+///
+/// Reading the shadow database usually requires root privileges, so this
+/// also returns `None` when the process is not allowed to read it.
+///
+/// # libc functions used
+///
+/// - [`getspnam_r`](https://docs.rs/libc/*/libc/fn.getspnam_r.html)
+///
+/// # Examples
+///
+/// ```no_run
+/// use uzers::shadow::get_shadow_by_name;
+///
+/// match get_shadow_by_name("stevedore") {
+///     Some(entry) => println!("Account expires on day {:?}", entry.expire()),
+///     None        => println!("No shadow entry"),
+/// }
+/// ```
+pub fn get_shadow_by_name<S: AsRef<OsStr> + ?Sized>(username: &S) -> Option<Shadow> {
+    let username = match CString::new(username.as_ref().as_bytes()) {
+        Ok(u) => u,
+        Err(_) => {
+            // The username that was passed in contained a null character,
+            // which will match no usernames.
+            return None;
+        }
+    };
+
+    let mut spwd = unsafe { mem::zeroed::<spwd>() };
+    let mut buf = EntryBuffer::new();
+    let mut result = ptr::null_mut::<spwd>();
+
+    #[cfg(feature = "logging")]
+    trace!("Running getspnam_r for user {:?}", username.as_ref());
+
+    loop {
+        let r = unsafe {
+            libc::getspnam_r(
+                username.as_ptr(),
+                &mut spwd,
+                buf.as_mut_ptr(),
+                buf.size(),
+                &mut result,
+            )
+        };
+
+        if r != libc::ERANGE {
+            break;
+        }
+
+        buf.grow()?;
+    }
+
+    // If result is null, there is no such entry, or an error has occurred,
+    // and errno gets set if there’s an error. Otherwise, the result of
+    // getspnam_r should be its input struct.
+    if result.is_null() || result != &mut spwd {
+        None
+    } else {
+        Some(unsafe { spwd_to_shadow(result.read()) })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry() -> Shadow {
+        Shadow {
+            name: "fred".into(),
+            password: "!".into(),
+            last_change: Some(100),
+            min_days: Some(0),
+            max_days: Some(30),
+            warn_days: Some(7),
+            inactive_days: None,
+            expire: Some(200),
+        }
+    }
+
+    fn day(n: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(n * SECONDS_PER_DAY + 3600)
+    }
+
+    #[test]
+    fn account_expiry() {
+        let entry = entry();
+        assert!(!entry.is_account_expired_at(day(199)));
+        assert!(entry.is_account_expired_at(day(200)));
+        assert_eq!(entry.days_until_expiry_at(day(150)), Some(50));
+        assert_eq!(entry.days_until_expiry_at(day(210)), Some(-10));
+    }
+
+    #[test]
+    fn no_account_expiry() {
+        let mut entry = entry();
+        entry.expire = None;
+        assert!(!entry.is_account_expired_at(day(100_000)));
+        assert_eq!(entry.days_until_expiry_at(day(100_000)), None);
+    }
+
+    #[test]
+    fn password_expiry() {
+        let entry = entry();
+        assert!(!entry.is_password_expired_at(day(130)));
+        assert!(entry.is_password_expired_at(day(131)));
+    }
+
+    #[test]
+    fn forced_password_change() {
+        let mut entry = entry();
+        entry.last_change = Some(0);
+        assert!(entry.is_password_expired_at(day(1)));
+    }
+
+    #[test]
+    fn password_age() {
+        let entry = entry();
+        assert_eq!(
+            entry.password_age_at(day(110)),
+            Some(Duration::from_secs(10 * SECONDS_PER_DAY))
+        );
+        assert_eq!(entry.password_age_at(day(90)), None);
+    }
+
+    #[test]
+    fn debug_hides_password() {
+        assert!(!format!("{:?}", entry()).contains('!'));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        let mut entry = entry();
+        entry.zeroize();
+        assert!(entry.password().is_empty());
+        assert_eq!(entry.name(), "fred");
+        assert_eq!(entry.max_days(), Some(30));
+    }
+}
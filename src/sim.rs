@@ -0,0 +1,169 @@
+//! Simulating cache policies against recorded lookups.
+//!
+//! How well a cache performs depends on the lookups a program makes: a
+//! daemon that resolves the same handful of users forever wants something
+//! different from a tool that lists the owners of a million files. This
+//! module replays a trace written by
+//! [`RecordingUsers`](../record/struct.RecordingUsers.html) against a
+//! [`UsersCache`] with a given [`CachePolicy`], and returns the cache’s own
+//! [`CacheStats`] for it.
+//!
+//! The cache answers its misses from the same trace, through a
+//! [`ReplayUsers`], so nothing is asked of the system. Recordings hold no
+//! times, so the cache’s clock moves on by a fixed step before every lookup.
+//! That keeps the results reproducible, so they can be checked in tests to
+//! guard against regressions in hit rates.
+//!
+//! This module is only built for the tests, with the `cache-sim` feature.
+
+use std::ffi::OsStr;
+use std::io;
+use std::time::{Duration, UNIX_EPOCH};
+
+use libc::{gid_t, uid_t};
+
+use base::{Group, User};
+use cache::{CachePolicy, CacheStats, UsersCache};
+use record::{RecordedLookup, ReplayUsers};
+use source::UserDbSource;
+use time::MockClock;
+use traits::{Groups, Users};
+
+/// A source that answers the lookups of a recorded trace.
+struct ReplaySource(ReplayUsers);
+
+impl UserDbSource for ReplaySource {
+    fn user_by_uid(&self, uid: uid_t) -> Option<User> {
+        self.0.get_user_by_uid(uid).map(|u| User::clone(&u))
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> Option<User> {
+        self.0.get_user_by_name(username).map(|u| User::clone(&u))
+    }
+
+    fn group_by_gid(&self, gid: gid_t) -> Option<Group> {
+        self.0.get_group_by_gid(gid).map(|g| Group::clone(&g))
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Group> {
+        self.0
+            .get_group_by_name(group_name)
+            .map(|g| Group::clone(&g))
+    }
+
+    fn user_groups(&self, _username: &OsStr, _gid: gid_t) -> Option<Vec<Group>> {
+        None
+    }
+
+    unsafe fn all_users(&self) -> Vec<User> {
+        let users = self.0.users_by_uid.values().flatten();
+        users.map(|u| User::clone(u)).collect()
+    }
+
+    unsafe fn all_groups(&self) -> Vec<Group> {
+        let groups = self.0.groups_by_gid.values().flatten();
+        groups.map(|g| Group::clone(g)).collect()
+    }
+}
+
+/// Replays the lookups of a recorded trace against a cache with the given
+/// policy, moving its clock on by `step` before each one, and returns the
+/// cache’s statistics.
+///
+/// Only lookups of users and groups by ID or name are replayed; the current
+/// and effective IDs and names are not cached, so they are skipped.
+///
+/// # Errors
+///
+/// This function will return an `InvalidData` error if the trace is not
+/// valid.
+pub(crate) fn simulate(trace: &str, policy: CachePolicy, step: Duration) -> io::Result<CacheStats> {
+    let replay = ReplayUsers::parse(trace)?;
+    let lookups = trace
+        .lines()
+        .filter(|line| !line.is_empty())
+        .filter_map(RecordedLookup::parse)
+        .collect::<Vec<_>>();
+
+    let clock = MockClock::new(UNIX_EPOCH);
+    let cache =
+        UsersCache::with_policy_and_clock(policy, clock.clone()).with_source(ReplaySource(replay));
+
+    for lookup in &lookups {
+        clock.advance(step);
+
+        let key = match lookup.key() {
+            Some(key) => key,
+            None => continue,
+        };
+        let id = || key.to_str().and_then(|id| id.parse().ok());
+
+        match lookup.kind() {
+            "uid" => drop(id().map(|uid| cache.get_user_by_uid(uid))),
+            "name" => drop(cache.get_user_by_name(key)),
+            "gid" => drop(id().map(|gid| cache.get_group_by_gid(gid))),
+            "group" => drop(cache.get_group_by_name(key)),
+            _ => {}
+        }
+    }
+
+    Ok(cache.stats())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TRACE: &str = "\
+uid 1000 fred:x:1000:100::/home/fred:/bin/sh
+uid 1001 bob:x:1001:100::/home/bob:/bin/sh
+uid 1000 fred:x:1000:100::/home/fred:/bin/sh
+uid 4242 -
+uid 4242 -
+name fred fred:x:1000:100::/home/fred:/bin/sh
+gid 100 staff:x:100:
+current-uid - 1000
+uid 1000 fred:x:1000:100::/home/fred:/bin/sh
+group staff staff:x:100:
+";
+
+    fn run(policy: CachePolicy) -> CacheStats {
+        simulate(TRACE, policy, Duration::from_millis(100)).unwrap()
+    }
+
+    #[test]
+    fn invalid_trace() {
+        let policy = CachePolicy::default();
+        assert!(simulate("uid 1000", policy, Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn default_policy() {
+        let stats = run(CachePolicy::default());
+        assert_eq!(stats.hits() + stats.misses(), 9);
+        assert_eq!(stats.hits(), 5);
+        assert_eq!(stats.negative_hits, 1);
+        assert_eq!(stats.group_hits, 1);
+        assert_eq!(stats.expirations, 0);
+    }
+
+    #[test]
+    fn without_negative_caching() {
+        let stats = run(CachePolicy {
+            negative_caching: false,
+            ..CachePolicy::default()
+        });
+        assert_eq!(stats.hits(), 4);
+        assert_eq!(stats.negative_hits, 0);
+    }
+
+    #[test]
+    fn ttl() {
+        let stats = run(CachePolicy {
+            positive_ttl: Some(Duration::from_millis(250)),
+            ..CachePolicy::default()
+        });
+        assert_eq!(stats.hits(), 2);
+        assert_eq!(stats.expirations, 3);
+    }
+}
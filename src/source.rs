@@ -0,0 +1,261 @@
+//! Replacing the database behind the top-level functions.
+//!
+//! The top-level functions, such as [`get_user_by_uid`](../fn.get_user_by_uid.html)
+//! and [`all_users`](../fn.all_users.html), ask the C library by default.
+//! Large programs and their tests sometimes need every one of those calls to
+//! read somewhere else instead, without having to change each call site to
+//! use a [`Users`](../trait.Users.html) provider.
+//!
+//! [`set_global_source`] does this for the whole process. It takes any
+//! [`UserDbSource`], such as a [`FilesSource`] that reads a particular
+//! `passwd` and `group` file, or a type of your own:
+//!
+//! ```no_run
+//! use uzers::source::{clear_global_source, set_global_source, FilesSource};
+//!
+//! set_global_source(FilesSource::new("fixtures/passwd", "fixtures/group"));
+//! assert!(uzers::get_user_by_name("fred").is_some());
+//!
+//! clear_global_source();
+//! ```
+//!
+//! Functions that ask the operating system about the current process, such
+//! as [`get_current_uid`](../fn.get_current_uid.html) and
+//! [`group_access_list`](../fn.group_access_list.html), are not affected,
+//! although the names and groups they look up are.
+
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use libc::{gid_t, uid_t};
+
+use base::{self, Group, User};
+use files::{read_group_file_with, read_passwd_file_with, Dialect};
+
+/// A database of users and groups that the top-level functions can be routed
+/// through.
+///
+/// Sources are shared between threads once they are set globally, so they
+/// have to be `Send` and `Sync`.
+pub trait UserDbSource: Send + Sync {
+    /// Returns the user with the given ID, if one exists.
+    fn user_by_uid(&self, uid: uid_t) -> Option<User>;
+
+    /// Returns the user with the given name, if one exists.
+    fn user_by_name(&self, username: &OsStr) -> Option<User>;
+
+    /// Returns the group with the given ID, if one exists.
+    fn group_by_gid(&self, gid: gid_t) -> Option<Group>;
+
+    /// Returns the group with the given name, if one exists.
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Group>;
+
+    /// Returns the groups of the user with the given name and primary group
+    /// ID, or `None` if they cannot be determined.
+    fn user_groups(&self, username: &OsStr, gid: gid_t) -> Option<Vec<Group>>;
+
+    /// Returns every user in the database.
+    ///
+    /// # Safety
+    ///
+    /// Sources that enumerate the C library’s database have the same
+    /// requirements as [`all_users`](../fn.all_users.html).
+    unsafe fn all_users(&self) -> Vec<User>;
+
+    /// Returns every group in the database.
+    ///
+    /// # Safety
+    ///
+    /// Sources that enumerate the C library’s database have the same
+    /// requirements as [`all_groups`](../fn.all_groups.html).
+    unsafe fn all_groups(&self) -> Vec<Group>;
+}
+
+/// The source that asks the C library, which is what the top-level functions
+/// use when no global source is set.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LibcSource;
+
+impl UserDbSource for LibcSource {
+    fn user_by_uid(&self, uid: uid_t) -> Option<User> {
+        base::sys_user_by_uid(uid).ok().flatten()
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> Option<User> {
+        base::sys_user_by_name(username).ok().flatten()
+    }
+
+    fn group_by_gid(&self, gid: gid_t) -> Option<Group> {
+        base::sys_group_by_gid(gid).ok().flatten()
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Group> {
+        base::sys_group_by_name(group_name).ok().flatten()
+    }
+
+    fn user_groups(&self, username: &OsStr, gid: gid_t) -> Option<Vec<Group>> {
+        base::sys_user_groups(username, gid)
+    }
+
+    unsafe fn all_users(&self) -> Vec<User> {
+        base::sys_all_users().collect()
+    }
+
+    unsafe fn all_groups(&self) -> Vec<Group> {
+        base::sys_all_groups().collect()
+    }
+}
+
+/// A source that reads a `passwd` file and a `group` file.
+///
+/// The files are read again on every lookup, so changes to them are seen
+/// straight away. Lines that cannot be parsed are skipped, and a file that
+/// cannot be read counts as empty; see the [`files`](../files/index.html)
+/// module. The files are read in the classic format unless another
+/// [`Dialect`] is chosen with [`with_dialect`](FilesSource::with_dialect).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilesSource {
+    passwd: PathBuf,
+    group: PathBuf,
+    dialect: Dialect,
+}
+
+impl FilesSource {
+    /// Creates a source that reads the given `passwd` and `group` files.
+    pub fn new<P: AsRef<Path>, G: AsRef<Path>>(passwd: P, group: G) -> Self {
+        Self {
+            passwd: passwd.as_ref().to_path_buf(),
+            group: group.as_ref().to_path_buf(),
+            dialect: Dialect::default(),
+        }
+    }
+
+    /// Reads the files in the given dialect instead of the classic format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use uzers::files::Dialect;
+    /// use uzers::source::FilesSource;
+    ///
+    /// let source = FilesSource::new("/etc/master.passwd", "/etc/group")
+    ///     .with_dialect(Dialect::MasterPasswd);
+    /// ```
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    fn users(&self) -> Vec<User> {
+        read_passwd_file_with(&self.passwd, self.dialect).unwrap_or_default()
+    }
+
+    fn groups(&self) -> Vec<Group> {
+        read_group_file_with(&self.group, self.dialect).unwrap_or_default()
+    }
+}
+
+impl Default for FilesSource {
+    /// Creates a source that reads `/etc/passwd` and `/etc/group`.
+    fn default() -> Self {
+        Self::new("/etc/passwd", "/etc/group")
+    }
+}
+
+impl UserDbSource for FilesSource {
+    fn user_by_uid(&self, uid: uid_t) -> Option<User> {
+        self.users().into_iter().find(|u| u.uid() == uid)
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> Option<User> {
+        self.users().into_iter().find(|u| u.name() == username)
+    }
+
+    fn group_by_gid(&self, gid: gid_t) -> Option<Group> {
+        self.groups().into_iter().find(|g| g.gid() == gid)
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Group> {
+        self.groups().into_iter().find(|g| g.name() == group_name)
+    }
+
+    fn user_groups(&self, username: &OsStr, gid: gid_t) -> Option<Vec<Group>> {
+        use base::os::unix::GroupExt;
+
+        let mut groups = self
+            .groups()
+            .into_iter()
+            .filter(|g| g.gid() == gid || g.members().iter().any(|m| m == username))
+            .collect::<Vec<_>>();
+        groups.sort_by_key(Group::gid);
+        groups.dedup_by_key(|g| g.gid());
+        Some(groups)
+    }
+
+    unsafe fn all_users(&self) -> Vec<User> {
+        self.users()
+    }
+
+    unsafe fn all_groups(&self) -> Vec<Group> {
+        self.groups()
+    }
+}
+
+/// The source that the top-level functions are routed through, if one has
+/// been set.
+static GLOBAL_SOURCE: RwLock<Option<Arc<dyn UserDbSource>>> = RwLock::new(None);
+
+/// Routes the top-level functions through the given source, for every thread
+/// of the process, replacing any source set before.
+///
+/// Lookups that are already running finish with the source they started
+/// with.
+pub fn set_global_source<S: UserDbSource + 'static>(source: S) {
+    *GLOBAL_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(source));
+}
+
+/// Routes the top-level functions through the C library again.
+pub fn clear_global_source() {
+    *GLOBAL_SOURCE.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the source that the top-level functions are routed through, or
+/// `None` if they use the C library.
+pub(crate) fn global_source() -> Option<Arc<dyn UserDbSource>> {
+    GLOBAL_SOURCE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fixtures() -> FilesSource {
+        FilesSource::new("tests/fixtures/passwd", "tests/fixtures/group")
+    }
+
+    #[test]
+    fn files_lookups() {
+        let source = fixtures();
+        assert_eq!(source.user_by_uid(1337).unwrap().name(), "fred");
+        assert_eq!(source.group_by_name("bosses".as_ref()).unwrap().gid(), 42);
+        assert!(source.user_by_name("bob".as_ref()).is_none());
+    }
+
+    #[test]
+    fn files_user_groups() {
+        let groups = fixtures().user_groups("bob".as_ref(), 42).unwrap();
+        let gids = groups.iter().map(Group::gid).collect::<Vec<_>>();
+        assert_eq!(gids, vec![42, 43]);
+    }
+
+    #[test]
+    fn missing_files() {
+        let source = FilesSource::new("/nonexistent/passwd", "/nonexistent/group");
+        assert!(source.user_by_uid(0).is_none());
+        assert!(unsafe { source.all_groups() }.is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+//! Generating users and groups for property tests.
+//!
+//! The functions in this module return [`proptest`] strategies that produce
+//! users and groups, so that code that handles them can be tested against
+//! many more of them than a handful of fixtures:
+//!
+//! ```
+//! #[macro_use]
+//! extern crate proptest;
+//! # extern crate uzers;
+//!
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//! use uzers::strategy::user;
+//!
+//! let mut runner = TestRunner::default();
+//! runner
+//!     .run(&user(), |user| {
+//!         prop_assert!(!user.name().is_empty());
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! ```
+//!
+//! In a test suite, the strategies can be used with the `proptest!` macro
+//! in the same way.
+//!
+//! [`user`] and [`group`] produce entries like the ones found on most
+//! systems: short lowercase names, IDs that are not the sentinel
+//! `(uid_t) -1`, and paths that look like paths. [`adversarial_user`] and
+//! [`adversarial_group`] produce entries that a system could only have by
+//! accident or by malice, and that a `passwd` or `group` file often cannot
+//! even hold: names that are not UTF-8, fields holding the `:`, `,` and
+//! newline separators of those files along with spaces and other control
+//! characters, any ID including the sentinel, very long fields, and groups
+//! with thousands of members. The only byte they never hold is NUL, which
+//! the C library’s entries cannot.
+//!
+//! This module is only available with the `proptest` feature.
+
+extern crate proptest;
+
+use self::proptest::collection::vec;
+use self::proptest::prelude::*;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+
+use libc::{gid_t, uid_t};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+use ids::{NO_GID, NO_UID};
+#[cfg(feature = "mock")]
+use mock::MockUsers;
+
+/// Returns a strategy for the IDs of users, leaving out the sentinel ID.
+pub fn uid() -> impl Strategy<Value = uid_t> {
+    (0..NO_UID).boxed()
+}
+
+/// Returns a strategy for the IDs of groups, leaving out the sentinel ID.
+pub fn gid() -> impl Strategy<Value = gid_t> {
+    (0..NO_GID).boxed()
+}
+
+/// Returns a strategy for names of users and groups in the form that
+/// `useradd` accepts by default.
+pub fn name() -> impl Strategy<Value = String> {
+    "[a-z_][a-z0-9_-]{0,31}"
+}
+
+/// Returns a strategy for names made of any bytes that can be stored in the
+/// C library’s entries, which is every byte but NUL.
+pub fn adversarial_name() -> impl Strategy<Value = OsString> {
+    vec(1..=u8::MAX, 1..256).prop_map(OsString::from_vec)
+}
+
+/// Returns a strategy for users like the ones found on most systems.
+pub fn user() -> impl Strategy<Value = User> {
+    let shell = prop_oneof![
+        Just("/bin/sh"),
+        Just("/bin/bash"),
+        Just("/usr/bin/zsh"),
+        Just("/usr/sbin/nologin"),
+    ];
+
+    (uid(), name(), gid(), "[A-Za-z ,.]{0,40}", shell).prop_map(|(uid, name, gid, gecos, shell)| {
+        User::new(uid, &name, gid)
+            .with_home_dir(&format!("/home/{}", name))
+            .with_shell(shell)
+            .with_gecos(&gecos)
+    })
+}
+
+/// Returns a strategy for users whose names, home directories, shells and
+/// GECOS fields are made of any bytes but NUL, and can be very long.
+pub fn adversarial_user() -> impl Strategy<Value = User> {
+    let field = || vec(1..=u8::MAX, 0..4096).prop_map(OsString::from_vec);
+
+    (
+        any::<uid_t>(),
+        adversarial_name(),
+        any::<gid_t>(),
+        field(),
+        field(),
+        field(),
+    )
+        .prop_map(|(uid, name, gid, home_dir, shell, gecos)| {
+            User::new(uid, &name, gid)
+                .with_home_dir(&home_dir)
+                .with_shell(&shell)
+                .with_gecos(&gecos)
+        })
+}
+
+/// Returns a strategy for groups like the ones found on most systems, with
+/// up to 8 members.
+pub fn group() -> impl Strategy<Value = Group> {
+    (gid(), name(), vec(name(), 0..8)).prop_map(|(gid, name, members)| {
+        members
+            .iter()
+            .fold(Group::new(gid, &name), |group, member| {
+                group.add_member(member)
+            })
+    })
+}
+
+/// Returns a strategy for groups whose names and members are made of any
+/// bytes but NUL, with up to 10,000 members.
+pub fn adversarial_group() -> impl Strategy<Value = Group> {
+    (
+        any::<gid_t>(),
+        adversarial_name(),
+        vec(adversarial_name(), 0..10_000),
+    )
+        .prop_map(|(gid, name, members)| {
+            members
+                .iter()
+                .fold(Group::new(gid, &name), |group, member| {
+                    group.add_member(member)
+                })
+        })
+}
+
+/// Returns a strategy for mock tables of up to 32 users and 32 groups made
+/// by [`user`] and [`group`].
+///
+/// The current and effective user is one of the users in the table, if it
+/// holds any, and its primary group is the current and effective group.
+#[cfg(feature = "mock")]
+pub fn mock_users() -> impl Strategy<Value = MockUsers> {
+    (
+        vec(user(), 0..32),
+        vec(group(), 0..32),
+        any::<prop::sample::Index>(),
+    )
+        .prop_map(|(users, groups, current)| {
+            let (uid, gid) = match users.is_empty() {
+                true => (0, 0),
+                false => {
+                    let user = current.get(&users);
+                    (user.uid(), user.primary_group_id())
+                }
+            };
+
+            let mut table = MockUsers::with_current_uid(uid).with_current_gid(gid);
+            table.extend(users);
+            table.extend(groups);
+            table
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use record::{group_field, parse_group_field};
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn users_are_valid(user in user()) {
+            prop_assert_ne!(user.uid(), NO_UID);
+            prop_assert!(user.name().to_str().is_some());
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn adversarial_groups_round_trip(group in adversarial_group()) {
+            // A lookup trace escapes every field, so everything but the
+            // sentinel ID, which no group can have, is read back as it was.
+            let parsed = parse_group_field(&group_field(Some(&group)));
+            prop_assert_eq!(parsed.is_some(), group.gid() != NO_GID);
+            if let Some(parsed) = parsed {
+                prop_assert_eq!(parsed.gid(), group.gid());
+                prop_assert_eq!(parsed.name(), group.name());
+                prop_assert_eq!(parsed.members(), group.members());
+            }
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        #[test]
+        fn current_user_is_in_table(users in mock_users()) {
+            use traits::{AllUsers, Users};
+
+            let current = users.get_user_by_uid(users.get_current_uid());
+            prop_assert_eq!(current.is_some(), users.get_all_users().count() > 0);
+        }
+    }
+}
@@ -2,8 +2,11 @@
 
 use libc::{c_int, gid_t, uid_t};
 use std::io;
+use std::os::unix::process::CommandExt as _;
+use std::process::Command;
 
-use base::{get_effective_gid, get_effective_uid};
+use base::os::unix::UserExt;
+use base::{get_effective_gid, get_effective_uid, get_user_groups, User};
 
 // NOTE: for whatever reason, it seems these are not available in libc on BSD platforms, so they
 //       need to be included manually
@@ -261,3 +264,116 @@ pub fn switch_user_group(uid: uid_t, gid: gid_t) -> io::Result<SwitchUserGuard>
     set_effective_uid(uid)?;
     Ok(current_state)
 }
+
+/// Extension to `std::process::Command` to run the command as another user.
+pub trait CommandExt {
+    /// Runs the command as the given user: with their user ID, their primary
+    /// group ID, and the supplementary groups they are a member of.
+    ///
+    /// The groups are set before the group ID, and the group ID before the
+    /// user ID, so the privileges needed to set each one are only dropped
+    /// once they are set. Typically, running a command as anyone other than
+    /// the user already running the process requires root privileges.
+    ///
+    /// The supplementary groups are looked up when this is called, as
+    /// `initgroups` would look them up, and set with `setgroups` in the child
+    /// process: `initgroups` itself is not safe to call between forking and
+    /// running the command.
+    ///
+    /// Do not combine this with `std`’s `uid` and `gid` methods, which leave
+    /// the command without supplementary groups. `std` switches to those IDs
+    /// before the closures given to `pre_exec` run, so by the time this sets
+    /// the groups, the privileges it needs are gone, and spawning the command
+    /// fails with `EPERM`. The `Command` does not tell whether they were
+    /// called, so this cannot check for it.
+    ///
+    /// # libc functions used
+    ///
+    /// - [`getgrouplist`](https://docs.rs/libc/*/libc/fn.getgrouplist.html)
+    /// - [`setgroups`](https://docs.rs/libc/*/libc/fn.setgroups.html)
+    /// - [`setgid`](https://docs.rs/libc/*/libc/fn.setgid.html)
+    /// - [`setuid`](https://docs.rs/libc/*/libc/fn.setuid.html)
+    ///
+    /// # Errors
+    ///
+    /// Spawning the command will return `Err` when an I/O error occurs
+    /// during any of the calls in the child process.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::process::Command;
+    /// use uzers::get_user_by_name;
+    /// use uzers::switch::CommandExt;
+    ///
+    /// let user = get_user_by_name("nobody").unwrap();
+    /// let status = Command::new("id")
+    ///     .run_as_user(&user)
+    ///     .with_user_env(&user)
+    ///     .status()
+    ///     .unwrap();
+    /// ```
+    fn run_as_user(&mut self, user: &User) -> &mut Self;
+
+    /// Sets the `HOME`, `USER`, `LOGNAME` and `SHELL` environment variables
+    /// of the command from the given user, as a login would.
+    fn with_user_env(&mut self, user: &User) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn run_as_user(&mut self, user: &User) -> &mut Self {
+        let uid = user.uid();
+        let gid = user.primary_group_id();
+        let groups = get_user_groups(user.name(), gid)
+            .map(|groups| groups.iter().map(|g| g.gid()).collect::<Vec<_>>())
+            .unwrap_or_else(|| vec![gid]);
+
+        let switch = move || {
+            // The count is a `size_t` on some platforms and a `c_int` on others.
+            #[allow(trivial_numeric_casts)]
+            let count = groups.len() as _;
+            if unsafe { libc::setgroups(count, groups.as_ptr()) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            set_current_gid(gid)?;
+            set_current_uid(uid)
+        };
+
+        // Everything the closure calls is safe to call after forking.
+        unsafe { self.pre_exec(switch) }
+    }
+
+    fn with_user_env(&mut self, user: &User) -> &mut Self {
+        self.env("HOME", user.home_dir())
+            .env("USER", user.name())
+            .env("LOGNAME", user.name())
+            .env("SHELL", user.shell())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use base::{get_current_uid, get_user_by_uid};
+
+    #[test]
+    fn run_as_current_user() {
+        let Some(user) = get_user_by_uid(get_current_uid()) else {
+            return;
+        };
+
+        let output = Command::new("id")
+            .arg("-u")
+            .run_as_user(&user)
+            .with_user_env(&user)
+            .output();
+
+        if get_current_uid() == 0 {
+            let output = output.unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "0");
+        } else {
+            assert!(output.is_err());
+        }
+    }
+}
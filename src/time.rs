@@ -0,0 +1,172 @@
+//! Sources of the current time.
+//!
+//! Some of this crate’s functions depend on what time it is: a report of
+//! stale accounts compares last logins to the current date, and a cache
+//! whose entries expire compares their age to the current time. To test code
+//! that uses them, the current time can be supplied by a [`Clock`].
+//!
+//! [`SystemClock`] reads the system’s real-time clock, and is what the
+//! functions that don’t take a clock use. [`MockClock`] returns a time that
+//! only moves when it’s told to:
+//!
+//! ```
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use uzers::time::{Clock, MockClock};
+//!
+//! let clock = MockClock::new(UNIX_EPOCH);
+//! clock.advance(Duration::from_secs(60));
+//! assert_eq!(clock.now(), UNIX_EPOCH + Duration::from_secs(60));
+//! ```
+//!
+//! Functions that check a date, such as
+//! [`Shadow::is_account_expired_at`](../shadow/struct.Shadow.html#method.is_account_expired_at),
+//! take it as a `SystemTime`, so pass them `clock.now()`.
+//!
+//! A clock also has a monotonic side, returned by [`Clock::instant`], which
+//! caches use to measure the age of their entries so that setting the
+//! system’s clock back or forth neither keeps entries forever nor expires
+//! them all at once.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// A source of the current time.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current time of a monotonic clock, which only ever moves
+    /// forwards, for measuring how much time has passed.
+    fn instant(&self) -> Instant;
+
+    /// Returns how much time has passed since the given time, or zero if it
+    /// lies in the future.
+    fn elapsed_since(&self, earlier: SystemTime) -> Duration {
+        self.now().duration_since(earlier).unwrap_or_default()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn instant(&self) -> Instant {
+        (**self).instant()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> SystemTime {
+        (**self).now()
+    }
+
+    fn instant(&self) -> Instant {
+        (**self).instant()
+    }
+}
+
+/// A clock that reads the system’s real-time and monotonic clocks.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when it’s told to.
+///
+/// Clones of a mock clock share the same time, so a test can keep one clone
+/// to move the time of another that it has handed over.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    now: Arc<Mutex<(SystemTime, Instant)>>,
+}
+
+impl MockClock {
+    /// Creates a new clock that is stopped at the given time.
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Arc::new(Mutex::new((now, Instant::now()))),
+        }
+    }
+
+    /// Moves the clock forwards by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        now.0 += duration;
+        now.1 += duration;
+    }
+
+    /// Sets the clock to the given time, which may be earlier than the
+    /// current one.
+    ///
+    /// Like setting the system’s clock, this leaves the monotonic time
+    /// returned by [`instant`](Clock::instant) where it was.
+    pub fn set(&self, now: SystemTime) {
+        self.now.lock().unwrap().0 = now;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        self.now.lock().unwrap().0
+    }
+
+    fn instant(&self) -> Instant {
+        self.now.lock().unwrap().1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn clones_share_time() {
+        let clock = MockClock::new(UNIX_EPOCH);
+        let clone = clock.clone();
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clone.now(), UNIX_EPOCH + Duration::from_secs(5));
+
+        clone.set(UNIX_EPOCH);
+        assert_eq!(clock.now(), UNIX_EPOCH);
+    }
+
+    #[test]
+    fn setting_leaves_instant() {
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(100));
+        let start = clock.instant();
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(clock.instant() - start, Duration::from_secs(5));
+
+        clock.set(UNIX_EPOCH);
+        assert_eq!(clock.instant() - start, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn elapsed() {
+        let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(10));
+        assert_eq!(clock.elapsed_since(UNIX_EPOCH), Duration::from_secs(10));
+        assert_eq!(
+            clock.elapsed_since(UNIX_EPOCH + Duration::from_secs(20)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn system_clock_moves() {
+        let before = SystemTime::now();
+        assert!(SystemClock.now() >= before);
+    }
+}
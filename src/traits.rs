@@ -1,9 +1,13 @@
 use std::ffi::OsStr;
+use std::io;
 use std::sync::Arc;
 
 use libc::{gid_t, uid_t};
 
+use base::os::unix::GroupExt;
 use base::{Group, User};
+use identity::Identity;
+use ids::{Gid, Uid};
 
 /// Trait for producers of users.
 pub trait Users {
@@ -56,6 +60,12 @@ pub trait AllUsers {
 
     /// Creates a new iterator over every user.
     fn get_all_users(&self) -> Self::UserIter<'_>;
+
+    /// Creates a new iterator over every user, boxed so that its type does
+    /// not have to be named.
+    fn get_all_users_boxed(&self) -> Box<dyn Iterator<Item = &User> + '_> {
+        Box::new(self.get_all_users())
+    }
 }
 
 /// Trait for providers of group iterators.
@@ -67,4 +77,722 @@ pub trait AllGroups {
 
     /// Creates a new iterator over every group.
     fn get_all_groups(&self) -> Self::GroupIter<'_>;
+
+    /// Creates a new iterator over every group, boxed so that its type does
+    /// not have to be named.
+    fn get_all_groups_boxed(&self) -> Box<dyn Iterator<Item = &Group> + '_> {
+        Box::new(self.get_all_groups())
+    }
+}
+
+/// Trait for providers of iterators over shared users that do not borrow
+/// the provider.
+///
+/// The users returned by [`AllUsers`] borrow the provider, so they cannot be
+/// kept once it’s gone, or sent to another thread while it stays behind.
+/// The iterator returned by this trait yields an `Arc` of each user instead.
+///
+/// ## Example
+///
+/// ```
+/// use std::thread;
+/// use uzers::{AllUsersOwned, User};
+/// use uzers::mock::MockUsers;
+///
+/// let mut users = MockUsers::with_current_uid(1000);
+/// users.add_user(User::new(1000, "fred", 100));
+///
+/// let all = users.get_all_users_owned();
+/// drop(users);
+/// let names = thread::spawn(move || all.map(|u| u.name().to_owned()).collect::<Vec<_>>());
+/// assert_eq!(names.join().unwrap(), ["fred"]);
+/// ```
+pub trait AllUsersOwned {
+    /// [`User`] iterator returned by
+    /// [`get_all_users_owned`][Self::get_all_users_owned].
+    type OwnedUserIter: Iterator<Item = Arc<User>>;
+
+    /// Creates a new iterator over every user.
+    fn get_all_users_owned(&self) -> Self::OwnedUserIter;
+}
+
+/// Trait for providers of iterators over shared groups that do not borrow
+/// the provider.
+///
+/// See [`AllUsersOwned`].
+pub trait AllGroupsOwned {
+    /// [`Group`] iterator returned by
+    /// [`get_all_groups_owned`][Self::get_all_groups_owned].
+    type OwnedGroupIter: Iterator<Item = Arc<Group>>;
+
+    /// Creates a new iterator over every group.
+    fn get_all_groups_owned(&self) -> Self::OwnedGroupIter;
+}
+
+/// Trait for producers of both users and groups, with helpers for the
+/// lookups that need both.
+///
+/// Every type that implements [`Users`] and [`Groups`] implements this
+/// trait, so code that needs both can ask for `U: UserDatabase` instead of
+/// naming each of them.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::{Group, Uid, User, UserDatabase};
+/// use uzers::mock::MockUsers;
+///
+/// fn describe<U: UserDatabase>(users: &U, uid: Uid) -> Option<String> {
+///     let (user, group) = users.user_and_primary_group(uid)?;
+///     let group = group.map_or_else(|| "?".into(), |g| g.name().to_string_lossy().into_owned());
+///     Some(format!("{}:{}", user.name().to_string_lossy(), group))
+/// }
+///
+/// let mut users = MockUsers::with_current_uid(1000);
+/// users.add_user(User::new(1000, "fred", 100));
+/// users.add_group(Group::new(100, "staff"));
+/// assert_eq!(describe(&users, Uid::from_raw(1000)).as_deref(), Some("fred:staff"));
+/// ```
+pub trait UserDatabase: Users + Groups {
+    /// Returns the primary group of the given user, or `None` if there is
+    /// no group with its ID.
+    fn primary_group_of(&self, user: &User) -> Option<Arc<Group>> {
+        self.get_group_by_gid(user.primary_group_id())
+    }
+
+    /// Returns the user with the given ID along with their primary group,
+    /// or `None` if there is no such user. The group is `None` if the user’s
+    /// primary group does not exist.
+    fn user_and_primary_group(&self, uid: Uid) -> Option<(Arc<User>, Option<Arc<Group>>)> {
+        let user = self.get_user_by_uid(uid.as_raw())?;
+        let group = self.primary_group_of(&user);
+        Some((user, group))
+    }
+
+    /// Returns the user running the process, or `None` if there is no user
+    /// with its ID.
+    fn current_user(&self) -> Option<Arc<User>> {
+        self.get_user_by_uid(self.get_current_uid())
+    }
+
+    /// Returns the group of the user running the process, or `None` if
+    /// there is no group with its ID.
+    fn current_group(&self) -> Option<Arc<Group>> {
+        self.get_group_by_gid(self.get_current_gid())
+    }
+
+    /// Returns whether the user with the given ID is a member of the group
+    /// with the given ID: either it’s their primary group, or the group lists
+    /// them as a member. Returns `false` if there is no such user.
+    ///
+    /// Only the member list of the group is checked, so a membership that
+    /// the system only knows of some other way, such as through a directory
+    /// service that does not list members, is not found. Use
+    /// [`SupplementaryGroups::groups_of`] for those.
+    ///
+    /// ```
+    /// use uzers::{Gid, Group, Uid, User, UserDatabase};
+    /// use uzers::mock::MockUsers;
+    /// use uzers::os::unix::GroupExt;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    /// users.add_group(Group::new(10, "wheel").add_member("fred"));
+    ///
+    /// let fred = Uid::from_raw(1000);
+    /// assert!(users.is_user_in_group(fred, Gid::from_raw(10)));
+    /// assert!(users.is_user_in_group(fred, Gid::from_raw(100)));
+    /// assert!(!users.is_user_in_group(fred, Gid::from_raw(27)));
+    /// ```
+    fn is_user_in_group(&self, uid: Uid, gid: Gid) -> bool {
+        let Some(user) = self.get_user_by_uid(uid.as_raw()) else {
+            return false;
+        };
+        if user.primary_group_id() == gid.as_raw() {
+            return true;
+        }
+        self.get_group_by_gid(gid.as_raw())
+            .is_some_and(|g| g.members().iter().any(|m| m == user.name()))
+    }
+
+    /// Returns whether the user with the given name is a member of the group
+    /// with the given name, as [`is_user_in_group`] does for IDs. Returns
+    /// `false` if there is no such user or no such group.
+    ///
+    /// [`is_user_in_group`]: UserDatabase::is_user_in_group
+    ///
+    /// ```
+    /// use uzers::{Group, User, UserDatabase};
+    /// use uzers::mock::MockUsers;
+    /// use uzers::os::unix::GroupExt;
+    ///
+    /// let mut users = MockUsers::with_current_uid(1000);
+    /// users.add_user(User::new(1000, "fred", 100));
+    /// users.add_group(Group::new(100, "staff"));
+    /// users.add_group(Group::new(10, "wheel").add_member("fred"));
+    ///
+    /// assert!(users.is_user_in_group_by_name("fred", "wheel"));
+    /// assert!(users.is_user_in_group_by_name("fred", "staff"));
+    /// assert!(!users.is_user_in_group_by_name("fred", "docker"));
+    /// ```
+    fn is_user_in_group_by_name<U, G>(&self, username: &U, group_name: &G) -> bool
+    where
+        U: AsRef<OsStr> + ?Sized,
+        G: AsRef<OsStr> + ?Sized,
+    {
+        let Some(user) = self.get_user_by_name(username) else {
+            return false;
+        };
+        let Some(group) = self.get_group_by_name(group_name) else {
+            return false;
+        };
+        user.primary_group_id() == group.gid() || group.members().iter().any(|m| m == user.name())
+    }
+}
+
+impl<T: Users + Groups + ?Sized> UserDatabase for T {}
+
+/// Trait for producers of users that also know which groups each user is a
+/// member of.
+///
+/// This lets code that checks group membership be generic over where the
+/// memberships come from: the system, through one of the caches, a
+/// [`UsersSnapshot`](cache/struct.UsersSnapshot.html) that works them out
+/// from the member lists of its groups, or a
+/// [`MockUsers`](mock/struct.MockUsers.html) table in tests.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::{SupplementaryGroups, Uid, User, Group};
+/// use uzers::mock::MockUsers;
+/// use uzers::os::unix::GroupExt;
+///
+/// fn is_admin<U: SupplementaryGroups>(users: &U, uid: Uid) -> bool {
+///     users.groups_of(uid).unwrap_or_default().iter().any(|g| g.name() == "wheel")
+/// }
+///
+/// let mut users = MockUsers::with_current_uid(1000);
+/// users.add_user(User::new(1000, "fred", 100));
+/// users.add_group(Group::new(10, "wheel").add_member("fred"));
+/// assert!(is_admin(&users, Uid::from_raw(1000)));
+/// ```
+pub trait SupplementaryGroups: Users {
+    /// Returns every group the user with the given name is a member of,
+    /// along with the group with the given ID, which is taken to be their
+    /// primary group. Returns `None` if the groups cannot be found.
+    fn get_user_groups<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+        gid: gid_t,
+    ) -> Option<Vec<Arc<Group>>>;
+
+    /// Returns every group the user with the given ID is a member of,
+    /// including their primary group, or `None` if there is no such user.
+    fn groups_of(&self, uid: Uid) -> Option<Vec<Arc<Group>>> {
+        let user = self.get_user_by_uid(uid.as_raw())?;
+        self.get_user_groups(user.name(), user.primary_group_id())
+    }
+
+    /// Returns the user with the given ID along with their primary and
+    /// supplementary groups, or `None` if there is no such user.
+    ///
+    /// The groups are found with [`groups_of`](SupplementaryGroups::groups_of),
+    /// so the caches answer from the lists they keep for each user.
+    ///
+    /// See [`identity_of`](fn.identity_of.html).
+    fn identity_of(&self, uid: Uid) -> Option<Identity>
+    where
+        Self: Groups,
+    {
+        let user = self.get_user_by_uid(uid.as_raw())?;
+        let primary_group = self.get_group_by_gid(user.primary_group_id());
+        let groups = self.groups_of(uid).unwrap_or_default();
+        Some(Identity::new(user, primary_group, groups))
+    }
+}
+
+/// Trait for producers of users whose lookups can fail.
+///
+/// [`Users`] cannot tell a user that does not exist from a lookup that
+/// failed, such as one that timed out asking a directory service, or one of
+/// a file that could not be read. Backends that can tell these apart
+/// implement this trait, returning `Ok(None)` when there is no such user and
+/// `Err` when the lookup failed, so that code generic over them can report
+/// the error.
+///
+/// The caches that ask the C library return the error it reported, and
+/// [`MockUsers`](mock/struct.MockUsers.html) fails the lookups it has been
+/// set up to fail. Producers whose lookups cannot fail, such as snapshots,
+/// implement this trait too, and never return `Err`.
+///
+/// ## Example
+///
+/// ```
+/// use std::io;
+/// use uzers::{TryUsers, User};
+/// use uzers::mock::MockUsers;
+///
+/// fn name_of<U: TryUsers>(users: &U, uid: u32) -> io::Result<String> {
+///     match users.try_get_user_by_uid(uid)? {
+///         Some(user) => Ok(user.name().to_string_lossy().into_owned()),
+///         None => Ok(format!("#{}", uid)),
+///     }
+/// }
+///
+/// let mut users = MockUsers::with_current_uid(1000);
+/// users.add_user(User::new(1000, "fred", 100));
+/// assert_eq!(name_of(&users, 1000).unwrap(), "fred");
+/// assert_eq!(name_of(&users, 1001).unwrap(), "#1001");
+/// ```
+pub trait TryUsers {
+    /// Returns the user with the given ID, `Ok(None)` if there is no such
+    /// user, or the error the lookup failed with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the user could not be looked up.
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>>;
+
+    /// Returns the user with the given name, `Ok(None)` if there is no such
+    /// user, or the error the lookup failed with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the user could not be looked up.
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>>;
+}
+
+impl<U: TryUsers + ?Sized> TryUsers for Box<U> {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        (**self).try_get_user_by_uid(uid)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        (**self).try_get_user_by_name(username)
+    }
+}
+
+/// Trait for producers of groups whose lookups can fail.
+///
+/// See [`TryUsers`].
+pub trait TryGroups {
+    /// Returns the group with the given ID, `Ok(None)` if there is no such
+    /// group, or the error the lookup failed with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the group could not be looked up.
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>>;
+
+    /// Returns the group with the given name, `Ok(None)` if there is no such
+    /// group, or the error the lookup failed with.
+    ///
+    /// # Errors
+    ///
+    /// This function will return `Err` if the group could not be looked up.
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>>;
+}
+
+impl<G: TryGroups + ?Sized> TryGroups for Box<G> {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        (**self).try_get_group_by_gid(gid)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        (**self).try_get_group_by_name(group_name)
+    }
+}
+
+/// A version of [`Users`] that can be used as a trait object.
+///
+/// The lookup by name of `Users` is generic over the type of the name, so
+/// there can be no `dyn Users`. This trait takes the name as an `&OsStr`
+/// instead, and is implemented for every type that implements `Users`, so
+/// the provider can be chosen at runtime:
+///
+/// ```
+/// use uzers::{DynUsers, Users, UsersCache};
+/// use uzers::mock::MockUsers;
+///
+/// fn current_name<U: Users>(users: &U) -> Option<String> {
+///     users.get_current_username().map(|n| n.to_string_lossy().into_owned())
+/// }
+///
+/// let testing = true;
+/// let users: Box<dyn DynUsers> = if testing {
+///     Box::new(MockUsers::with_current_uid(1000))
+/// } else {
+///     Box::new(UsersCache::new())
+/// };
+///
+/// assert_eq!(current_name(&users), None);
+/// ```
+///
+/// `dyn DynUsers`, and `Box`es of it, implement `Users` in turn, so they can
+/// be passed to code that takes any `Users`. A `Box<dyn DynUsers + Send>`
+/// has to be converted to a `Box<dyn DynUsers>` first.
+pub trait DynUsers {
+    /// Returns a `User` if one exists for the given user ID; otherwise, returns `None`.
+    fn user_by_uid(&self, uid: uid_t) -> Option<Arc<User>>;
+
+    /// Returns a `User` if one exists for the given username; otherwise, returns `None`.
+    fn user_by_name(&self, username: &OsStr) -> Option<Arc<User>>;
+
+    /// Returns the user ID for the user running the process.
+    fn current_uid(&self) -> uid_t;
+
+    /// Returns the username of the user running the process.
+    fn current_username(&self) -> Option<Arc<OsStr>>;
+
+    /// Returns the effective user id.
+    fn effective_uid(&self) -> uid_t;
+
+    /// Returns the effective username.
+    fn effective_username(&self) -> Option<Arc<OsStr>>;
+}
+
+impl<T: Users> DynUsers for T {
+    fn user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.get_user_by_uid(uid)
+    }
+
+    fn user_by_name(&self, username: &OsStr) -> Option<Arc<User>> {
+        self.get_user_by_name(username)
+    }
+
+    fn current_uid(&self) -> uid_t {
+        self.get_current_uid()
+    }
+
+    fn current_username(&self) -> Option<Arc<OsStr>> {
+        self.get_current_username()
+    }
+
+    fn effective_uid(&self) -> uid_t {
+        self.get_effective_uid()
+    }
+
+    fn effective_username(&self) -> Option<Arc<OsStr>> {
+        self.get_effective_username()
+    }
+}
+
+impl Users for dyn DynUsers + '_ {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.user_by_uid(uid)
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.user_by_name(username.as_ref())
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        self.current_uid()
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        self.current_username()
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        self.effective_uid()
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        self.effective_username()
+    }
+}
+
+impl<U: Users + ?Sized> Users for Box<U> {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        (**self).get_user_by_uid(uid)
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        (**self).get_user_by_name(username)
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        (**self).get_current_uid()
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        (**self).get_current_username()
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        (**self).get_effective_uid()
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        (**self).get_effective_username()
+    }
+}
+
+/// A version of [`Groups`] that can be used as a trait object.
+///
+/// See [`DynUsers`].
+pub trait DynGroups {
+    /// Returns a `Group` if one exists for the given group ID; otherwise, returns `None`.
+    fn group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>>;
+
+    /// Returns a `Group` if one exists for the given groupname; otherwise, returns `None`.
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Arc<Group>>;
+
+    /// Returns the group ID for the user running the process.
+    fn current_gid(&self) -> gid_t;
+
+    /// Returns the group name of the user running the process.
+    fn current_groupname(&self) -> Option<Arc<OsStr>>;
+
+    /// Returns the effective group id.
+    fn effective_gid(&self) -> gid_t;
+
+    /// Returns the effective group name.
+    fn effective_groupname(&self) -> Option<Arc<OsStr>>;
+}
+
+impl<T: Groups> DynGroups for T {
+    fn group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.get_group_by_gid(gid)
+    }
+
+    fn group_by_name(&self, group_name: &OsStr) -> Option<Arc<Group>> {
+        self.get_group_by_name(group_name)
+    }
+
+    fn current_gid(&self) -> gid_t {
+        self.get_current_gid()
+    }
+
+    fn current_groupname(&self) -> Option<Arc<OsStr>> {
+        self.get_current_groupname()
+    }
+
+    fn effective_gid(&self) -> gid_t {
+        self.get_effective_gid()
+    }
+
+    fn effective_groupname(&self) -> Option<Arc<OsStr>> {
+        self.get_effective_groupname()
+    }
+}
+
+impl Groups for dyn DynGroups + '_ {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.group_by_gid(gid)
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.group_by_name(group_name.as_ref())
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        self.current_gid()
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        self.current_groupname()
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        self.effective_gid()
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        self.effective_groupname()
+    }
+}
+
+impl<G: Groups + ?Sized> Groups for Box<G> {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        (**self).get_group_by_gid(gid)
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        (**self).get_group_by_name(group_name)
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        (**self).get_current_gid()
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        (**self).get_current_groupname()
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        (**self).get_effective_gid()
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        (**self).get_effective_groupname()
+    }
+}
+
+/// A version of [`AllUsers`] that can be used as a trait object.
+///
+/// The iterator type of `AllUsers` depends on the lifetime of the provider,
+/// so there can be no `dyn AllUsers`. This trait returns a boxed iterator
+/// instead, and is implemented for every type that implements `AllUsers`.
+///
+/// ## Example
+///
+/// ```
+/// use uzers::{DynAllUsers, NullUsers, User};
+/// use uzers::mock::MockUsers;
+///
+/// let mut mock = MockUsers::with_current_uid(1000);
+/// mock.add_user(User::new(1000, "fred", 100));
+///
+/// let providers: Vec<Box<dyn DynAllUsers>> = vec![Box::new(mock), Box::new(NullUsers::new())];
+/// let total: usize = providers.iter().map(|p| p.all_users().count()).sum();
+/// assert_eq!(total, 1);
+/// ```
+pub trait DynAllUsers {
+    /// Creates a new iterator over every user.
+    fn all_users(&self) -> Box<dyn Iterator<Item = &User> + '_>;
+}
+
+impl<T: AllUsers> DynAllUsers for T {
+    fn all_users(&self) -> Box<dyn Iterator<Item = &User> + '_> {
+        self.get_all_users_boxed()
+    }
+}
+
+/// A version of [`AllGroups`] that can be used as a trait object.
+///
+/// See [`DynAllUsers`].
+pub trait DynAllGroups {
+    /// Creates a new iterator over every group.
+    fn all_groups(&self) -> Box<dyn Iterator<Item = &Group> + '_>;
+}
+
+impl<T: AllGroups> DynAllGroups for T {
+    fn all_groups(&self) -> Box<dyn Iterator<Item = &Group> + '_> {
+        self.get_all_groups_boxed()
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::*;
+
+    use mock::MockUsers;
+    use null::NullUsers;
+
+    #[test]
+    fn trait_objects() {
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(1000, "fred", 100));
+        mock.add_group(Group::new(100, "staff"));
+
+        let users: Box<dyn DynUsers> = Box::new(mock);
+        assert_eq!(users.get_user_by_name("fred").unwrap().uid(), 1000);
+        assert_eq!(&*users.get_current_username().unwrap(), "fred");
+
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_group(Group::new(1000, "staff"));
+        let groups: &dyn DynGroups = &mock;
+        assert_eq!(groups.get_group_by_name("staff").unwrap().gid(), 1000);
+        assert_eq!(&*groups.get_current_groupname().unwrap(), "staff");
+    }
+
+    #[test]
+    fn database() {
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(1000, "fred", 100));
+        mock.add_user(User::new(1001, "jane", 101));
+        mock.add_group(Group::new(100, "staff"));
+
+        let (user, group) = mock.user_and_primary_group(Uid::from_raw(1000)).unwrap();
+        assert_eq!(user.name(), "fred");
+        assert_eq!(group.unwrap().name(), "staff");
+        assert!(mock
+            .user_and_primary_group(Uid::from_raw(1001))
+            .unwrap()
+            .1
+            .is_none());
+        assert!(mock.user_and_primary_group(Uid::from_raw(1002)).is_none());
+        assert_eq!(mock.current_user().unwrap().uid(), 1000);
+        assert!(mock.current_group().is_none());
+
+        mock.add_group(Group::new(10, "wheel"));
+        let in_group = |uid, gid| mock.is_user_in_group(Uid::from_raw(uid), Gid::from_raw(gid));
+        assert!(!in_group(1000, 10));
+        mock.add_user_to_group(1000, 10);
+        let in_group = |uid, gid| mock.is_user_in_group(Uid::from_raw(uid), Gid::from_raw(gid));
+        assert!(in_group(1000, 10));
+        assert!(!in_group(1001, 10));
+        assert!(in_group(1001, 101));
+        assert!(!in_group(1002, 101));
+
+        assert!(mock.is_user_in_group_by_name("fred", "wheel"));
+        assert!(mock.is_user_in_group_by_name("fred", "staff"));
+        assert!(!mock.is_user_in_group_by_name("jane", "wheel"));
+        assert!(!mock.is_user_in_group_by_name("jane", "nobody"));
+        assert!(!mock.is_user_in_group_by_name("bob", "staff"));
+    }
+
+    #[test]
+    fn identity() {
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(1000, "fred", 100));
+        mock.add_group(Group::new(100, "staff"));
+        mock.add_group(Group::new(10, "wheel"));
+        mock.add_user_to_group(1000, 10);
+
+        let identity = mock.identity_of(Uid::from_raw(1000)).unwrap();
+        assert_eq!(identity.user.name(), "fred");
+        assert_eq!(identity.primary_group.unwrap().gid(), 100);
+        let gids = identity.supplementary_groups.iter().map(|g| g.gid());
+        assert_eq!(gids.collect::<Vec<_>>(), [10]);
+        assert!(mock.identity_of(Uid::from_raw(1001)).is_none());
+    }
+
+    #[test]
+    fn boxed_iterators() {
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_user(User::new(1000, "fred", 100));
+        mock.add_group(Group::new(100, "staff"));
+        assert_eq!(mock.get_all_users_boxed().count(), 1);
+
+        let groups: &dyn DynAllGroups = &mock;
+        let names = groups.all_groups().map(|g| g.name().to_owned());
+        assert_eq!(names.collect::<Vec<_>>(), ["staff"]);
+    }
+
+    #[test]
+    fn fallible() {
+        fn gid_of<G: TryGroups + ?Sized>(groups: &G, name: &str) -> io::Result<Option<gid_t>> {
+            Ok(groups.try_get_group_by_name(name)?.map(|g| g.gid()))
+        }
+
+        let mut mock = MockUsers::with_current_uid(1000);
+        mock.add_group(Group::new(100, "staff"));
+        mock.fail_groupname("staff", libc::EIO);
+
+        assert_eq!(
+            gid_of(&mock, "staff").unwrap_err().raw_os_error(),
+            Some(libc::EIO)
+        );
+        assert_eq!(gid_of(&mock, "wheel").unwrap(), None);
+        assert!(mock.get_group_by_name("staff").is_none());
+
+        let boxed = Box::new(NullUsers::new());
+        assert!(gid_of(&boxed, "staff").unwrap().is_none());
+    }
 }
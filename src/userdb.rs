@@ -0,0 +1,402 @@
+//! Users and groups from systemd’s user database.
+//!
+//! Since version 245, systemd exposes users and groups through the
+//! `io.systemd.UserDatabase` [Varlink](https://varlink.org/) interface. This
+//! includes users that never appear in `/etc/passwd`, such as *dynamic users*
+//! of services, users managed by `systemd-homed`, and the users of
+//! containers and virtual machines.
+//!
+//! The [`UserDb`] type queries that interface directly and implements the
+//! same [`Users`](../trait.Users.html) and [`Groups`](../trait.Groups.html)
+//! traits as the other providers in this crate, so it can be used wherever
+//! they are:
+//!
+//! ```no_run
+//! use uzers::{Users, userdb::UserDb};
+//!
+//! let userdb = UserDb::new();
+//! if let Some(user) = userdb.get_user_by_name("systemd-network") {
+//!     println!("Found user #{}", user.uid());
+//! }
+//! ```
+//!
+//! This module is only available with the `userdb` feature.
+
+extern crate serde_json;
+
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "logging")]
+extern crate log;
+#[cfg(feature = "logging")]
+use self::log::trace;
+
+use self::serde_json::{json, Value};
+use libc::{gid_t, uid_t};
+
+use base::os::unix::{GroupExt, UserExt};
+use base::{Group, User};
+use traits::{Groups, TryGroups, TryUsers, Users};
+
+/// The socket of the service that merges the records of every other user
+/// database service.
+pub const MULTIPLEXER_SOCKET: &str = "/run/systemd/userdb/io.systemd.Multiplexer";
+
+/// A provider of users and groups that queries systemd’s user database.
+///
+/// Every lookup connects to the service’s socket and sends one query, so
+/// this type does not cache anything. Combine it with a cache if lookups are
+/// repeated.
+#[derive(Clone, Debug)]
+pub struct UserDb {
+    socket: PathBuf,
+    service: String,
+}
+
+impl Default for UserDb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserDb {
+    /// Creates a provider that queries the multiplexer service, which
+    /// answers with the records of every user database service on the
+    /// system.
+    pub fn new() -> Self {
+        Self::with_socket(MULTIPLEXER_SOCKET)
+    }
+
+    /// Creates a provider that queries the service listening on the given
+    /// socket, such as `/run/systemd/userdb/io.systemd.DynamicUser`.
+    ///
+    /// The name of the service is taken from the socket’s file name.
+    pub fn with_socket<P: AsRef<Path>>(socket: P) -> Self {
+        let socket = socket.as_ref().to_path_buf();
+        let service = socket
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        Self { socket, service }
+    }
+
+    /// Calls a method of the `io.systemd.UserDatabase` interface, and
+    /// returns the `record` of the reply, or `None` if there is no such
+    /// record.
+    fn call(&self, method: &str, mut parameters: Value) -> io::Result<Option<Value>> {
+        parameters["service"] = Value::from(self.service.as_str());
+        let request = json!({
+            "method": format!("io.systemd.UserDatabase.{}", method),
+            "parameters": parameters,
+        });
+
+        #[cfg(feature = "logging")]
+        trace!("Sending {} to {:?}", request, self.socket);
+
+        let mut stream = UnixStream::connect(&self.socket)?;
+        let mut message = request.to_string().into_bytes();
+        message.push(0);
+        stream.write_all(&message)?;
+
+        let mut reply = Vec::new();
+        BufReader::new(stream).read_until(0, &mut reply)?;
+        if reply.pop() != Some(0) {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "incomplete varlink reply",
+            ));
+        }
+
+        let mut reply: Value = serde_json::from_slice(&reply)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        match reply["error"].as_str() {
+            None => Ok(Some(reply["parameters"]["record"].take())),
+            Some("io.systemd.UserDatabase.NoRecordFound") => Ok(None),
+            Some(error) => Err(io::Error::other(error.to_string())),
+        }
+    }
+
+    /// Logs and discards the error of a lookup, if it failed.
+    fn logged<T>(&self, result: io::Result<Option<T>>) -> Option<T> {
+        result
+            .inspect_err(|_e| {
+                #[cfg(feature = "logging")]
+                trace!("Query to {:?} failed: {}", self.socket, _e);
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Converts a record, if there is one, returning an `InvalidData` error if
+/// it is missing a field.
+fn converted<T>(
+    record: Option<Value>,
+    convert: fn(&Value) -> Option<T>,
+) -> io::Result<Option<Arc<T>>> {
+    match record {
+        Some(record) => match convert(&record) {
+            Some(converted) => Ok(Some(Arc::new(converted))),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record is missing a name or ID",
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Converts a JSON user record to a `User`.
+fn record_to_user(record: &Value) -> Option<User> {
+    let name = record["userName"].as_str()?;
+    let uid = record["uid"].as_u64()? as uid_t;
+    let gid = record["gid"].as_u64().map_or(uid, |g| g as gid_t);
+
+    let mut user = User::new(uid, name, gid);
+    if let Some(home) = record["homeDirectory"].as_str() {
+        user = user.with_home_dir(home);
+    }
+    if let Some(shell) = record["shell"].as_str() {
+        user = user.with_shell(shell);
+    }
+    if let Some(real_name) = record["realName"].as_str() {
+        user = user.with_gecos(real_name);
+    }
+
+    Some(user)
+}
+
+/// Converts a JSON group record to a `Group`.
+fn record_to_group(record: &Value) -> Option<Group> {
+    let name = record["groupName"].as_str()?;
+    let gid = record["gid"].as_u64()? as gid_t;
+
+    let mut group = Group::new(gid, name);
+    if let Some(members) = record["members"].as_array() {
+        for member in members.iter().filter_map(Value::as_str) {
+            group = group.add_member(member);
+        }
+    }
+
+    Some(group)
+}
+
+impl Users for UserDb {
+    fn get_user_by_uid(&self, uid: uid_t) -> Option<Arc<User>> {
+        self.logged(self.try_get_user_by_uid(uid))
+    }
+
+    fn get_user_by_name<S: AsRef<OsStr> + ?Sized>(&self, username: &S) -> Option<Arc<User>> {
+        self.logged(self.try_get_user_by_name(username))
+    }
+
+    fn get_current_uid(&self) -> uid_t {
+        super::get_current_uid()
+    }
+
+    fn get_current_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_current_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+
+    fn get_effective_uid(&self) -> uid_t {
+        super::get_effective_uid()
+    }
+
+    fn get_effective_username(&self) -> Option<Arc<OsStr>> {
+        let uid = self.get_effective_uid();
+        self.get_user_by_uid(uid).map(|u| Arc::clone(&u.name_arc))
+    }
+}
+
+impl Groups for UserDb {
+    fn get_group_by_gid(&self, gid: gid_t) -> Option<Arc<Group>> {
+        self.logged(self.try_get_group_by_gid(gid))
+    }
+
+    fn get_group_by_name<S: AsRef<OsStr> + ?Sized>(&self, group_name: &S) -> Option<Arc<Group>> {
+        self.logged(self.try_get_group_by_name(group_name))
+    }
+
+    fn get_current_gid(&self) -> gid_t {
+        super::get_current_gid()
+    }
+
+    fn get_current_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_current_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+
+    fn get_effective_gid(&self) -> gid_t {
+        super::get_effective_gid()
+    }
+
+    fn get_effective_groupname(&self) -> Option<Arc<OsStr>> {
+        let gid = self.get_effective_gid();
+        self.get_group_by_gid(gid).map(|g| Arc::clone(&g.name_arc))
+    }
+}
+
+impl TryUsers for UserDb {
+    fn try_get_user_by_uid(&self, uid: uid_t) -> io::Result<Option<Arc<User>>> {
+        let record = self.call("GetUserRecord", json!({ "uid": uid }))?;
+        converted(record, record_to_user)
+    }
+
+    fn try_get_user_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        username: &S,
+    ) -> io::Result<Option<Arc<User>>> {
+        // Records are JSON, so names that aren’t UTF-8 cannot exist.
+        let username = match username.as_ref().to_str() {
+            Some(username) => username,
+            None => return Ok(None),
+        };
+        let record = self.call("GetUserRecord", json!({ "userName": username }))?;
+        converted(record, record_to_user)
+    }
+}
+
+impl TryGroups for UserDb {
+    fn try_get_group_by_gid(&self, gid: gid_t) -> io::Result<Option<Arc<Group>>> {
+        let record = self.call("GetGroupRecord", json!({ "gid": gid }))?;
+        converted(record, record_to_group)
+    }
+
+    fn try_get_group_by_name<S: AsRef<OsStr> + ?Sized>(
+        &self,
+        group_name: &S,
+    ) -> io::Result<Option<Arc<Group>>> {
+        let group_name = match group_name.as_ref().to_str() {
+            Some(group_name) => group_name,
+            None => return Ok(None),
+        };
+        let record = self.call("GetGroupRecord", json!({ "groupName": group_name }))?;
+        converted(record, record_to_group)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Read;
+    use std::os::unix::net::UnixListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    #[test]
+    fn user_record() {
+        let record = json!({
+            "userName": "fred",
+            "uid": 61234,
+            "gid": 61234,
+            "homeDirectory": "/home/fred",
+            "shell": "/bin/zsh",
+            "realName": "Fred Santa",
+        });
+
+        let user = record_to_user(&record).unwrap();
+        assert_eq!(user.uid(), 61234);
+        assert_eq!(user.name(), "fred");
+        assert_eq!(user.home_dir(), Path::new("/home/fred"));
+        assert_eq!(user.shell(), Path::new("/bin/zsh"));
+        assert_eq!(user.gecos(), "Fred Santa");
+    }
+
+    #[test]
+    fn group_record() {
+        let record = json!({
+            "groupName": "contributors",
+            "gid": 43,
+            "members": ["bob", "martha"],
+        });
+
+        let group = record_to_group(&record).unwrap();
+        assert_eq!(group.gid(), 43);
+        assert_eq!(group.members(), &["bob", "martha"]);
+    }
+
+    #[test]
+    fn invalid_record() {
+        assert!(record_to_user(&json!({ "uid": 1 })).is_none());
+        assert!(record_to_group(&json!({ "groupName": "x" })).is_none());
+    }
+
+    /// Starts a service that answers a single query with the given reply.
+    fn serve_once(name: &str, reply: &'static str) -> (PathBuf, thread::JoinHandle<String>) {
+        let dir =
+            std::env::temp_dir().join(format!("uzers-userdb-{}-{}", name, std::process::id()));
+        let _ = std::fs::create_dir(&dir);
+        let socket = dir.join("io.systemd.Test");
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut reader = BufReader::new(stream);
+            reader.read_until(0, &mut request).unwrap();
+
+            let mut stream = reader.into_inner();
+            stream.write_all(reply.as_bytes()).unwrap();
+            stream.write_all(&[0]).unwrap();
+
+            let mut rest = Vec::new();
+            let _ = stream.read_to_end(&mut rest);
+            request.pop();
+            String::from_utf8(request).unwrap()
+        });
+
+        (socket, handle)
+    }
+
+    #[test]
+    fn query_user() {
+        let (socket, server) = serve_once(
+            "user",
+            r#"{"parameters":{"record":{"userName":"fred","uid":1337,"gid":42},"incomplete":false}}"#,
+        );
+
+        let user = UserDb::with_socket(&socket).get_user_by_uid(1337).unwrap();
+        let request: Value = serde_json::from_str(&server.join().unwrap()).unwrap();
+
+        assert_eq!(user.name(), "fred");
+        assert_eq!(user.primary_group_id(), 42);
+        assert_eq!(request["method"], "io.systemd.UserDatabase.GetUserRecord");
+        assert_eq!(request["parameters"]["uid"], 1337);
+        assert_eq!(request["parameters"]["service"], "io.systemd.Test");
+    }
+
+    #[test]
+    fn query_missing_group() {
+        let (socket, server) = serve_once(
+            "group",
+            r#"{"error":"io.systemd.UserDatabase.NoRecordFound","parameters":{}}"#,
+        );
+
+        let group = UserDb::with_socket(&socket).get_group_by_name("nobody-here");
+        server.join().unwrap();
+
+        assert!(group.is_none());
+    }
+
+    #[test]
+    fn query_failure() {
+        let (socket, server) = serve_once(
+            "failure",
+            r#"{"error":"io.systemd.UserDatabase.ServiceNotAvailable","parameters":{}}"#,
+        );
+
+        let result = UserDb::with_socket(&socket).try_get_group_by_gid(43);
+        server.join().unwrap();
+
+        let error = result.unwrap_err();
+        assert!(error.to_string().contains("ServiceNotAvailable"));
+    }
+}
@@ -0,0 +1,250 @@
+//! A preview of the reorganized API planned for version 1.0.
+//!
+//! The next major release reorganizes this crate: providers get a module of
+//! their own, lookups report why they failed instead of returning `None`,
+//! IDs get types of their own, and the [`Users`] and [`Groups`] traits are
+//! split by what they do. This module offers that API next to the current
+//! one, so that large programs can port to it one call site at a time and
+//! report what is missing before the breaking release.
+//!
+//! Everything here works with the providers of the current API: the lookup
+//! traits in [`traits`] are implemented for every type that implements
+//! [`TryUsers`] or [`TryGroups`], so a lookup that fails is reported as an
+//! [`Error::Io`](error::Error::Io) or
+//! [`Error::InvalidData`](error::Error::InvalidData) rather than as a user
+//! that does not exist.
+//!
+//! ```
+//! use uzers::v1::prelude::*;
+//! use uzers::v1::providers::ReplayUsers;
+//!
+//! let users = ReplayUsers::parse(
+//!     "current-uid - 1000\n\
+//!      uid 1000 fred:x:1000:100::/home/fred:/bin/sh\n",
+//! )
+//! .unwrap();
+//!
+//! let me = users.current_uid();
+//! assert_eq!(users.user(me).unwrap().name(), "fred");
+//! assert!(matches!(users.user(Uid::from_raw(4242)), Err(Error::NotFound)));
+//! ```
+//!
+//! This module is only available with the `v1-preview` feature. It is not
+//! covered by the semantic versioning of the current API, and may change in
+//! any release until 1.0.
+//!
+//! [`Users`]: ../trait.Users.html
+//! [`Groups`]: ../trait.Groups.html
+//! [`TryUsers`]: ../trait.TryUsers.html
+//! [`TryGroups`]: ../trait.TryGroups.html
+
+pub mod error {
+    //! The error type of every fallible operation.
+
+    use std::error;
+    use std::fmt;
+    use std::io;
+
+    /// Why an operation failed.
+    #[derive(Debug)]
+    pub enum Error {
+        /// No user or group matched the lookup.
+        NotFound,
+
+        /// The database could not be read.
+        Io(io::Error),
+
+        /// The database holds an entry that cannot be parsed.
+        InvalidData(String),
+    }
+
+    /// The result of an operation that can fail with an [`Error`].
+    pub type Result<T> = std::result::Result<T, Error>;
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Error::NotFound => f.write_str("no such user or group"),
+                Error::Io(e) => write!(f, "cannot read the database: {}", e),
+                Error::InvalidData(entry) => write!(f, "invalid entry: {}", entry),
+            }
+        }
+    }
+
+    impl error::Error for Error {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            match self {
+                Error::Io(e) => Some(e),
+                _ => None,
+            }
+        }
+    }
+
+    impl From<io::Error> for Error {
+        fn from(error: io::Error) -> Self {
+            match error.kind() {
+                io::ErrorKind::InvalidData => Error::InvalidData(error.to_string()),
+                _ => Error::Io(error),
+            }
+        }
+    }
+}
+
+pub mod id {
+    //! Types for user and group IDs, so that one cannot be passed where the
+    //! other is expected.
+    //!
+    //! These are the [`Uid`] and [`Gid`] of the current API, so IDs pass
+    //! between the two without being converted.
+
+    pub use ids::{Gid, Uid};
+}
+
+pub mod providers {
+    //! Every type that users and groups can be looked up in.
+
+    #[cfg(feature = "cache")]
+    pub use cache::{BoundedUsersCache, Cached, SyncUsersCache, UsersCache, UsersSnapshot};
+    #[cfg(feature = "mock")]
+    pub use mock::MockUsers;
+    pub use record::{RecordingUsers, ReplayUsers};
+    pub use source::{FilesSource, LibcSource, UserDbSource};
+}
+
+pub mod traits {
+    //! The lookup traits, split by what they look up.
+
+    use std::ffi::OsStr;
+    use std::sync::Arc;
+
+    use base::{Group, User};
+    use traits::{Groups, TryGroups, TryUsers, Users};
+
+    use super::error::{Error, Result};
+    use super::id::{Gid, Uid};
+
+    /// Looking up users.
+    pub trait UserLookup {
+        /// Returns the user with the given ID.
+        fn user(&self, uid: Uid) -> Result<Arc<User>>;
+
+        /// Returns the user with the given name.
+        fn user_named(&self, name: &OsStr) -> Result<Arc<User>>;
+    }
+
+    /// Looking up groups.
+    pub trait GroupLookup {
+        /// Returns the group with the given ID.
+        fn group(&self, gid: Gid) -> Result<Arc<Group>>;
+
+        /// Returns the group with the given name.
+        fn group_named(&self, name: &OsStr) -> Result<Arc<Group>>;
+    }
+
+    /// The identity of the running process.
+    pub trait ProcessIdentity {
+        /// Returns the user ID of the process.
+        fn current_uid(&self) -> Uid;
+
+        /// Returns the effective user ID of the process.
+        fn effective_uid(&self) -> Uid;
+
+        /// Returns the group ID of the process.
+        fn current_gid(&self) -> Gid;
+
+        /// Returns the effective group ID of the process.
+        fn effective_gid(&self) -> Gid;
+    }
+
+    impl<T: TryUsers + ?Sized> UserLookup for T {
+        fn user(&self, uid: Uid) -> Result<Arc<User>> {
+            self.try_get_user_by_uid(uid.as_raw())?
+                .ok_or(Error::NotFound)
+        }
+
+        fn user_named(&self, name: &OsStr) -> Result<Arc<User>> {
+            self.try_get_user_by_name(name)?.ok_or(Error::NotFound)
+        }
+    }
+
+    impl<T: TryGroups + ?Sized> GroupLookup for T {
+        fn group(&self, gid: Gid) -> Result<Arc<Group>> {
+            self.try_get_group_by_gid(gid.as_raw())?
+                .ok_or(Error::NotFound)
+        }
+
+        fn group_named(&self, name: &OsStr) -> Result<Arc<Group>> {
+            self.try_get_group_by_name(name)?.ok_or(Error::NotFound)
+        }
+    }
+
+    impl<T: Users + Groups + ?Sized> ProcessIdentity for T {
+        fn current_uid(&self) -> Uid {
+            Uid::from_raw(self.get_current_uid())
+        }
+
+        fn effective_uid(&self) -> Uid {
+            Uid::from_raw(self.get_effective_uid())
+        }
+
+        fn current_gid(&self) -> Gid {
+            Gid::from_raw(self.get_current_gid())
+        }
+
+        fn effective_gid(&self) -> Gid {
+            Gid::from_raw(self.get_effective_gid())
+        }
+    }
+}
+
+pub mod prelude {
+    //! The traits and types that almost every user of the API needs.
+
+    pub use super::error::{Error, Result};
+    pub use super::id::{Gid, Uid};
+    pub use super::traits::{GroupLookup, ProcessIdentity, UserLookup};
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod test {
+    use super::prelude::*;
+
+    use std::io;
+
+    use base::{Group, User};
+    use mock::MockUsers;
+
+    fn users() -> MockUsers {
+        let mut users = MockUsers::with_current_uid(1000);
+        users.add_user(User::new(1000, "fred", 100));
+        users.add_group(Group::new(100, "staff"));
+        users
+    }
+
+    #[test]
+    fn lookups() {
+        let users = users();
+        assert_eq!(users.user_named("fred".as_ref()).unwrap().uid(), 1000);
+        assert_eq!(users.group(Gid::from_raw(100)).unwrap().name(), "staff");
+        assert!(matches!(
+            users.group_named("wheel".as_ref()),
+            Err(Error::NotFound)
+        ));
+        assert_eq!(users.effective_uid(), Uid::from_raw(1000));
+    }
+
+    #[test]
+    fn failed_lookups() {
+        let mut users = users();
+        users.fail_uid(1000, libc::EIO);
+        assert!(matches!(users.user(Uid::from_raw(1000)), Err(Error::Io(_))));
+        assert_eq!(users.group(Gid::from_raw(100)).unwrap().name(), "staff");
+    }
+
+    #[test]
+    fn errors() {
+        let error = Error::from(io::Error::new(io::ErrorKind::InvalidData, "bad line"));
+        assert!(matches!(error, Error::InvalidData(_)));
+        assert_eq!(Error::NotFound.to_string(), "no such user or group");
+    }
+}
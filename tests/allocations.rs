@@ -0,0 +1,80 @@
+//! Checks that looking up a cached name does not allocate.
+
+#![cfg(feature = "cache")]
+
+extern crate uzers;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use uzers::cache::{SyncUsersCache, UsersCache};
+use uzers::{Groups, Users};
+
+/// An allocator that counts the allocations made by each thread, so that the
+/// tests running next to each other do not disturb the counts.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    f();
+    ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn users_cache_hits() {
+    let cache = UsersCache::new();
+    let name = cache.get_current_username();
+    let name = name.as_deref().unwrap_or("user\u{0}name".as_ref());
+    cache.get_user_by_name(name);
+    cache.get_group_by_name("group\u{0}name");
+
+    assert_eq!(allocations(|| drop(cache.get_user_by_name(name))), 0);
+    assert_eq!(
+        allocations(|| drop(cache.get_group_by_name("group\u{0}name"))),
+        0
+    );
+}
+
+#[test]
+fn snapshot_hits() {
+    let cache = UsersCache::new();
+    let name = cache.get_current_username();
+    let snapshot = cache.freeze();
+
+    if let Some(name) = name {
+        assert_eq!(allocations(|| drop(snapshot.get_user_by_name(&*name))), 0);
+    }
+    assert_eq!(
+        allocations(|| drop(snapshot.get_user_by_name("user\u{0}name"))),
+        0
+    );
+}
+
+#[test]
+fn sync_cache_hits() {
+    let cache = SyncUsersCache::new();
+    cache.get_user_by_name("user\u{0}name");
+
+    assert_eq!(
+        allocations(|| drop(cache.get_user_by_name("user\u{0}name"))),
+        0
+    );
+}
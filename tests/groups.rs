@@ -6,7 +6,7 @@ mod integration {
     fn test_group_by_name() {
         let group = uzers::get_group_by_name("bosses");
 
-        assert_eq!(group.is_some(), true);
+        assert!(group.is_some());
 
         let group = group.unwrap();
 
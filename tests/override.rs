@@ -0,0 +1,45 @@
+extern crate uzers;
+
+#[cfg(feature = "test-override")]
+mod overridden {
+    use std::env;
+    use std::path::PathBuf;
+
+    use uzers::files::{GROUP_FILE_VAR, PASSWD_FILE_VAR};
+    use uzers::os::unix::{GroupExt, UserExt};
+
+    fn use_fixtures() {
+        env::set_var(PASSWD_FILE_VAR, "tests/fixtures/passwd");
+        env::set_var(GROUP_FILE_VAR, "tests/fixtures/group");
+    }
+
+    #[test]
+    fn users_from_fixture() {
+        use_fixtures();
+
+        let user = uzers::get_user_by_name("fred").unwrap();
+        assert_eq!(user.uid(), 1337);
+        assert_eq!(user.home_dir(), PathBuf::from("/home/fred"));
+
+        assert_eq!(uzers::get_user_by_uid(1337).unwrap().name(), "fred");
+        assert!(uzers::get_user_by_uid(0).is_none());
+
+        let all = unsafe { uzers::all_users() }.collect::<Vec<_>>();
+        assert_eq!(all.len(), 1);
+    }
+
+    #[test]
+    fn groups_from_fixture() {
+        use_fixtures();
+
+        let group = uzers::get_group_by_name("contributors").unwrap();
+        assert_eq!(group.gid(), 43);
+        assert_eq!(group.members(), &["bob", "martha"]);
+
+        assert_eq!(uzers::get_group_by_gid(42).unwrap().name(), "bosses");
+        assert!(uzers::get_group_by_name("root").is_none());
+
+        let all = unsafe { uzers::all_groups() }.collect::<Vec<_>>();
+        assert_eq!(all.len(), 2);
+    }
+}
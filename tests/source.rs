@@ -0,0 +1,39 @@
+extern crate uzers;
+
+use uzers::os::unix::GroupExt;
+use uzers::source::{clear_global_source, set_global_source, FilesSource};
+
+#[test]
+fn free_functions_use_global_source() {
+    set_global_source(FilesSource::new(
+        "tests/fixtures/passwd",
+        "tests/fixtures/group",
+    ));
+
+    assert_eq!(uzers::get_user_by_uid(1337).unwrap().name(), "fred");
+    assert_eq!(uzers::get_group_by_name("bosses").unwrap().gid(), 42);
+    assert!(uzers::get_user_by_name("root").is_none());
+
+    let users = unsafe { uzers::all_users() }.collect::<Vec<_>>();
+    assert_eq!(users.len(), 1);
+
+    let groups = uzers::get_user_groups("martha", 42).unwrap();
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[1].members(), &["bob", "martha"]);
+
+    // A group that a cache finds by name is kept in a single `Arc`, which
+    // lookups by ID share.
+    #[cfg(feature = "cache")]
+    {
+        use std::sync::Arc;
+        use uzers::{Groups, UsersCache};
+
+        let cache = UsersCache::new();
+        let by_name = cache.get_group_by_name("bosses").unwrap();
+        let by_gid = cache.get_group_by_gid(42).unwrap();
+        assert!(Arc::ptr_eq(&by_name, &by_gid));
+        assert_eq!(Arc::strong_count(&by_name), 3);
+    }
+
+    clear_global_source();
+}
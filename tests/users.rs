@@ -10,7 +10,7 @@ mod integration {
     fn test_user_by_name() {
         let user = uzers::get_user_by_name("fred");
 
-        assert_eq!(user.is_some(), true);
+        assert!(user.is_some());
 
         let user = user.unwrap();
 